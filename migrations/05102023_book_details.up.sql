@@ -0,0 +1,8 @@
+-- Describe a book beyond its name, year, category and author
+ALTER TABLE book
+ADD COLUMN description text,
+ADD COLUMN publisher varchar(255),
+ADD COLUMN edition integer CHECK (edition >= 1),
+ADD COLUMN language char(2),
+ADD COLUMN page_count integer CHECK (page_count >= 1),
+ADD COLUMN cover_image_url varchar(2048);
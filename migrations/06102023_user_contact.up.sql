@@ -0,0 +1,4 @@
+-- Users can be reached by email or phone, an email belongs to a single user
+ALTER TABLE users
+ADD COLUMN email varchar(255) UNIQUE,
+ADD COLUMN phone varchar(20);
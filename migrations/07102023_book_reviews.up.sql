@@ -0,0 +1,16 @@
+-- Create the book_reviews table with UUID primary key, a user reviews a book once
+CREATE TABLE IF NOT EXISTS book_reviews (
+  Id UUID DEFAULT uuid_generate_v4() PRIMARY KEY,
+  book_name varchar(255) NOT NULL,
+  nation_id varchar(100) NOT NULL,
+  rating integer NOT NULL CHECK (rating BETWEEN 1 AND 5),
+  comment varchar(1000),
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  UNIQUE (book_name, nation_id)
+);
+
+-- the national ID constraint is deferrable so a national ID can be corrected
+ALTER TABLE book_reviews
+ADD FOREIGN KEY (book_name) REFERENCES book(name),
+ADD CONSTRAINT book_reviews_nation_id_fkey FOREIGN KEY (nation_id) REFERENCES users(nation_id)
+  DEFERRABLE INITIALLY IMMEDIATE;
@@ -0,0 +1,3 @@
+-- Deleted books and users are kept, marked with their deletion time
+ALTER TABLE book ADD COLUMN deleted_at TIMESTAMPTZ;
+ALTER TABLE users ADD COLUMN deleted_at TIMESTAMPTZ;
@@ -0,0 +1,9 @@
+-- Create the audit_log table with UUID primary key
+CREATE TABLE IF NOT EXISTS audit_log (
+  Id UUID DEFAULT uuid_generate_v4() PRIMARY KEY,
+  entity_type varchar(100) NOT NULL,
+  entity_id varchar(255),
+  action varchar(255) NOT NULL,
+  payload_json jsonb,
+  performed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
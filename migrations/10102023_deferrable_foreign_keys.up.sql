@@ -0,0 +1,7 @@
+-- The author constraint is deferrable so an author can be renamed together with their books
+ALTER TABLE book
+ALTER CONSTRAINT book_author_fkey DEFERRABLE INITIALLY IMMEDIATE;
+
+-- The national ID constraint is deferrable so a national ID can be corrected
+ALTER TABLE users_history
+ALTER CONSTRAINT users_history_nation_id_fkey DEFERRABLE INITIALLY IMMEDIATE;
@@ -0,0 +1,2 @@
+-- An ISBN identifies a single book
+ALTER TABLE book ADD COLUMN isbn varchar(17) UNIQUE;
@@ -0,0 +1,59 @@
+-- Add the library_id column to every table, existing rows belong to the default library
+ALTER TABLE book ADD COLUMN library_id varchar(100) NOT NULL DEFAULT 'default';
+ALTER TABLE author ADD COLUMN library_id varchar(100) NOT NULL DEFAULT 'default';
+ALTER TABLE users ADD COLUMN library_id varchar(100) NOT NULL DEFAULT 'default';
+ALTER TABLE users_history ADD COLUMN library_id varchar(100) NOT NULL DEFAULT 'default';
+ALTER TABLE book_reviews ADD COLUMN library_id varchar(100) NOT NULL DEFAULT 'default';
+ALTER TABLE audit_log ADD COLUMN library_id varchar(100) NOT NULL DEFAULT 'default';
+
+-- Drop the foreign keys referencing the unique constraints that are scoped to a library below
+ALTER TABLE book DROP CONSTRAINT book_author_fkey;
+
+ALTER TABLE users_history
+DROP CONSTRAINT users_history_book_name_fkey,
+DROP CONSTRAINT users_history_nation_id_fkey;
+
+ALTER TABLE book_reviews
+DROP CONSTRAINT book_reviews_book_name_fkey,
+DROP CONSTRAINT book_reviews_nation_id_fkey;
+
+-- Names, ISBNs, national IDs and emails are unique within a library
+ALTER TABLE book
+DROP CONSTRAINT book_name_key,
+DROP CONSTRAINT book_isbn_key,
+ADD CONSTRAINT book_name_key UNIQUE (library_id, name),
+ADD CONSTRAINT book_isbn_key UNIQUE (library_id, isbn);
+
+ALTER TABLE author
+DROP CONSTRAINT author_name_key,
+ADD CONSTRAINT author_name_key UNIQUE (library_id, name);
+
+ALTER TABLE users
+DROP CONSTRAINT users_nation_id_key,
+DROP CONSTRAINT users_email_key,
+ADD CONSTRAINT users_nation_id_key UNIQUE (library_id, nation_id),
+ADD CONSTRAINT users_email_key UNIQUE (library_id, email);
+
+ALTER TABLE book_reviews
+DROP CONSTRAINT book_reviews_book_name_nation_id_key,
+ADD CONSTRAINT book_reviews_book_name_nation_id_key UNIQUE (library_id, book_name, nation_id);
+
+-- Recreate the foreign keys within a library, keeping their names and deferrability
+ALTER TABLE book
+ADD CONSTRAINT book_author_fkey FOREIGN KEY (library_id, author)
+  REFERENCES author(library_id, name)
+  DEFERRABLE INITIALLY IMMEDIATE;
+
+ALTER TABLE users_history
+ADD CONSTRAINT users_history_book_name_fkey FOREIGN KEY (library_id, book_name)
+  REFERENCES book(library_id, name),
+ADD CONSTRAINT users_history_nation_id_fkey FOREIGN KEY (library_id, nation_id)
+  REFERENCES users(library_id, nation_id)
+  DEFERRABLE INITIALLY IMMEDIATE;
+
+ALTER TABLE book_reviews
+ADD CONSTRAINT book_reviews_book_name_fkey FOREIGN KEY (library_id, book_name)
+  REFERENCES book(library_id, name),
+ADD CONSTRAINT book_reviews_nation_id_fkey FOREIGN KEY (library_id, nation_id)
+  REFERENCES users(library_id, nation_id)
+  DEFERRABLE INITIALLY IMMEDIATE;
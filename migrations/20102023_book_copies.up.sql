@@ -0,0 +1,21 @@
+-- A book can have several physical copies, existing books are their first copy
+ALTER TABLE book
+ADD COLUMN copy_number integer NOT NULL DEFAULT 1 CHECK (copy_number >= 1);
+
+ALTER TABLE users_history ADD COLUMN copy_number integer NOT NULL DEFAULT 1;
+
+-- Drop the foreign keys referencing the name of a book, which is no longer unique
+ALTER TABLE users_history DROP CONSTRAINT users_history_book_name_fkey;
+ALTER TABLE book_reviews DROP CONSTRAINT book_reviews_book_name_fkey;
+
+-- Names and ISBNs are unique per copy within a library
+ALTER TABLE book
+DROP CONSTRAINT book_name_key,
+DROP CONSTRAINT book_isbn_key,
+ADD CONSTRAINT book_name_key UNIQUE (library_id, name, copy_number),
+ADD CONSTRAINT book_isbn_key UNIQUE (library_id, isbn, copy_number);
+
+-- A rental references the copy that was rented
+ALTER TABLE users_history
+ADD CONSTRAINT users_history_book_name_fkey FOREIGN KEY (library_id, book_name, copy_number)
+  REFERENCES book(library_id, name, copy_number);
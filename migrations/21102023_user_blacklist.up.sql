@@ -0,0 +1,2 @@
+-- Blacklisted users are not allowed to rent books
+ALTER TABLE users ADD COLUMN blacklisted_at timestamptz;
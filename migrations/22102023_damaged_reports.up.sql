@@ -0,0 +1,15 @@
+-- Create the damaged_reports table with UUID primary key, a report is made for a copy of a book
+CREATE TABLE IF NOT EXISTS damaged_reports (
+  Id UUID DEFAULT uuid_generate_v4() PRIMARY KEY,
+  library_id varchar(100) NOT NULL DEFAULT 'default',
+  book_name varchar(255) NOT NULL,
+  copy_number integer NOT NULL DEFAULT 1,
+  nation_id varchar(100) NOT NULL,
+  description varchar(500) NOT NULL,
+  reported_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  CONSTRAINT damaged_reports_book_name_fkey FOREIGN KEY (library_id, book_name, copy_number)
+    REFERENCES book(library_id, name, copy_number),
+  CONSTRAINT damaged_reports_nation_id_fkey FOREIGN KEY (library_id, nation_id)
+    REFERENCES users(library_id, nation_id)
+    DEFERRABLE INITIALLY IMMEDIATE
+);
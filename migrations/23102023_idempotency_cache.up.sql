@@ -0,0 +1,11 @@
+-- Create the idempotency_cache table, the response of a request sent with an
+-- idempotency key is kept until the key expires
+CREATE TABLE IF NOT EXISTS idempotency_cache (
+  library_id varchar(100) NOT NULL DEFAULT 'default',
+  idempotency_key UUID NOT NULL,
+  request_path varchar(500) NOT NULL,
+  response_body JSONB,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  expires_at TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (library_id, idempotency_key)
+);
@@ -0,0 +1,15 @@
+-- Create the tags table with UUID primary key, tag names are unique within a library
+CREATE TABLE IF NOT EXISTS tags (
+  Id UUID DEFAULT uuid_generate_v4() PRIMARY KEY,
+  library_id varchar(100) NOT NULL DEFAULT 'default',
+  name varchar(50) NOT NULL,
+  CONSTRAINT tags_name_key UNIQUE (library_id, name)
+);
+
+-- A tag is given to a book by its name, so all the copies of the book share it
+CREATE TABLE IF NOT EXISTS book_tags (
+  library_id varchar(100) NOT NULL DEFAULT 'default',
+  book_name varchar(255) NOT NULL,
+  tag_id UUID NOT NULL REFERENCES tags(Id) ON DELETE CASCADE,
+  PRIMARY KEY (library_id, book_name, tag_id)
+);
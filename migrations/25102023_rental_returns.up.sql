@@ -0,0 +1,2 @@
+-- When the rented copy was returned, NULL while it is not
+ALTER TABLE users_history ADD COLUMN returned_at timestamptz;
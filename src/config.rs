@@ -0,0 +1,158 @@
+use axum::http::HeaderValue;
+use std::env::var;
+
+/// Default maximum size of a request body in bytes, 1 MB.
+pub const DEFAULT_REQUEST_BODY_LIMIT_BYTES: usize = 1024 * 1024;
+
+/// Default maximum duration of a request in milliseconds, 30 seconds.
+pub const DEFAULT_REQUEST_TIMEOUT_MS: u64 = 30_000;
+
+/// Represents the configuration of the web application.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppConfig {
+    /// Maximum size of a request body in bytes.
+    pub request_body_limit_bytes: usize,
+    /// Origins allowed to make cross-origin requests, `*` allows any origin.
+    /// CORS is disabled when empty.
+    pub cors_origins: Vec<String>,
+    /// Maximum number of requests per minute across all clients. Rate
+    /// limiting is disabled when 0.
+    pub rate_limit_rpm: u32,
+    /// Maximum duration of a request in milliseconds, slower requests are
+    /// answered with `504 Gateway Timeout`.
+    pub request_timeout_ms: u64,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            request_body_limit_bytes: DEFAULT_REQUEST_BODY_LIMIT_BYTES,
+            cors_origins: vec![],
+            rate_limit_rpm: 0,
+            request_timeout_ms: DEFAULT_REQUEST_TIMEOUT_MS,
+        }
+    }
+}
+
+impl AppConfig {
+    /// Reads the configuration from the environment variables.
+    ///
+    /// * `REQUEST_BODY_LIMIT_BYTES`: defaults to `DEFAULT_REQUEST_BODY_LIMIT_BYTES`.
+    /// * `CORS_ORIGINS`: a comma-separated list of origins, defaults to none.
+    /// * `RATE_LIMIT_RPM`: defaults to 0, no rate limiting.
+    /// * `REQUEST_TIMEOUT_MS`: defaults to `DEFAULT_REQUEST_TIMEOUT_MS`.
+    ///
+    /// ## Panics
+    ///
+    /// This function will panic if a variable is set but has an invalid value.
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| var(name).ok())
+    }
+
+    /// Reads the configuration from the variables returned by `get`.
+    ///
+    /// ## Panics
+    ///
+    /// This function will panic if a variable is set but has an invalid value.
+    fn from_vars(get: impl Fn(&str) -> Option<String>) -> Self {
+        let default = Self::default();
+        let request_body_limit_bytes =
+            get("REQUEST_BODY_LIMIT_BYTES").map_or(default.request_body_limit_bytes, |limit| {
+                limit
+                    .parse()
+                    .expect("REQUEST_BODY_LIMIT_BYTES must be a number of bytes")
+            });
+        let cors_origins = get("CORS_ORIGINS").map_or(default.cors_origins, |origins| {
+            origins
+                .split(',')
+                .map(str::trim)
+                .filter(|origin| !origin.is_empty())
+                .map(|origin| {
+                    HeaderValue::from_str(origin).expect("CORS_ORIGINS must be a list of origins");
+                    origin.to_owned()
+                })
+                .collect()
+        });
+        let rate_limit_rpm = get("RATE_LIMIT_RPM").map_or(default.rate_limit_rpm, |rpm| {
+            rpm.parse()
+                .expect("RATE_LIMIT_RPM must be a number of requests per minute")
+        });
+        let request_timeout_ms =
+            get("REQUEST_TIMEOUT_MS").map_or(default.request_timeout_ms, |timeout| {
+                timeout
+                    .parse()
+                    .expect("REQUEST_TIMEOUT_MS must be a number of milliseconds")
+            });
+        Self {
+            request_body_limit_bytes,
+            cors_origins,
+            rate_limit_rpm,
+            request_timeout_ms,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn from_map(vars: &[(&str, &str)]) -> AppConfig {
+        let vars: HashMap<_, _> = vars.iter().copied().collect();
+        AppConfig::from_vars(|name| vars.get(name).map(|value| value.to_string()))
+    }
+
+    #[test]
+    fn test_app_config_defaults() {
+        assert_eq!(from_map(&[]), AppConfig::default());
+    }
+
+    #[test]
+    fn test_app_config() {
+        let config = from_map(&[
+            ("REQUEST_BODY_LIMIT_BYTES", "2048"),
+            (
+                "CORS_ORIGINS",
+                "https://example.com, http://localhost:3000,",
+            ),
+            ("RATE_LIMIT_RPM", "120"),
+            ("REQUEST_TIMEOUT_MS", "5000"),
+        ]);
+        assert_eq!(
+            config,
+            AppConfig {
+                request_body_limit_bytes: 2048,
+                cors_origins: vec![
+                    "https://example.com".to_owned(),
+                    "http://localhost:3000".to_owned()
+                ],
+                rate_limit_rpm: 120,
+                request_timeout_ms: 5000,
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "REQUEST_BODY_LIMIT_BYTES")]
+    fn test_app_config_invalid_body_limit() {
+        from_map(&[("REQUEST_BODY_LIMIT_BYTES", "1MB")]);
+    }
+
+    #[test]
+    #[should_panic(expected = "RATE_LIMIT_RPM")]
+    fn test_app_config_invalid_rate_limit() {
+        from_map(&[("RATE_LIMIT_RPM", "-1")]);
+    }
+
+    #[test]
+    #[should_panic(expected = "REQUEST_TIMEOUT_MS")]
+    fn test_app_config_invalid_request_timeout() {
+        from_map(&[("REQUEST_TIMEOUT_MS", "30s")]);
+    }
+
+    #[test]
+    #[should_panic(expected = "CORS_ORIGINS")]
+    fn test_app_config_invalid_cors_origins() {
+        from_map(&[("CORS_ORIGINS", "https://exam\0ple.com")]);
+    }
+}
@@ -1,62 +1,346 @@
+use crate::helper;
 use crate::library;
 use crate::library_web;
-use utoipa::OpenApi;
+use axum::{http::header::CONTENT_TYPE, response::IntoResponse};
+use std::sync::OnceLock;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
 
 #[derive(OpenApi)]
 #[openapi(
     info(
         title = "Library API",
-        version = "0.1.0",
-        description = "API for managing library book rentals."
+        version = "1.0.0",
+        description = "API for managing library book rentals.\n\n\
+            Request bodies are limited to 1 MB by default, configurable with the \
+            `REQUEST_BODY_LIMIT_BYTES` environment variable. Larger bodies are \
+            rejected with `413 Payload Too Large`. Request bodies other than \
+            JSON, or NDJSON and multipart forms for file uploads, are rejected with \
+            `415 Unsupported Media Type`, and requests not accepting JSON \
+            responses with `406 Not Acceptable`, except for the exports, the \
+            reports, the QR codes and the events.\n\n\
+            Every request operates on a single library, selected with the \
+            `X-Library-Id` header. Requests without the header operate on the \
+            `default` library.\n\n\
+            Routes are versioned under `/api/{version}`, e.g. `/api/v1/book`. \
+            The unversioned `/api/book`, `/api/author`, `/api/user`, `/api/stats` \
+            and `/api/audit` paths redirect to the latest version with \
+            `301 Moved Permanently`.\n\n\
+            Write endpoints are documented with the `BearerAuth` scheme for \
+            gateways authenticating requests with a JWT in front of the API, \
+            the API itself does not verify the token. The admin routes \
+            additionally require the `X-User-Role: admin` header, set by the \
+            gateway from the token, and reject other requests with \
+            `403 Forbidden`."
     ),
+    modifiers(&SecurityAddon),
     paths(
 
+        //audit
+        library_web::audit::audits,
+
         //author
         library_web::author::create_author,
         library_web::author::authors,
+        library_web::author::count_authors,
         library_web::author::get_author,
+        library_web::author::get_author_by_name,
+        library_web::author::country_peers,
+        library_web::author::author_stats,
+        library_web::author::patch_author,
+        library_web::author::merge_authors,
+        library_web::author::export_authors_json,
+        library_web::author::import_authors_json,
+
+        //batch
+        library_web::batch::batch,
 
         //book
         library_web::book::create_book,
+        library_web::book::create_book_copies,
         library_web::book::books,
+        library_web::book::count_books,
+        library_web::book::available_books,
+        library_web::book::rented_books,
+        library_web::book::deleted_books,
+        library_web::book::new_arrivals,
+        library_web::book::popular_books_this_month,
+        library_web::book::trending_books,
+        library_web::book::delete_book,
+        library_web::book::delete_books,
+        library_web::book::restore_book,
+        library_web::book::reassign_book_author,
+        library_web::book::merge_books,
         library_web::book::get_book,
+        library_web::book::get_book_by_name,
+        library_web::book::get_book_by_isbn,
+        library_web::book::random_book,
+        library_web::book::books_by_category,
+        library_web::book::books_by_year,
+        library_web::book::books_by_author_name,
+        library_web::book::book_availability,
+        library_web::book::book_copies,
+        library_web::book::current_renter,
+        library_web::book::rental_rate,
+        library_web::book::book_qr_code,
+        library_web::book::similar_books,
+        library_web::book::import_books_csv,
+        library_web::book::export_books_csv,
+        library_web::book::catalogue_report_pdf,
+        library_web::ws::book_updates,
+        library_web::events::book_available,
+
+        //damage
+        library_web::damage::flag_book,
+        library_web::damage::book_flags,
+
+        //review
+        library_web::review::rate_book,
+        library_web::review::review_book,
+        library_web::review::reviews,
+
+        //stats
+        library_web::stats::top_books,
+        library_web::stats::top_users,
+        library_web::stats::category_distribution,
+        library_web::stats::monthly_rentals,
+
+        //tag
+        library_web::tag::book_tags,
+        library_web::tag::tag_book,
+        library_web::tag::untag_book,
+        library_web::tag::tags,
 
         //user
         library_web::user::create_user,
+        library_web::user::register_and_rent,
         library_web::user::rent_book,
+        library_web::user::rent_books,
+        library_web::user::transfer_rental,
+        library_web::user::set_due_date,
+        library_web::user::return_book,
         library_web::user::users,
+        library_web::user::count_users,
         library_web::user::get_user,
+        library_web::user::user_history,
+        library_web::user::user_profile,
+        library_web::user::user_stats,
+        library_web::user::user_overdue_rentals,
+        library_web::user::book_recommendations,
+        library_web::user::update_user,
+        library_web::user::change_nation_id,
+        library_web::user::delete_user,
+        library_web::user::delete_history_row,
+        library_web::user::restore_user,
+        library_web::user::blacklist_user,
+        library_web::user::unblacklist_user,
+        library_web::user::blacklisted_users,
+        library_web::user::active_users,
+        library_web::user::inactive_users,
+
+        //version
+        library_web::version::version,
 
     ),
     components(schemas(
+
+        //audit
+        library::audit::AuditRow,
+        library_web::audit::AuditsBody,
     
         //author
         library::author::Author,
         library::author::AuthorRow,
+        library::author::AuthorPatch,
+        library::author::MergeAuthorsBody,
+        library::author::AuthorStats,
         library_web::author::CreatedAuthorBody,
         library_web::author::AuthorsBody,
         library_web::author::GetAuthorBody,
+        library_web::author::AuthorStatsBody,
+        library_web::author::UpdatedAuthorBody,
+        library_web::author::MergedAuthorsBody,
+
+        //batch
+        library_web::batch::BatchRequest,
+        library_web::batch::SubRequest,
+        library_web::batch::SubResponse,
+        library_web::batch::BatchResponse,
 
         //book
         library::book::Book,
+        library::book::BookAuthor,
+        library::book::BookSummary,
         library::book::Status,
+        library::book::TagMatch,
+        library::book::BookAvailability,
+        library::book::BookCopyRow,
+        library::book::DeleteResult,
+        library::book::MergeBooksBody,
         library_web::book::CreatedBookBody,
+        library_web::book::MergedBooksBody,
+        library_web::book::CreateCopiesBody,
+        library_web::book::CreatedCopiesBody,
+        library_web::book::BulkDeleteRequest,
+        library_web::book::BulkDeleteBody,
         library_web::book::BooksBody,
+        library_web::book::CountBody,
         library_web::book::GetBookBody,
+        library_web::book::BookMessageBody,
+        library_web::book::BookAvailabilityBody,
+        library_web::book::BookCopiesBody,
+        library_web::book::CurrentRenterBody,
+        library_web::book::RentalRateBody,
+        library_web::book::NewArrivalsBody,
+        library_web::book::ImportBooksForm,
+        library_web::book::ImportError,
+        library_web::book::ImportResultBody,
+        library_web::ws::BookStatusEvent,
+        library_web::events::BookAvailableEvent,
+
+        //damage
+        library::damage::FlagBookBody,
+        library::damage::DamageReportRow,
+        library_web::damage::CreatedFlagBody,
+        library_web::damage::FlagsBody,
+
+        //error
+        helper::web::ErrorBody,
+        helper::web::ServerErr,
+
+        //review
+        library::review::RatingBody,
+        library::review::ReviewBody,
+        library::review::ReviewRow,
+        library_web::review::CreatedRatingBody,
+        library_web::review::CreatedReviewBody,
+        library_web::review::ReviewsBody,
+
+        //stats
+        library::stats::TopBookRow,
+        library::stats::TopUserRow,
+        library_web::stats::TopBooksBody,
+        library::stats::CategoryStat,
+        library::stats::MonthlyRentalStat,
+        library::stats::RentalRateStats,
+        library_web::stats::TopUsersBody,
+        library_web::stats::CategoryDistributionBody,
+        library_web::stats::MonthlyRentalsBody,
+
+        //tag
+        library::tag::TagBookBody,
+        library::tag::TagRow,
+        library_web::tag::BookTagsBody,
+        library_web::tag::TagsBody,
     
         //user
         library::user::User,
         library::user::RentBook,
+        library::user::RentBooks,
         library::user::UserRow,
         library::user::UserRentBook,
         library::user::UserHistoryRow,
+        library::user::UserUpdate,
+        library::user::UserStats,
+        library::user::BlacklistedUserRow,
+        library::user::InactiveUserRow,
         library_web::user::CreatedUserBody,
+        library_web::user::RegisterAndRentBody,
+        library_web::user::RegisteredBody,
         library_web::user::RentedBookBody,
+        library_web::user::RentedBooksBody,
+        library_web::user::TransferBody,
+        library_web::user::SetDueDateBody,
         library_web::user::UsersBody,
         library_web::user::GetUserBody,
+        library_web::user::UserProfileBody,
+        library_web::user::UpdatedUserBody,
+        library_web::user::UserStatsBody,
+        library_web::user::OverdueRentalsBody,
+        library_web::user::ChangeNationIdBody,
+        library_web::user::UserMessageBody,
+        library_web::user::BlacklistedUsersBody,
+        library_web::user::InactiveUsersBody,
+
+        //version
+        helper::web::ApiVersion,
+        library_web::version::VersionBody,
 
         ),
     ),
    
 )]
 pub struct ApiDoc;
+
+/// Adds the `BearerAuth` security scheme, a JWT sent in the `Authorization`
+/// header, referenced by the write endpoints.
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "BearerAuth",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+    }
+}
+
+/// Serves the OpenAPI spec as YAML, for the tools not reading the JSON one
+/// served at `/api/docs/openapi.json`.
+///
+/// The spec is serialized on the first request and reused afterwards.
+pub async fn openapi_yaml() -> impl IntoResponse {
+    static SPEC: OnceLock<String> = OnceLock::new();
+    let spec = SPEC.get_or_init(|| {
+        serde_yaml::to_string(&ApiDoc::openapi()).expect("failed to serialize the spec")
+    });
+    ([(CONTENT_TYPE, "text/yaml")], spec.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_security_schemes() {
+        let spec: Value = serde_json::from_str(
+            &ApiDoc::openapi()
+                .to_json()
+                .expect("failed to serialize the spec"),
+        )
+        .expect("failed to parse the spec");
+        assert_eq!(
+            spec["components"]["securitySchemes"]["BearerAuth"],
+            json!({"type": "http", "scheme": "bearer", "bearerFormat": "JWT"})
+        );
+        // only the write endpoints are protected
+        let bearer_auth = json!([{"BearerAuth": []}]);
+        assert_eq!(
+            spec["paths"]["/api/v1/book/create"]["post"]["security"],
+            bearer_auth
+        );
+        assert_eq!(
+            spec["paths"]["/api/v1/user/{nation_id}"]["delete"]["security"],
+            bearer_auth
+        );
+        assert_eq!(spec["paths"]["/api/v1/book"]["get"]["security"], Value::Null);
+    }
+
+    #[test]
+    fn test_versioned_paths() {
+        let openapi = ApiDoc::openapi();
+        assert_eq!(openapi.info.version, "1.0.0");
+        assert!(openapi
+            .paths
+            .paths
+            .keys()
+            .all(|path| path == "/api/version" || path.starts_with("/api/v1/")));
+    }
+}
@@ -0,0 +1,103 @@
+use std::{env::var, panic::Location, sync::OnceLock, time::Duration};
+
+/// Default duration above which a query is slow, in milliseconds.
+pub const DEFAULT_SLOW_QUERY_THRESHOLD_MS: u64 = 500;
+
+/// Returns the duration above which a query is slow.
+///
+/// It is read once from the `SLOW_QUERY_THRESHOLD_MS` environment variable
+/// and defaults to `DEFAULT_SLOW_QUERY_THRESHOLD_MS`.
+///
+/// ## Panics
+///
+/// This function will panic if the variable is set but is not a number of
+/// milliseconds.
+pub fn slow_query_threshold() -> Duration {
+    static THRESHOLD: OnceLock<Duration> = OnceLock::new();
+    *THRESHOLD.get_or_init(|| parse_threshold(var("SLOW_QUERY_THRESHOLD_MS").ok()))
+}
+
+/// Parses the value of `SLOW_QUERY_THRESHOLD_MS`.
+fn parse_threshold(value: Option<String>) -> Duration {
+    let millis = value.map_or(DEFAULT_SLOW_QUERY_THRESHOLD_MS, |millis| {
+        millis
+            .parse()
+            .expect("SLOW_QUERY_THRESHOLD_MS must be a number of milliseconds")
+    });
+    Duration::from_millis(millis)
+}
+
+/// Emits a `warn` event when a query took longer than `threshold`.
+///
+/// The event has the duration of the query as `elapsed_ms`, the place of
+/// the query in the source as `location`, e.g. `src/library/book.rs:540:6`,
+/// and the SQL of the query as `sql` when it is known.
+///
+/// ## Returns
+///
+/// `true` if the query was slow.
+pub fn warn_if_slow(
+    elapsed: Duration,
+    threshold: Duration,
+    location: &Location<'_>,
+    sql: Option<&str>,
+) -> bool {
+    if elapsed <= threshold {
+        return false;
+    }
+    let elapsed_ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+    match sql {
+        Some(sql) => {
+            tracing::warn!(elapsed_ms, sql = sql.trim(), location = %location, "slow query detected")
+        }
+        None => tracing::warn!(elapsed_ms, location = %location, "slow query detected"),
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_threshold() {
+        assert_eq!(
+            parse_threshold(None),
+            Duration::from_millis(DEFAULT_SLOW_QUERY_THRESHOLD_MS)
+        );
+        assert_eq!(
+            parse_threshold(Some("50".to_owned())),
+            Duration::from_millis(50)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "SLOW_QUERY_THRESHOLD_MS")]
+    fn test_parse_threshold_invalid() {
+        parse_threshold(Some("1s".to_owned()));
+    }
+
+    #[test]
+    fn test_warn_if_slow() {
+        let threshold = Duration::from_millis(100);
+        let location = Location::caller();
+        assert!(!warn_if_slow(
+            Duration::from_millis(100),
+            threshold,
+            location,
+            None
+        ));
+        assert!(warn_if_slow(
+            Duration::from_millis(101),
+            threshold,
+            location,
+            None
+        ));
+        assert!(warn_if_slow(
+            Duration::from_millis(101),
+            threshold,
+            location,
+            Some("SELECT 1")
+        ));
+    }
+}
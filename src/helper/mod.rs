@@ -1 +1,3 @@
-pub mod web;
\ No newline at end of file
+pub mod db;
+pub mod pagination;
+pub mod web;
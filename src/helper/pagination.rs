@@ -0,0 +1,172 @@
+use crate::helper::web::ErrorResponse;
+use axum::{
+    http::{
+        header::{HOST, LINK},
+        HeaderMap, HeaderValue, StatusCode, Uri,
+    },
+    Json,
+};
+
+/// Represents a successful response of a paginated list, whose headers link
+/// to the other pages.
+pub type PaginatedResponse<T> = Result<(StatusCode, HeaderMap, Json<T>), ErrorResponse>;
+
+/// Returns the URL of the current request without its pagination parameters.
+///
+/// The URL is made of the `Host` header, relative to the server when
+/// missing, the path and the query parameters other than `limit` and
+/// `offset`, left encoded as they were sent.
+///
+/// ## Arguments
+///
+/// * `headers`: The headers of the request.
+/// * `uri`: The original URI of the request, before any nesting.
+pub fn base_url(headers: &HeaderMap, uri: &Uri) -> String {
+    let host = headers
+        .get(HOST)
+        .and_then(|host| host.to_str().ok())
+        .map(|host| format!("http://{host}"))
+        .unwrap_or_default();
+    let query = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| {
+            let key = pair.split('=').next().unwrap_or_default();
+            !pair.is_empty() && key != "limit" && key != "offset"
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    if query.is_empty() {
+        format!("{host}{}", uri.path())
+    } else {
+        format!("{host}{}?{query}", uri.path())
+    }
+}
+
+/// Adds an RFC 5988 `Link` header linking to the first, previous, next and
+/// last pages of a list.
+///
+/// The previous page is left out on the first page and the next page on the
+/// last one. Nothing is added when `limit` is not positive.
+///
+/// ## Arguments
+///
+/// * `headers`: The headers of the response.
+/// * `base_url`: The URL of the list without pagination parameters, see
+///   `base_url`.
+/// * `limit`: The number of items per page.
+/// * `offset`: The number of items skipped by the current page.
+/// * `total`: The number of items of the whole list.
+pub fn add_link_headers(
+    headers: &mut HeaderMap,
+    base_url: &str,
+    limit: i64,
+    offset: i64,
+    total: i64,
+) {
+    if limit <= 0 {
+        return;
+    }
+    let offset = offset.max(0);
+    let separator = if base_url.contains('?') { '&' } else { '?' };
+    let link = |offset: i64, rel: &str| {
+        format!("<{base_url}{separator}limit={limit}&offset={offset}>; rel=\"{rel}\"")
+    };
+
+    let last = (total - 1).max(0) / limit * limit;
+    let mut links = vec![link(0, "first")];
+    if offset > 0 {
+        links.push(link((offset - limit).max(0), "prev"));
+    }
+    if offset + limit < total {
+        links.push(link(offset + limit, "next"));
+    }
+    links.push(link(last, "last"));
+
+    if let Ok(value) = HeaderValue::from_str(&links.join(", ")) {
+        headers.insert(LINK, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(limit: i64, offset: i64, total: i64) -> String {
+        let mut headers = HeaderMap::new();
+        add_link_headers(
+            &mut headers,
+            "http://localhost/api/v1/book?name=rust",
+            limit,
+            offset,
+            total,
+        );
+        headers[LINK].to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn test_base_url() {
+        let mut headers = HeaderMap::new();
+        let uri: Uri = "/api/v1/book?limit=10&name=rust%20book&offset=20&year=2020"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            base_url(&headers, &uri),
+            "/api/v1/book?name=rust%20book&year=2020"
+        );
+        headers.insert(HOST, HeaderValue::from_static("localhost:8000"));
+        assert_eq!(
+            base_url(&headers, &uri),
+            "http://localhost:8000/api/v1/book?name=rust%20book&year=2020"
+        );
+        let uri: Uri = "/api/v1/book?limit=10".parse().unwrap();
+        assert_eq!(
+            base_url(&headers, &uri),
+            "http://localhost:8000/api/v1/book"
+        );
+    }
+
+    #[test]
+    fn test_add_link_headers_first_page() {
+        assert_eq!(
+            link(10, 0, 25),
+            "<http://localhost/api/v1/book?name=rust&limit=10&offset=0>; rel=\"first\", \
+             <http://localhost/api/v1/book?name=rust&limit=10&offset=10>; rel=\"next\", \
+             <http://localhost/api/v1/book?name=rust&limit=10&offset=20>; rel=\"last\""
+        );
+    }
+
+    #[test]
+    fn test_add_link_headers_middle_page() {
+        assert_eq!(
+            link(10, 10, 25),
+            "<http://localhost/api/v1/book?name=rust&limit=10&offset=0>; rel=\"first\", \
+             <http://localhost/api/v1/book?name=rust&limit=10&offset=0>; rel=\"prev\", \
+             <http://localhost/api/v1/book?name=rust&limit=10&offset=20>; rel=\"next\", \
+             <http://localhost/api/v1/book?name=rust&limit=10&offset=20>; rel=\"last\""
+        );
+    }
+
+    #[test]
+    fn test_add_link_headers_last_page() {
+        assert_eq!(
+            link(10, 20, 25),
+            "<http://localhost/api/v1/book?name=rust&limit=10&offset=0>; rel=\"first\", \
+             <http://localhost/api/v1/book?name=rust&limit=10&offset=10>; rel=\"prev\", \
+             <http://localhost/api/v1/book?name=rust&limit=10&offset=20>; rel=\"last\""
+        );
+    }
+
+    #[test]
+    fn test_add_link_headers_empty_list() {
+        assert_eq!(
+            link(10, 0, 0),
+            "<http://localhost/api/v1/book?name=rust&limit=10&offset=0>; rel=\"first\", \
+             <http://localhost/api/v1/book?name=rust&limit=10&offset=0>; rel=\"last\""
+        );
+        let mut headers = HeaderMap::new();
+        add_link_headers(&mut headers, "/api/v1/book", 0, 0, 25);
+        assert!(headers.get(LINK).is_none());
+    }
+}
@@ -1,40 +1,232 @@
-use axum::{http::StatusCode, response::Json};
+use crate::library::error::LibraryError;
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Json, Response as AxumResponse},
+};
 use serde::Serialize;
 use std::fmt::{Display, Formatter, Result as fmtResult};
+use utoipa::ToSchema;
 
 /// Represents a response returned by the server.
 ///
-/// The `Response` type is a tuple that consists of the following components:
+/// The `Response` type is a result that consists of the following components:
 ///
-/// - `StatusCode`: An enumeration representing the HTTP status code of the response.
-/// - `Result<Json<T>, String>`: A result type that contains either a JSON payload of
-///                              type `T` or an error message as a string.
-pub type Response<T> = (StatusCode, Result<Json<T>, String>);
+/// - `(StatusCode, Json<T>)`: The HTTP status code and the JSON payload of type
+///   `T` of a successful response.
+/// - `ErrorResponse`: The HTTP status code and the `ErrorBody` of a failed
+///   response. A `LibraryError` converts into it, so handlers propagate library
+///   errors with the `?` operator.
+pub type Response<T> = Result<(StatusCode, Json<T>), ErrorResponse>;
+
+/// Represents an error response, made of its HTTP status code and body.
+pub type ErrorResponse = (StatusCode, Json<ErrorBody>);
 
 /// Represents server errors that can occur during the execution of the application.
 ///
 /// The `ServerErr` enum provides different variants to represent various server errors.
-/// Currently, the following variant is available:
+/// Currently, the following variants are available:
 ///
 /// - `Internal`: Represents an internal server error.
-#[derive(Debug, Clone, PartialEq, Eq, Copy, Serialize, serde::Deserialize)]
+/// - `NotFound`: Represents a missing resource.
+/// - `Conflict`: Represents a conflict with the current state of a resource.
+/// - `BadRequest`: Represents an invalid request.
+/// - `Forbidden`: Represents a request that is not allowed.
+/// - `Timeout`: Represents a request that took too long to complete.
+/// - `UnsupportedMediaType`: Represents a request body of an unsupported type.
+/// - `NotAcceptable`: Represents a response type the client doesn't accept.
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Serialize, serde::Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ServerErr {
     Internal,
+    NotFound,
+    Conflict,
+    BadRequest,
+    Forbidden,
+    Timeout,
+    UnsupportedMediaType,
+    NotAcceptable,
 }
 
 impl Display for ServerErr {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmtResult {
         match self {
             ServerErr::Internal => write!(f, "Internal server error"),
+            ServerErr::NotFound => write!(f, "Not found"),
+            ServerErr::Conflict => write!(f, "Conflict"),
+            ServerErr::BadRequest => write!(f, "Bad request"),
+            ServerErr::Forbidden => write!(f, "Forbidden"),
+            ServerErr::Timeout => write!(f, "Timeout"),
+            ServerErr::UnsupportedMediaType => write!(f, "Unsupported media type"),
+            ServerErr::NotAcceptable => write!(f, "Not acceptable"),
+        }
+    }
+}
+
+/// Represents the versions of the API.
+///
+/// The routes of each version are nested under `/api/{version}`, e.g.
+/// `/api/v1/book`. Currently, the following versions are available:
+///
+/// - `V1`: The first version of the API.
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Serialize, serde::Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiVersion {
+    V1,
+}
+
+impl ApiVersion {
+    /// The version served by the unversioned paths.
+    pub const LATEST: ApiVersion = ApiVersion::V1;
+
+    /// All versions of the API.
+    pub const ALL: [ApiVersion; 1] = [ApiVersion::V1];
+
+    /// Returns the path prefix of the routes of the version, e.g. `/api/v1`.
+    pub fn prefix(&self) -> String {
+        format!("/api/{self}")
+    }
+}
+
+impl Display for ApiVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmtResult {
+        match self {
+            ApiVersion::V1 => write!(f, "v1"),
         }
     }
 }
 
-/// Represents an internal server error response.
-pub async fn internal_server_error<T>() -> Response<T> {
-    (
-        StatusCode::INTERNAL_SERVER_ERROR,
-        Err(ServerErr::Internal.to_string()),
-    )
+/// Represents the body of an error response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, serde::Deserialize, ToSchema)]
+pub struct ErrorBody {
+    pub error: ServerErr,
+    /// Details of the error, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Maps the errors of the `library` module to error responses.
+///
+/// - `LibraryError::NotFound`: `404 Not Found`.
+/// - `LibraryError::Conflict`: `409 Conflict`, with the violated constraint.
+/// - `LibraryError::Invalid`: `400 Bad Request`, with the reason of the rejection.
+/// - `LibraryError::Forbidden`: `403 Forbidden`, with the reason of the refusal.
+/// - `LibraryError::DatabaseError`: `500 Internal Server Error`. The error is
+///   logged and not exposed to the client.
+impl From<LibraryError> for ErrorResponse {
+    fn from(err: LibraryError) -> Self {
+        let (status, error, message) = match err {
+            LibraryError::NotFound => (StatusCode::NOT_FOUND, ServerErr::NotFound, None),
+            LibraryError::Conflict(_) => (
+                StatusCode::CONFLICT,
+                ServerErr::Conflict,
+                Some(err.to_string()),
+            ),
+            LibraryError::Invalid(reason) => {
+                (StatusCode::BAD_REQUEST, ServerErr::BadRequest, Some(reason))
+            }
+            LibraryError::Forbidden(reason) => {
+                (StatusCode::FORBIDDEN, ServerErr::Forbidden, Some(reason))
+            }
+            LibraryError::DatabaseError(err) => {
+                tracing::error!(error = %err, "database error");
+                (StatusCode::INTERNAL_SERVER_ERROR, ServerErr::Internal, None)
+            }
+        };
+        (status, Json(ErrorBody { error, message }))
+    }
+}
+
+impl IntoResponse for LibraryError {
+    fn into_response(self) -> AxumResponse {
+        ErrorResponse::from(self).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn error_response(err: LibraryError) -> (StatusCode, serde_json::Value) {
+        let response = err.into_response();
+        let status = response.status();
+        let bytes = hyper::body::to_bytes(response.into_body())
+            .await
+            .expect("failed to read response body");
+        let body = serde_json::from_slice(&bytes).expect("failed to deserialize response");
+        (status, body)
+    }
+
+    #[test]
+    fn test_api_version() {
+        assert_eq!(ApiVersion::V1.to_string(), "v1");
+        assert_eq!(ApiVersion::V1.prefix(), "/api/v1");
+        assert_eq!(
+            serde_json::to_value(ApiVersion::V1).unwrap(),
+            serde_json::json!("v1")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_library_error_into_response() {
+        assert_eq!(
+            error_response(LibraryError::NotFound).await,
+            (
+                StatusCode::NOT_FOUND,
+                serde_json::json!({"error": "not_found"})
+            )
+        );
+        assert_eq!(
+            error_response(LibraryError::Conflict("book_name_key".to_owned())).await,
+            (
+                StatusCode::CONFLICT,
+                serde_json::json!({
+                    "error": "conflict",
+                    "message": "duplicate value for book_name_key"
+                })
+            )
+        );
+        assert_eq!(
+            error_response(LibraryError::Invalid("invalid name".to_owned())).await,
+            (
+                StatusCode::BAD_REQUEST,
+                serde_json::json!({"error": "bad_request", "message": "invalid name"})
+            )
+        );
+        assert_eq!(
+            error_response(LibraryError::Forbidden("user is blacklisted".to_owned())).await,
+            (
+                StatusCode::FORBIDDEN,
+                serde_json::json!({"error": "forbidden", "message": "user is blacklisted"})
+            )
+        );
+        assert_eq!(
+            error_response(LibraryError::DatabaseError(sqlx::Error::PoolTimedOut)).await,
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                serde_json::json!({"error": "internal"})
+            )
+        );
+    }
+
+    #[test]
+    fn test_error_response_from_library_error() {
+        let (status, Json(body)) = ErrorResponse::from(LibraryError::NotFound);
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(
+            body,
+            ErrorBody {
+                error: ServerErr::NotFound,
+                message: None,
+            }
+        );
+        let (status, Json(body)) = ErrorResponse::from(LibraryError::Invalid("bad".to_owned()));
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body,
+            ErrorBody {
+                error: ServerErr::BadRequest,
+                message: Some("bad".to_owned()),
+            }
+        );
+    }
 }
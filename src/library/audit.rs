@@ -0,0 +1,171 @@
+use crate::library::error::LibraryError;
+use crate::telemetry::db_metrics::timed_sql;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgPool;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+/// Default number of audit log entries returned.
+pub const DEFAULT_AUDIT_LIMIT: i64 = 50;
+
+/// Maximum number of audit log entries returned.
+pub const MAX_AUDIT_LIMIT: i64 = 500;
+
+/// Represents an audit log entry of a mutating request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub entity_type: String,
+    pub entity_id: Option<String>,
+    pub action: String,
+    pub payload_json: Option<Value>,
+}
+
+/// Represents a row in the audit_log table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct AuditRow {
+    pub id: Uuid,
+    pub entity_type: String,
+    pub entity_id: Option<String>,
+    pub action: String,
+    #[schema(value_type = Option<Object>)]
+    pub payload_json: Option<Value>,
+    pub performed_at: DateTime<Utc>,
+}
+
+/// Represents a query for filtering audit log entries.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, IntoParams)]
+pub struct AuditQuery {
+    pub entity_type: Option<String>,
+    /// Number of entries to return, defaults to 50 and is capped at 500.
+    pub limit: Option<i64>,
+}
+
+/// Inserts an audit log entry into the database.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the entry belongs to.
+/// * `entry`: The audit log entry to insert.
+///
+/// ## Returns
+///
+/// The UUID of the inserted entry.
+///
+/// ## Errors
+///
+/// This function returns an error if the insertion fails or if there is an
+/// issue with the database connection.
+pub async fn insert_audit(
+    pool: &PgPool,
+    library_id: &str,
+    entry: &AuditEntry,
+) -> Result<Uuid, LibraryError> {
+    timed_sql!(sqlx::query!(
+        r#"
+            INSERT INTO audit_log (library_id, entity_type, entity_id, action, payload_json)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id
+        "#,
+        library_id,
+        entry.entity_type,
+        entry.entity_id,
+        entry.action,
+        entry.payload_json,
+    )
+    .fetch_one(pool))
+    .await
+    .map(|record| record.id)
+    .map_err(LibraryError::from)
+}
+
+/// Retrieves the most recent audit log entries based on the provided query.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose entries are retrieved.
+/// * `query`: The query parameters for filtering the entries.
+///
+/// ## Returns
+///
+/// A vector of `AuditRow` representing the matching entries, newest first.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+pub async fn audits(
+    pool: &PgPool,
+    library_id: &str,
+    query: &AuditQuery,
+) -> Result<Vec<AuditRow>, LibraryError> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_AUDIT_LIMIT)
+        .clamp(1, MAX_AUDIT_LIMIT);
+    timed_sql!(sqlx::query_as!(
+        AuditRow,
+        r#"
+        SELECT id, entity_type, entity_id, action, payload_json, performed_at
+        FROM audit_log
+        WHERE library_id = $1 AND ($2::text IS NULL OR entity_type = $2)
+        ORDER BY performed_at DESC
+        LIMIT $3
+        "#,
+        library_id,
+        query.entity_type,
+        limit,
+    )
+    .fetch_all(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::library::DEFAULT_LIBRARY_ID;
+    use crate::tests::sequential::sequential_test;
+    use serde_json::json;
+
+    sequential_test!(
+        async fn test_audit() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let entity_id = Uuid::from_u128(rand::random()).to_string();
+            let entry = AuditEntry {
+                entity_type: "test".to_owned(),
+                entity_id: Some(entity_id.clone()),
+                action: "POST /api/test/:test_id".to_owned(),
+                payload_json: Some(json!({ "name": "test" })),
+            };
+            // insert_audit
+            let result_id = insert_audit(&pool, DEFAULT_LIBRARY_ID, &entry)
+                .await
+                .expect("failed to insert audit");
+            // audits
+            let audits_result = audits(
+                &pool,
+                DEFAULT_LIBRARY_ID,
+                &AuditQuery {
+                    entity_type: Some("test".to_owned()),
+                    limit: Some(MAX_AUDIT_LIMIT),
+                },
+            )
+            .await
+            .expect("failed to get audits");
+            let row = audits_result
+                .iter()
+                .find(|row| row.id == result_id)
+                .expect("inserted audit not found");
+            assert_eq!(row.entity_id, Some(entity_id));
+            assert_eq!(row.action, entry.action);
+            assert_eq!(row.payload_json, entry.payload_json);
+            assert!(audits_result.iter().all(|row| row.entity_type == "test"));
+        }
+    );
+}
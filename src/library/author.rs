@@ -1,14 +1,57 @@
+use crate::library::error::LibraryError;
+use crate::telemetry::db_metrics::{timed_sql, TimedQuery};
+use async_stream::try_stream;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::{Stream, TryStreamExt};
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, QueryBuilder};
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
+/// Maximum number of lines accepted by an author import.
+pub const MAX_AUTHOR_IMPORT_LINES: usize = 1000;
+
+/// Default number of authors from the same country returned.
+pub const DEFAULT_COUNTRY_PEERS_LIMIT: i64 = 5;
+
+/// Maximum number of authors from the same country returned.
+pub const MAX_COUNTRY_PEERS_LIMIT: i64 = 50;
+
 /// Represents an author.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct Author {
     pub name: String,
     pub country: String,
     pub birth_date: String,
+    /// When the author was created, set by the database.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(read_only)]
+    pub created_at: Option<DateTime<Utc>>,
+    /// When the author was last updated, set by the database.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(read_only)]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl Author {
+    /// Validates the author before it is written to the database.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns a message describing the problem when the name
+    /// or country is empty, or the birth date is not a `YYYY-MM-DD` date.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".to_owned());
+        }
+        if self.country.trim().is_empty() {
+            return Err("country must not be empty".to_owned());
+        }
+        if NaiveDate::parse_from_str(&self.birth_date, "%Y-%m-%d").is_err() {
+            return Err("birth_date must be a YYYY-MM-DD date".to_owned());
+        }
+        Ok(())
+    }
 }
 
 /// Represents a row in the author table of the database.
@@ -20,12 +63,91 @@ pub struct AuthorRow {
     pub books: Option<Vec<String>>,
 }
 
+/// Represents a partial update of an author.
+///
+/// Fields that are `None` are left unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct AuthorPatch {
+    pub name: Option<String>,
+    pub country: Option<String>,
+    pub birth_date: Option<NaiveDate>,
+}
+
+impl AuthorPatch {
+    /// Returns whether the patch does not change any field.
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.country.is_none() && self.birth_date.is_none()
+    }
+
+    /// Validates the patch before it is written to the database.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns a message describing the problem when the patch
+    /// is empty or sets an empty name or country.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.is_empty() {
+            return Err("patch must set at least one field".to_owned());
+        }
+        if self
+            .name
+            .as_ref()
+            .is_some_and(|name| name.trim().is_empty())
+        {
+            return Err("name must not be empty".to_owned());
+        }
+        if self
+            .country
+            .as_ref()
+            .is_some_and(|country| country.trim().is_empty())
+        {
+            return Err("country must not be empty".to_owned());
+        }
+        Ok(())
+    }
+}
+
+/// Represents the statistics of the books of an author.
+///
+/// The books are counted by copy, the deleted ones are left out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct AuthorStats {
+    pub total_books: i64,
+    pub available_books: i64,
+    pub rented_books: i64,
+    /// The number of rentals of the books, returned or not.
+    pub total_rentals_all_time: i64,
+    /// The most rented book, ties are broken alphabetically.
+    pub most_rented_title: Option<String>,
+}
+
+/// Represents a request to merge an author entered twice into the other one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct MergeAuthorsBody {
+    /// The author kept, who gets the books of the discarded one.
+    pub keep_id: Uuid,
+    /// The author deleted once their books are reassigned.
+    pub discard_id: Uuid,
+}
+
 /// Represents a query for filtering authors.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, IntoParams)]
 pub struct AuthorQuery {
     pub name: Option<String>,
     pub country: Option<String>,
     pub birth_date: Option<String>,
+    /// Maximum number of authors to return, all matching authors if not given.
+    pub limit: Option<i64>,
+    /// Number of authors to skip, defaults to 0.
+    pub offset: Option<i64>,
+}
+
+/// Represents the query parameters for listing the authors from the same
+/// country as an author.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, IntoParams)]
+pub struct CountryPeersQuery {
+    /// Number of authors to return, defaults to 5 and is capped at 50.
+    pub limit: Option<i64>,
 }
 
 /// Inserts an author into the database.
@@ -33,6 +155,7 @@ pub struct AuthorQuery {
 /// ## Arguments
 ///
 /// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the author belongs to.
 /// * `author`: The author to insert.
 ///
 /// ## Returns
@@ -43,20 +166,74 @@ pub struct AuthorQuery {
 ///
 /// This function returns an error if the author insertion fails or
 /// if there is an issue with the database connection.
-pub async fn insert_author(pool: &PgPool, author: &Author) -> Result<Uuid, sqlx::Error> {
-    sqlx::query!(
+#[tracing::instrument(level = "debug", skip(pool, author), fields(name = %author.name))]
+pub async fn insert_author(
+    pool: &PgPool,
+    library_id: &str,
+    author: &Author,
+) -> Result<Uuid, LibraryError> {
+    timed_sql!(sqlx::query!(
         r#"
-            INSERT INTO author (name, country, birth_date)
-            VALUES ($1, $2, $3)
+            INSERT INTO author (library_id, name, country, birth_date)
+            VALUES ($1, $2, $3, $4)
             RETURNING Id
         "#,
+        library_id,
         author.name,
         author.country,
         author.birth_date,
     )
-    .fetch_one(pool)
+    .fetch_one(pool))
     .await
     .map(|record| record.id)
+    .map_err(LibraryError::from)
+}
+
+/// Inserts several authors into the database with a single statement.
+///
+/// Authors whose name is already taken, either by an existing author or by an
+/// earlier author in `authors`, are skipped.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the authors belong to.
+/// * `authors`: The authors to insert.
+///
+/// ## Returns
+///
+/// The names of the inserted authors.
+///
+/// ## Errors
+///
+/// This function returns an error if the insertion fails or if there is an
+/// issue with the database connection.
+#[tracing::instrument(level = "debug", skip(pool, authors), fields(count = authors.len()))]
+pub async fn insert_authors(
+    pool: &PgPool,
+    library_id: &str,
+    authors: &[Author],
+) -> Result<Vec<String>, LibraryError> {
+    if authors.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut query = QueryBuilder::<Postgres>::new(
+        "INSERT INTO author (library_id, name, country, birth_date) ",
+    );
+    query.push_values(authors, |mut row, author| {
+        row.push_bind(library_id)
+            .push_bind(&author.name)
+            .push_bind(&author.country)
+            .push_bind(&author.birth_date);
+    });
+    query.push(" ON CONFLICT (library_id, name) DO NOTHING RETURNING name");
+    let sql = query.sql().to_owned();
+    let rows: Vec<(String,)> = query
+        .build_query_as()
+        .fetch_all(pool)
+        .timed_sql(sql)
+        .await?;
+    Ok(rows.into_iter().map(|(name,)| name).collect())
 }
 
 /// Retrieves a list of authors from the database based on the provided query.
@@ -64,43 +241,142 @@ pub async fn insert_author(pool: &PgPool, author: &Author) -> Result<Uuid, sqlx:
 /// ## Arguments
 ///
 /// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose authors are retrieved.
 /// * `author`: The author query parameters.
 ///
 /// ## Returns
 ///
-/// A vector of `Author` objects that match the query criteria.
+/// A vector of `Author` objects that match the query criteria, ordered by
+/// name.
 ///
 /// ## Errors
 ///
 /// This function returns an error if the query fails or if there is an issue
 /// with the database connection.
-pub async fn authors(pool: &PgPool, author: &AuthorQuery) -> Result<Vec<Author>, sqlx::Error> {
-    let result = sqlx::query_as!(
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn authors(
+    pool: &PgPool,
+    library_id: &str,
+    author: &AuthorQuery,
+) -> Result<Vec<Author>, LibraryError> {
+    let result = timed_sql!(sqlx::query_as!(
         Author,
         r#"
-        SELECT name, country, birth_date FROM author
+        SELECT name, country, birth_date, created_at as "created_at?", updated_at as "updated_at?"
+        FROM author
         WHERE
-            ($1::text IS NULL OR name = $1)
-            AND ($2::text IS NULL OR country = $2)
-            AND ($3::text IS NULL OR birth_date = $3)
+            library_id = $1
+            AND ($2::text IS NULL OR name = $2)
+            AND ($3::text IS NULL OR country = $3)
+            AND ($4::text IS NULL OR birth_date = $4)
+        ORDER BY name
+        LIMIT $5 OFFSET $6
         "#,
+        library_id,
         author.name,
         author.country,
         author.birth_date,
+        author.limit,
+        author.offset.unwrap_or_default(),
     )
-    .fetch_all(pool)
+    .fetch_all(pool))
     .await?;
     if result.is_empty() {
-        return Err(sqlx::Error::RowNotFound);
+        return Err(LibraryError::NotFound);
     }
     Ok(result)
 }
 
+/// Counts the authors matching the provided query.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose authors are counted.
+/// * `author`: The author query parameters.
+///
+/// ## Returns
+///
+/// The number of authors that match the query criteria, 0 if none does.
+/// `limit` and `offset` are ignored.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn count_authors(
+    pool: &PgPool,
+    library_id: &str,
+    author: &AuthorQuery,
+) -> Result<i64, LibraryError> {
+    timed_sql!(sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM author
+        WHERE
+            library_id = $1
+            AND ($2::text IS NULL OR name = $2)
+            AND ($3::text IS NULL OR country = $3)
+            AND ($4::text IS NULL OR birth_date = $4)
+        "#,
+        library_id,
+        author.name,
+        author.country,
+        author.birth_date,
+    )
+    .fetch_one(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Streams all authors of a library.
+///
+/// The authors are read from the database while the stream is consumed, so
+/// the whole table is never held in memory. The stream owns the pool so it
+/// can outlive the caller, e.g. as a response body.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose authors are streamed.
+///
+/// ## Returns
+///
+/// A stream of `Author` objects, ordered by name.
+///
+/// ## Errors
+///
+/// The stream yields an error if the query fails or if there is an issue with
+/// the database connection.
+pub fn author_records(
+    pool: PgPool,
+    library_id: String,
+) -> impl Stream<Item = Result<Author, LibraryError>> + Send + 'static {
+    try_stream! {
+        let mut records = timed_sql!(sqlx::query_as!(
+            Author,
+            r#"
+            SELECT name, country, birth_date, created_at as "created_at?", updated_at as "updated_at?"
+            FROM author
+            WHERE library_id = $1
+            ORDER BY name
+            "#,
+            library_id,
+        )
+        .fetch(&pool));
+        while let Some(record) = records.try_next().await? {
+            yield record;
+        }
+    }
+}
+
 /// Retrieves detailed information about a specific author from the database.
 ///
 /// ## Arguments
 ///
 /// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the author belongs to.
 /// * `author_id`: The ID of the author to retrieve.
 ///
 /// ## Returns
@@ -111,26 +387,369 @@ pub async fn authors(pool: &PgPool, author: &AuthorQuery) -> Result<Vec<Author>,
 ///
 /// This function returns an error if the retrieval fails or if there is an issue
 /// with the database connection.
-pub async fn get_author(pool: &PgPool, author_id: Uuid) -> Result<AuthorRow, sqlx::Error> {
-    sqlx::query_as!(
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn get_author(
+    pool: &PgPool,
+    library_id: &str,
+    author_id: Uuid,
+) -> Result<AuthorRow, LibraryError> {
+    timed_sql!(sqlx::query_as!(
         AuthorRow,
         r#"
         SELECT author.name,
-            (SELECT array_agg(book.name) FROM book WHERE book.author = author.name) as books,
+            (SELECT array_agg(book.name) FROM book
+                WHERE book.library_id = author.library_id AND book.author = author.name
+                    AND book.deleted_at IS NULL) as books,
             author.birth_date,
             author.country
         FROM author
-        WHERE author.id = $1;
+        WHERE author.library_id = $1 AND author.id = $2;
         "#,
+        library_id,
         author_id,
     )
-    .fetch_one(pool)
+    .fetch_one(pool))
     .await
+    .map_err(LibraryError::from)
+}
+
+/// Retrieves detailed information about an author by their name.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the author belongs to.
+/// * `name`: The name of the author to retrieve.
+///
+/// ## Returns
+///
+/// An `AuthorRow` object containing detailed information about the author.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if there is no author
+/// with the given name, or an error if the retrieval fails or there is an
+/// issue with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn get_author_by_name(
+    pool: &PgPool,
+    library_id: &str,
+    name: &str,
+) -> Result<AuthorRow, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        AuthorRow,
+        r#"
+        SELECT author.name,
+            (SELECT array_agg(book.name) FROM book
+                WHERE book.library_id = author.library_id AND book.author = author.name
+                    AND book.deleted_at IS NULL) as books,
+            author.birth_date,
+            author.country
+        FROM author
+        WHERE author.library_id = $1 AND author.name = $2;
+        "#,
+        library_id,
+        name,
+    )
+    .fetch_one(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Retrieves the statistics of the books of an author.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the author belongs to.
+/// * `author_id`: The ID of the author.
+///
+/// ## Returns
+///
+/// The `AuthorStats` of the author, with zero counts if they have no books.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if there is no author with
+/// the given ID, or an error if the retrieval fails or there is an issue with
+/// the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn author_stats(
+    pool: &PgPool,
+    library_id: &str,
+    author_id: Uuid,
+) -> Result<AuthorStats, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        AuthorStats,
+        r#"
+        WITH books AS (
+            SELECT book.name, book.copy_number, book.status
+            FROM book
+            JOIN author ON author.library_id = book.library_id AND author.name = book.author
+            WHERE book.library_id = $1 AND author.id = $2 AND book.deleted_at IS NULL
+        ),
+        rentals AS (
+            SELECT users_history.book_name
+            FROM users_history
+            JOIN books ON books.name = users_history.book_name
+                AND books.copy_number = users_history.copy_number
+            WHERE users_history.library_id = $1
+        )
+        SELECT
+            (SELECT COUNT(*) FROM books) as "total_books!",
+            (SELECT COUNT(*) FROM books WHERE status = 'Available') as "available_books!",
+            (SELECT COUNT(*) FROM books WHERE status = 'Rented') as "rented_books!",
+            (SELECT COUNT(*) FROM rentals) as "total_rentals_all_time!",
+            (SELECT book_name FROM rentals
+                GROUP BY book_name
+                ORDER BY COUNT(*) DESC, book_name
+                LIMIT 1) as most_rented_title
+        FROM author
+        WHERE library_id = $1 AND id = $2
+        "#,
+        library_id,
+        author_id,
+    )
+    .fetch_one(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Retrieves the authors from a country, except the given one.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the authors belong to.
+/// * `country`: The country of the authors.
+/// * `exclude_id`: The ID of the author to leave out.
+/// * `limit`: The maximum number of authors to return.
+///
+/// ## Returns
+///
+/// A vector of `Author` objects, ordered by name. The vector is empty if no
+/// other author is from the country.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn authors_by_country(
+    pool: &PgPool,
+    library_id: &str,
+    country: &str,
+    exclude_id: Uuid,
+    limit: i64,
+) -> Result<Vec<Author>, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        Author,
+        r#"
+        SELECT name, country, birth_date, created_at as "created_at?", updated_at as "updated_at?"
+        FROM author
+        WHERE library_id = $1 AND country = $2 AND id <> $3
+        ORDER BY name
+        LIMIT $4
+        "#,
+        library_id,
+        country,
+        exclude_id,
+        limit,
+    )
+    .fetch_all(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Partially updates an author.
+///
+/// Only the fields set in `patch` are changed. When the name changes, the
+/// `author` column of the author's books is updated in the same transaction.
+/// An empty patch leaves the author unchanged.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the author belongs to.
+/// * `author_id`: The ID of the author to update.
+/// * `patch`: The fields to update.
+///
+/// ## Returns
+///
+/// The updated `Author`.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if there is no author with
+/// the given ID, or an error if the update fails (e.g. the new name is already
+/// taken) or there is an issue with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn patch_author(
+    pool: &PgPool,
+    library_id: &str,
+    author_id: Uuid,
+    patch: &AuthorPatch,
+) -> Result<Author, LibraryError> {
+    let mut transaction = pool.begin().await?;
+
+    let current = timed_sql!(sqlx::query_as!(
+        Author,
+        r#"
+        SELECT name, country, birth_date,
+            created_at as "created_at?", updated_at as "updated_at?"
+        FROM author
+        WHERE library_id = $1 AND id = $2
+        FOR UPDATE
+        "#,
+        library_id,
+        author_id,
+    )
+    .fetch_one(&mut transaction))
+    .await?;
+    if patch.is_empty() {
+        transaction.rollback().await?;
+        return Ok(current);
+    }
+
+    // the books keep referring to the old name until they are updated below
+    timed_sql!(sqlx::query!("SET CONSTRAINTS book_author_fkey DEFERRED").execute(&mut transaction))
+        .await?;
+
+    let mut query = QueryBuilder::<Postgres>::new("UPDATE author SET ");
+    let mut fields = query.separated(", ");
+    if let Some(name) = &patch.name {
+        fields.push("name = ").push_bind_unseparated(name);
+    }
+    if let Some(country) = &patch.country {
+        fields.push("country = ").push_bind_unseparated(country);
+    }
+    if let Some(birth_date) = &patch.birth_date {
+        fields
+            .push("birth_date = ")
+            .push_bind_unseparated(birth_date.format("%Y-%m-%d").to_string());
+    }
+    query
+        .push(" WHERE id = ")
+        .push_bind(author_id)
+        .push(" RETURNING name, country, birth_date, created_at, updated_at");
+    let sql = query.sql().to_owned();
+    let author = query
+        .build_query_as::<Author>()
+        .fetch_one(&mut transaction)
+        .timed_sql(sql)
+        .await?;
+
+    if author.name != current.name {
+        timed_sql!(sqlx::query!(
+            r#"
+            UPDATE book
+            SET author = $2
+            WHERE library_id = $1 AND author = $3
+            "#,
+            library_id,
+            author.name,
+            current.name,
+        )
+        .execute(&mut transaction))
+        .await?;
+    }
+
+    transaction.commit().await?;
+    Ok(author)
+}
+
+/// Merges an author into another one.
+///
+/// In a single transaction, the books of the discarded author, deleted ones
+/// included, are reassigned to the kept author, then the discarded author is
+/// deleted. The kept author is left unchanged.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the authors belong to.
+/// * `keep_id`: The ID of the author to keep.
+/// * `discard_id`: The ID of the author to delete.
+///
+/// ## Returns
+///
+/// The number of books reassigned to the kept author.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::Invalid` if both IDs are the same,
+/// `LibraryError::NotFound` if either author does not exist, or an error if
+/// there is an issue with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn merge_authors(
+    pool: &PgPool,
+    library_id: &str,
+    keep_id: Uuid,
+    discard_id: Uuid,
+) -> Result<u64, LibraryError> {
+    if keep_id == discard_id {
+        return Err(LibraryError::Invalid(
+            "an author cannot be merged into themselves".to_owned(),
+        ));
+    }
+    let mut transaction = pool.begin().await?;
+
+    // both authors are locked in the same order by concurrent merges
+    let authors = timed_sql!(sqlx::query!(
+        r#"
+        SELECT id, name
+        FROM author
+        WHERE library_id = $1 AND id = ANY($2)
+        ORDER BY id
+        FOR UPDATE
+        "#,
+        library_id,
+        &[keep_id, discard_id][..],
+    )
+    .fetch_all(&mut transaction))
+    .await?;
+    let name = |id: Uuid| {
+        authors
+            .iter()
+            .find(|author| author.id == id)
+            .map(|author| author.name.clone())
+            .ok_or(LibraryError::NotFound)
+    };
+    let (keep_name, discard_name) = (name(keep_id)?, name(discard_id)?);
+
+    let reassigned = timed_sql!(sqlx::query!(
+        r#"
+        UPDATE book
+        SET author = $2
+        WHERE library_id = $1 AND author = $3
+        "#,
+        library_id,
+        keep_name,
+        discard_name,
+    )
+    .execute(&mut transaction))
+    .await?
+    .rows_affected();
+    timed_sql!(sqlx::query!(
+        "DELETE FROM author WHERE library_id = $1 AND id = $2",
+        library_id,
+        discard_id,
+    )
+    .execute(&mut transaction))
+    .await?;
+
+    transaction.commit().await?;
+    Ok(reassigned)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::library::book::{self, Book};
+    use crate::library::stats::tests::rent_book_times;
+    use crate::library::user::{self, User, UserRentBook};
+    use crate::middleware::library::DEFAULT_LIBRARY_ID;
+    use crate::tests::sequential::sequential_test;
     use chrono::{Duration, Utc};
     use fake::faker::address::en::CountryName;
     use fake::faker::name::en::Name as FakeName;
@@ -148,6 +767,8 @@ mod tests {
                 name: FakeName().fake::<String>(),
                 country: CountryName().fake::<String>(),
                 birth_date: Self::create_fake_date().await,
+                created_at: None,
+                updated_at: None,
             }
         }
         /// Creates a fake birth date within a range of 20 to 60 years ago.
@@ -164,79 +785,388 @@ mod tests {
         }
     }
 
-    #[tokio::test]
-    async fn test_author() {
-        let pool = crate::database::postgres::init::pg_pool()
+    sequential_test!(
+        async fn test_author() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let author = Author::create_fake_author().await;
+            // insert_author
+            let result_id = insert_author(&pool, DEFAULT_LIBRARY_ID, &author)
+                .await
+                .expect("failed to insert author");
+            let fetched_author = timed_sql!(sqlx::query_as!(
+                Author,
+                r#"
+                SELECT name, country, birth_date,
+                    created_at as "created_at?", updated_at as "updated_at?"
+                FROM author WHERE Id = $1
+                "#,
+                result_id,
+            )
+            .fetch_one(&pool))
             .await
-            .expect("failed to connect to postgres");
-        let author = Author::create_fake_author().await;
-        // insert_author
-        let result_id = insert_author(&pool, &author)
+            .expect("unmatched author");
+            assert!(fetched_author.created_at.is_some());
+            assert_eq!(fetched_author.created_at, fetched_author.updated_at);
+            assert_eq!(
+                Author {
+                    created_at: None,
+                    updated_at: None,
+                    ..fetched_author
+                },
+                author
+            );
+            // get_author
+            let get_author_result = get_author(&pool, DEFAULT_LIBRARY_ID, result_id)
+                .await
+                .expect("failed to get author");
+            assert_eq!(author.name, get_author_result.name);
+            // authors
+            // 1: all authors
+            let authors_result = authors(
+                &pool,
+                DEFAULT_LIBRARY_ID,
+                &AuthorQuery {
+                    name: None,
+                    country: None,
+                    birth_date: None,
+                    limit: None,
+                    offset: None,
+                },
+            )
+            .await;
+            assert!(authors_result
+                .as_ref()
+                .map(|authors| !authors.is_empty())
+                .unwrap_or_else(|_| false));
+            assert!(authors_result.is_ok());
+            // 2: Get authors by country (with country filter)
+            let authors_by_country_result = authors(
+                &pool,
+                DEFAULT_LIBRARY_ID,
+                &AuthorQuery {
+                    name: None,
+                    country: Some(author.country.clone()),
+                    birth_date: None,
+                    limit: None,
+                    offset: None,
+                },
+            )
+            .await;
+            assert!(authors_by_country_result.is_ok());
+            // 3: Get authors by birth date (with birth date filter)
+            let authors_by_birth_date_result = authors(
+                &pool,
+                DEFAULT_LIBRARY_ID,
+                &AuthorQuery {
+                    name: None,
+                    country: None,
+                    birth_date: Some(author.birth_date.clone()),
+                    limit: None,
+                    offset: None,
+                },
+            )
+            .await;
+            assert!(authors_by_birth_date_result.is_ok());
+            // 4: exact given criterias
+            let authors_by_all_criteria = authors(
+                &pool,
+                DEFAULT_LIBRARY_ID,
+                &AuthorQuery {
+                    name: Some(author.name.clone()),
+                    country: Some(author.country.clone()),
+                    birth_date: Some(author.birth_date.clone()),
+                    limit: None,
+                    offset: None,
+                },
+            )
+            .await;
+            assert!(authors_by_all_criteria.is_ok());
+        }
+    );
+
+    sequential_test!(
+        async fn test_get_author_by_name() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let book = Book::create_fake_book(&pool).await;
+            book::insert_book(&pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+            let author = get_author_by_name(&pool, DEFAULT_LIBRARY_ID, &book.author)
+                .await
+                .expect("failed to get author by name");
+            assert_eq!(author.name, book.author);
+            assert_eq!(author.books, Some(vec![book.name]));
+
+            let result = get_author_by_name(&pool, DEFAULT_LIBRARY_ID, "no such author").await;
+            assert!(matches!(result, Err(LibraryError::NotFound)));
+        }
+    );
+
+    sequential_test!(
+        async fn test_validate_author() {
+            let mut author = Author::create_fake_author().await;
+            assert!(author.validate().is_ok());
+            author.birth_date = "15/06/1990".to_owned();
+            assert!(author.validate().is_err());
+            author.birth_date = "1990-06-15".to_owned();
+            author.name = " ".to_owned();
+            assert!(author.validate().is_err());
+            author.name = "name".to_owned();
+            author.country = String::new();
+            assert!(author.validate().is_err());
+        }
+    );
+
+    sequential_test!(
+        async fn test_insert_authors() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let existing = Author::create_fake_author().await;
+            insert_author(&pool, DEFAULT_LIBRARY_ID, &existing)
+                .await
+                .expect("failed to insert author");
+            let first = Author::create_fake_author().await;
+            let second = Author::create_fake_author().await;
+            let authors = [first.clone(), existing, second.clone(), first.clone()];
+            let inserted = insert_authors(&pool, DEFAULT_LIBRARY_ID, &authors)
+                .await
+                .expect("failed to insert authors");
+            assert_eq!(inserted.len(), 2);
+            assert!(inserted.contains(&first.name));
+            assert!(inserted.contains(&second.name));
+            let inserted = insert_authors(&pool, DEFAULT_LIBRARY_ID, &[])
+                .await
+                .expect("failed to insert no authors");
+            assert!(inserted.is_empty());
+        }
+    );
+
+    #[test]
+    fn test_validate_author_patch() {
+        let mut patch = AuthorPatch::default();
+        assert!(patch.validate().is_err());
+        patch.country = Some("Turkey".to_owned());
+        assert!(patch.validate().is_ok());
+        patch.name = Some(" ".to_owned());
+        assert!(patch.validate().is_err());
+        patch.name = None;
+        patch.country = Some(String::new());
+        assert!(patch.validate().is_err());
+    }
+
+    sequential_test!(
+        async fn test_patch_author() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let author = Author::create_fake_author().await;
+            let author_id = insert_author(&pool, DEFAULT_LIBRARY_ID, &author)
+                .await
+                .expect("failed to insert author");
+            let mut book = Book::create_fake_book(&pool).await;
+            book.author = author.name.clone();
+            book::insert_book(&pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+
+            // empty patch
+            let patched_author = patch_author(
+                &pool,
+                DEFAULT_LIBRARY_ID,
+                author_id,
+                &AuthorPatch::default(),
+            )
             .await
-            .expect("failed to insert author");
-        let fetched_author = sqlx::query_as!(
-            Author,
-            r#"
-            SELECT name, country, birth_date FROM author WHERE Id = $1
-            "#,
-            result_id,
-        )
-        .fetch_one(&pool)
-        .await;
-        assert_eq!(fetched_author.expect("unmatched author"), author);
-        // get_author
-        let get_author_result = get_author(&pool, result_id)
+            .expect("failed to patch author");
+            assert_eq!(patched_author.name, author.name);
+            assert_eq!(patched_author.created_at, patched_author.updated_at);
+
+            // only birth_date
+            let birth_date = NaiveDate::from_ymd_opt(1950, 1, 31).unwrap();
+            let patch = AuthorPatch {
+                birth_date: Some(birth_date),
+                ..Default::default()
+            };
+            let patched_author = patch_author(&pool, DEFAULT_LIBRARY_ID, author_id, &patch)
+                .await
+                .expect("failed to patch author");
+            assert_eq!(patched_author.name, author.name);
+            assert_eq!(patched_author.country, author.country);
+            assert_eq!(patched_author.birth_date, "1950-01-31");
+            assert!(patched_author.updated_at > patched_author.created_at);
+
+            // the name change cascades to the books
+            let new_name = format!("{} {}", author.name, Uuid::nil());
+            let patch = AuthorPatch {
+                name: Some(new_name.clone()),
+                ..Default::default()
+            };
+            let patched_author = patch_author(&pool, DEFAULT_LIBRARY_ID, author_id, &patch)
+                .await
+                .expect("failed to patch author");
+            assert_eq!(patched_author.name, new_name);
+            let get_author_result = get_author(&pool, DEFAULT_LIBRARY_ID, author_id)
+                .await
+                .expect("failed to get author");
+            assert_eq!(get_author_result.books, Some(vec![book.name.clone()]));
+            let book_author = timed_sql!(sqlx::query_scalar!(
+                "SELECT author FROM book WHERE name = $1",
+                book.name
+            )
+            .fetch_one(&pool))
             .await
-            .expect("failed to get author");
-        assert_eq!(author.name, get_author_result.name);
-        // authors
-        // 1: all authors
-        let authors_result = authors(
-            &pool,
-            &AuthorQuery {
-                name: None,
-                country: None,
-                birth_date: None,
-            },
-        )
-        .await;
-        assert!(authors_result
-            .as_ref()
-            .map(|authors| !authors.is_empty())
-            .unwrap_or_else(|_| false));
-        assert!(authors_result.is_ok());
-        // 2: Get authors by country (with country filter)
-        let authors_by_country_result = authors(
-            &pool,
-            &AuthorQuery {
-                name: None,
-                country: Some(author.country.clone()),
-                birth_date: None,
-            },
-        )
-        .await;
-        assert!(authors_by_country_result.is_ok());
-        // 3: Get authors by birth date (with birth date filter)
-        let authors_by_birth_date_result = authors(
-            &pool,
-            &AuthorQuery {
-                name: None,
-                country: None,
-                birth_date: Some(author.birth_date.clone()),
-            },
-        )
-        .await;
-        assert!(authors_by_birth_date_result.is_ok());
-        // 4: exact given criterias
-        let authors_by_all_criteria = authors(
-            &pool,
-            &AuthorQuery {
-                name: Some(author.name.clone()),
-                country: Some(author.country.clone()),
-                birth_date: Some(author.birth_date.clone()),
-            },
-        )
-        .await;
-        assert!(authors_by_all_criteria.is_ok());
-    }
+            .expect("failed to fetch book");
+            assert_eq!(book_author, new_name);
+
+            // the name of another author cannot be taken
+            let another_author = Author::create_fake_author().await;
+            insert_author(&pool, DEFAULT_LIBRARY_ID, &another_author)
+                .await
+                .expect("failed to insert author");
+            let patch = AuthorPatch {
+                name: Some(another_author.name),
+                ..Default::default()
+            };
+            assert!(patch_author(&pool, DEFAULT_LIBRARY_ID, author_id, &patch)
+                .await
+                .is_err());
+
+            // unknown author
+            let result = patch_author(&pool, DEFAULT_LIBRARY_ID, Uuid::nil(), &patch).await;
+            assert!(matches!(result, Err(LibraryError::NotFound)));
+        }
+    );
+
+    sequential_test!(
+        async fn test_merge_authors() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let keep = Author::create_fake_author().await;
+            let keep_id = insert_author(&pool, DEFAULT_LIBRARY_ID, &keep)
+                .await
+                .expect("failed to insert author");
+            let discard = Author::create_fake_author().await;
+            let discard_id = insert_author(&pool, DEFAULT_LIBRARY_ID, &discard)
+                .await
+                .expect("failed to insert author");
+            let mut books = vec![];
+            for author in [&keep, &discard, &discard] {
+                let mut book = Book::create_fake_book(&pool).await;
+                book.author = author.name.clone();
+                book::insert_book(&pool, DEFAULT_LIBRARY_ID, &book)
+                    .await
+                    .expect("failed to insert book");
+                books.push(book.name);
+            }
+
+            // same or unknown authors
+            assert!(matches!(
+                merge_authors(&pool, DEFAULT_LIBRARY_ID, keep_id, keep_id).await,
+                Err(LibraryError::Invalid(_))
+            ));
+            assert!(matches!(
+                merge_authors(&pool, DEFAULT_LIBRARY_ID, keep_id, Uuid::nil()).await,
+                Err(LibraryError::NotFound)
+            ));
+            assert!(matches!(
+                merge_authors(&pool, DEFAULT_LIBRARY_ID, Uuid::nil(), discard_id).await,
+                Err(LibraryError::NotFound)
+            ));
+
+            let reassigned = merge_authors(&pool, DEFAULT_LIBRARY_ID, keep_id, discard_id)
+                .await
+                .expect("failed to merge authors");
+            assert_eq!(reassigned, 2);
+            let mut kept_books = get_author(&pool, DEFAULT_LIBRARY_ID, keep_id)
+                .await
+                .expect("failed to get author")
+                .books
+                .unwrap_or_default();
+            kept_books.sort();
+            books.sort();
+            assert_eq!(kept_books, books);
+            assert!(matches!(
+                get_author(&pool, DEFAULT_LIBRARY_ID, discard_id).await,
+                Err(LibraryError::NotFound)
+            ));
+        }
+    );
+
+    sequential_test!(
+        async fn test_author_stats() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let author = Author::create_fake_author().await;
+            let author_id = insert_author(&pool, DEFAULT_LIBRARY_ID, &author)
+                .await
+                .expect("failed to insert author");
+
+            // no books
+            let stats = author_stats(&pool, DEFAULT_LIBRARY_ID, author_id)
+                .await
+                .expect("failed to get author stats");
+            assert_eq!(
+                stats,
+                AuthorStats {
+                    total_books: 0,
+                    available_books: 0,
+                    rented_books: 0,
+                    total_rentals_all_time: 0,
+                    most_rented_title: None,
+                }
+            );
+
+            let mut books = vec![];
+            for _ in 0..3 {
+                let mut book = Book::create_fake_book(&pool).await;
+                book.author = author.name.clone();
+                book::insert_book(&pool, DEFAULT_LIBRARY_ID, &book)
+                    .await
+                    .expect("failed to insert book");
+                books.push(book.name);
+            }
+            let user = User::create_fake_user().await;
+            user::insert_user(&pool, DEFAULT_LIBRARY_ID, &user)
+                .await
+                .expect("failed to insert user");
+            // the first book is rented three times and still rented, the
+            // second one once and returned, the third one never
+            rent_book_times(&pool, &user.nation_id, &books[0], 2).await;
+            let user_rent_book = UserRentBook {
+                nation_id: user.nation_id.clone(),
+                book_name: books[0].clone(),
+                due_date: "2999-01-01".to_owned(),
+            };
+            user::rent_book(&pool, DEFAULT_LIBRARY_ID, &user_rent_book)
+                .await
+                .expect("failed to rent book");
+            rent_book_times(&pool, &user.nation_id, &books[1], 1).await;
+
+            let stats = author_stats(&pool, DEFAULT_LIBRARY_ID, author_id)
+                .await
+                .expect("failed to get author stats");
+            assert_eq!(
+                stats,
+                AuthorStats {
+                    total_books: 3,
+                    available_books: 2,
+                    rented_books: 1,
+                    total_rentals_all_time: 4,
+                    most_rented_title: Some(books[0].clone()),
+                }
+            );
+
+            assert!(matches!(
+                author_stats(&pool, DEFAULT_LIBRARY_ID, Uuid::nil()).await,
+                Err(LibraryError::NotFound)
+            ));
+        }
+    );
 }
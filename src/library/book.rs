@@ -1,8 +1,59 @@
-use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use crate::library::error::LibraryError;
+use crate::library::tag::normalize_tag;
+use crate::telemetry::db_metrics::timed_sql;
+use async_stream::try_stream;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::{Stream, TryStreamExt};
+use serde::{Deserialize, Deserializer, Serialize};
+use sqlx::{Acquire, PgExecutor, PgPool};
+use url::Url;
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
+/// Maximum number of characters allowed in a book description.
+pub const MAX_DESCRIPTION_LEN: usize = 5000;
+
+/// Default number of days a book counts as a new arrival.
+pub const DEFAULT_NEW_ARRIVALS_DAYS: i64 = 30;
+
+/// Default number of new arrivals returned.
+pub const DEFAULT_NEW_ARRIVALS_LIMIT: i64 = 10;
+
+/// Maximum number of new arrivals returned.
+pub const MAX_NEW_ARRIVALS_LIMIT: i64 = 100;
+
+/// Default number of books recommended to a user.
+pub const DEFAULT_RECOMMENDATIONS_LIMIT: i64 = 5;
+
+/// Maximum number of books recommended to a user.
+pub const MAX_RECOMMENDATIONS_LIMIT: i64 = 50;
+
+/// Default number of books similar to a book returned.
+pub const DEFAULT_SIMILAR_BOOKS_LIMIT: i64 = 5;
+
+/// Maximum number of books similar to a book returned.
+pub const MAX_SIMILAR_BOOKS_LIMIT: i64 = 50;
+
+/// Default number of books returned per page of a category, a year or an
+/// author.
+pub const DEFAULT_BOOKS_PAGE_LIMIT: i64 = 20;
+
+/// Maximum number of books returned per page of a category, a year or an
+/// author.
+pub const MAX_BOOKS_PAGE_LIMIT: i64 = 100;
+
+/// Earliest publication year accepted when listing the books of a year.
+pub const MIN_BOOK_YEAR: i32 = 1000;
+
+/// Latest publication year accepted when listing the books of a year.
+pub const MAX_BOOK_YEAR: i32 = 2100;
+
+/// Maximum number of copies of a book registered at once.
+pub const MAX_BOOK_COPIES: i32 = 100;
+
+/// Maximum number of books deleted at once.
+pub const MAX_BULK_DELETE_BOOKS: usize = 100;
+
 /// Represents the status of a book.
 #[derive(Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize, sqlx::Type, ToSchema)]
 #[serde(rename_all = "lowercase")]
@@ -26,16 +77,408 @@ pub struct Book {
     pub category: String,
     pub status: Status,
     pub author: String,
+    pub description: Option<String>,
+    pub publisher: Option<String>,
+    pub edition: Option<i32>,
+    /// ISO-639-1 two-letter language code, e.g. `en`.
+    pub language: Option<String>,
+    pub page_count: Option<i32>,
+    pub cover_image_url: Option<String>,
+    /// ISBN-10 or ISBN-13, hyphens are allowed.
+    pub isbn: Option<String>,
+    /// The number of the physical copy of the book, starting from 1.
+    #[serde(default = "default_copy_number")]
+    #[schema(default = 1, minimum = 1)]
+    pub copy_number: i32,
+    /// When the book was created, set by the database.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(read_only)]
+    pub created_at: Option<DateTime<Utc>>,
+    /// When the book was last updated, set by the database.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(read_only)]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Represents a book without its description, as returned by list queries.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct BookSummary {
+    pub id: Uuid,
+    pub name: String,
+    pub year: i32,
+    pub category: String,
+    pub status: Status,
+    pub author: String,
+    pub publisher: Option<String>,
+    pub edition: Option<i32>,
+    /// ISO-639-1 two-letter language code, e.g. `en`.
+    pub language: Option<String>,
+    pub page_count: Option<i32>,
+    pub cover_image_url: Option<String>,
+    /// ISBN-10 or ISBN-13, hyphens are allowed.
+    pub isbn: Option<String>,
+    /// The number of the physical copy of the book, starting from 1.
+    pub copy_number: i32,
+}
+
+/// Represents a physical copy of a book.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct BookCopyRow {
+    pub id: Uuid,
+    pub copy_number: i32,
+    pub status: Status,
+}
+
+/// Represents the current availability of a book.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BookAvailability {
+    pub status: Status,
+    /// The number of users waiting for the book. The library does not take
+    /// reservations, so the queue is always empty for now.
+    pub queue_length: i64,
+    /// The due date of the current rental, if the book is rented.
+    pub estimated_return_date: Option<NaiveDate>,
+}
+
+/// The header of a CSV file of books.
+pub const BOOK_CSV_HEADER: [&str; 6] = ["name", "year", "category", "status", "author", "isbn"];
+
+/// Represents a row of a CSV file of books, see `BOOK_CSV_HEADER`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BookCsvRecord {
+    pub name: String,
+    pub year: i32,
+    pub category: String,
+    pub status: Status,
+    pub author: String,
+    pub isbn: Option<String>,
+}
+
+/// The copy number of a book when it is not given.
+fn default_copy_number() -> i32 {
+    1
+}
+
+impl From<BookCsvRecord> for Book {
+    fn from(record: BookCsvRecord) -> Self {
+        Self {
+            name: record.name,
+            year: record.year,
+            category: record.category,
+            status: record.status,
+            author: record.author,
+            description: None,
+            publisher: None,
+            edition: None,
+            language: None,
+            page_count: None,
+            cover_image_url: None,
+            isbn: record.isbn,
+            copy_number: 1,
+            created_at: None,
+            updated_at: None,
+        }
+    }
+}
+
+impl Book {
+    /// Validates the book before it is written to the database.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns a message describing the first invalid field,
+    /// e.g. when the description exceeds `MAX_DESCRIPTION_LEN` characters,
+    /// the edition, copy number or page count is less than 1, the language
+    /// is not an ISO-639-1 code, the cover image URL is not an `http(s)` URL
+    /// or the ISBN is not valid, see `is_valid_isbn`.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(description) = &self.description {
+            if description.chars().count() > MAX_DESCRIPTION_LEN {
+                return Err(format!(
+                    "description must be at most {MAX_DESCRIPTION_LEN} characters"
+                ));
+            }
+        }
+        if self.edition.is_some_and(|edition| edition < 1) {
+            return Err("edition must be at least 1".to_owned());
+        }
+        if let Some(language) = &self.language {
+            if language.len() != 2 || !language.bytes().all(|b| b.is_ascii_lowercase()) {
+                return Err("language must be a two-letter lowercase ISO-639-1 code".to_owned());
+            }
+        }
+        if self.copy_number < 1 {
+            return Err("copy_number must be at least 1".to_owned());
+        }
+        if self.page_count.is_some_and(|page_count| page_count < 1) {
+            return Err("page_count must be at least 1".to_owned());
+        }
+        if let Some(cover_image_url) = &self.cover_image_url {
+            let valid = Url::parse(cover_image_url)
+                .is_ok_and(|url| matches!(url.scheme(), "http" | "https"));
+            if !valid {
+                return Err("cover_image_url must be a valid http or https URL".to_owned());
+            }
+        }
+        if let Some(isbn) = &self.isbn {
+            if !is_valid_isbn(isbn) {
+                return Err("isbn must be a valid ISBN-10 or ISBN-13".to_owned());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns whether an ISBN-10 or ISBN-13, hyphens allowed, has a valid check
+/// digit.
+///
+/// The digits of an ISBN-10, the last one being `X` for 10, weighted from 10
+/// down to 1 sum to a multiple of 11. The digits of an ISBN-13 weighted
+/// alternately by 1 and 3 sum to a multiple of 10.
+pub fn is_valid_isbn(isbn: &str) -> bool {
+    let isbn = isbn.replace('-', "");
+    let digits: Option<Vec<u32>> = isbn
+        .chars()
+        .enumerate()
+        .map(|(i, c)| match c {
+            'X' | 'x' if isbn.len() == 10 && i == 9 => Some(10),
+            c => c.to_digit(10),
+        })
+        .collect();
+    match digits {
+        Some(digits) if digits.len() == 10 => {
+            let sum: u32 = digits
+                .iter()
+                .zip((1..=10).rev())
+                .map(|(digit, weight)| digit * weight)
+                .sum();
+            sum % 11 == 0
+        }
+        Some(digits) if digits.len() == 13 => {
+            let sum: u32 = digits
+                .iter()
+                .zip([1, 3].into_iter().cycle())
+                .map(|(digit, weight)| digit * weight)
+                .sum();
+            sum % 10 == 0
+        }
+        _ => false,
+    }
 }
 
 /// Represents the query parameters for filtering books.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow, IntoParams)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, sqlx::FromRow, IntoParams,
+)]
 pub struct BookQuery {
     pub name: Option<String>,
     pub year: Option<i32>,
     pub category: Option<String>,
     pub status: Option<Status>,
     pub author: Option<String>,
+    /// Matches books whose publisher contains the given text, ignoring case.
+    pub publisher: Option<String>,
+    pub edition: Option<i32>,
+    /// Matches books whose edition is greater than or equal to the given one.
+    pub edition_min: Option<i32>,
+    pub language: Option<String>,
+    /// Matches books with at least the given number of pages.
+    pub page_count_min: Option<i32>,
+    /// Matches books with at most the given number of pages.
+    pub page_count_max: Option<i32>,
+    /// Maximum number of books to return, all matching books if not given.
+    pub limit: Option<i64>,
+    /// Number of books to skip, defaults to 0. Ignored when `after` is given.
+    pub offset: Option<i64>,
+    /// Returns the books whose ID is greater than the given one, ordered by
+    /// ID instead of name. Unlike `offset`, no book is skipped or returned
+    /// twice when books are added between pages. Start from the nil UUID
+    /// `00000000-0000-0000-0000-000000000000` and continue with the returned
+    /// `next_cursor`.
+    pub after: Option<Uuid>,
+    /// Matches books having the given comma-separated tags, e.g.
+    /// `rust,systems`. Tags are compared like they are stored, trimmed and
+    /// lowercased.
+    #[serde(default, deserialize_with = "deserialize_tags")]
+    #[param(value_type = Option<String>)]
+    pub tags: Option<Vec<String>>,
+    /// Whether books must have all the tags, the default, or any of them.
+    pub tag_match: Option<TagMatch>,
+}
+
+/// Represents how the tags of a `BookQuery` are matched.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Copy, Default, Serialize, Deserialize, sqlx::Type, ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum TagMatch {
+    /// Books must have all the tags.
+    #[default]
+    All,
+    /// Books must have at least one of the tags.
+    Any,
+}
+
+/// Deserializes comma-separated tags, normalized and without duplicates.
+///
+/// No tags, e.g. `tags=`, do not filter books.
+fn deserialize_tags<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(tags) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    let mut tags: Vec<String> = tags
+        .split(',')
+        .map(normalize_tag)
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    Ok((!tags.is_empty()).then_some(tags))
+}
+
+impl BookQuery {
+    /// Returns the number of books to skip, always 0 when paginating with a
+    /// cursor.
+    pub fn offset_or_default(&self) -> i64 {
+        if self.after.is_some() {
+            return 0;
+        }
+        self.offset.unwrap_or_default().max(0)
+    }
+
+    /// Returns the maximum number of books to return, at least 1 when given.
+    pub fn limit_or_default(&self) -> Option<i64> {
+        self.limit.map(|limit| limit.max(1))
+    }
+
+    /// Returns the cursor of the page following `books`, the page returned
+    /// for this query.
+    ///
+    /// The cursor is only returned when paginating with a cursor and the page
+    /// is full, i.e. there may be more books.
+    pub fn next_cursor(&self, books: &[BookSummary]) -> Option<Uuid> {
+        let limit = self.limit_or_default()?;
+        if self.after.is_none() || (books.len() as i64) < limit {
+            return None;
+        }
+        books.last().map(|book| book.id)
+    }
+
+    /// Returns whether books must have all the tags of the query.
+    pub fn match_all_tags(&self) -> bool {
+        self.tag_match.unwrap_or_default() == TagMatch::All
+    }
+}
+
+/// Represents a request to merge a book entered twice into the other one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct MergeBooksBody {
+    /// The copy kept, which gets the rentals of the discarded one.
+    pub keep_id: Uuid,
+    /// The copy deleted once its rentals are moved.
+    pub discard_id: Uuid,
+}
+
+/// Represents the query parameters for listing new arrivals.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, IntoParams)]
+pub struct NewArrivalsQuery {
+    /// Number of days a book counts as a new arrival, defaults to 30.
+    pub days: Option<i64>,
+    /// Number of books to return, defaults to 10 and is capped at 100.
+    pub limit: Option<i64>,
+}
+
+impl NewArrivalsQuery {
+    /// Validates the query.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns a message when the number of days is not
+    /// positive.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.days.is_some_and(|days| days < 1) {
+            return Err("days must be at least 1".to_owned());
+        }
+        Ok(())
+    }
+}
+
+/// Represents the outcome of deleting one of several books.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct DeleteResult {
+    pub id: Uuid,
+    pub success: bool,
+    /// Why the book was not deleted, none if it was.
+    pub reason: Option<String>,
+}
+
+/// Represents the new author of a book.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BookAuthor {
+    /// The name of an existing author.
+    pub author: String,
+}
+
+/// Represents the pagination parameters for listing the books of a category,
+/// a year or an author.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, IntoParams)]
+pub struct BooksPageQuery {
+    /// Number of books to return, defaults to 20 and is capped at 100.
+    pub limit: Option<i64>,
+    /// Number of books to skip, defaults to 0.
+    pub offset: Option<i64>,
+}
+
+impl BooksPageQuery {
+    /// Returns the requested limit, falling back to the default and capped at
+    /// the maximum.
+    pub fn limit_or_default(&self) -> i64 {
+        self.limit
+            .unwrap_or(DEFAULT_BOOKS_PAGE_LIMIT)
+            .clamp(1, MAX_BOOKS_PAGE_LIMIT)
+    }
+
+    /// Returns the number of books to skip, 0 if not given or negative.
+    pub fn offset_or_default(&self) -> i64 {
+        self.offset.unwrap_or_default().max(0)
+    }
+}
+
+/// Represents the query parameters for recommending books to a user.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, IntoParams)]
+pub struct RecommendationsQuery {
+    /// Number of books to return, defaults to 5 and is capped at 50.
+    pub limit: Option<i64>,
+}
+
+impl RecommendationsQuery {
+    /// Returns the requested limit, falling back to the default and capped at
+    /// the maximum.
+    pub fn limit_or_default(&self) -> i64 {
+        self.limit
+            .unwrap_or(DEFAULT_RECOMMENDATIONS_LIMIT)
+            .clamp(1, MAX_RECOMMENDATIONS_LIMIT)
+    }
+}
+
+/// Represents the query parameters for listing the books similar to a book.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, IntoParams)]
+pub struct SimilarBooksQuery {
+    /// Number of books to return, defaults to 5 and is capped at 50.
+    pub limit: Option<i64>,
+}
+
+impl SimilarBooksQuery {
+    /// Returns the requested limit, falling back to the default and capped at
+    /// the maximum.
+    pub fn limit_or_default(&self) -> i64 {
+        self.limit
+            .unwrap_or(DEFAULT_SIMILAR_BOOKS_LIMIT)
+            .clamp(1, MAX_SIMILAR_BOOKS_LIMIT)
+    }
 }
 
 /// Inserts a book into the database.
@@ -43,6 +486,7 @@ pub struct BookQuery {
 /// ## Arguments
 ///
 /// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
 /// * `book`: The book to insert.
 ///
 /// ## Returns
@@ -53,22 +497,134 @@ pub struct BookQuery {
 ///
 /// This function returns an error if the book insertion fails or if there
 /// is an issue with the database connection.
-pub async fn insert_book(pool: &PgPool, book: &Book) -> Result<Uuid, sqlx::Error> {
-    sqlx::query!(
+#[tracing::instrument(level = "debug", skip(pool, book), fields(name = %book.name))]
+pub async fn insert_book(
+    pool: &PgPool,
+    library_id: &str,
+    book: &Book,
+) -> Result<Uuid, LibraryError> {
+    insert_book_with(pool, library_id, book).await
+}
+
+/// Inserts the physical copies of a book into the database inside a single
+/// transaction.
+///
+/// The copies are numbered from 1 to `copies`, the `copy_number` of `book` is
+/// ignored. Either all copies are inserted or none of them.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
+/// * `book`: The book to insert the copies of.
+/// * `copies`: The number of copies to insert.
+///
+/// ## Returns
+///
+/// The UUIDs of the inserted copies, ordered by copy number.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::Conflict` if a copy of the book
+/// already exists, or an error if the insertion fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool, book), fields(name = %book.name))]
+pub async fn insert_book_copies(
+    pool: &PgPool,
+    library_id: &str,
+    book: &Book,
+    copies: i32,
+) -> Result<Vec<Uuid>, LibraryError> {
+    let mut transaction = pool.begin().await?;
+    let mut ids = Vec::with_capacity(usize::try_from(copies).unwrap_or_default());
+    for copy_number in 1..=copies {
+        let copy = Book {
+            copy_number,
+            ..book.clone()
+        };
+        ids.push(insert_book_with(&mut transaction, library_id, &copy).await?);
+    }
+    transaction.commit().await?;
+    Ok(ids)
+}
+
+/// Inserts several books into the database inside a single transaction.
+///
+/// Every book is inserted in its own savepoint, so a book that cannot be
+/// inserted, e.g. because its name is already taken, does not prevent the
+/// others from being inserted.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the books belong to.
+/// * `books`: The books to insert.
+///
+/// ## Returns
+///
+/// The result of inserting each book, in the same order as `books`: the UUID
+/// of the inserted book, or the error that caused its insertion to fail.
+///
+/// ## Errors
+///
+/// This function returns an error if the transaction cannot be started or
+/// committed, or if there is an issue with the database connection.
+#[tracing::instrument(level = "debug", skip(pool, books), fields(count = books.len()))]
+pub async fn insert_books(
+    pool: &PgPool,
+    library_id: &str,
+    books: &[Book],
+) -> Result<Vec<Result<Uuid, LibraryError>>, LibraryError> {
+    let mut transaction = pool.begin().await?;
+    let mut results = Vec::with_capacity(books.len());
+    for book in books {
+        let mut savepoint = transaction.begin().await?;
+        let result = insert_book_with(&mut savepoint, library_id, book).await;
+        if result.is_ok() {
+            savepoint.commit().await?;
+        } else {
+            savepoint.rollback().await?;
+        }
+        results.push(result);
+    }
+    transaction.commit().await?;
+    Ok(results)
+}
+
+/// Inserts a book using the given executor, see `insert_book`.
+async fn insert_book_with<'e>(
+    executor: impl PgExecutor<'e>,
+    library_id: &str,
+    book: &Book,
+) -> Result<Uuid, LibraryError> {
+    timed_sql!(sqlx::query!(
         r#"
-            INSERT INTO book (name, year, category, status, author)
-            VALUES ($1, $2, $3, $4, $5)
+            INSERT INTO book (
+                library_id, name, year, category, status, author, description, publisher, edition,
+                language, page_count, cover_image_url, isbn, copy_number
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
             RETURNING Id
         "#,
+        library_id,
         book.name,
         book.year,
         book.category,
         book.status as Status,
         book.author,
+        book.description,
+        book.publisher,
+        book.edition,
+        book.language,
+        book.page_count,
+        book.cover_image_url,
+        book.isbn,
+        book.copy_number,
     )
-    .fetch_one(pool)
+    .fetch_one(executor))
     .await
     .map(|record| record.id)
+    .map_err(LibraryError::from)
 }
 
 /// Retrieves a list of books from the database based on the provided query.
@@ -76,47 +632,241 @@ pub async fn insert_book(pool: &PgPool, book: &Book) -> Result<Uuid, sqlx::Error
 /// ## Arguments
 ///
 /// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose books are retrieved.
 /// * `book`: The query parameters for filtering the books.
 ///
 /// ## Returns
 ///
-/// A vector of `Book` objects representing the retrieved books.
+/// A vector of `BookSummary` objects representing the retrieved books, ordered
+/// by name, or by ID when the query has a cursor. The description is left out
+/// to keep the list payload small.
 ///
 /// ## Errors
 ///
 /// This function returns an error if the query fails or if there is an issue
 /// with the database connection.
-pub async fn books(pool: &PgPool, book: &BookQuery) -> Result<Vec<Book>, sqlx::Error> {
-    let result = sqlx::query_as!(
-        Book,
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn books(
+    pool: &PgPool,
+    library_id: &str,
+    book: &BookQuery,
+) -> Result<Vec<BookSummary>, LibraryError> {
+    let result = timed_sql!(sqlx::query_as!(
+        BookSummary,
         r#"
-        SELECT name, year, category, status as "status: _", author FROM book
+        SELECT id, name, year, category, status as "status: _", author, publisher, edition,
+            language, page_count, cover_image_url, isbn, copy_number
+        FROM book
         WHERE
-            ($1::text IS NULL OR name = $1)
-            AND ($2::integer IS NULL OR year = $2)
-            AND ($3::text IS NULL OR category = $3)
-            AND ($4::status IS NULL OR status = $4)
-            AND ($5::text IS NULL OR author = $5)
+            library_id = $1
+            AND deleted_at IS NULL
+            AND ($2::text IS NULL OR name = $2)
+            AND ($3::integer IS NULL OR year = $3)
+            AND ($4::text IS NULL OR category = $4)
+            AND ($5::status IS NULL OR status = $5)
+            AND ($6::text IS NULL OR author = $6)
+            AND ($7::text IS NULL OR publisher ILIKE '%' || $7 || '%')
+            AND ($8::integer IS NULL OR edition = $8)
+            AND ($9::integer IS NULL OR edition >= $9)
+            AND ($10::text IS NULL OR language = $10)
+            AND ($11::integer IS NULL OR page_count >= $11)
+            AND ($12::integer IS NULL OR page_count <= $12)
+            AND ($15::uuid IS NULL OR id > $15)
+            AND ($16::varchar[] IS NULL OR (
+                SELECT COUNT(*)
+                FROM book_tags
+                JOIN tags ON tags.id = book_tags.tag_id
+                WHERE book_tags.library_id = book.library_id
+                    AND book_tags.book_name = book.name AND tags.name = ANY($16)
+            ) >= CASE WHEN $17 THEN cardinality($16) ELSE 1 END)
+        ORDER BY CASE WHEN $15::uuid IS NULL THEN name END, id
+        LIMIT $13 OFFSET $14
         "#,
+        library_id,
         book.name,
         book.year,
         book.category,
         book.status.unwrap_or_default() as Status,
         book.author,
+        book.publisher,
+        book.edition,
+        book.edition_min,
+        book.language,
+        book.page_count_min,
+        book.page_count_max,
+        book.limit_or_default(),
+        book.offset_or_default(),
+        book.after,
+        book.tags.as_deref(),
+        book.match_all_tags(),
     )
-    .fetch_all(pool)
+    .fetch_all(pool))
     .await?;
     if result.is_empty() {
-        return Err(sqlx::Error::RowNotFound);
+        return Err(LibraryError::NotFound);
     }
     Ok(result)
 }
 
+/// Counts the books matching the provided query.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose books are counted.
+/// * `book`: The query parameters for filtering the books.
+///
+/// ## Returns
+///
+/// The number of books `books` would return without pagination, so `limit`,
+/// `offset` and `after` are ignored. Unlike `books`, no matching book is not
+/// an error and returns 0.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn count_books(
+    pool: &PgPool,
+    library_id: &str,
+    book: &BookQuery,
+) -> Result<i64, LibraryError> {
+    timed_sql!(sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM book
+        WHERE
+            library_id = $1
+            AND deleted_at IS NULL
+            AND ($2::text IS NULL OR name = $2)
+            AND ($3::integer IS NULL OR year = $3)
+            AND ($4::text IS NULL OR category = $4)
+            AND ($5::status IS NULL OR status = $5)
+            AND ($6::text IS NULL OR author = $6)
+            AND ($7::text IS NULL OR publisher ILIKE '%' || $7 || '%')
+            AND ($8::integer IS NULL OR edition = $8)
+            AND ($9::integer IS NULL OR edition >= $9)
+            AND ($10::text IS NULL OR language = $10)
+            AND ($11::integer IS NULL OR page_count >= $11)
+            AND ($12::integer IS NULL OR page_count <= $12)
+            AND ($13::varchar[] IS NULL OR (
+                SELECT COUNT(*)
+                FROM book_tags
+                JOIN tags ON tags.id = book_tags.tag_id
+                WHERE book_tags.library_id = book.library_id
+                    AND book_tags.book_name = book.name AND tags.name = ANY($13)
+            ) >= CASE WHEN $14 THEN cardinality($13) ELSE 1 END)
+        "#,
+        library_id,
+        book.name,
+        book.year,
+        book.category,
+        book.status.unwrap_or_default() as Status,
+        book.author,
+        book.publisher,
+        book.edition,
+        book.edition_min,
+        book.language,
+        book.page_count_min,
+        book.page_count_max,
+        book.tags.as_deref(),
+        book.match_all_tags(),
+    )
+    .fetch_one(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Streams the books matching the provided query as CSV records.
+///
+/// Unlike `books`, the books are read from the database while the stream is
+/// consumed, so the whole result set is never held in memory. The stream owns
+/// the pool, the library ID and the query so it can outlive the caller, e.g.
+/// as a response body.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose books are streamed.
+/// * `book`: The query parameters for filtering the books.
+///
+/// ## Returns
+///
+/// A stream of `BookCsvRecord` objects, ordered like `books`. Unlike `books`,
+/// all statuses are returned unless the query sets one.
+///
+/// ## Errors
+///
+/// The stream yields an error if the query fails or if there is an issue with
+/// the database connection.
+pub fn book_csv_records(
+    pool: PgPool,
+    library_id: String,
+    book: BookQuery,
+) -> impl Stream<Item = Result<BookCsvRecord, LibraryError>> + Send + 'static {
+    try_stream! {
+        let mut records = timed_sql!(sqlx::query_as!(
+            BookCsvRecord,
+            r#"
+            SELECT name, year, category, status as "status: _", author, isbn
+            FROM book
+            WHERE
+                library_id = $1
+                AND deleted_at IS NULL
+                AND ($2::text IS NULL OR name = $2)
+                AND ($3::integer IS NULL OR year = $3)
+                AND ($4::text IS NULL OR category = $4)
+                AND ($5::status IS NULL OR status = $5)
+                AND ($6::text IS NULL OR author = $6)
+                AND ($7::text IS NULL OR publisher ILIKE '%' || $7 || '%')
+                AND ($8::integer IS NULL OR edition = $8)
+                AND ($9::integer IS NULL OR edition >= $9)
+                AND ($10::text IS NULL OR language = $10)
+                AND ($11::integer IS NULL OR page_count >= $11)
+                AND ($12::integer IS NULL OR page_count <= $12)
+                AND ($15::uuid IS NULL OR id > $15)
+                AND ($16::varchar[] IS NULL OR (
+                    SELECT COUNT(*)
+                    FROM book_tags
+                    JOIN tags ON tags.id = book_tags.tag_id
+                    WHERE book_tags.library_id = book.library_id
+                        AND book_tags.book_name = book.name AND tags.name = ANY($16)
+                ) >= CASE WHEN $17 THEN cardinality($16) ELSE 1 END)
+            ORDER BY CASE WHEN $15::uuid IS NULL THEN name END, id
+            LIMIT $13 OFFSET $14
+            "#,
+            library_id,
+            book.name,
+            book.year,
+            book.category,
+            book.status as Option<Status>,
+            book.author,
+            book.publisher,
+            book.edition,
+            book.edition_min,
+            book.language,
+            book.page_count_min,
+            book.page_count_max,
+            book.limit_or_default(),
+            book.offset_or_default(),
+            book.after,
+            book.tags.as_deref(),
+            book.match_all_tags(),
+        )
+        .fetch(&pool));
+        while let Some(record) = records.try_next().await? {
+            yield record;
+        }
+    }
+}
+
 /// Retrieves detailed information about a specific book from the database.
 ///
 /// ## Arguments
 ///
 /// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
 /// * `book_id`: The ID of the book to retrieve.
 ///
 /// ## Returns
@@ -127,174 +877,1863 @@ pub async fn books(pool: &PgPool, book: &BookQuery) -> Result<Vec<Book>, sqlx::E
 ///
 /// This function returns an error if the retrieval fails or if there is an issue
 /// with the database connection.
-pub async fn get_book(pool: &PgPool, book_id: Uuid) -> Result<Book, sqlx::Error> {
-    sqlx::query_as!(
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn get_book(
+    pool: &PgPool,
+    library_id: &str,
+    book_id: Uuid,
+) -> Result<Book, LibraryError> {
+    timed_sql!(sqlx::query_as!(
         Book,
         r#"
-        SELECT name, year, category, status as "status: _", author
+        SELECT name, year, category, status as "status: _", author, description, publisher,
+            edition, language, page_count, cover_image_url, isbn, copy_number,
+            created_at as "created_at?", updated_at as "updated_at?"
         FROM book
-        WHERE id = $1
+        WHERE library_id = $1 AND id = $2 AND deleted_at IS NULL
         "#,
+        library_id,
         book_id,
     )
-    .fetch_one(pool)
+    .fetch_one(pool))
     .await
+    .map_err(LibraryError::from)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::library::author::{self, Author};
-    use fake::faker::lorem::en::Sentence as FakeTitle;
-    use fake::faker::lorem::en::Word as FakeCategory;
-    use fake::Fake;
-    use rand::Rng;
-    use std::ops::Range;
-
-    impl Book {
-        /// Creates a fake book object.
-        ///
-        /// This method generates a fake book with random attributes using the `fake` crate.
-        /// It also inserts a fake author associated with the book into the database.
-        ///
-        /// ## Arguments
-        ///
-        /// * `pool`: The PostgreSQL database connection pool.
-        ///
-        /// ## Returns
-        ///
-        /// A `Book` object representing the fake book with randomly generated attributes.
-        ///
-        /// ## Panics
-        ///
-        /// This method will panic if it fails to insert the fake author into the database.
-        pub async fn create_fake_book(pool: &PgPool) -> Self {
-            // first insert an author
-            let fake_author = Author::create_fake_author().await;
-            let _insert_author = author::insert_author(pool, &fake_author)
-                .await
-                .expect("failed to insert fake author");
-            let mut rng = rand::thread_rng();
-            let fake_year = rng.gen_range(1900..=2023);
-            Self {
-                name: FakeTitle(Range { start: 10, end: 30 }).fake::<String>(),
-                year: fake_year,
-                category: FakeCategory().fake::<String>(),
-                status: Status::default(),
-                author: fake_author.name,
-            }
-        }
-    }
+/// Retrieves detailed information about a book by its name.
+///
+/// When the book has several copies, the lowest-numbered copy is returned.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
+/// * `name`: The name of the book to retrieve.
+///
+/// ## Returns
+///
+/// A `Book` object containing the detailed information of the retrieved book.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if there is no book with
+/// the given name that is not deleted, or an error if the retrieval fails or
+/// there is an issue with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn get_book_by_name(
+    pool: &PgPool,
+    library_id: &str,
+    name: &str,
+) -> Result<Book, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        Book,
+        r#"
+        SELECT name, year, category, status as "status: _", author, description, publisher,
+            edition, language, page_count, cover_image_url, isbn, copy_number,
+            created_at as "created_at?", updated_at as "updated_at?"
+        FROM book
+        WHERE library_id = $1 AND name = $2 AND deleted_at IS NULL
+        ORDER BY copy_number
+        LIMIT 1
+        "#,
+        library_id,
+        name,
+    )
+    .fetch_one(pool))
+    .await
+    .map_err(LibraryError::from)
+}
 
-    #[tokio::test]
-    async fn test_book() {
-        let pool = crate::database::postgres::init::pg_pool()
-            .await
-            .expect("failed to connect to postgres");
-        let book = Book::create_fake_book(&pool).await;
-        // insert_book
-        let result_id = insert_book(&pool, &book)
-            .await
-            .expect("failed to insert book");
-        let fetched_book = sqlx::query_as!(
-            Book,
-            r#"
-            SELECT name, year, category, status as "status: _", author
-            FROM book
-            WHERE Id = $1
+/// Retrieves detailed information about a book by its ISBN.
+///
+/// Hyphens are ignored, so `978-0-306-40615-7` finds a book stored as
+/// `9780306406157` and the other way around. When the book has several
+/// copies, the first one is returned.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
+/// * `isbn`: The ISBN of the book, see `is_valid_isbn`.
+///
+/// ## Returns
+///
+/// A `Book` object containing the detailed information of the retrieved book.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if there is no book with
+/// the given ISBN that is not deleted, or an error if the retrieval fails or
+/// there is an issue with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn get_book_by_isbn(
+    pool: &PgPool,
+    library_id: &str,
+    isbn: &str,
+) -> Result<Book, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        Book,
+        r#"
+        SELECT name, year, category, status as "status: _", author, description, publisher,
+            edition, language, page_count, cover_image_url, isbn, copy_number,
+            created_at as "created_at?", updated_at as "updated_at?"
+        FROM book
+        WHERE library_id = $1 AND UPPER(REPLACE(isbn, '-', '')) = $2 AND deleted_at IS NULL
+        ORDER BY copy_number
+        LIMIT 1
+        "#,
+        library_id,
+        isbn.replace('-', "").to_uppercase(),
+    )
+    .fetch_one(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Retrieves a random available book.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
+///
+/// ## Returns
+///
+/// A `Book` object containing the detailed information of a book picked at
+/// random among the available ones.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if no book is available, or
+/// an error if the retrieval fails or there is an issue with the database
+/// connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn random_book(pool: &PgPool, library_id: &str) -> Result<Book, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        Book,
+        r#"
+        SELECT name, year, category, status as "status: _", author, description, publisher,
+            edition, language, page_count, cover_image_url, isbn, copy_number,
+            created_at as "created_at?", updated_at as "updated_at?"
+        FROM book
+        WHERE library_id = $1 AND status = 'Available' AND deleted_at IS NULL
+        ORDER BY RANDOM()
+        LIMIT 1
+        "#,
+        library_id,
+    )
+    .fetch_one(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Retrieves the current availability of a book.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
+/// * `book_id`: The ID of the book.
+///
+/// ## Returns
+///
+/// A `BookAvailability` with the status of the book and, when the book is
+/// rented, the due date of the current rental.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if there is no book with
+/// the given ID that is not deleted, or an error if the retrieval fails or
+/// there is an issue with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn book_availability(
+    pool: &PgPool,
+    library_id: &str,
+    book_id: Uuid,
+) -> Result<BookAvailability, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        BookAvailability,
+        r#"
+        SELECT
+            status as "status: _",
+            0::int8 as "queue_length!",
+            CASE WHEN status = 'Rented' THEN (
+                SELECT MIN(users_history.due_date::date)
+                FROM users_history
+                WHERE users_history.library_id = book.library_id
+                    AND users_history.book_name = book.name
+                    AND users_history.copy_number = book.copy_number
+                    AND users_history.created_at = (
+                        SELECT MAX(created_at) FROM users_history
+                        WHERE users_history.library_id = book.library_id
+                            AND users_history.book_name = book.name
+                            AND users_history.copy_number = book.copy_number
+                    )
+            ) END as estimated_return_date
+        FROM book
+        WHERE library_id = $1 AND id = $2 AND deleted_at IS NULL
+        "#,
+        library_id,
+        book_id,
+    )
+    .fetch_one(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Retrieves the physical copies of a book.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
+/// * `book_name`: The name of the book.
+///
+/// ## Returns
+///
+/// A list of `BookCopyRow` with the status of every copy of the book that is
+/// not deleted, ordered by copy number.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if the book has no copy
+/// that is not deleted, or an error if the retrieval fails or there is an
+/// issue with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn book_copies(
+    pool: &PgPool,
+    library_id: &str,
+    book_name: &str,
+) -> Result<Vec<BookCopyRow>, LibraryError> {
+    let result = timed_sql!(sqlx::query_as!(
+        BookCopyRow,
+        r#"
+        SELECT id, copy_number, status as "status: _"
+        FROM book
+        WHERE library_id = $1 AND name = $2 AND deleted_at IS NULL
+        ORDER BY copy_number
+        "#,
+        library_id,
+        book_name,
+    )
+    .fetch_all(pool))
+    .await?;
+    if result.is_empty() {
+        return Err(LibraryError::NotFound);
+    }
+    Ok(result)
+}
+
+/// Soft-deletes a book by marking it as deleted.
+///
+/// The book is kept in the database, but it is no longer returned by the
+/// other book queries until it is restored.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
+/// * `book_id`: The ID of the book to delete.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if there is no book with
+/// the given ID that is not already deleted, or an error if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn delete_book(
+    pool: &PgPool,
+    library_id: &str,
+    book_id: Uuid,
+) -> Result<(), LibraryError> {
+    let result = timed_sql!(sqlx::query!(
+        r#"
+        UPDATE book
+        SET deleted_at = NOW()
+        WHERE library_id = $1 AND id = $2 AND deleted_at IS NULL
+        "#,
+        library_id,
+        book_id,
+    )
+    .execute(pool))
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(LibraryError::NotFound);
+    }
+    Ok(())
+}
+
+/// Soft-deletes several books inside a single transaction.
+///
+/// The books are locked while they are deleted, so a book can't be rented in
+/// between. Books that are rented or don't exist are not deleted, the others
+/// are.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the books belong to.
+/// * `ids`: The IDs of the books to delete.
+///
+/// ## Returns
+///
+/// A `DeleteResult` for each of the IDs, in the same order.
+///
+/// ## Errors
+///
+/// This function returns an error if the deletion fails or if there is an
+/// issue with the database connection, in which case no book is deleted.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn delete_books(
+    pool: &PgPool,
+    library_id: &str,
+    ids: &[Uuid],
+) -> Result<Vec<DeleteResult>, LibraryError> {
+    let mut transaction = pool.begin().await?;
+    let books = timed_sql!(sqlx::query!(
+        r#"
+        SELECT id, status as "status: Status"
+        FROM book
+        WHERE library_id = $1 AND id = ANY($2) AND deleted_at IS NULL
+        FOR UPDATE
+        "#,
+        library_id,
+        ids,
+    )
+    .fetch_all(&mut transaction))
+    .await?;
+    let deletable: Vec<Uuid> = books
+        .iter()
+        .filter(|book| book.status != Status::Rented)
+        .map(|book| book.id)
+        .collect();
+    timed_sql!(sqlx::query!(
+        r#"
+        UPDATE book
+        SET deleted_at = NOW()
+        WHERE library_id = $1 AND id = ANY($2)
+        "#,
+        library_id,
+        &deletable,
+    )
+    .execute(&mut transaction))
+    .await?;
+    transaction.commit().await?;
+
+    let results = ids
+        .iter()
+        .map(|&id| {
+            let reason = match books.iter().find(|book| book.id == id) {
+                None => Some("not found"),
+                Some(book) if book.status == Status::Rented => Some("currently rented"),
+                Some(_) => None,
+            };
+            DeleteResult {
+                id,
+                success: reason.is_none(),
+                reason: reason.map(str::to_owned),
+            }
+        })
+        .collect();
+    Ok(results)
+}
+
+/// Restores a soft-deleted book.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
+/// * `book_id`: The ID of the book to restore.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if there is no deleted
+/// book with the given ID, or an error if there is an issue with the database
+/// connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn restore_book(
+    pool: &PgPool,
+    library_id: &str,
+    book_id: Uuid,
+) -> Result<(), LibraryError> {
+    let result = timed_sql!(sqlx::query!(
+        r#"
+        UPDATE book
+        SET deleted_at = NULL
+        WHERE library_id = $1 AND id = $2 AND deleted_at IS NOT NULL
+        "#,
+        library_id,
+        book_id,
+    )
+    .execute(pool))
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(LibraryError::NotFound);
+    }
+    Ok(())
+}
+
+/// Merges a book entered twice into the other one.
+///
+/// In a single transaction, the rentals and the damage reports of the
+/// discarded copy are moved to the kept copy, then the discarded copy is
+/// deleted for good. When it was the last copy of its title, the reviews and
+/// the tags of the title are moved to the title of the kept copy as well,
+/// except the reviews of users who already reviewed the kept title.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the books belong to.
+/// * `keep_id`: The ID of the copy to keep.
+/// * `discard_id`: The ID of the copy to delete.
+///
+/// ## Returns
+///
+/// The number of rentals moved to the kept copy.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::Invalid` if both IDs are the same,
+/// `LibraryError::NotFound` if either book does not exist or is deleted,
+/// `LibraryError::Forbidden` if the discarded copy is currently rented, or an
+/// error if the rows cannot be moved or there is an issue with the database
+/// connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn merge_books(
+    pool: &PgPool,
+    library_id: &str,
+    keep_id: Uuid,
+    discard_id: Uuid,
+) -> Result<u64, LibraryError> {
+    if keep_id == discard_id {
+        return Err(LibraryError::Invalid(
+            "a book cannot be merged into itself".to_owned(),
+        ));
+    }
+    let mut transaction = pool.begin().await?;
+
+    // both books are locked in the same order by concurrent merges
+    let books = timed_sql!(sqlx::query!(
+        r#"
+        SELECT id, name, copy_number, status as "status: Status"
+        FROM book
+        WHERE library_id = $1 AND id = ANY($2) AND deleted_at IS NULL
+        ORDER BY id
+        FOR UPDATE
+        "#,
+        library_id,
+        &[keep_id, discard_id][..],
+    )
+    .fetch_all(&mut transaction))
+    .await?;
+    let keep = books
+        .iter()
+        .find(|book| book.id == keep_id)
+        .ok_or(LibraryError::NotFound)?;
+    let discard = books
+        .iter()
+        .find(|book| book.id == discard_id)
+        .ok_or(LibraryError::NotFound)?;
+    if discard.status == Status::Rented {
+        return Err(LibraryError::Forbidden(
+            "a rented book cannot be merged".to_owned(),
+        ));
+    }
+
+    let moved = timed_sql!(sqlx::query!(
+        r#"
+        UPDATE users_history
+        SET book_name = $2, copy_number = $3
+        WHERE library_id = $1 AND book_name = $4 AND copy_number = $5
+        "#,
+        library_id,
+        keep.name,
+        keep.copy_number,
+        discard.name,
+        discard.copy_number,
+    )
+    .execute(&mut transaction))
+    .await?
+    .rows_affected();
+    timed_sql!(sqlx::query!(
+        r#"
+        UPDATE damaged_reports
+        SET book_name = $2, copy_number = $3
+        WHERE library_id = $1 AND book_name = $4 AND copy_number = $5
+        "#,
+        library_id,
+        keep.name,
+        keep.copy_number,
+        discard.name,
+        discard.copy_number,
+    )
+    .execute(&mut transaction))
+    .await?;
+    timed_sql!(sqlx::query!(
+        "DELETE FROM book WHERE library_id = $1 AND id = $2",
+        library_id,
+        discard_id,
+    )
+    .execute(&mut transaction))
+    .await?;
+
+    let title_left = timed_sql!(sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM book WHERE library_id = $1 AND name = $2) as "exists!""#,
+        library_id,
+        discard.name,
+    )
+    .fetch_one(&mut transaction))
+    .await?;
+    if !title_left {
+        timed_sql!(sqlx::query!(
+            r#"
+            DELETE FROM book_reviews discarded
+            USING book_reviews kept
+            WHERE discarded.library_id = $1 AND discarded.book_name = $3
+                AND kept.library_id = $1 AND kept.book_name = $2
+                AND kept.nation_id = discarded.nation_id
             "#,
-            result_id,
-        )
-        .fetch_one(&pool)
-        .await;
-        assert_eq!(fetched_book.expect("unmatched book"), book);
-        // get_book
-        let get_book_result = get_book(&pool, result_id)
-            .await
-            .expect("failed to get book");
-        assert_eq!(book.name, get_book_result.name);
-        // books
-        // 1: all books
-        let books_result = books(
-            &pool,
-            &BookQuery {
-                name: None,
-                year: None,
-                category: None,
-                status: None,
-                author: None,
-            },
+            library_id,
+            keep.name,
+            discard.name,
         )
-        .await;
-        assert!(books_result
-            .as_ref()
-            .map(|books| !books.is_empty())
-            .unwrap_or_else(|_| false));
-        assert!(books_result.is_ok());
-        // 2: Get books by year (with year filter)
-        let books_by_year_result = books(
-            &pool,
-            &BookQuery {
-                name: None,
-                year: Some(book.year),
-                category: None,
-                status: None,
-                author: None,
-            },
+        .execute(&mut transaction))
+        .await?;
+        timed_sql!(sqlx::query!(
+            "UPDATE book_reviews SET book_name = $2 WHERE library_id = $1 AND book_name = $3",
+            library_id,
+            keep.name,
+            discard.name,
         )
-        .await;
-        assert!(books_by_year_result.is_ok());
-        // 3: Get books by category (with category filter)
-        let books_by_category_result = books(
-            &pool,
-            &BookQuery {
-                name: None,
-                year: None,
-                category: Some(book.category.clone()),
-                status: None,
-                author: None,
-            },
+        .execute(&mut transaction))
+        .await?;
+        timed_sql!(sqlx::query!(
+            r#"
+            INSERT INTO book_tags (library_id, book_name, tag_id)
+            SELECT library_id, $2, tag_id
+            FROM book_tags
+            WHERE library_id = $1 AND book_name = $3
+            ON CONFLICT DO NOTHING
+            "#,
+            library_id,
+            keep.name,
+            discard.name,
         )
-        .await;
-        assert!(books_by_category_result.is_ok());
-        // 4: Get books by status (with status filter)
-        let books_by_status_result = books(
-            &pool,
-            &BookQuery {
-                name: None,
-                year: None,
-                category: None,
-                status: Some(book.status),
-                author: None,
-            },
+        .execute(&mut transaction))
+        .await?;
+        timed_sql!(sqlx::query!(
+            "DELETE FROM book_tags WHERE library_id = $1 AND book_name = $2",
+            library_id,
+            discard.name,
         )
-        .await;
-        assert!(books_by_status_result.is_ok());
-        // 5: Get books by author (with author filter)
-        let books_by_author_result = books(
-            &pool,
-            &BookQuery {
-                name: None,
-                year: None,
-                category: None,
-                status: None,
-                author: Some(book.author.clone()),
-            },
+        .execute(&mut transaction))
+        .await?;
+    }
+
+    transaction.commit().await?;
+    Ok(moved)
+}
+
+/// Reassigns a book to another author.
+///
+/// The author is shared by all copies of a title, so every copy with the name
+/// of the given book is reassigned.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
+/// * `book_id`: The ID of the book to reassign.
+/// * `new_author_name`: The name of the new author.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if there is no book with
+/// the given ID that is not deleted or no author with the given name, or an
+/// error if there is an issue with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn reassign_book_author(
+    pool: &PgPool,
+    library_id: &str,
+    book_id: Uuid,
+    new_author_name: &str,
+) -> Result<(), LibraryError> {
+    let result = timed_sql!(sqlx::query!(
+        r#"
+        UPDATE book
+        SET author = author.name
+        FROM author
+        WHERE
+            author.library_id = $1
+            AND author.name = $3
+            AND book.library_id = $1
+            AND book.deleted_at IS NULL
+            AND book.name = (
+                SELECT name FROM book
+                WHERE library_id = $1 AND id = $2 AND deleted_at IS NULL
+            )
+        "#,
+        library_id,
+        book_id,
+        new_author_name,
+    )
+    .execute(pool))
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(LibraryError::NotFound);
+    }
+    Ok(())
+}
+
+/// Retrieves the list of soft-deleted books.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose deleted books are retrieved.
+///
+/// ## Returns
+///
+/// A vector of `BookSummary` objects representing the deleted books, most
+/// recently deleted first. The vector is empty if no book is deleted.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn deleted_books(
+    pool: &PgPool,
+    library_id: &str,
+) -> Result<Vec<BookSummary>, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        BookSummary,
+        r#"
+        SELECT id, name, year, category, status as "status: _", author, publisher, edition,
+            language, page_count, cover_image_url, isbn, copy_number
+        FROM book
+        WHERE library_id = $1 AND deleted_at IS NOT NULL
+        ORDER BY deleted_at DESC
+        "#,
+        library_id,
+    )
+    .fetch_all(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Retrieves a page of the books of a category, ordered by name.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose books are retrieved.
+/// * `category`: The category of the books.
+/// * `limit`: The maximum number of books to return.
+/// * `offset`: The number of books to skip.
+///
+/// ## Returns
+///
+/// A tuple of the page of `BookSummary` objects and the total number of
+/// books in the category. The page is empty if it is past the last book.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn books_by_category(
+    pool: &PgPool,
+    library_id: &str,
+    category: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<BookSummary>, i64), LibraryError> {
+    books_page(pool, library_id, Some(category), None, None, limit, offset).await
+}
+
+/// Retrieves a page of the books published in a year, ordered by name.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose books are retrieved.
+/// * `year`: The publication year of the books.
+/// * `limit`: The maximum number of books to return.
+/// * `offset`: The number of books to skip.
+///
+/// ## Returns
+///
+/// A tuple of the page of `BookSummary` objects and the total number of
+/// books published in the year. The page is empty if it is past the last
+/// book.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn books_by_year(
+    pool: &PgPool,
+    library_id: &str,
+    year: i32,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<BookSummary>, i64), LibraryError> {
+    books_page(pool, library_id, None, Some(year), None, limit, offset).await
+}
+
+/// Retrieves a page of the books of an author, ordered by name.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose books are retrieved.
+/// * `author`: The name of the author.
+/// * `limit`: The maximum number of books to return.
+/// * `offset`: The number of books to skip.
+///
+/// ## Returns
+///
+/// A tuple of the page of `BookSummary` objects and the total number of
+/// books of the author. The page is empty if the author has no book or if it
+/// is past the last book.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if there is no author with
+/// the given name, or an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn books_by_author_name(
+    pool: &PgPool,
+    library_id: &str,
+    author: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<BookSummary>, i64), LibraryError> {
+    let page = books_page(pool, library_id, None, None, Some(author), limit, offset).await?;
+    if page.1 > 0 {
+        return Ok(page);
+    }
+    // an author without books is told apart from an unknown author
+    let author_exists = timed_sql!(sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (SELECT 1 FROM author WHERE library_id = $1 AND name = $2) as "exists!"
+        "#,
+        library_id,
+        author,
+    )
+    .fetch_one(pool))
+    .await?;
+    if !author_exists {
+        return Err(LibraryError::NotFound);
+    }
+    Ok(page)
+}
+
+/// Retrieves a page of the books matching the given filters, ordered by name,
+/// and the total number of matching books. A filter that is `None` matches
+/// every book.
+async fn books_page(
+    pool: &PgPool,
+    library_id: &str,
+    category: Option<&str>,
+    year: Option<i32>,
+    author: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<BookSummary>, i64), LibraryError> {
+    let books = timed_sql!(sqlx::query_as!(
+        BookSummary,
+        r#"
+        SELECT id, name, year, category, status as "status: _", author, publisher, edition,
+            language, page_count, cover_image_url, isbn, copy_number
+        FROM book
+        WHERE library_id = $1 AND deleted_at IS NULL
+            AND ($2::text IS NULL OR category = $2)
+            AND ($3::integer IS NULL OR year = $3)
+            AND ($4::text IS NULL OR author = $4)
+        ORDER BY name, copy_number
+        LIMIT $5 OFFSET $6
+        "#,
+        library_id,
+        category,
+        year,
+        author,
+        limit,
+        offset,
+    )
+    .fetch_all(pool))
+    .await?;
+    let total = timed_sql!(sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "total!"
+        FROM book
+        WHERE library_id = $1 AND deleted_at IS NULL
+            AND ($2::text IS NULL OR category = $2)
+            AND ($3::integer IS NULL OR year = $3)
+            AND ($4::text IS NULL OR author = $4)
+        "#,
+        library_id,
+        category,
+        year,
+        author,
+    )
+    .fetch_one(pool))
+    .await?;
+    Ok((books, total))
+}
+
+/// Retrieves the books added in the last `days` days.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose new arrivals are retrieved.
+/// * `days`: The number of days a book counts as a new arrival.
+/// * `limit`: The maximum number of books to return.
+///
+/// ## Returns
+///
+/// A vector of `Book` objects, the most recently added first. The vector is
+/// empty if no book was added in the period.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn new_arrivals(
+    pool: &PgPool,
+    library_id: &str,
+    days: i64,
+    limit: i64,
+) -> Result<Vec<Book>, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        Book,
+        r#"
+        SELECT name, year, category, status as "status: _", author, description, publisher,
+            edition, language, page_count, cover_image_url, isbn, copy_number,
+            created_at as "created_at?", updated_at as "updated_at?"
+        FROM book
+        WHERE library_id = $1 AND deleted_at IS NULL
+            AND created_at > NOW() - $2::int8 * INTERVAL '1 day'
+        ORDER BY created_at DESC, name
+        LIMIT $3
+        "#,
+        library_id,
+        days,
+        limit,
+    )
+    .fetch_all(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Recommends books to a user based on their rental history.
+///
+/// The recommendations are the available books of the category the user
+/// rented the most, excluding the books the user already rented. Ties between
+/// categories are broken alphabetically. A book with several available copies
+/// is recommended once, as its lowest-numbered available copy.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the user belongs to.
+/// * `nation_id`: The national ID of the user.
+/// * `limit`: The maximum number of books to return.
+///
+/// ## Returns
+///
+/// A vector of `BookSummary` objects in random order. The vector is empty if
+/// the user has not rented any book yet.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn book_recommendations(
+    pool: &PgPool,
+    library_id: &str,
+    nation_id: &str,
+    limit: i64,
+) -> Result<Vec<BookSummary>, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        BookSummary,
+        r#"
+        WITH favorite_category AS (
+            SELECT book.category
+            FROM users_history
+            JOIN book ON book.library_id = users_history.library_id
+                AND book.name = users_history.book_name
+                AND book.copy_number = users_history.copy_number
+            WHERE users_history.library_id = $1 AND users_history.nation_id = $2
+            GROUP BY book.category
+            ORDER BY COUNT(*) DESC, book.category
+            LIMIT 1
         )
-        .await;
-        assert!(books_by_author_result.is_ok());
-        // 6: Exact given criteria
-        let books_by_all_criteria = books(
-            &pool,
-            &BookQuery {
-                name: Some(book.name.clone()),
-                year: Some(book.year),
-                category: Some(book.category.clone()),
-                status: Some(book.status),
-                author: Some(book.author.clone()),
-            },
+        SELECT id, name, year, category, status as "status: _", author, publisher, edition,
+            language, page_count, cover_image_url, isbn, copy_number
+        FROM book
+        WHERE library_id = $1 AND deleted_at IS NULL AND status = 'Available'
+            AND category = (SELECT category FROM favorite_category)
+            AND copy_number = (
+                SELECT MIN(copy.copy_number) FROM book AS copy
+                WHERE copy.library_id = book.library_id AND copy.name = book.name
+                    AND copy.status = 'Available' AND copy.deleted_at IS NULL
+            )
+            AND NOT EXISTS (
+                SELECT 1 FROM users_history
+                WHERE users_history.library_id = book.library_id
+                    AND users_history.nation_id = $2
+                    AND users_history.book_name = book.name
+            )
+        ORDER BY random()
+        LIMIT $3
+        "#,
+        library_id,
+        nation_id,
+        limit,
+    )
+    .fetch_all(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Retrieves the books similar to a book.
+///
+/// A book is similar when it shares the category or the author of the given
+/// book. The books sharing both come first, then the books are ordered by
+/// name. The copies of the given book are left out, and a book with several
+/// copies is listed once, as its lowest-numbered copy.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
+/// * `book_id`: The ID of the book.
+/// * `limit`: The maximum number of books to return.
+///
+/// ## Returns
+///
+/// A vector of `BookSummary` objects, empty if the book does not exist or no
+/// book is similar to it.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn similar_books(
+    pool: &PgPool,
+    library_id: &str,
+    book_id: Uuid,
+    limit: i64,
+) -> Result<Vec<BookSummary>, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        BookSummary,
+        r#"
+        WITH target AS (
+            SELECT name, category, author
+            FROM book
+            WHERE library_id = $1 AND id = $2 AND deleted_at IS NULL
         )
-        .await;
-        assert!(books_by_all_criteria.is_ok());
+        SELECT book.id, book.name, book.year, book.category, book.status as "status: _",
+            book.author, book.publisher, book.edition, book.language, book.page_count,
+            book.cover_image_url, book.isbn, book.copy_number
+        FROM book, target
+        WHERE book.library_id = $1 AND book.deleted_at IS NULL
+            AND book.name <> target.name
+            AND (book.category = target.category OR book.author = target.author)
+            AND book.copy_number = (
+                SELECT MIN(copy.copy_number) FROM book AS copy
+                WHERE copy.library_id = book.library_id AND copy.name = book.name
+                    AND copy.deleted_at IS NULL
+            )
+        ORDER BY (book.category = target.category)::integer
+                + (book.author = target.author)::integer DESC,
+            book.name
+        LIMIT $3
+        "#,
+        library_id,
+        book_id,
+        limit,
+    )
+    .fetch_all(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::author::{self, Author};
+    use crate::library::review;
+    use crate::library::stats::tests::rent_book_times;
+    use crate::library::tag;
+    use crate::library::user::{self, User, UserRentBook};
+    use crate::middleware::library::DEFAULT_LIBRARY_ID;
+    use crate::tests::sequential::sequential_test;
+    use fake::faker::company::en::CompanyName as FakePublisher;
+    use fake::faker::lorem::en::Paragraph as FakeDescription;
+    use fake::faker::lorem::en::Sentence as FakeTitle;
+    use fake::faker::lorem::en::Word as FakeCategory;
+    use fake::Fake;
+    use rand::Rng;
+    use std::ops::Range;
+
+    impl Book {
+        /// Generates a random ISBN-13 with a valid check digit.
+        pub fn fake_isbn() -> String {
+            let mut rng = rand::thread_rng();
+            let mut digits = vec![9, 7, 8];
+            digits.extend((0..9).map(|_| rng.gen_range(0..10)));
+            let sum: u32 = digits
+                .iter()
+                .enumerate()
+                .map(|(i, digit)| if i % 2 == 0 { *digit } else { digit * 3 })
+                .sum();
+            digits.push((10 - sum % 10) % 10);
+            digits.iter().map(|digit| digit.to_string()).collect()
+        }
+
+        /// Creates a fake book object.
+        ///
+        /// This method generates a fake book with random attributes using the `fake` crate.
+        /// It also inserts a fake author associated with the book into the database.
+        ///
+        /// ## Arguments
+        ///
+        /// * `pool`: The PostgreSQL database connection pool.
+        ///
+        /// ## Returns
+        ///
+        /// A `Book` object representing the fake book with randomly generated attributes.
+        ///
+        /// ## Panics
+        ///
+        /// This method will panic if it fails to insert the fake author into the database.
+        pub async fn create_fake_book(pool: &PgPool) -> Self {
+            // first insert an author
+            let fake_author = Author::create_fake_author().await;
+            let _insert_author = author::insert_author(pool, DEFAULT_LIBRARY_ID, &fake_author)
+                .await
+                .expect("failed to insert fake author");
+            let mut rng = rand::thread_rng();
+            let fake_year = rng.gen_range(1900..=2023);
+            Self {
+                name: FakeTitle(Range { start: 10, end: 30 }).fake::<String>(),
+                year: fake_year,
+                category: FakeCategory().fake::<String>(),
+                status: Status::default(),
+                author: fake_author.name,
+                description: Some(FakeDescription(Range { start: 1, end: 3 }).fake::<String>()),
+                publisher: Some(FakePublisher().fake::<String>()),
+                edition: Some(rng.gen_range(1..=10)),
+                language: Some("en".to_owned()),
+                page_count: Some(rng.gen_range(20..=1500)),
+                cover_image_url: Some(format!(
+                    "https://covers.example.com/{}.jpg",
+                    rng.gen::<u32>()
+                )),
+                isbn: Some(Self::fake_isbn()),
+                copy_number: 1,
+                created_at: None,
+                updated_at: None,
+            }
+        }
+    }
+
+    impl From<(Uuid, Book)> for BookSummary {
+        fn from((id, book): (Uuid, Book)) -> Self {
+            Self {
+                id,
+                name: book.name,
+                year: book.year,
+                category: book.category,
+                status: book.status,
+                author: book.author,
+                publisher: book.publisher,
+                edition: book.edition,
+                language: book.language,
+                page_count: book.page_count,
+                cover_image_url: book.cover_image_url,
+                isbn: book.isbn,
+                copy_number: book.copy_number,
+            }
+        }
+    }
+
+    #[test]
+    fn test_next_cursor() {
+        let book = Book {
+            name: "name".to_owned(),
+            year: 2000,
+            category: "category".to_owned(),
+            status: Status::Available,
+            author: "author".to_owned(),
+            description: None,
+            publisher: None,
+            edition: None,
+            language: None,
+            page_count: None,
+            cover_image_url: None,
+            isbn: None,
+            copy_number: 1,
+            created_at: None,
+            updated_at: None,
+        };
+        let books = vec![
+            BookSummary::from((Uuid::from_u128(1), book.clone())),
+            BookSummary::from((Uuid::from_u128(2), book)),
+        ];
+        let query = BookQuery {
+            limit: Some(2),
+            offset: Some(5),
+            after: Some(Uuid::nil()),
+            ..BookQuery::default()
+        };
+        assert_eq!(query.offset_or_default(), 0);
+        assert_eq!(query.next_cursor(&books), Some(Uuid::from_u128(2)));
+        assert_eq!(query.next_cursor(&books[..1]), None);
+        let query = BookQuery {
+            after: None,
+            ..query
+        };
+        assert_eq!(query.offset_or_default(), 5);
+        assert_eq!(query.next_cursor(&books), None);
+        let query = BookQuery {
+            limit: Some(-3),
+            offset: Some(-5),
+            ..query
+        };
+        assert_eq!(query.limit_or_default(), Some(1));
+        assert_eq!(query.offset_or_default(), 0);
+        let query = BookQuery {
+            limit: None,
+            after: Some(Uuid::nil()),
+            ..query
+        };
+        assert_eq!(query.next_cursor(&books), None);
+    }
+
+    #[test]
+    fn test_books_page_query() {
+        let query = BooksPageQuery::default();
+        assert_eq!(query.limit_or_default(), DEFAULT_BOOKS_PAGE_LIMIT);
+        assert_eq!(query.offset_or_default(), 0);
+        let query = BooksPageQuery {
+            limit: Some(MAX_BOOKS_PAGE_LIMIT + 1),
+            offset: Some(-1),
+        };
+        assert_eq!(query.limit_or_default(), MAX_BOOKS_PAGE_LIMIT);
+        assert_eq!(query.offset_or_default(), 0);
+    }
+
+    #[test]
+    fn test_recommendations_query_limit() {
+        assert_eq!(
+            RecommendationsQuery::default().limit_or_default(),
+            DEFAULT_RECOMMENDATIONS_LIMIT
+        );
+        assert_eq!(
+            RecommendationsQuery { limit: Some(0) }.limit_or_default(),
+            1
+        );
+        assert_eq!(
+            RecommendationsQuery { limit: Some(1000) }.limit_or_default(),
+            MAX_RECOMMENDATIONS_LIMIT
+        );
+    }
+
+    #[test]
+    fn test_similar_books_query_limit() {
+        assert_eq!(
+            SimilarBooksQuery::default().limit_or_default(),
+            DEFAULT_SIMILAR_BOOKS_LIMIT
+        );
+        assert_eq!(SimilarBooksQuery { limit: Some(0) }.limit_or_default(), 1);
+        assert_eq!(
+            SimilarBooksQuery { limit: Some(1000) }.limit_or_default(),
+            MAX_SIMILAR_BOOKS_LIMIT
+        );
+    }
+
+    #[test]
+    fn test_book_query_tags() {
+        let query = |uri: &str| {
+            axum::extract::Query::<BookQuery>::try_from_uri(&uri.parse().unwrap())
+                .expect("failed to parse query")
+                .0
+        };
+        let book = query("/api/v1/book?tags=Rust,%20systems,rust&tag_match=any");
+        assert_eq!(
+            book.tags,
+            Some(vec!["rust".to_owned(), "systems".to_owned()])
+        );
+        assert_eq!(book.tag_match, Some(TagMatch::Any));
+        assert!(!book.match_all_tags());
+        let book = query("/api/v1/book?tags=rust");
+        assert_eq!(book.tags, Some(vec!["rust".to_owned()]));
+        assert!(book.match_all_tags());
+        assert_eq!(query("/api/v1/book?tags=,").tags, None);
+        assert_eq!(query("/api/v1/book").tags, None);
+    }
+
+    #[test]
+    fn test_validate_book() {
+        let mut book = Book {
+            name: "name".to_owned(),
+            year: 2000,
+            category: "category".to_owned(),
+            status: Status::default(),
+            author: "author".to_owned(),
+            description: None,
+            publisher: None,
+            edition: None,
+            language: None,
+            page_count: None,
+            cover_image_url: None,
+            isbn: None,
+            copy_number: 1,
+            created_at: None,
+            updated_at: None,
+        };
+        assert!(book.validate().is_ok());
+        book.description = Some("a".repeat(MAX_DESCRIPTION_LEN));
+        assert!(book.validate().is_ok());
+        book.description = Some("a".repeat(MAX_DESCRIPTION_LEN + 1));
+        assert!(book.validate().is_err());
+        book.description = None;
+        book.edition = Some(1);
+        assert!(book.validate().is_ok());
+        book.edition = Some(0);
+        assert!(book.validate().is_err());
+        book.edition = None;
+        book.language = Some("tr".to_owned());
+        assert!(book.validate().is_ok());
+        book.language = Some("TR".to_owned());
+        assert!(book.validate().is_err());
+        book.language = Some("tur".to_owned());
+        assert!(book.validate().is_err());
+        book.language = None;
+        book.page_count = Some(1);
+        assert!(book.validate().is_ok());
+        book.page_count = Some(0);
+        assert!(book.validate().is_err());
+        book.page_count = None;
+        book.cover_image_url = Some("http://example.com/cover.png".to_owned());
+        assert!(book.validate().is_ok());
+        book.cover_image_url = Some("https://example.com/cover.png".to_owned());
+        assert!(book.validate().is_ok());
+        book.cover_image_url = Some("ftp://example.com/cover.png".to_owned());
+        assert!(book.validate().is_err());
+        book.cover_image_url = Some("not a url".to_owned());
+        assert!(book.validate().is_err());
+        book.cover_image_url = None;
+        book.isbn = Some("978-0-306-40615-7".to_owned());
+        assert!(book.validate().is_ok());
+        book.isbn = Some("0-8044-2957-X".to_owned());
+        assert!(book.validate().is_ok());
+        book.isbn = Some("0-8044-2957-x".to_owned());
+        assert!(book.validate().is_ok());
+        book.isbn = Some("978-0-306-40615".to_owned());
+        assert!(book.validate().is_err());
+        book.isbn = Some("978-0-306-40615-8".to_owned());
+        assert!(book.validate().is_err());
+        book.isbn = Some("97A0306406157".to_owned());
+        assert!(book.validate().is_err());
+    }
+
+    #[test]
+    fn test_is_valid_isbn() {
+        for isbn in [
+            "978-0-306-40615-7",
+            "9780306406157",
+            "0-306-40615-2",
+            "080442957X",
+            "080442957x",
+        ] {
+            assert!(is_valid_isbn(isbn), "{isbn}");
+        }
+        for isbn in [
+            "978-0-306-40615-8",
+            "0-306-40615-X",
+            "97803064061",
+            "97A0306406157",
+            "X804429570",
+            "",
+        ] {
+            assert!(!is_valid_isbn(isbn), "{isbn}");
+        }
+        assert!(is_valid_isbn(&Book::fake_isbn()));
     }
+
+    sequential_test!(
+        async fn test_insert_books() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let first = Book::create_fake_book(&pool).await;
+            let second = Book::create_fake_book(&pool).await;
+            // the duplicate fails without rolling back the other books
+            let books = [first.clone(), first.clone(), second.clone()];
+            let results = insert_books(&pool, DEFAULT_LIBRARY_ID, &books)
+                .await
+                .expect("failed to insert books");
+            assert_eq!(results.len(), 3);
+            let first_id = results[0].as_ref().expect("failed to insert first book");
+            assert!(matches!(&results[1], Err(LibraryError::Conflict(_))));
+            let second_id = results[2].as_ref().expect("failed to insert second book");
+            for (id, book) in [(first_id, &first), (second_id, &second)] {
+                let fetched_book = get_book(&pool, DEFAULT_LIBRARY_ID, *id)
+                    .await
+                    .expect("failed to get book");
+                assert_eq!(fetched_book.name, book.name);
+            }
+            let results = insert_books(&pool, DEFAULT_LIBRARY_ID, &[])
+                .await
+                .expect("failed to insert no books");
+            assert!(results.is_empty());
+        }
+    );
+
+    sequential_test!(
+        async fn test_insert_book_copies() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let book = Book::create_fake_book(&pool).await;
+            let ids = insert_book_copies(&pool, DEFAULT_LIBRARY_ID, &book, 3)
+                .await
+                .expect("failed to insert book copies");
+            assert_eq!(ids.len(), 3);
+            for (copy_number, id) in (1..).zip(&ids) {
+                let copy = get_book(&pool, DEFAULT_LIBRARY_ID, *id)
+                    .await
+                    .expect("failed to get book copy");
+                assert_eq!(copy.copy_number, copy_number);
+                assert_eq!(copy.name, book.name);
+            }
+            // the copies are inserted all or nothing
+            let result = insert_book_copies(&pool, DEFAULT_LIBRARY_ID, &book, 5).await;
+            assert!(matches!(result, Err(LibraryError::Conflict(_))));
+            let copies = books(
+                &pool,
+                DEFAULT_LIBRARY_ID,
+                &BookQuery {
+                    name: Some(book.name.clone()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .expect("failed to get books");
+            assert_eq!(copies.len(), 3);
+
+            // the lowest-numbered available copy is rented
+            let user = User::create_fake_user().await;
+            user::insert_user(&pool, DEFAULT_LIBRARY_ID, &user)
+                .await
+                .expect("failed to insert user");
+            let user_rent_book = UserRentBook {
+                nation_id: user.nation_id,
+                book_name: book.name,
+                due_date: "2030-06-15".to_owned(),
+            };
+            for rented in 0..ids.len() {
+                user::rent_book(&pool, DEFAULT_LIBRARY_ID, &user_rent_book)
+                    .await
+                    .expect("failed to rent book");
+                for (copy, id) in ids.iter().enumerate() {
+                    let availability = book_availability(&pool, DEFAULT_LIBRARY_ID, *id)
+                        .await
+                        .expect("failed to get book availability");
+                    let status = if copy <= rented {
+                        Status::Rented
+                    } else {
+                        Status::Available
+                    };
+                    assert_eq!(availability.status, status);
+                }
+            }
+            let result = user::rent_book(&pool, DEFAULT_LIBRARY_ID, &user_rent_book).await;
+            assert!(matches!(result, Err(LibraryError::NotFound)));
+        }
+    );
+
+    sequential_test!(
+        async fn test_get_book_by_name() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let book = Book::create_fake_book(&pool).await;
+            let book_id = insert_book(&pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+            let fetched_book = get_book_by_name(&pool, DEFAULT_LIBRARY_ID, &book.name)
+                .await
+                .expect("failed to get book by name");
+            assert_eq!(
+                Book {
+                    created_at: None,
+                    updated_at: None,
+                    ..fetched_book
+                },
+                book
+            );
+
+            delete_book(&pool, DEFAULT_LIBRARY_ID, book_id)
+                .await
+                .expect("failed to delete book");
+            let result = get_book_by_name(&pool, DEFAULT_LIBRARY_ID, &book.name).await;
+            assert!(matches!(result, Err(LibraryError::NotFound)));
+        }
+    );
+
+    sequential_test!(
+        async fn test_book_availability() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let book = Book::create_fake_book(&pool).await;
+            let book_id = insert_book(&pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+            let availability = book_availability(&pool, DEFAULT_LIBRARY_ID, book_id)
+                .await
+                .expect("failed to get book availability");
+            assert_eq!(
+                availability,
+                BookAvailability {
+                    status: Status::Available,
+                    queue_length: 0,
+                    estimated_return_date: None,
+                }
+            );
+
+            let user = User::create_fake_user().await;
+            user::insert_user(&pool, DEFAULT_LIBRARY_ID, &user)
+                .await
+                .expect("failed to insert user");
+            let user_rent_book = UserRentBook {
+                nation_id: user.nation_id,
+                book_name: book.name,
+                due_date: "2030-06-15".to_owned(),
+            };
+            user::rent_book(&pool, DEFAULT_LIBRARY_ID, &user_rent_book)
+                .await
+                .expect("failed to rent book");
+            let availability = book_availability(&pool, DEFAULT_LIBRARY_ID, book_id)
+                .await
+                .expect("failed to get book availability");
+            assert_eq!(
+                availability,
+                BookAvailability {
+                    status: Status::Rented,
+                    queue_length: 0,
+                    estimated_return_date: NaiveDate::from_ymd_opt(2030, 6, 15),
+                }
+            );
+
+            // deleted books have no availability
+            delete_book(&pool, DEFAULT_LIBRARY_ID, book_id)
+                .await
+                .expect("failed to delete book");
+            let result = book_availability(&pool, DEFAULT_LIBRARY_ID, book_id).await;
+            assert!(matches!(result, Err(LibraryError::NotFound)));
+        }
+    );
+
+    sequential_test!(
+        async fn test_books_by_tags() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let tag_1 = format!("tag-{}", Uuid::new_v4());
+            let tag_2 = format!("tag-{}", Uuid::new_v4());
+            // the first book has both tags, the second only the first one
+            let both = Book::create_fake_book(&pool).await;
+            let both_id = insert_book(&pool, DEFAULT_LIBRARY_ID, &both)
+                .await
+                .expect("failed to insert book");
+            tag::tag_book(
+                &pool,
+                DEFAULT_LIBRARY_ID,
+                both_id,
+                &[tag_1.clone(), tag_2.clone()],
+            )
+            .await
+            .expect("failed to tag book");
+            let one = Book::create_fake_book(&pool).await;
+            let one_id = insert_book(&pool, DEFAULT_LIBRARY_ID, &one)
+                .await
+                .expect("failed to insert book");
+            tag::tag_book(&pool, DEFAULT_LIBRARY_ID, one_id, &[tag_1.clone()])
+                .await
+                .expect("failed to tag book");
+
+            let names = |books: Vec<BookSummary>| {
+                let mut names: Vec<String> = books.into_iter().map(|book| book.name).collect();
+                names.sort();
+                names
+            };
+            let mut expected = vec![both.name.clone(), one.name.clone()];
+            expected.sort();
+            // all the tags
+            let query = BookQuery {
+                tags: Some(vec![tag_1.clone(), tag_2.clone()]),
+                ..Default::default()
+            };
+            let result = books(&pool, DEFAULT_LIBRARY_ID, &query)
+                .await
+                .expect("failed to get books by tags");
+            assert_eq!(names(result), vec![both.name.clone()]);
+            let count = count_books(&pool, DEFAULT_LIBRARY_ID, &query)
+                .await
+                .expect("failed to count books by tags");
+            assert_eq!(count, 1);
+            // any of the tags
+            let query = BookQuery {
+                tag_match: Some(TagMatch::Any),
+                ..query
+            };
+            let result = books(&pool, DEFAULT_LIBRARY_ID, &query)
+                .await
+                .expect("failed to get books by tags");
+            assert_eq!(names(result), expected);
+            // unknown tag
+            let query = BookQuery {
+                tags: Some(vec![format!("tag-{}", Uuid::new_v4())]),
+                ..Default::default()
+            };
+            assert!(matches!(
+                books(&pool, DEFAULT_LIBRARY_ID, &query).await,
+                Err(LibraryError::NotFound)
+            ));
+        }
+    );
+
+    sequential_test!(
+        async fn test_merge_books() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let keep = Book::create_fake_book(&pool).await;
+            let keep_id = insert_book(&pool, DEFAULT_LIBRARY_ID, &keep)
+                .await
+                .expect("failed to insert book");
+            let discard = Book::create_fake_book(&pool).await;
+            let discard_id = insert_book(&pool, DEFAULT_LIBRARY_ID, &discard)
+                .await
+                .expect("failed to insert book");
+            let user = User::create_fake_user().await;
+            user::insert_user(&pool, DEFAULT_LIBRARY_ID, &user)
+                .await
+                .expect("failed to insert user");
+            rent_book_times(&pool, &user.nation_id, &discard.name, 2).await;
+            let review = review::ReviewBody {
+                nation_id: user.nation_id.clone(),
+                rating: 4,
+                comment: None,
+            };
+            review::insert_review(&pool, DEFAULT_LIBRARY_ID, discard_id, &review)
+                .await
+                .expect("failed to insert review");
+            let tag_name = format!("tag-{}", Uuid::new_v4());
+            tag::tag_book(&pool, DEFAULT_LIBRARY_ID, discard_id, &[tag_name.clone()])
+                .await
+                .expect("failed to tag book");
+
+            // same or unknown books
+            assert!(matches!(
+                merge_books(&pool, DEFAULT_LIBRARY_ID, keep_id, keep_id).await,
+                Err(LibraryError::Invalid(_))
+            ));
+            assert!(matches!(
+                merge_books(&pool, DEFAULT_LIBRARY_ID, keep_id, Uuid::nil()).await,
+                Err(LibraryError::NotFound)
+            ));
+
+            let moved = merge_books(&pool, DEFAULT_LIBRARY_ID, keep_id, discard_id)
+                .await
+                .expect("failed to merge books");
+            assert_eq!(moved, 2);
+            assert!(matches!(
+                get_book(&pool, DEFAULT_LIBRARY_ID, discard_id).await,
+                Err(LibraryError::NotFound)
+            ));
+            let history = user::get_user(&pool, DEFAULT_LIBRARY_ID, user.nation_id.clone())
+                .await
+                .expect("failed to get user history");
+            assert_eq!(history.len(), 2);
+            assert!(history.iter().all(|rental| rental.book_name == keep.name));
+            let rating = review::book_rating(&pool, DEFAULT_LIBRARY_ID, &keep.name)
+                .await
+                .expect("failed to get book rating");
+            assert_eq!(rating.review_count, 1);
+            let tags = tag::book_tags(&pool, DEFAULT_LIBRARY_ID, keep_id)
+                .await
+                .expect("failed to get book tags");
+            assert_eq!(tags, vec![tag_name]);
+
+            // a rented book cannot be merged
+            let rented = Book {
+                status: Status::Rented,
+                ..Book::create_fake_book(&pool).await
+            };
+            let rented_id = insert_book(&pool, DEFAULT_LIBRARY_ID, &rented)
+                .await
+                .expect("failed to insert book");
+            assert!(matches!(
+                merge_books(&pool, DEFAULT_LIBRARY_ID, keep_id, rented_id).await,
+                Err(LibraryError::Forbidden(_))
+            ));
+        }
+    );
+
+    sequential_test!(
+        async fn test_new_arrivals() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let new_book = Book::create_fake_book(&pool).await;
+            let old_book = Book::create_fake_book(&pool).await;
+            for book in [&old_book, &new_book] {
+                insert_book(&pool, DEFAULT_LIBRARY_ID, book)
+                    .await
+                    .expect("failed to insert book");
+            }
+            timed_sql!(sqlx::query!(
+                "UPDATE book SET created_at = NOW() - INTERVAL '10 days' WHERE name = $1",
+                old_book.name,
+            )
+            .execute(&pool))
+            .await
+            .expect("failed to backdate book");
+
+            let result = new_arrivals(&pool, DEFAULT_LIBRARY_ID, 5, MAX_NEW_ARRIVALS_LIMIT)
+                .await
+                .expect("failed to get new arrivals");
+            let contains =
+                |result: &[Book], book: &Book| result.iter().any(|b| b.name == book.name);
+            assert!(contains(&result, &new_book));
+            assert!(!contains(&result, &old_book));
+            let result = new_arrivals(&pool, DEFAULT_LIBRARY_ID, 11, MAX_NEW_ARRIVALS_LIMIT)
+                .await
+                .expect("failed to get new arrivals");
+            assert!(contains(&result, &old_book));
+            let result = new_arrivals(&pool, DEFAULT_LIBRARY_ID, 5, 1)
+                .await
+                .expect("failed to get new arrivals");
+            assert_eq!(result.len(), 1);
+
+            assert!(NewArrivalsQuery::default().validate().is_ok());
+            let query = NewArrivalsQuery {
+                days: Some(0),
+                limit: None,
+            };
+            assert!(query.validate().is_err());
+        }
+    );
+
+    sequential_test!(
+        async fn test_book() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let book = Book::create_fake_book(&pool).await;
+            // insert_book
+            let result_id = insert_book(&pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+            let fetched_book = timed_sql!(sqlx::query_as!(
+                Book,
+                r#"
+                SELECT name, year, category, status as "status: _", author, description, publisher,
+                    edition, language, page_count, cover_image_url, isbn, copy_number,
+                    created_at as "created_at?", updated_at as "updated_at?"
+                FROM book
+                WHERE Id = $1
+                "#,
+                result_id,
+            )
+            .fetch_one(&pool))
+            .await
+            .expect("unmatched book");
+            assert!(fetched_book.created_at.is_some());
+            assert_eq!(fetched_book.created_at, fetched_book.updated_at);
+            assert_eq!(
+                Book {
+                    created_at: None,
+                    updated_at: None,
+                    ..fetched_book
+                },
+                book
+            );
+            // get_book
+            let get_book_result = get_book(&pool, DEFAULT_LIBRARY_ID, result_id)
+                .await
+                .expect("failed to get book");
+            assert_eq!(book.name, get_book_result.name);
+            assert_eq!(book.description, get_book_result.description);
+            // books
+            // 1: all books
+            let books_result = books(&pool, DEFAULT_LIBRARY_ID, &BookQuery::default()).await;
+            assert!(books_result
+                .as_ref()
+                .map(|books| !books.is_empty())
+                .unwrap_or_else(|_| false));
+            assert!(books_result.is_ok());
+            // 2: Get books by year (with year filter)
+            let books_by_year_result = books(
+                &pool,
+                DEFAULT_LIBRARY_ID,
+                &BookQuery {
+                    year: Some(book.year),
+                    ..Default::default()
+                },
+            )
+            .await;
+            assert!(books_by_year_result.is_ok());
+            // 3: Get books by category (with category filter)
+            let books_by_category_result = books(
+                &pool,
+                DEFAULT_LIBRARY_ID,
+                &BookQuery {
+                    category: Some(book.category.clone()),
+                    ..Default::default()
+                },
+            )
+            .await;
+            assert!(books_by_category_result.is_ok());
+            // 4: Get books by status (with status filter)
+            let books_by_status_result = books(
+                &pool,
+                DEFAULT_LIBRARY_ID,
+                &BookQuery {
+                    status: Some(book.status),
+                    ..Default::default()
+                },
+            )
+            .await;
+            assert!(books_by_status_result.is_ok());
+            // 5: Get books by author (with author filter)
+            let books_by_author_result = books(
+                &pool,
+                DEFAULT_LIBRARY_ID,
+                &BookQuery {
+                    author: Some(book.author.clone()),
+                    ..Default::default()
+                },
+            )
+            .await;
+            assert!(books_by_author_result.is_ok());
+            // 6: Exact given criteria
+            let books_by_all_criteria = books(
+                &pool,
+                DEFAULT_LIBRARY_ID,
+                &BookQuery {
+                    name: Some(book.name.clone()),
+                    year: Some(book.year),
+                    category: Some(book.category.clone()),
+                    status: Some(book.status),
+                    author: Some(book.author.clone()),
+                    ..Default::default()
+                },
+            )
+            .await;
+            assert!(books_by_all_criteria.is_ok());
+            // 7: Get books by partial publisher (case-insensitive)
+            let publisher = book.publisher.clone().expect("fake book has a publisher");
+            let books_by_publisher_result = books(
+                &pool,
+                DEFAULT_LIBRARY_ID,
+                &BookQuery {
+                    publisher: Some(publisher[1..].to_uppercase()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .expect("failed to get books by publisher");
+            assert!(books_by_publisher_result
+                .iter()
+                .any(|result| result.name == book.name));
+            // 8: Get books by exact and minimum edition
+            let edition = book.edition.expect("fake book has an edition");
+            let books_by_edition_result = books(
+                &pool,
+                DEFAULT_LIBRARY_ID,
+                &BookQuery {
+                    name: Some(book.name.clone()),
+                    edition: Some(edition),
+                    ..Default::default()
+                },
+            )
+            .await;
+            assert!(books_by_edition_result.is_ok());
+            let books_by_edition_min_result = books(
+                &pool,
+                DEFAULT_LIBRARY_ID,
+                &BookQuery {
+                    name: Some(book.name.clone()),
+                    edition_min: Some(edition),
+                    ..Default::default()
+                },
+            )
+            .await;
+            assert!(books_by_edition_min_result.is_ok());
+            let books_by_edition_min_result = books(
+                &pool,
+                DEFAULT_LIBRARY_ID,
+                &BookQuery {
+                    name: Some(book.name.clone()),
+                    edition_min: Some(edition + 1),
+                    ..Default::default()
+                },
+            )
+            .await;
+            assert!(matches!(
+                books_by_edition_min_result,
+                Err(LibraryError::NotFound)
+            ));
+            // 9: Soft-deleted books are hidden until restored
+            let book_by_name = BookQuery {
+                name: Some(book.name.clone()),
+                ..Default::default()
+            };
+            delete_book(&pool, DEFAULT_LIBRARY_ID, result_id)
+                .await
+                .expect("failed to delete book");
+            assert!(delete_book(&pool, DEFAULT_LIBRARY_ID, result_id)
+                .await
+                .is_err());
+            assert!(get_book(&pool, DEFAULT_LIBRARY_ID, result_id)
+                .await
+                .is_err());
+            assert!(matches!(
+                books(&pool, DEFAULT_LIBRARY_ID, &book_by_name).await,
+                Err(LibraryError::NotFound)
+            ));
+            let deleted_books_result = deleted_books(&pool, DEFAULT_LIBRARY_ID)
+                .await
+                .expect("failed to get deleted books");
+            assert!(deleted_books_result
+                .iter()
+                .any(|result| result.name == book.name));
+            restore_book(&pool, DEFAULT_LIBRARY_ID, result_id)
+                .await
+                .expect("failed to restore book");
+            assert!(restore_book(&pool, DEFAULT_LIBRARY_ID, result_id)
+                .await
+                .is_err());
+            assert!(books(&pool, DEFAULT_LIBRARY_ID, &book_by_name)
+                .await
+                .is_ok());
+        }
+    );
 }
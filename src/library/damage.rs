@@ -0,0 +1,215 @@
+use crate::library::error::LibraryError;
+use crate::telemetry::db_metrics::timed_sql;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Maximum number of characters allowed in the description of a damage.
+pub const MAX_DAMAGE_DESCRIPTION_LEN: usize = 500;
+
+/// Represents a report of a damaged book submitted by a user.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct FlagBookBody {
+    pub nation_id: String,
+    /// What is damaged, at most 500 characters.
+    pub description: String,
+}
+
+/// Represents a row in the damaged_reports table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct DamageReportRow {
+    pub id: Uuid,
+    pub nation_id: String,
+    pub description: String,
+    pub reported_at: DateTime<Utc>,
+}
+
+impl FlagBookBody {
+    /// Validates the report before it is written to the database.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns a message if the description is blank or exceeds
+    /// `MAX_DAMAGE_DESCRIPTION_LEN` characters.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.description.trim().is_empty() {
+            return Err("description must not be blank".to_owned());
+        }
+        if self.description.chars().count() > MAX_DAMAGE_DESCRIPTION_LEN {
+            return Err(format!(
+                "description must be at most {MAX_DAMAGE_DESCRIPTION_LEN} characters"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Reports a copy of a book as damaged.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
+/// * `book_id`: The ID of the damaged copy of the book.
+/// * `report`: The report to insert.
+///
+/// ## Returns
+///
+/// The UUID of the inserted report.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if there is no book or no
+/// user with the given IDs that is not deleted, or an error if there is an
+/// issue with the database connection.
+#[tracing::instrument(level = "debug", skip(pool, report))]
+pub async fn flag_book(
+    pool: &PgPool,
+    library_id: &str,
+    book_id: Uuid,
+    report: &FlagBookBody,
+) -> Result<Uuid, LibraryError> {
+    timed_sql!(sqlx::query!(
+        r#"
+            INSERT INTO damaged_reports (library_id, book_name, copy_number, nation_id, description)
+            SELECT book.library_id, book.name, book.copy_number, users.nation_id, $4
+            FROM book
+            JOIN users ON users.library_id = book.library_id
+                AND users.nation_id = $3 AND users.deleted_at IS NULL
+            WHERE book.library_id = $1 AND book.id = $2 AND book.deleted_at IS NULL
+            RETURNING id
+        "#,
+        library_id,
+        book_id,
+        report.nation_id,
+        report.description,
+    )
+    .fetch_one(pool))
+    .await
+    .map(|record| record.id)
+    .map_err(LibraryError::from)
+}
+
+/// Retrieves the damage reports of a copy of a book, newest first.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
+/// * `book_id`: The ID of the copy of the book.
+///
+/// ## Returns
+///
+/// A vector of `DamageReportRow` representing the reports of the copy. The
+/// vector is empty if the copy has not been reported.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn book_flags(
+    pool: &PgPool,
+    library_id: &str,
+    book_id: Uuid,
+) -> Result<Vec<DamageReportRow>, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        DamageReportRow,
+        r#"
+        SELECT damaged_reports.id, damaged_reports.nation_id, damaged_reports.description,
+            damaged_reports.reported_at
+        FROM damaged_reports
+        JOIN book ON book.library_id = damaged_reports.library_id
+            AND book.name = damaged_reports.book_name
+            AND book.copy_number = damaged_reports.copy_number
+        WHERE book.library_id = $1 AND book.id = $2
+        ORDER BY damaged_reports.reported_at DESC, damaged_reports.id
+        "#,
+        library_id,
+        book_id,
+    )
+    .fetch_all(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::book::{self, Book};
+    use crate::library::user::{self, User};
+    use crate::middleware::library::DEFAULT_LIBRARY_ID;
+    use crate::tests::sequential::sequential_test;
+
+    #[test]
+    fn test_validate_flag_book() {
+        let mut report = FlagBookBody {
+            nation_id: "12345678901".to_owned(),
+            description: "torn cover".to_owned(),
+        };
+        assert!(report.validate().is_ok());
+        report.description = " ".to_owned();
+        assert!(report.validate().is_err());
+        report.description = "a".repeat(MAX_DAMAGE_DESCRIPTION_LEN);
+        assert!(report.validate().is_ok());
+        report.description = "a".repeat(MAX_DAMAGE_DESCRIPTION_LEN + 1);
+        assert!(report.validate().is_err());
+    }
+
+    sequential_test!(
+        async fn test_flag_book() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            // insert book copies, user
+            let book = Book::create_fake_book(&pool).await;
+            let ids = book::insert_book_copies(&pool, DEFAULT_LIBRARY_ID, &book, 2)
+                .await
+                .expect("failed to insert book copies");
+            let user = User::create_fake_user().await;
+            user::insert_user(&pool, DEFAULT_LIBRARY_ID, &user)
+                .await
+                .expect("failed to insert user");
+            // book_flags without reports
+            let flags = book_flags(&pool, DEFAULT_LIBRARY_ID, ids[0])
+                .await
+                .expect("failed to get book flags");
+            assert!(flags.is_empty());
+            // flag_book
+            let report = FlagBookBody {
+                nation_id: user.nation_id.clone(),
+                description: "missing pages".to_owned(),
+            };
+            let report_id = flag_book(&pool, DEFAULT_LIBRARY_ID, ids[0], &report)
+                .await
+                .expect("failed to flag book");
+            // unknown book or user
+            assert!(matches!(
+                flag_book(&pool, DEFAULT_LIBRARY_ID, Uuid::nil(), &report).await,
+                Err(LibraryError::NotFound)
+            ));
+            let unknown_user = FlagBookBody {
+                nation_id: "unknown".to_owned(),
+                ..report.clone()
+            };
+            assert!(matches!(
+                flag_book(&pool, DEFAULT_LIBRARY_ID, ids[0], &unknown_user).await,
+                Err(LibraryError::NotFound)
+            ));
+            // book_flags only returns the reports of the copy
+            let flags = book_flags(&pool, DEFAULT_LIBRARY_ID, ids[0])
+                .await
+                .expect("failed to get book flags");
+            assert_eq!(flags.len(), 1);
+            assert_eq!(flags[0].id, report_id);
+            assert_eq!(flags[0].nation_id, report.nation_id);
+            assert_eq!(flags[0].description, report.description);
+            let flags = book_flags(&pool, DEFAULT_LIBRARY_ID, ids[1])
+                .await
+                .expect("failed to get book flags");
+            assert!(flags.is_empty());
+        }
+    );
+}
@@ -0,0 +1,75 @@
+use std::fmt::{Display, Formatter, Result as fmtResult};
+
+/// SQLSTATE code of a unique violation in PostgreSQL.
+const UNIQUE_VIOLATION: &str = "23505";
+
+/// Represents the errors returned by the functions of the `library` module.
+///
+/// The following variants are available:
+///
+/// - `NotFound`: The requested rows do not exist.
+/// - `Conflict`: The rows conflict with existing ones. It carries the name of
+///   the violated unique constraint.
+/// - `Invalid`: The input was rejected before reaching the database. It
+///   carries the reason of the rejection.
+/// - `Forbidden`: The operation is not allowed in the current state of the
+///   rows, e.g. a blacklisted user renting a book. It carries the reason.
+/// - `DatabaseError`: Any other error of the database.
+#[derive(Debug)]
+pub enum LibraryError {
+    NotFound,
+    Conflict(String),
+    Invalid(String),
+    Forbidden(String),
+    DatabaseError(sqlx::Error),
+}
+
+impl Display for LibraryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmtResult {
+        match self {
+            LibraryError::NotFound => write!(f, "not found"),
+            LibraryError::Conflict(constraint) => write!(f, "duplicate value for {constraint}"),
+            LibraryError::Invalid(reason) => write!(f, "{reason}"),
+            LibraryError::Forbidden(reason) => write!(f, "{reason}"),
+            LibraryError::DatabaseError(err) => write!(f, "database error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for LibraryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LibraryError::DatabaseError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<sqlx::Error> for LibraryError {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::RowNotFound => LibraryError::NotFound,
+            sqlx::Error::Database(err) if err.code().as_deref() == Some(UNIQUE_VIOLATION) => {
+                LibraryError::Conflict(err.constraint().unwrap_or_default().to_owned())
+            }
+            err => LibraryError::DatabaseError(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_sqlx_error() {
+        assert!(matches!(
+            LibraryError::from(sqlx::Error::RowNotFound),
+            LibraryError::NotFound
+        ));
+        assert!(matches!(
+            LibraryError::from(sqlx::Error::PoolTimedOut),
+            LibraryError::DatabaseError(sqlx::Error::PoolTimedOut)
+        ));
+    }
+}
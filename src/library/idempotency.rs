@@ -0,0 +1,253 @@
+use crate::library::error::LibraryError;
+use crate::telemetry::db_metrics::timed_sql;
+use sqlx::PgPool;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// How long the response of a request sent with an idempotency key is kept.
+pub const IDEMPOTENCY_KEY_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Represents the state of an idempotency key when a request using it starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IdempotencyKeyState {
+    /// The key was unused or expired and is now reserved by the request.
+    Reserved,
+    /// Another request with the key is still being handled.
+    InProgress,
+    /// A request with the key completed with the given response body.
+    Completed(serde_json::Value),
+    /// The key was used for a request to another path.
+    Mismatch,
+}
+
+/// Reserves an idempotency key for a request.
+///
+/// A key is reserved for `lease`, the longest the request may take, after
+/// which it can be reused, so a request that never finishes does not hold
+/// the key for long. The reservation is either completed with
+/// `complete_idempotency_key`, which keeps the key for `IDEMPOTENCY_KEY_TTL`,
+/// or released with `release_idempotency_key` once the request is handled.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the request operates on.
+/// * `key`: The idempotency key of the request.
+/// * `request_path`: The path of the request.
+/// * `lease`: How long the key is reserved for while the request is handled.
+///
+/// ## Returns
+///
+/// `IdempotencyKeyState::Reserved` if the request must be handled, or the
+/// state of the key otherwise.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn reserve_idempotency_key(
+    pool: &PgPool,
+    library_id: &str,
+    key: Uuid,
+    request_path: &str,
+    lease: Duration,
+) -> Result<IdempotencyKeyState, LibraryError> {
+    let reserved = timed_sql!(sqlx::query!(
+        r#"
+        INSERT INTO idempotency_cache (library_id, idempotency_key, request_path, expires_at)
+        VALUES ($1, $2, $3, NOW() + make_interval(secs => $4))
+        ON CONFLICT (library_id, idempotency_key) DO UPDATE
+        SET request_path = EXCLUDED.request_path,
+            response_body = NULL,
+            created_at = NOW(),
+            expires_at = EXCLUDED.expires_at
+        WHERE idempotency_cache.expires_at <= NOW()
+        RETURNING idempotency_key
+        "#,
+        library_id,
+        key,
+        request_path,
+        lease.as_secs_f64(),
+    )
+    .fetch_optional(pool))
+    .await?;
+    if reserved.is_some() {
+        return Ok(IdempotencyKeyState::Reserved);
+    }
+
+    let existing = timed_sql!(sqlx::query!(
+        r#"
+        SELECT request_path, response_body
+        FROM idempotency_cache
+        WHERE library_id = $1 AND idempotency_key = $2
+        "#,
+        library_id,
+        key,
+    )
+    .fetch_optional(pool))
+    .await?;
+    // a key released in the meantime is reported as in progress, so the
+    // client retries
+    Ok(match existing {
+        Some(row) if row.request_path != request_path => IdempotencyKeyState::Mismatch,
+        Some(row) => row.response_body.map_or(
+            IdempotencyKeyState::InProgress,
+            IdempotencyKeyState::Completed,
+        ),
+        None => IdempotencyKeyState::InProgress,
+    })
+}
+
+/// Stores the response body of the request that reserved an idempotency key,
+/// which is then kept for `IDEMPOTENCY_KEY_TTL`.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the request operates on.
+/// * `key`: The idempotency key of the request.
+/// * `response_body`: The JSON body of the response.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool, response_body))]
+pub async fn complete_idempotency_key(
+    pool: &PgPool,
+    library_id: &str,
+    key: Uuid,
+    response_body: &serde_json::Value,
+) -> Result<(), LibraryError> {
+    timed_sql!(sqlx::query!(
+        r#"
+        UPDATE idempotency_cache
+        SET response_body = $3, expires_at = NOW() + make_interval(secs => $4)
+        WHERE library_id = $1 AND idempotency_key = $2
+        "#,
+        library_id,
+        key,
+        response_body,
+        IDEMPOTENCY_KEY_TTL.as_secs_f64(),
+    )
+    .execute(pool))
+    .await?;
+    Ok(())
+}
+
+/// Releases an idempotency key whose request failed, so it can be retried.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the request operates on.
+/// * `key`: The idempotency key of the request.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn release_idempotency_key(
+    pool: &PgPool,
+    library_id: &str,
+    key: Uuid,
+) -> Result<(), LibraryError> {
+    timed_sql!(sqlx::query!(
+        r#"
+        DELETE FROM idempotency_cache
+        WHERE library_id = $1 AND idempotency_key = $2 AND response_body IS NULL
+        "#,
+        library_id,
+        key,
+    )
+    .execute(pool))
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::library::DEFAULT_LIBRARY_ID;
+    use crate::tests::sequential::sequential_test;
+    use serde_json::json;
+
+    sequential_test!(
+        async fn test_idempotency_key_states() {
+            let pool = &crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let key = Uuid::from_u128(1);
+            sqlx::query("DELETE FROM idempotency_cache WHERE idempotency_key = $1")
+                .bind(key)
+                .execute(pool)
+                .await
+                .expect("failed to delete idempotency keys");
+            let path = "/api/v1/user/rent/1";
+            let lease = Duration::from_secs(60);
+
+            let state = reserve_idempotency_key(pool, DEFAULT_LIBRARY_ID, key, path, lease).await;
+            assert_eq!(state.unwrap(), IdempotencyKeyState::Reserved);
+            let state = reserve_idempotency_key(pool, DEFAULT_LIBRARY_ID, key, path, lease).await;
+            assert_eq!(state.unwrap(), IdempotencyKeyState::InProgress);
+            let state =
+                reserve_idempotency_key(pool, DEFAULT_LIBRARY_ID, key, "/other", lease).await;
+            assert_eq!(state.unwrap(), IdempotencyKeyState::Mismatch);
+            // keys are scoped to a library
+            let state = reserve_idempotency_key(pool, "other", key, path, lease).await;
+            assert_eq!(state.unwrap(), IdempotencyKeyState::Reserved);
+
+            let body = json!({ "message": "done" });
+            complete_idempotency_key(pool, DEFAULT_LIBRARY_ID, key, &body)
+                .await
+                .expect("failed to complete idempotency key");
+            // a completed key is not released
+            release_idempotency_key(pool, DEFAULT_LIBRARY_ID, key)
+                .await
+                .expect("failed to release idempotency key");
+            let state = reserve_idempotency_key(pool, DEFAULT_LIBRARY_ID, key, path, lease).await;
+            assert_eq!(state.unwrap(), IdempotencyKeyState::Completed(body));
+            // a completed key is kept past the lease
+            let expires_in = sqlx::query_scalar::<_, f64>(
+                r#"
+                SELECT EXTRACT(EPOCH FROM expires_at - NOW())::float8
+                FROM idempotency_cache
+                WHERE library_id = $1 AND idempotency_key = $2
+                "#,
+            )
+            .bind(DEFAULT_LIBRARY_ID)
+            .bind(key)
+            .fetch_one(pool)
+            .await
+            .expect("failed to get expiry of idempotency key");
+            assert!(expires_in > lease.as_secs_f64());
+
+            release_idempotency_key(pool, "other", key)
+                .await
+                .expect("failed to release idempotency key");
+            let state = reserve_idempotency_key(pool, "other", key, path, lease).await;
+            assert_eq!(state.unwrap(), IdempotencyKeyState::Reserved);
+            // a request outliving its lease no longer holds the key
+            release_idempotency_key(pool, "other", key)
+                .await
+                .expect("failed to release idempotency key");
+            let state = reserve_idempotency_key(pool, "other", key, path, Duration::ZERO).await;
+            assert_eq!(state.unwrap(), IdempotencyKeyState::Reserved);
+            let state = reserve_idempotency_key(pool, "other", key, path, lease).await;
+            assert_eq!(state.unwrap(), IdempotencyKeyState::Reserved);
+
+            // expired keys are reserved again
+            sqlx::query(
+                "UPDATE idempotency_cache SET expires_at = NOW() WHERE idempotency_key = $1",
+            )
+            .bind(key)
+            .execute(pool)
+            .await
+            .expect("failed to expire idempotency keys");
+            let state = reserve_idempotency_key(pool, DEFAULT_LIBRARY_ID, key, path, lease).await;
+            assert_eq!(state.unwrap(), IdempotencyKeyState::Reserved);
+        }
+    );
+}
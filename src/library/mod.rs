@@ -1,3 +1,10 @@
+pub mod audit;
 pub mod author;
 pub mod book;
+pub mod damage;
+pub mod error;
+pub mod idempotency;
+pub mod review;
+pub mod stats;
+pub mod tag;
 pub mod user;
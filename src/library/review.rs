@@ -0,0 +1,332 @@
+use crate::library::error::LibraryError;
+use crate::telemetry::db_metrics::timed_sql;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+/// Maximum number of characters allowed in a review comment.
+pub const MAX_COMMENT_LEN: usize = 1000;
+
+/// Default number of reviews returned per page.
+pub const DEFAULT_REVIEWS_LIMIT: i64 = 20;
+
+/// Maximum number of reviews returned per page.
+pub const MAX_REVIEWS_LIMIT: i64 = 100;
+
+/// Represents a rating submitted by a user for a book.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct RatingBody {
+    pub nation_id: String,
+    pub rating: i32,
+}
+
+/// Represents a review, a rating with an optional comment, submitted by a user
+/// for a book.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ReviewBody {
+    pub nation_id: String,
+    pub rating: i32,
+    pub comment: Option<String>,
+}
+
+/// Represents a row in the book_reviews table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct ReviewRow {
+    pub nation_id: String,
+    pub rating: i32,
+    pub comment: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Represents the pagination parameters for listing reviews.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, IntoParams)]
+pub struct ReviewQuery {
+    /// Number of reviews to return, defaults to 20 and is capped at 100.
+    pub limit: Option<i64>,
+    /// Number of reviews to skip, defaults to 0.
+    pub offset: Option<i64>,
+}
+
+/// Represents the aggregated rating of a book.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BookRating {
+    pub average_rating: Option<f64>,
+    pub review_count: i64,
+}
+
+impl From<RatingBody> for ReviewBody {
+    fn from(rating: RatingBody) -> Self {
+        Self {
+            nation_id: rating.nation_id,
+            rating: rating.rating,
+            comment: None,
+        }
+    }
+}
+
+impl ReviewBody {
+    /// Validates the review before it is written to the database.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns a message if the rating is not between 1 and 5 or
+    /// if the comment exceeds `MAX_COMMENT_LEN` characters.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=5).contains(&self.rating) {
+            return Err("rating must be between 1 and 5".to_owned());
+        }
+        if let Some(comment) = &self.comment {
+            if comment.chars().count() > MAX_COMMENT_LEN {
+                return Err(format!(
+                    "comment must be at most {MAX_COMMENT_LEN} characters"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Inserts a review for the given book into the database.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
+/// * `book_id`: The ID of the reviewed book.
+/// * `review`: The review to insert.
+///
+/// ## Returns
+///
+/// The UUID of the inserted review.
+///
+/// ## Errors
+///
+/// This function returns an error if the book does not exist, if the user
+/// has already rated the book or if there is an issue with the database
+/// connection.
+pub async fn insert_review(
+    pool: &PgPool,
+    library_id: &str,
+    book_id: Uuid,
+    review: &ReviewBody,
+) -> Result<Uuid, LibraryError> {
+    timed_sql!(sqlx::query!(
+        r#"
+            INSERT INTO book_reviews (library_id, book_name, nation_id, rating, comment)
+            SELECT library_id, name, $3, $4, $5
+            FROM book
+            WHERE library_id = $1 AND id = $2 AND deleted_at IS NULL
+            RETURNING id
+        "#,
+        library_id,
+        book_id,
+        review.nation_id,
+        review.rating,
+        review.comment,
+    )
+    .fetch_one(pool))
+    .await
+    .map(|record| record.id)
+    .map_err(LibraryError::from)
+}
+
+/// Retrieves the average rating and the number of reviews of a book.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
+/// * `book_name`: The name of the book.
+///
+/// ## Returns
+///
+/// A `BookRating` with the average rating, which is `None` if the book has
+/// not been rated yet, and the number of reviews.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+pub async fn book_rating(
+    pool: &PgPool,
+    library_id: &str,
+    book_name: &str,
+) -> Result<BookRating, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        BookRating,
+        r#"
+        SELECT AVG(rating)::float8 as average_rating, COUNT(*) as "review_count!"
+        FROM book_reviews
+        WHERE library_id = $1 AND book_name = $2
+        "#,
+        library_id,
+        book_name,
+    )
+    .fetch_one(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Retrieves a page of reviews of a book, newest first.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
+/// * `book_id`: The ID of the book.
+/// * `query`: The pagination parameters.
+///
+/// ## Returns
+///
+/// A vector of `ReviewRow` representing the reviews of the book. The vector
+/// is empty if the page is past the last review.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+pub async fn reviews(
+    pool: &PgPool,
+    library_id: &str,
+    book_id: Uuid,
+    query: &ReviewQuery,
+) -> Result<Vec<ReviewRow>, LibraryError> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_REVIEWS_LIMIT)
+        .clamp(1, MAX_REVIEWS_LIMIT);
+    let offset = query.offset.unwrap_or_default().max(0);
+    timed_sql!(sqlx::query_as!(
+        ReviewRow,
+        r#"
+        SELECT book_reviews.nation_id, book_reviews.rating, book_reviews.comment,
+            book_reviews.created_at
+        FROM book_reviews
+        JOIN book ON book.library_id = book_reviews.library_id
+            AND book.name = book_reviews.book_name
+        WHERE book.library_id = $1 AND book.id = $2 AND book.deleted_at IS NULL
+        ORDER BY book_reviews.created_at DESC, book_reviews.id
+        LIMIT $3 OFFSET $4
+        "#,
+        library_id,
+        book_id,
+        limit,
+        offset,
+    )
+    .fetch_all(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::book::{self, Book};
+    use crate::library::user::{self, User};
+    use crate::middleware::library::DEFAULT_LIBRARY_ID;
+    use crate::tests::sequential::sequential_test;
+
+    #[test]
+    fn test_validate_review() {
+        let mut review = ReviewBody::from(RatingBody {
+            nation_id: "12345678901".to_owned(),
+            rating: 1,
+        });
+        assert!(review.validate().is_ok());
+        review.rating = 5;
+        assert!(review.validate().is_ok());
+        review.rating = 0;
+        assert!(review.validate().is_err());
+        review.rating = 6;
+        assert!(review.validate().is_err());
+        review.rating = 3;
+        review.comment = Some("a".repeat(MAX_COMMENT_LEN));
+        assert!(review.validate().is_ok());
+        review.comment = Some("a".repeat(MAX_COMMENT_LEN + 1));
+        assert!(review.validate().is_err());
+    }
+
+    sequential_test!(
+        async fn test_review() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            // insert book, users
+            let book = Book::create_fake_book(&pool).await;
+            let book_id = book::insert_book(&pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+            let user_1 = User::create_fake_user().await;
+            user::insert_user(&pool, DEFAULT_LIBRARY_ID, &user_1)
+                .await
+                .expect("failed to insert user");
+            let user_2 = User::create_fake_user().await;
+            user::insert_user(&pool, DEFAULT_LIBRARY_ID, &user_2)
+                .await
+                .expect("failed to insert user");
+            // book_rating without reviews
+            let rating = book_rating(&pool, DEFAULT_LIBRARY_ID, &book.name)
+                .await
+                .expect("failed to get book rating");
+            assert_eq!(rating.average_rating, None);
+            assert_eq!(rating.review_count, 0);
+            // insert_review
+            let review_1 = ReviewBody {
+                nation_id: user_1.nation_id.clone(),
+                rating: 2,
+                comment: None,
+            };
+            insert_review(&pool, DEFAULT_LIBRARY_ID, book_id, &review_1)
+                .await
+                .expect("failed to insert review");
+            let review_2 = ReviewBody {
+                nation_id: user_2.nation_id.clone(),
+                rating: 5,
+                comment: Some("great book".to_owned()),
+            };
+            insert_review(&pool, DEFAULT_LIBRARY_ID, book_id, &review_2)
+                .await
+                .expect("failed to insert review");
+            // one review per user per book
+            assert!(insert_review(&pool, DEFAULT_LIBRARY_ID, book_id, &review_1)
+                .await
+                .is_err());
+            // unknown book
+            assert!(matches!(
+                insert_review(&pool, DEFAULT_LIBRARY_ID, Uuid::nil(), &review_1).await,
+                Err(LibraryError::NotFound)
+            ));
+            // book_rating
+            let rating = book_rating(&pool, DEFAULT_LIBRARY_ID, &book.name)
+                .await
+                .expect("failed to get book rating");
+            assert_eq!(rating.average_rating, Some(3.5));
+            assert_eq!(rating.review_count, 2);
+            // reviews
+            let reviews_result =
+                reviews(&pool, DEFAULT_LIBRARY_ID, book_id, &ReviewQuery::default())
+                    .await
+                    .expect("failed to get reviews");
+            assert_eq!(reviews_result.len(), 2);
+            assert!(reviews_result
+                .iter()
+                .any(|review| review.nation_id == review_2.nation_id
+                    && review.comment == review_2.comment));
+            let reviews_page = reviews(
+                &pool,
+                DEFAULT_LIBRARY_ID,
+                book_id,
+                &ReviewQuery {
+                    limit: Some(1),
+                    offset: Some(1),
+                },
+            )
+            .await
+            .expect("failed to get reviews");
+            assert_eq!(reviews_page.len(), 1);
+            assert_eq!(reviews_page[0], reviews_result[1]);
+        }
+    );
+}
@@ -0,0 +1,864 @@
+use crate::library::error::LibraryError;
+use crate::telemetry::db_metrics::timed_sql;
+use chrono::{Datelike, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::{IntoParams, ToSchema};
+
+/// Default number of rows returned by the top lists.
+pub const DEFAULT_TOP_LIMIT: i64 = 10;
+
+/// Maximum number of rows returned by the top lists.
+pub const MAX_TOP_LIMIT: i64 = 50;
+
+/// Represents a query for the top lists.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, IntoParams)]
+pub struct TopQuery {
+    /// Number of rows to return, defaults to 10 and is capped at 50.
+    pub limit: Option<i64>,
+}
+
+impl TopQuery {
+    /// Returns the requested limit, falling back to the default and capped at
+    /// the maximum.
+    pub fn limit_or_default(&self) -> i64 {
+        self.limit
+            .unwrap_or(DEFAULT_TOP_LIMIT)
+            .clamp(1, MAX_TOP_LIMIT)
+    }
+}
+
+/// Default number of hours of rentals the trending books are ranked on.
+pub const DEFAULT_TRENDING_HOURS: i64 = 24;
+
+/// Maximum number of hours of rentals the trending books are ranked on.
+pub const MAX_TRENDING_HOURS: i64 = 720;
+
+/// Represents a query for the trending books.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, IntoParams)]
+pub struct TrendingQuery {
+    /// Number of past hours whose rentals are counted, from 1 to 720,
+    /// defaults to 24.
+    pub hours: Option<i64>,
+    /// Number of rows to return, defaults to 10 and is capped at 50.
+    pub limit: Option<i64>,
+}
+
+impl TrendingQuery {
+    /// Validates the query.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns a message when the number of hours is not
+    /// between 1 and 720.
+    pub fn validate(&self) -> Result<(), String> {
+        if self
+            .hours
+            .is_some_and(|hours| !(1..=MAX_TRENDING_HOURS).contains(&hours))
+        {
+            return Err(format!("hours must be between 1 and {MAX_TRENDING_HOURS}"));
+        }
+        Ok(())
+    }
+
+    /// Returns the requested number of hours, falling back to the default.
+    pub fn hours_or_default(&self) -> i64 {
+        self.hours.unwrap_or(DEFAULT_TRENDING_HOURS)
+    }
+
+    /// Returns the requested limit, falling back to the default and capped at
+    /// the maximum.
+    pub fn limit_or_default(&self) -> i64 {
+        self.limit
+            .unwrap_or(DEFAULT_TOP_LIMIT)
+            .clamp(1, MAX_TOP_LIMIT)
+    }
+}
+
+/// The first year that can be queried for monthly rentals.
+pub const MIN_STATS_YEAR: i32 = 2000;
+
+/// Represents a query for the monthly rental counts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, IntoParams)]
+pub struct MonthlyRentalsQuery {
+    /// The year to count the rentals of, from 2000 up to next year.
+    pub year: i32,
+}
+
+impl MonthlyRentalsQuery {
+    /// Validates the query.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns a message when the year is before 2000 or after
+    /// next year.
+    pub fn validate(&self) -> Result<(), String> {
+        let max_year = Utc::now().year() + 1;
+        if !(MIN_STATS_YEAR..=max_year).contains(&self.year) {
+            return Err(format!(
+                "year must be between {MIN_STATS_YEAR} and {max_year}"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Represents the number of rentals in a month.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct MonthlyRentalStat {
+    /// The month, from 1 to 12.
+    pub month: i32,
+    pub rental_count: i64,
+}
+
+/// Represents a book and the number of times it was rented.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct TopBookRow {
+    pub book_name: String,
+    pub rental_count: i64,
+}
+
+/// Represents a user and the number of books they rented.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct TopUserRow {
+    pub nation_id: String,
+    pub user_name: String,
+    pub rental_count: i64,
+}
+
+/// Represents the number of books in a category.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct CategoryStat {
+    pub category: String,
+    pub total: i64,
+    pub available: i64,
+    pub rented: i64,
+}
+
+/// Represents how often a book is rented, all its copies included.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RentalRateStats {
+    pub total_rentals: i64,
+    pub rentals_last_30_days: i64,
+    /// The average number of days the rentals returned with
+    /// `user::return_book` lasted, `null` if no rental was returned.
+    pub avg_days_to_return: Option<f64>,
+}
+
+/// Retrieves the most rented books.
+///
+/// Deleted books are left out, and books with the same number of rentals are
+/// ordered by name.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose books are ranked.
+/// * `limit`: The maximum number of books to return.
+///
+/// ## Returns
+///
+/// A vector of `TopBookRow`, the most rented book first.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+pub async fn top_books(
+    pool: &PgPool,
+    library_id: &str,
+    limit: i64,
+) -> Result<Vec<TopBookRow>, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        TopBookRow,
+        r#"
+        SELECT users_history.book_name, COUNT(*) as "rental_count!"
+        FROM users_history
+        JOIN book ON book.library_id = users_history.library_id
+            AND book.name = users_history.book_name
+            AND book.copy_number = users_history.copy_number
+        WHERE book.library_id = $1 AND book.deleted_at IS NULL
+        GROUP BY users_history.book_name
+        ORDER BY "rental_count!" DESC, users_history.book_name
+        LIMIT $2
+        "#,
+        library_id,
+        limit,
+    )
+    .fetch_all(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Retrieves the most rented books of the current calendar month.
+///
+/// The month is the current UTC month. Deleted books are left out, and books
+/// with the same number of rentals are ordered by name.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose books are ranked.
+/// * `limit`: The maximum number of books to return.
+///
+/// ## Returns
+///
+/// A vector of `TopBookRow`, the most rented book of the month first.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+pub async fn popular_books_this_month(
+    pool: &PgPool,
+    library_id: &str,
+    limit: i64,
+) -> Result<Vec<TopBookRow>, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        TopBookRow,
+        r#"
+        SELECT users_history.book_name, COUNT(*) as "rental_count!"
+        FROM users_history
+        JOIN book ON book.library_id = users_history.library_id
+            AND book.name = users_history.book_name
+            AND book.copy_number = users_history.copy_number
+        WHERE book.library_id = $1 AND book.deleted_at IS NULL
+            AND DATE_TRUNC('month', users_history.created_at AT TIME ZONE 'UTC')
+                = DATE_TRUNC('month', NOW() AT TIME ZONE 'UTC')
+        GROUP BY users_history.book_name
+        ORDER BY "rental_count!" DESC, users_history.book_name
+        LIMIT $2
+        "#,
+        library_id,
+        limit,
+    )
+    .fetch_all(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Retrieves the books rented the most in the last hours.
+///
+/// Deleted books are left out, and books with the same number of rentals are
+/// ordered by name.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose books are ranked.
+/// * `window_hours`: The number of past hours whose rentals are counted.
+/// * `limit`: The maximum number of books to return.
+///
+/// ## Returns
+///
+/// A vector of `TopBookRow`, the most rented book of the window first.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+pub async fn trending_books(
+    pool: &PgPool,
+    library_id: &str,
+    window_hours: i64,
+    limit: i64,
+) -> Result<Vec<TopBookRow>, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        TopBookRow,
+        r#"
+        SELECT users_history.book_name, COUNT(*) as "rental_count!"
+        FROM users_history
+        JOIN book ON book.library_id = users_history.library_id
+            AND book.name = users_history.book_name
+            AND book.copy_number = users_history.copy_number
+        WHERE book.library_id = $1 AND book.deleted_at IS NULL
+            AND users_history.created_at >= NOW() - make_interval(hours => $2::int)
+        GROUP BY users_history.book_name
+        ORDER BY "rental_count!" DESC, users_history.book_name
+        LIMIT $3
+        "#,
+        library_id,
+        window_hours as i32,
+        limit,
+    )
+    .fetch_all(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Retrieves how often a book is rented.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
+/// * `book_name`: The name of the book.
+///
+/// ## Returns
+///
+/// The `RentalRateStats` of the book, with zero counts if it was never
+/// rented.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+pub async fn rental_rate(
+    pool: &PgPool,
+    library_id: &str,
+    book_name: &str,
+) -> Result<RentalRateStats, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        RentalRateStats,
+        r#"
+        SELECT COUNT(*) as "total_rentals!",
+            COUNT(*) FILTER (WHERE created_at >= NOW() - INTERVAL '30 days')
+                as "rentals_last_30_days!",
+            (AVG(EXTRACT(EPOCH FROM returned_at - created_at)) / 86400)::float8
+                as avg_days_to_return
+        FROM users_history
+        WHERE library_id = $1 AND book_name = $2
+        "#,
+        library_id,
+        book_name,
+    )
+    .fetch_one(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Retrieves the users with the most rentals.
+///
+/// Deleted users are left out, and users with the same number of rentals are
+/// ordered by national ID.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose users are ranked.
+/// * `limit`: The maximum number of users to return.
+///
+/// ## Returns
+///
+/// A vector of `TopUserRow`, the most active user first.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+pub async fn top_users(
+    pool: &PgPool,
+    library_id: &str,
+    limit: i64,
+) -> Result<Vec<TopUserRow>, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        TopUserRow,
+        r#"
+        SELECT users.nation_id, users.name as user_name, COUNT(*) as "rental_count!"
+        FROM users_history
+        JOIN users ON users.library_id = users_history.library_id
+            AND users.nation_id = users_history.nation_id
+        WHERE users.library_id = $1 AND users.deleted_at IS NULL
+        GROUP BY users.nation_id, users.name
+        ORDER BY "rental_count!" DESC, users.nation_id
+        LIMIT $2
+        "#,
+        library_id,
+        limit,
+    )
+    .fetch_all(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Counts the books of each category by status.
+///
+/// Deleted books are left out. Books that are neither available nor rented
+/// only count towards the total.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose books are counted.
+///
+/// ## Returns
+///
+/// A vector of `CategoryStat`, ordered by category.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+pub async fn category_distribution(
+    pool: &PgPool,
+    library_id: &str,
+) -> Result<Vec<CategoryStat>, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        CategoryStat,
+        r#"
+        SELECT
+            category,
+            COUNT(*) as "total!",
+            COUNT(*) FILTER (WHERE status = 'Available') as "available!",
+            COUNT(*) FILTER (WHERE status = 'Rented') as "rented!"
+        FROM book
+        WHERE library_id = $1 AND deleted_at IS NULL
+        GROUP BY category
+        ORDER BY category
+        "#,
+        library_id,
+    )
+    .fetch_all(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Counts the rentals of each month of a year.
+///
+/// Rentals are grouped by the UTC month they were made in, and every month of
+/// the year is returned, including the ones without rentals.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose rentals are counted.
+/// * `year`: The year to count the rentals of.
+///
+/// ## Returns
+///
+/// A vector of twelve `MonthlyRentalStat`, from January to December.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+pub async fn monthly_rentals(
+    pool: &PgPool,
+    library_id: &str,
+    year: i32,
+) -> Result<Vec<MonthlyRentalStat>, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        MonthlyRentalStat,
+        r#"
+        SELECT
+            EXTRACT(MONTH FROM months.month)::int4 as "month!",
+            COUNT(users_history.id) as "rental_count!"
+        FROM generate_series(
+            make_date($2, 1, 1)::timestamp,
+            make_date($2, 12, 1)::timestamp,
+            interval '1 month'
+        ) as months(month)
+        LEFT JOIN users_history
+            ON users_history.library_id = $1
+            AND DATE_TRUNC('month', users_history.created_at AT TIME ZONE 'UTC') = months.month
+        GROUP BY months.month
+        ORDER BY months.month
+        "#,
+        library_id,
+        year,
+    )
+    .fetch_all(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::library::book::{self, Book, Status};
+    use crate::library::user::{self, User, UserRentBook};
+    use crate::middleware::library::DEFAULT_LIBRARY_ID;
+    use crate::tests::sequential::sequential_test;
+
+    /// Rents the book to the user `times` times, making the book available
+    /// again after each rental.
+    ///
+    /// ## Panics
+    ///
+    /// This function will panic if a rental fails.
+    pub async fn rent_book_times(pool: &PgPool, nation_id: &str, book_name: &str, times: usize) {
+        for _ in 0..times {
+            let user_rent_book = UserRentBook {
+                nation_id: nation_id.to_owned(),
+                book_name: book_name.to_owned(),
+                due_date: "2999-01-01".to_owned(),
+            };
+            user::rent_book(pool, DEFAULT_LIBRARY_ID, &user_rent_book)
+                .await
+                .expect("failed to rent book");
+            timed_sql!(sqlx::query!(
+                "UPDATE book SET status = 'Available' WHERE name = $1",
+                book_name,
+            )
+            .execute(pool))
+            .await
+            .expect("failed to return book");
+        }
+    }
+
+    /// Records a rental made at the given time, without changing the status of
+    /// the book.
+    ///
+    /// ## Panics
+    ///
+    /// This function will panic if the insertion fails.
+    pub async fn insert_rental_at(pool: &PgPool, nation_id: &str, book_name: &str, at: &str) {
+        timed_sql!(sqlx::query!(
+            r#"
+            INSERT INTO users_history (nation_id, book_name, due_date, created_at)
+            VALUES ($1, $2, '2999-01-01', $3::text::timestamptz)
+            "#,
+            nation_id,
+            book_name,
+            at,
+        )
+        .execute(pool))
+        .await
+        .expect("failed to insert rental");
+    }
+
+    #[test]
+    fn test_validate_monthly_rentals_query() {
+        let year = Utc::now().year();
+        for valid_year in [MIN_STATS_YEAR, year, year + 1] {
+            assert!(MonthlyRentalsQuery { year: valid_year }.validate().is_ok());
+        }
+        for invalid_year in [MIN_STATS_YEAR - 1, year + 2] {
+            assert!(MonthlyRentalsQuery { year: invalid_year }
+                .validate()
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn test_top_query_limit() {
+        assert_eq!(TopQuery::default().limit_or_default(), DEFAULT_TOP_LIMIT);
+        assert_eq!(TopQuery { limit: Some(0) }.limit_or_default(), 1);
+        assert_eq!(TopQuery { limit: Some(5) }.limit_or_default(), 5);
+        assert_eq!(
+            TopQuery { limit: Some(1000) }.limit_or_default(),
+            MAX_TOP_LIMIT
+        );
+    }
+
+    #[test]
+    fn test_trending_query() {
+        let query = TrendingQuery::default();
+        assert!(query.validate().is_ok());
+        assert_eq!(query.hours_or_default(), DEFAULT_TRENDING_HOURS);
+        assert_eq!(query.limit_or_default(), DEFAULT_TOP_LIMIT);
+        for hours in [1, MAX_TRENDING_HOURS] {
+            let query = TrendingQuery {
+                hours: Some(hours),
+                limit: None,
+            };
+            assert!(query.validate().is_ok());
+        }
+        for hours in [0, -1, MAX_TRENDING_HOURS + 1] {
+            let query = TrendingQuery {
+                hours: Some(hours),
+                limit: None,
+            };
+            assert!(query.validate().is_err());
+        }
+    }
+
+    sequential_test!(
+        async fn test_top_books() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let user = User::create_fake_user().await;
+            user::insert_user(&pool, DEFAULT_LIBRARY_ID, &user)
+                .await
+                .expect("failed to insert user");
+            let popular_book = Book::create_fake_book(&pool).await;
+            let other_book = Book::create_fake_book(&pool).await;
+            for book in [&popular_book, &other_book] {
+                book::insert_book(&pool, DEFAULT_LIBRARY_ID, book)
+                    .await
+                    .expect("failed to insert book");
+            }
+            rent_book_times(&pool, &user.nation_id, &popular_book.name, 6).await;
+            rent_book_times(&pool, &user.nation_id, &other_book.name, 5).await;
+
+            let result = top_books(&pool, DEFAULT_LIBRARY_ID, MAX_TOP_LIMIT)
+                .await
+                .expect("failed to get top books");
+            let position = |name: &str| result.iter().position(|row| row.book_name == name);
+            let popular_position = position(&popular_book.name).expect("popular book not found");
+            let other_position = position(&other_book.name).expect("other book not found");
+            assert!(popular_position < other_position);
+            assert_eq!(result[popular_position].rental_count, 6);
+            assert_eq!(result[other_position].rental_count, 5);
+            assert!(result
+                .windows(2)
+                .all(|rows| rows[0].rental_count >= rows[1].rental_count));
+
+            let result = top_books(&pool, DEFAULT_LIBRARY_ID, 1)
+                .await
+                .expect("failed to get top books");
+            assert_eq!(result.len(), 1);
+        }
+    );
+
+    sequential_test!(
+        async fn test_top_users() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let active_user = User::create_fake_user().await;
+            let other_user = User::create_fake_user().await;
+            for user in [&active_user, &other_user] {
+                user::insert_user(&pool, DEFAULT_LIBRARY_ID, user)
+                    .await
+                    .expect("failed to insert user");
+            }
+            let book = Book::create_fake_book(&pool).await;
+            book::insert_book(&pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+            rent_book_times(&pool, &active_user.nation_id, &book.name, 5).await;
+            rent_book_times(&pool, &other_user.nation_id, &book.name, 3).await;
+
+            let result = top_users(&pool, DEFAULT_LIBRARY_ID, MAX_TOP_LIMIT)
+                .await
+                .expect("failed to get top users");
+            let position =
+                |nation_id: &str| result.iter().position(|row| row.nation_id == nation_id);
+            let active_position = position(&active_user.nation_id).expect("active user not found");
+            let other_position = position(&other_user.nation_id).expect("other user not found");
+            assert!(active_position < other_position);
+            assert_eq!(
+                result[active_position],
+                TopUserRow {
+                    nation_id: active_user.nation_id.clone(),
+                    user_name: active_user.name.clone(),
+                    rental_count: 5,
+                }
+            );
+            assert_eq!(result[other_position].rental_count, 3);
+
+            // deleted users are left out
+            user::delete_user(&pool, DEFAULT_LIBRARY_ID, active_user.nation_id.clone())
+                .await
+                .expect("failed to delete user");
+            let result = top_users(&pool, DEFAULT_LIBRARY_ID, MAX_TOP_LIMIT)
+                .await
+                .expect("failed to get top users");
+            assert!(!result
+                .iter()
+                .any(|row| row.nation_id == active_user.nation_id));
+        }
+    );
+
+    sequential_test!(
+        async fn test_category_distribution() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let user = User::create_fake_user().await;
+            let category = format!("category {}", user.nation_id);
+            let mut book_ids = Vec::new();
+            for status in [
+                Status::Available,
+                Status::Available,
+                Status::Rented,
+                Status::NOTAvailable,
+                Status::Available,
+            ] {
+                let mut book = Book::create_fake_book(&pool).await;
+                book.category = category.clone();
+                book.status = status;
+                let book_id = book::insert_book(&pool, DEFAULT_LIBRARY_ID, &book)
+                    .await
+                    .expect("failed to insert book");
+                book_ids.push(book_id);
+            }
+            // deleted books are left out
+            book::delete_book(&pool, DEFAULT_LIBRARY_ID, book_ids[4])
+                .await
+                .expect("failed to delete book");
+
+            let result = category_distribution(&pool, DEFAULT_LIBRARY_ID)
+                .await
+                .expect("failed to get category distribution");
+            let stat = result
+                .iter()
+                .find(|stat| stat.category == category)
+                .expect("category not found");
+            assert_eq!(
+                stat,
+                &CategoryStat {
+                    category,
+                    total: 4,
+                    available: 2,
+                    rented: 1,
+                }
+            );
+            assert!(result
+                .iter()
+                .all(|stat| stat.total >= stat.available + stat.rented));
+        }
+    );
+
+    sequential_test!(
+        async fn test_monthly_rentals() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let user = User::create_fake_user().await;
+            user::insert_user(&pool, DEFAULT_LIBRARY_ID, &user)
+                .await
+                .expect("failed to insert user");
+            let book = Book::create_fake_book(&pool).await;
+            book::insert_book(&pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+
+            let before = monthly_rentals(&pool, DEFAULT_LIBRARY_ID, 2002)
+                .await
+                .expect("failed to get monthly rentals");
+            assert_eq!(
+                before.iter().map(|stat| stat.month).collect::<Vec<_>>(),
+                (1..=12).collect::<Vec<_>>()
+            );
+            for at in [
+                "2002-03-01T00:00:00Z",
+                "2002-03-31T23:59:59Z",
+                "2002-11-15T12:00:00Z",
+                "2003-03-01T00:00:00Z",
+            ] {
+                insert_rental_at(&pool, &user.nation_id, &book.name, at).await;
+            }
+            let after = monthly_rentals(&pool, DEFAULT_LIBRARY_ID, 2002)
+                .await
+                .expect("failed to get monthly rentals");
+            let added = before
+                .iter()
+                .zip(after.iter())
+                .map(|(before, after)| after.rental_count - before.rental_count)
+                .collect::<Vec<_>>();
+            assert_eq!(added, vec![0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 1, 0]);
+        }
+    );
+
+    sequential_test!(
+        async fn test_rental_rate() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let user = User::create_fake_user().await;
+            user::insert_user(&pool, DEFAULT_LIBRARY_ID, &user)
+                .await
+                .expect("failed to insert user");
+            let book = Book::create_fake_book(&pool).await;
+            book::insert_book(&pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+
+            let result = rental_rate(&pool, DEFAULT_LIBRARY_ID, &book.name)
+                .await
+                .expect("failed to get rental rate");
+            assert_eq!(
+                result,
+                RentalRateStats {
+                    total_rentals: 0,
+                    rentals_last_30_days: 0,
+                    avg_days_to_return: None,
+                }
+            );
+
+            insert_rental_at(&pool, &user.nation_id, &book.name, "2002-03-01T00:00:00Z").await;
+            rent_book_times(&pool, &user.nation_id, &book.name, 2).await;
+            let result = rental_rate(&pool, DEFAULT_LIBRARY_ID, &book.name)
+                .await
+                .expect("failed to get rental rate");
+            assert_eq!(result.total_rentals, 3);
+            assert_eq!(result.rentals_last_30_days, 2);
+            assert_eq!(result.avg_days_to_return, None);
+
+            // only the returned rentals are averaged
+            timed_sql!(sqlx::query!(
+                r#"
+                UPDATE users_history SET returned_at = created_at + INTERVAL '4 days'
+                WHERE book_name = $1 AND created_at < '2003-01-01'
+                "#,
+                book.name,
+            )
+            .execute(&pool))
+            .await
+            .expect("failed to return rental");
+            timed_sql!(sqlx::query!(
+                r#"
+                UPDATE users_history SET returned_at = created_at + INTERVAL '2 days'
+                WHERE id = (
+                    SELECT id FROM users_history
+                    WHERE book_name = $1 AND created_at >= '2003-01-01'
+                    LIMIT 1
+                )
+                "#,
+                book.name,
+            )
+            .execute(&pool))
+            .await
+            .expect("failed to return rental");
+            let result = rental_rate(&pool, DEFAULT_LIBRARY_ID, &book.name)
+                .await
+                .expect("failed to get rental rate");
+            assert_eq!(result.avg_days_to_return, Some(3.0));
+        }
+    );
+
+    sequential_test!(
+        async fn test_trending_books() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let user = User::create_fake_user().await;
+            user::insert_user(&pool, DEFAULT_LIBRARY_ID, &user)
+                .await
+                .expect("failed to insert user");
+            let trending_book = Book::create_fake_book(&pool).await;
+            let past_book = Book::create_fake_book(&pool).await;
+            for book in [&trending_book, &past_book] {
+                book::insert_book(&pool, DEFAULT_LIBRARY_ID, book)
+                    .await
+                    .expect("failed to insert book");
+            }
+            // the past book was rented more, but not in the window
+            let hours_ago = |hours| (Utc::now() - chrono::Duration::hours(hours)).to_rfc3339();
+            for at in [hours_ago(1), hours_ago(5)] {
+                insert_rental_at(&pool, &user.nation_id, &trending_book.name, &at).await;
+            }
+            for _ in 0..3 {
+                insert_rental_at(&pool, &user.nation_id, &past_book.name, &hours_ago(30)).await;
+            }
+
+            let result = trending_books(&pool, DEFAULT_LIBRARY_ID, 24, MAX_TOP_LIMIT)
+                .await
+                .expect("failed to get trending books");
+            let find = |name: &str| result.iter().find(|row| row.book_name == name);
+            assert_eq!(
+                find(&trending_book.name).map(|row| row.rental_count),
+                Some(2)
+            );
+            assert_eq!(find(&past_book.name), None);
+
+            let result = trending_books(&pool, DEFAULT_LIBRARY_ID, 2, MAX_TOP_LIMIT)
+                .await
+                .expect("failed to get trending books");
+            let find = |name: &str| result.iter().find(|row| row.book_name == name);
+            assert_eq!(
+                find(&trending_book.name).map(|row| row.rental_count),
+                Some(1)
+            );
+
+            let result = trending_books(&pool, DEFAULT_LIBRARY_ID, 48, MAX_TOP_LIMIT)
+                .await
+                .expect("failed to get trending books");
+            let find = |name: &str| result.iter().find(|row| row.book_name == name);
+            assert_eq!(find(&past_book.name).map(|row| row.rental_count), Some(3));
+        }
+    );
+}
@@ -0,0 +1,369 @@
+use crate::library::error::LibraryError;
+use crate::telemetry::db_metrics::timed_sql;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Maximum number of characters allowed in a tag.
+pub const MAX_TAG_LEN: usize = 50;
+
+/// Maximum number of tags given to a book in a single request.
+pub const MAX_TAGS_PER_REQUEST: usize = 20;
+
+/// Represents the tags given to a book.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TagBookBody {
+    /// The tags, at most 20 of at most 50 characters each. They are trimmed
+    /// and lowercased before being stored.
+    pub tags: Vec<String>,
+}
+
+/// Represents a tag along with the number of books having it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct TagRow {
+    pub name: String,
+    pub book_count: i64,
+}
+
+impl TagBookBody {
+    /// Validates the tags before they are written to the database.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns a message if there are no tags, more than
+    /// `MAX_TAGS_PER_REQUEST` tags, or a tag that is blank or exceeds
+    /// `MAX_TAG_LEN` characters.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.tags.is_empty() {
+            return Err("tags must not be empty".to_owned());
+        }
+        if self.tags.len() > MAX_TAGS_PER_REQUEST {
+            return Err(format!(
+                "at most {MAX_TAGS_PER_REQUEST} tags can be given at once"
+            ));
+        }
+        for tag in &self.tags {
+            let tag = tag.trim();
+            if tag.is_empty() {
+                return Err("tags must not be blank".to_owned());
+            }
+            if tag.chars().count() > MAX_TAG_LEN {
+                return Err(format!("tags must be at most {MAX_TAG_LEN} characters"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the tags as they are stored, trimmed, lowercased and without
+    /// duplicates.
+    pub fn normalized(&self) -> Vec<String> {
+        let mut tags: Vec<String> = self.tags.iter().map(|tag| normalize_tag(tag)).collect();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+}
+
+/// Returns a tag as it is stored, trimmed and lowercased.
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+/// Gives tags to a book, creating the tags that do not exist yet.
+///
+/// The tags are given to the book by its name, so all its copies share them.
+/// Tags the book already has are left as they are.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
+/// * `book_id`: The ID of the book.
+/// * `tags`: The normalized tags, see `TagBookBody::normalized`.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if there is no book with
+/// the given ID that is not deleted, or an error if there is an issue with
+/// the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn tag_book(
+    pool: &PgPool,
+    library_id: &str,
+    book_id: Uuid,
+    tags: &[String],
+) -> Result<(), LibraryError> {
+    let mut transaction = pool.begin().await?;
+    let book_name = timed_sql!(sqlx::query_scalar!(
+        "SELECT name FROM book WHERE library_id = $1 AND id = $2 AND deleted_at IS NULL",
+        library_id,
+        book_id,
+    )
+    .fetch_one(&mut transaction))
+    .await?;
+    timed_sql!(sqlx::query!(
+        r#"
+        INSERT INTO tags (library_id, name)
+        SELECT $1, UNNEST($2::varchar[])
+        ON CONFLICT (library_id, name) DO NOTHING
+        "#,
+        library_id,
+        tags,
+    )
+    .execute(&mut transaction))
+    .await?;
+    timed_sql!(sqlx::query!(
+        r#"
+        INSERT INTO book_tags (library_id, book_name, tag_id)
+        SELECT library_id, $2, id
+        FROM tags
+        WHERE library_id = $1 AND name = ANY($3)
+        ON CONFLICT DO NOTHING
+        "#,
+        library_id,
+        book_name,
+        tags,
+    )
+    .execute(&mut transaction))
+    .await?;
+    transaction.commit().await.map_err(LibraryError::from)
+}
+
+/// Retrieves the tags of a book, sorted by name.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
+/// * `book_id`: The ID of the book.
+///
+/// ## Returns
+///
+/// A vector of the names of the tags of the book. The vector is empty if the
+/// book has no tags.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn book_tags(
+    pool: &PgPool,
+    library_id: &str,
+    book_id: Uuid,
+) -> Result<Vec<String>, LibraryError> {
+    timed_sql!(sqlx::query_scalar!(
+        r#"
+        SELECT tags.name
+        FROM book_tags
+        JOIN tags ON tags.id = book_tags.tag_id
+        JOIN book ON book.library_id = book_tags.library_id
+            AND book.name = book_tags.book_name
+        WHERE book.library_id = $1 AND book.id = $2
+        ORDER BY tags.name
+        "#,
+        library_id,
+        book_id,
+    )
+    .fetch_all(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Removes a tag from a book.
+///
+/// The tag itself is kept, with one book less.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
+/// * `book_id`: The ID of the book.
+/// * `tag`: The name of the tag, normalized before the lookup.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if the book does not exist
+/// or does not have the tag, or an error if there is an issue with the
+/// database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn untag_book(
+    pool: &PgPool,
+    library_id: &str,
+    book_id: Uuid,
+    tag: &str,
+) -> Result<(), LibraryError> {
+    let result = timed_sql!(sqlx::query!(
+        r#"
+        DELETE FROM book_tags
+        USING book, tags
+        WHERE book.library_id = $1 AND book.id = $2
+            AND book_tags.library_id = book.library_id
+            AND book_tags.book_name = book.name
+            AND tags.id = book_tags.tag_id AND tags.name = $3
+        "#,
+        library_id,
+        book_id,
+        normalize_tag(tag),
+    )
+    .execute(pool))
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(LibraryError::NotFound);
+    }
+    Ok(())
+}
+
+/// Retrieves the tags of a library with the number of books having them,
+/// sorted by name.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library.
+///
+/// ## Returns
+///
+/// A vector of `TagRow`. Tags removed from all their books are listed with
+/// a `book_count` of 0.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn tags(pool: &PgPool, library_id: &str) -> Result<Vec<TagRow>, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        TagRow,
+        r#"
+        SELECT tags.name, COUNT(book_tags.book_name) as "book_count!"
+        FROM tags
+        LEFT JOIN book_tags ON book_tags.tag_id = tags.id
+        WHERE tags.library_id = $1
+        GROUP BY tags.id, tags.name
+        ORDER BY tags.name
+        "#,
+        library_id,
+    )
+    .fetch_all(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::book::{self, Book};
+    use crate::middleware::library::DEFAULT_LIBRARY_ID;
+    use crate::tests::sequential::sequential_test;
+
+    #[test]
+    fn test_validate_tag_book() {
+        let mut body = TagBookBody {
+            tags: vec!["Fantasy".to_owned(), " classic ".to_owned()],
+        };
+        assert!(body.validate().is_ok());
+        body.tags = vec![];
+        assert!(body.validate().is_err());
+        body.tags = vec![" ".to_owned()];
+        assert!(body.validate().is_err());
+        body.tags = vec!["a".repeat(MAX_TAG_LEN)];
+        assert!(body.validate().is_ok());
+        body.tags = vec!["a".repeat(MAX_TAG_LEN + 1)];
+        assert!(body.validate().is_err());
+        body.tags = vec!["a".to_owned(); MAX_TAGS_PER_REQUEST + 1];
+        assert!(body.validate().is_err());
+    }
+
+    #[test]
+    fn test_normalized_tags() {
+        let body = TagBookBody {
+            tags: vec![
+                "Fantasy".to_owned(),
+                " classic ".to_owned(),
+                "fantasy".to_owned(),
+            ],
+        };
+        assert_eq!(body.normalized(), vec!["classic", "fantasy"]);
+    }
+
+    sequential_test!(
+        async fn test_tag_book() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            // insert books, the first one with two copies
+            let book_1 = Book::create_fake_book(&pool).await;
+            let ids = book::insert_book_copies(&pool, DEFAULT_LIBRARY_ID, &book_1, 2)
+                .await
+                .expect("failed to insert book copies");
+            let book_2 = Book::create_fake_book(&pool).await;
+            let book_2_id = book::insert_book(&pool, DEFAULT_LIBRARY_ID, &book_2)
+                .await
+                .expect("failed to insert book");
+            // tag_book
+            let tag_1 = format!("tag-{}", Uuid::new_v4());
+            let tag_2 = format!("tag-{}", Uuid::new_v4());
+            tag_book(
+                &pool,
+                DEFAULT_LIBRARY_ID,
+                ids[0],
+                &[tag_1.clone(), tag_2.clone()],
+            )
+            .await
+            .expect("failed to tag book");
+            // tagging twice keeps a single tag
+            tag_book(&pool, DEFAULT_LIBRARY_ID, book_2_id, &[tag_1.clone()])
+                .await
+                .expect("failed to tag book");
+            tag_book(&pool, DEFAULT_LIBRARY_ID, book_2_id, &[tag_1.clone()])
+                .await
+                .expect("failed to tag book");
+            // unknown book
+            assert!(matches!(
+                tag_book(&pool, DEFAULT_LIBRARY_ID, Uuid::nil(), &[tag_1.clone()]).await,
+                Err(LibraryError::NotFound)
+            ));
+            // book_tags, shared by the copies
+            for id in &ids {
+                let book_tags_result = book_tags(&pool, DEFAULT_LIBRARY_ID, *id)
+                    .await
+                    .expect("failed to get book tags");
+                let mut expected = vec![tag_1.clone(), tag_2.clone()];
+                expected.sort();
+                assert_eq!(book_tags_result, expected);
+            }
+            // tags
+            let tags_result = tags(&pool, DEFAULT_LIBRARY_ID)
+                .await
+                .expect("failed to get tags");
+            let count = |name: &str| {
+                tags_result
+                    .iter()
+                    .find(|tag| tag.name == name)
+                    .map(|tag| tag.book_count)
+            };
+            assert_eq!(count(&tag_1), Some(2));
+            assert_eq!(count(&tag_2), Some(1));
+            // untag_book
+            untag_book(&pool, DEFAULT_LIBRARY_ID, ids[1], &tag_2.to_uppercase())
+                .await
+                .expect("failed to untag book");
+            assert!(matches!(
+                untag_book(&pool, DEFAULT_LIBRARY_ID, ids[1], &tag_2).await,
+                Err(LibraryError::NotFound)
+            ));
+            let book_tags_result = book_tags(&pool, DEFAULT_LIBRARY_ID, ids[0])
+                .await
+                .expect("failed to get book tags");
+            assert_eq!(book_tags_result, vec![tag_1.clone()]);
+            let tags_result = tags(&pool, DEFAULT_LIBRARY_ID)
+                .await
+                .expect("failed to get tags");
+            assert!(tags_result
+                .iter()
+                .any(|tag| tag.name == tag_2 && tag.book_count == 0));
+        }
+    );
+}
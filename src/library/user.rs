@@ -1,13 +1,132 @@
+use crate::library::error::LibraryError;
+use crate::telemetry::db_metrics::timed_sql;
+use chrono::{DateTime, NaiveDate, Utc};
+use email_address::EmailAddress;
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
-use utoipa::{ToSchema, IntoParams};
+use sqlx::{PgExecutor, PgPool};
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
+/// Default number of blacklisted users returned per page.
+pub const DEFAULT_BLACKLISTED_USERS_LIMIT: i64 = 20;
+
+/// Maximum number of blacklisted users returned per page.
+pub const MAX_BLACKLISTED_USERS_LIMIT: i64 = 100;
+
+/// Default number of active users returned per page.
+pub const DEFAULT_ACTIVE_USERS_LIMIT: i64 = 20;
+
+/// Maximum number of active users returned per page.
+pub const MAX_ACTIVE_USERS_LIMIT: i64 = 100;
+
+/// Default number of days without a rental after which a user is inactive.
+pub const DEFAULT_INACTIVE_DAYS: i64 = 90;
+
+/// Default number of inactive users returned per page.
+pub const DEFAULT_INACTIVE_USERS_LIMIT: i64 = 20;
+
+/// Maximum number of inactive users returned per page.
+pub const MAX_INACTIVE_USERS_LIMIT: i64 = 100;
+
+/// Maximum number of books rented at once.
+pub const MAX_RENT_BOOKS: usize = 5;
+
 /// Represents a user.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct User {
     pub nation_id: String,
     pub name: String,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    /// When the user was created, set by the database.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(read_only)]
+    pub created_at: Option<DateTime<Utc>>,
+    /// When the user was last updated, set by the database.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(read_only)]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl User {
+    /// Validates the user before it is written to the database.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns a message describing the first invalid field,
+    /// e.g. when the email is not a well-formed address or the phone is not
+    /// a `+` followed by digits.
+    pub fn validate(&self) -> Result<(), String> {
+        validate_email(self.email.as_deref())?;
+        validate_phone(self.phone.as_deref())
+    }
+}
+
+/// Represents an update of a user's profile.
+///
+/// Fields that are `None` are left unchanged. The national ID identifies the
+/// user and cannot be updated.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct UserUpdate {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+}
+
+impl UserUpdate {
+    /// Validates the update before it is written to the database.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns a message describing the first invalid field,
+    /// e.g. when the name is empty or the email or phone is malformed.
+    pub fn validate(&self) -> Result<(), String> {
+        if self
+            .name
+            .as_ref()
+            .is_some_and(|name| name.trim().is_empty())
+        {
+            return Err("name must not be empty".to_owned());
+        }
+        validate_email(self.email.as_deref())?;
+        validate_phone(self.phone.as_deref())
+    }
+}
+
+/// Validates a national ID, which must consist of exactly 11 digits.
+///
+/// ## Errors
+///
+/// This function returns a message when the national ID is not 11 digits.
+pub fn validate_nation_id(nation_id: &str) -> Result<(), String> {
+    if nation_id.len() != 11 || !nation_id.chars().all(|c| c.is_ascii_digit()) {
+        return Err("nation_id must consist of 11 digits".to_owned());
+    }
+    Ok(())
+}
+
+/// Checks that the email, if any, is a well-formed address.
+fn validate_email(email: Option<&str>) -> Result<(), String> {
+    match email {
+        Some(email) if !EmailAddress::is_valid(email) => {
+            Err("email must be a valid email address".to_owned())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks that the phone, if any, is a `+` followed by digits.
+fn validate_phone(phone: Option<&str>) -> Result<(), String> {
+    let Some(phone) = phone else {
+        return Ok(());
+    };
+    let valid = phone
+        .strip_prefix('+')
+        .is_some_and(|digits| !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()));
+    if !valid {
+        return Err("phone must be a '+' followed by digits".to_owned());
+    }
+    Ok(())
 }
 
 /// Represents a book rental by a user.
@@ -21,17 +140,38 @@ pub struct UserRentBook {
 /// Represents a row in the user's rental history.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct UserHistoryRow {
+    /// The ID of the rental.
+    pub history_id: Uuid,
     pub name: String,
     pub nation_id: String,
+    pub email: Option<String>,
+    pub phone: Option<String>,
     pub book_name: String,
     pub due_date: String,
 }
 
+/// Represents the rental statistics of a user.
+///
+/// A rental is active when the book is still rented and no one rented it
+/// after the user, and overdue when it is active and its due date has passed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct UserStats {
+    pub total_rentals: i64,
+    pub active_rentals: i64,
+    pub overdue_rentals: i64,
+    /// The most rented category, ties are broken alphabetically.
+    pub favorite_category: Option<String>,
+}
+
 /// Represents a query for retrieving users.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow, IntoParams)]
 pub struct UserQuery {
     pub user_name: Option<String>,
     pub book_name: Option<String>,
+    /// Maximum number of rentals to return, all matching rentals if not given.
+    pub limit: Option<i64>,
+    /// Number of rentals to skip, defaults to 0.
+    pub offset: Option<i64>,
 }
 
 /// Represents a row in the user table.
@@ -42,6 +182,104 @@ pub struct UserRow {
     pub book_name: String,
 }
 
+/// Represents a blacklisted user.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct BlacklistedUserRow {
+    pub nation_id: String,
+    pub name: String,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub blacklisted_at: DateTime<Utc>,
+}
+
+/// Represents the pagination parameters for listing blacklisted users.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, IntoParams)]
+pub struct BlacklistedUsersQuery {
+    /// Number of users to return, defaults to 20 and is capped at 100.
+    pub limit: Option<i64>,
+    /// Number of users to skip, defaults to 0.
+    pub offset: Option<i64>,
+}
+
+/// Represents the pagination parameters for listing active users.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, IntoParams)]
+pub struct ActiveUsersQuery {
+    /// Number of rentals to return, defaults to 20 and is capped at 100.
+    pub limit: Option<i64>,
+    /// Number of rentals to skip, defaults to 0.
+    pub offset: Option<i64>,
+}
+
+impl ActiveUsersQuery {
+    /// Returns the requested limit, falling back to the default and capped at
+    /// the maximum.
+    pub fn limit_or_default(&self) -> i64 {
+        self.limit
+            .unwrap_or(DEFAULT_ACTIVE_USERS_LIMIT)
+            .clamp(1, MAX_ACTIVE_USERS_LIMIT)
+    }
+
+    /// Returns the number of rentals to skip, 0 if not given or negative.
+    pub fn offset_or_default(&self) -> i64 {
+        self.offset.unwrap_or_default().max(0)
+    }
+}
+
+/// Represents a user without recent rentals.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct InactiveUserRow {
+    pub nation_id: String,
+    pub name: String,
+    pub email: Option<String>,
+    /// The time of the last rental of the user, none if they never rented.
+    pub last_rental_at: Option<DateTime<Utc>>,
+}
+
+/// Represents the query parameters for listing inactive users.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, IntoParams)]
+pub struct InactiveUsersQuery {
+    /// Number of days without a rental after which a user is inactive,
+    /// defaults to 90.
+    pub days: Option<i64>,
+    /// Number of users to return, defaults to 20 and is capped at 100.
+    pub limit: Option<i64>,
+    /// Number of users to skip, defaults to 0.
+    pub offset: Option<i64>,
+}
+
+impl InactiveUsersQuery {
+    /// Validates the query.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns a message when the number of days is not
+    /// positive.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.days.is_some_and(|days| days < 1) {
+            return Err("days must be at least 1".to_owned());
+        }
+        Ok(())
+    }
+
+    /// Returns the requested number of days, falling back to the default.
+    pub fn days_or_default(&self) -> i64 {
+        self.days.unwrap_or(DEFAULT_INACTIVE_DAYS)
+    }
+
+    /// Returns the requested limit, falling back to the default and capped at
+    /// the maximum.
+    pub fn limit_or_default(&self) -> i64 {
+        self.limit
+            .unwrap_or(DEFAULT_INACTIVE_USERS_LIMIT)
+            .clamp(1, MAX_INACTIVE_USERS_LIMIT)
+    }
+
+    /// Returns the number of users to skip, 0 if not given or negative.
+    pub fn offset_or_default(&self) -> i64 {
+        self.offset.unwrap_or_default().max(0)
+    }
+}
+
 /// Represents a book to be rented.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub struct RentBook {
@@ -49,11 +287,36 @@ pub struct RentBook {
     pub due_date: String,
 }
 
+/// Represents several books to be rented at once.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct RentBooks {
+    /// The books to rent, between 1 and `MAX_RENT_BOOKS`.
+    pub books: Vec<RentBook>,
+}
+
+impl RentBooks {
+    /// Validates the books to rent.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns a message when there are no books or more than
+    /// `MAX_RENT_BOOKS`.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_RENT_BOOKS).contains(&self.books.len()) {
+            return Err(format!(
+                "books must contain between 1 and {MAX_RENT_BOOKS} books"
+            ));
+        }
+        Ok(())
+    }
+}
+
 /// Inserts a new user into the database.
 ///
 /// ## Arguments
 ///
 /// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the user belongs to.
 /// * `user`: The user to insert.
 ///
 /// ## Returns
@@ -64,19 +327,79 @@ pub struct RentBook {
 ///
 /// This function returns an error if the user insertion fails or
 /// if there is an issue with the database connection.
-pub async fn insert_user(pool: &PgPool, user: &User) -> Result<Uuid, sqlx::Error> {
-    sqlx::query!(
+#[tracing::instrument(level = "debug", skip(pool, user), fields(nation_id = %user.nation_id))]
+pub async fn insert_user(
+    pool: &PgPool,
+    library_id: &str,
+    user: &User,
+) -> Result<Uuid, LibraryError> {
+    insert_user_row(pool, library_id, user).await
+}
+
+/// Inserts a user with the given executor, see `insert_user`.
+async fn insert_user_row<'e>(
+    executor: impl PgExecutor<'e>,
+    library_id: &str,
+    user: &User,
+) -> Result<Uuid, LibraryError> {
+    timed_sql!(sqlx::query!(
         r#"
-            INSERT INTO users (nation_id, name)
-            VALUES ($1, $2)
+            INSERT INTO users (library_id, nation_id, name, email, phone)
+            VALUES ($1, $2, $3, $4, $5)
             RETURNING id
         "#,
+        library_id,
         user.nation_id,
         user.name,
+        user.email,
+        user.phone,
     )
-    .fetch_one(pool)
+    .fetch_one(executor))
     .await
     .map(|record| record.id)
+    .map_err(LibraryError::from)
+}
+
+/// Inserts a new user and rents a book for them in a single transaction, so
+/// the user is not created when the book cannot be rented.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the user and the book belong to.
+/// * `user`: The user to insert.
+/// * `rent`: The book to rent and its due date.
+///
+/// ## Returns
+///
+/// The UUID of the inserted user and the rental information.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::Conflict` if a user with the same
+/// national ID or email exists, `LibraryError::NotFound` if no copy of the
+/// book is available, or an error if there is an issue with the database
+/// connection.
+#[tracing::instrument(level = "debug", skip(pool, user), fields(nation_id = %user.nation_id))]
+pub async fn register_and_rent(
+    pool: &PgPool,
+    library_id: &str,
+    user: &User,
+    rent: &RentBook,
+) -> Result<(Uuid, UserRentBook), LibraryError> {
+    let mut transaction = pool.begin().await?;
+    let user_id = insert_user_row(&mut transaction, library_id, user).await?;
+    let rental = UserRentBook {
+        nation_id: user.nation_id.clone(),
+        book_name: rent.book_name.clone(),
+        due_date: rent.due_date.clone(),
+    };
+    if !rent_available_copy(&mut transaction, library_id, &rental).await? {
+        transaction.rollback().await?;
+        return Err(LibraryError::NotFound);
+    }
+    transaction.commit().await?;
+    Ok((user_id, rental))
 }
 
 /// Rent a book for a user.
@@ -84,46 +407,397 @@ pub async fn insert_user(pool: &PgPool, user: &User) -> Result<Uuid, sqlx::Error
 /// ## Arguments
 ///
 /// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book and the user belong to.
 /// * `data`: The rental information.
 ///
 /// ## Returns
 ///
-/// This function returns `Result<(), sqlx::Error>` indicating whether
+/// This function returns `Result<(), LibraryError>` indicating whether
 /// the book rental was successful or if an error occurred.
 ///
 /// ## Errors
 ///
-/// This function returns an error if the book rental fails or
-/// if there is an issue with the database connection.
-pub async fn rent_book(pool: &PgPool, data: &UserRentBook) -> Result<(), sqlx::Error> {
+/// This function returns `LibraryError::Forbidden` if the user is
+/// blacklisted, `LibraryError::NotFound` if the user does not exist or no copy
+/// of the book is available, or an error if the book rental fails or if there
+/// is an issue with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn rent_book(
+    pool: &PgPool,
+    library_id: &str,
+    data: &UserRentBook,
+) -> Result<(), LibraryError> {
+    let mut transaction = pool.begin().await?;
+    if let Err(err) = lock_renter(&mut transaction, library_id, &data.nation_id).await {
+        transaction.rollback().await?;
+        return Err(err);
+    }
+    if !rent_available_copy(&mut transaction, library_id, data).await? {
+        transaction.rollback().await?;
+        return Err(LibraryError::NotFound);
+    }
+    transaction.commit().await.map_err(LibraryError::from)
+}
+
+/// Represents the outcome of renting several books at once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RentBooksOutcome {
+    /// All the books were rented.
+    Rented(Vec<UserRentBook>),
+    /// None of the books were rented, because the named ones had no
+    /// available copy.
+    Unavailable(Vec<String>),
+}
+
+/// Rent several books for a user inside a single transaction.
+///
+/// Either all the books are rented or none of them. A book listed several
+/// times is rented as many copies.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the books and the user belong to.
+/// * `nation_id`: The national ID of the user renting the books.
+/// * `books`: The books to rent.
+///
+/// ## Returns
+///
+/// `RentBooksOutcome::Rented` with the rentals if all the books were rented,
+/// or `RentBooksOutcome::Unavailable` with the books that had no available
+/// copy otherwise.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::Forbidden` if the user is
+/// blacklisted, `LibraryError::NotFound` if the user does not exist, or an
+/// error if the book rental fails or if there is an issue with the database
+/// connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn rent_books(
+    pool: &PgPool,
+    library_id: &str,
+    nation_id: &str,
+    books: &[RentBook],
+) -> Result<RentBooksOutcome, LibraryError> {
     let mut transaction = pool.begin().await?;
+    if let Err(err) = lock_renter(&mut transaction, library_id, nation_id).await {
+        transaction.rollback().await?;
+        return Err(err);
+    }
+
+    let mut rented = Vec::with_capacity(books.len());
+    let mut unavailable = Vec::new();
+    for book in books {
+        let data = UserRentBook {
+            nation_id: nation_id.to_owned(),
+            book_name: book.book_name.clone(),
+            due_date: book.due_date.clone(),
+        };
+        if rent_available_copy(&mut transaction, library_id, &data).await? {
+            rented.push(data);
+        } else {
+            unavailable.push(data.book_name);
+        }
+    }
+
+    if !unavailable.is_empty() {
+        transaction.rollback().await?;
+        return Ok(RentBooksOutcome::Unavailable(unavailable));
+    }
+    transaction.commit().await?;
+    Ok(RentBooksOutcome::Rented(rented))
+}
+
+/// Locks a user so it cannot be blacklisted while books are rented.
+///
+/// The transaction is expected to be rolled back on error, a transaction
+/// that is only dropped keeps its locks until its connection is used again.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::Forbidden` if the user is
+/// blacklisted, `LibraryError::NotFound` if the user does not exist, or an
+/// error if the query fails.
+async fn lock_renter<'e>(
+    executor: impl PgExecutor<'e>,
+    library_id: &str,
+    nation_id: &str,
+) -> Result<(), LibraryError> {
+    let user = timed_sql!(sqlx::query!(
+        r#"
+        SELECT blacklisted_at IS NOT NULL as "blacklisted!"
+        FROM users
+        WHERE library_id = $1 AND nation_id = $2 AND deleted_at IS NULL
+        FOR SHARE
+        "#,
+        library_id,
+        nation_id,
+    )
+    .fetch_optional(executor))
+    .await?;
+    match user {
+        None => Err(LibraryError::NotFound),
+        Some(user) if user.blacklisted => {
+            Err(LibraryError::Forbidden("user is blacklisted".to_owned()))
+        }
+        Some(_) => Ok(()),
+    }
+}
 
-    // Check if the book is available and update its status to 'Rented' in the same query
-    let rent_book = sqlx::query!(
+/// Rents the lowest-numbered available copy of a book and records the rental.
+///
+/// ## Returns
+///
+/// `false` if no copy of the book is available.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails.
+async fn rent_available_copy<'e>(
+    executor: impl PgExecutor<'e>,
+    library_id: &str,
+    data: &UserRentBook,
+) -> Result<bool, LibraryError> {
+    // Rent the copy and record the rental in the same query, the rental is
+    // only recorded if the user is not deleted
+    let rent_book = timed_sql!(sqlx::query!(
         r#"
         WITH updated_book AS (
             UPDATE book
             SET status = 'Rented'
-            WHERE name = $1 AND status = 'Available'
-            RETURNING name
+            WHERE id = (
+                SELECT id FROM book
+                WHERE library_id = $1 AND name = $2 AND status = 'Available'
+                    AND deleted_at IS NULL
+                ORDER BY copy_number
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING library_id, name, copy_number
         )
-        INSERT INTO users_history (nation_id, book_name, due_date)
-        SELECT $2, name, $3
+        INSERT INTO users_history (library_id, nation_id, book_name, copy_number, due_date)
+        SELECT updated_book.library_id, users.nation_id, updated_book.name,
+            updated_book.copy_number, $4
         FROM updated_book
+        JOIN users ON users.library_id = updated_book.library_id
+            AND users.nation_id = $3 AND users.deleted_at IS NULL
         "#,
+        library_id,
         data.book_name,
         data.nation_id,
         data.due_date,
     )
-    .execute(&mut transaction)
+    .execute(executor))
+    .await?;
+    Ok(rent_book.rows_affected() > 0)
+}
+
+/// Transfers the rental of a book to another user.
+///
+/// The rental is the latest rental of a rented copy of the book, and only
+/// its renter changes, so the due date and the rental date are kept. When
+/// the user rents several copies of the book, the rental of the lowest
+/// numbered copy is transferred.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book and the users belong to.
+/// * `from_nation_id`: The national ID of the user renting the book.
+/// * `to_nation_id`: The national ID of the user the rental is transferred
+///   to.
+/// * `book_name`: The name of the rented book.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::Forbidden` if the user the rental is
+/// transferred to is blacklisted, `LibraryError::NotFound` if either user
+/// does not exist or if the book is not rented by `from_nation_id`, or an
+/// error if there is an issue with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn transfer_rental(
+    pool: &PgPool,
+    library_id: &str,
+    from_nation_id: &str,
+    to_nation_id: &str,
+    book_name: &str,
+) -> Result<(), LibraryError> {
+    let mut transaction = pool.begin().await?;
+    if let Err(err) = lock_renter(&mut transaction, library_id, to_nation_id).await {
+        transaction.rollback().await?;
+        return Err(err);
+    }
+
+    // the rental is only transferred if the current renter is not deleted
+    let transfer = timed_sql!(sqlx::query!(
+        r#"
+        WITH current_rentals AS (
+            SELECT DISTINCT ON (users_history.copy_number)
+                users_history.id, users_history.nation_id, users_history.copy_number
+            FROM users_history
+            JOIN book ON book.library_id = users_history.library_id
+                AND book.name = users_history.book_name
+                AND book.copy_number = users_history.copy_number
+            WHERE users_history.library_id = $1 AND users_history.book_name = $2
+                AND book.status = 'Rented' AND book.deleted_at IS NULL
+            ORDER BY users_history.copy_number, users_history.created_at DESC
+        )
+        UPDATE users_history
+        SET nation_id = $4, updated_at = NOW()
+        WHERE id = (
+            SELECT current_rentals.id
+            FROM current_rentals
+            JOIN users ON users.library_id = $1
+                AND users.nation_id = current_rentals.nation_id
+            WHERE current_rentals.nation_id = $3 AND users.deleted_at IS NULL
+            ORDER BY current_rentals.copy_number
+            LIMIT 1
+        )
+        "#,
+        library_id,
+        book_name,
+        from_nation_id,
+        to_nation_id,
+    )
+    .execute(&mut transaction))
     .await?;
 
-    if rent_book.rows_affected() == 0 {
+    if transfer.rows_affected() == 0 {
         transaction.rollback().await?;
-        return Err(sqlx::Error::RowNotFound);
+        return Err(LibraryError::NotFound);
+    }
+    transaction.commit().await.map_err(LibraryError::from)
+}
+
+/// Sets the due date of the current rentals of a book by a user, earlier or
+/// later than the current one.
+///
+/// The current rentals are the latest rentals of the rented copies of the
+/// book, so when the user rents several copies of the book, all their due
+/// dates change.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book and the user belong to.
+/// * `nation_id`: The national ID of the user renting the book.
+/// * `book_name`: The name of the rented book.
+/// * `due_date`: The new due date.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if the book is not rented
+/// by the user, or an error if there is an issue with the database
+/// connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn set_due_date(
+    pool: &PgPool,
+    library_id: &str,
+    nation_id: &str,
+    book_name: &str,
+    due_date: NaiveDate,
+) -> Result<(), LibraryError> {
+    let result = timed_sql!(sqlx::query!(
+        r#"
+        WITH current_rentals AS (
+            SELECT DISTINCT ON (users_history.copy_number)
+                users_history.id, users_history.nation_id
+            FROM users_history
+            JOIN book ON book.library_id = users_history.library_id
+                AND book.name = users_history.book_name
+                AND book.copy_number = users_history.copy_number
+            WHERE users_history.library_id = $1 AND users_history.book_name = $2
+                AND book.status = 'Rented' AND book.deleted_at IS NULL
+            ORDER BY users_history.copy_number, users_history.created_at DESC
+        )
+        UPDATE users_history
+        SET due_date = $4, updated_at = NOW()
+        FROM current_rentals
+        WHERE users_history.id = current_rentals.id AND current_rentals.nation_id = $3
+        "#,
+        library_id,
+        book_name,
+        nation_id,
+        due_date.format("%Y-%m-%d").to_string(),
+    )
+    .execute(pool))
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(LibraryError::NotFound);
     }
+    Ok(())
+}
 
-    transaction.commit().await
+/// Returns a rented book, i.e. records the return of the current rental of
+/// the book by a user and makes its copy available again.
+///
+/// The current rentals are the latest rentals of the rented copies of the
+/// book that are not returned yet. When the user rents several copies of the
+/// book, the lowest numbered copy is returned.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book and the user belong to.
+/// * `nation_id`: The national ID of the user renting the book.
+/// * `book_name`: The name of the rented book.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if the book is not rented
+/// by the user, or an error if there is an issue with the database
+/// connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn return_book(
+    pool: &PgPool,
+    library_id: &str,
+    nation_id: &str,
+    book_name: &str,
+) -> Result<(), LibraryError> {
+    // record the return and release the copy in the same query
+    let returned = timed_sql!(sqlx::query!(
+        r#"
+        WITH current_rentals AS (
+            SELECT DISTINCT ON (users_history.copy_number)
+                users_history.id, users_history.nation_id, users_history.copy_number,
+                users_history.returned_at
+            FROM users_history
+            JOIN book ON book.library_id = users_history.library_id
+                AND book.name = users_history.book_name
+                AND book.copy_number = users_history.copy_number
+            WHERE users_history.library_id = $1 AND users_history.book_name = $2
+                AND book.status = 'Rented' AND book.deleted_at IS NULL
+            ORDER BY users_history.copy_number, users_history.created_at DESC
+        ),
+        returned_rental AS (
+            UPDATE users_history
+            SET returned_at = NOW(), updated_at = NOW()
+            WHERE id = (
+                SELECT id
+                FROM current_rentals
+                WHERE nation_id = $3 AND returned_at IS NULL
+                ORDER BY copy_number
+                LIMIT 1
+            )
+            RETURNING copy_number
+        )
+        UPDATE book
+        SET status = 'Available'
+        FROM returned_rental
+        WHERE book.library_id = $1 AND book.name = $2
+            AND book.copy_number = returned_rental.copy_number
+        "#,
+        library_id,
+        book_name,
+        nation_id,
+    )
+    .execute(pool))
+    .await?;
+    if returned.rows_affected() == 0 {
+        return Err(LibraryError::NotFound);
+    }
+    Ok(())
 }
 
 /// Retrieve a list of users based on the given query parameters.
@@ -131,191 +805,1740 @@ pub async fn rent_book(pool: &PgPool, data: &UserRentBook) -> Result<(), sqlx::E
 /// ## Arguments
 ///
 /// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose users are retrieved.
 /// * `user`: The query parameters for filtering users.
 ///
 /// ## Returns
 ///
-/// A vector of `UserRow` representing the retrieved user records.
+/// A vector of `UserRow` representing the retrieved user records, one per
+/// rental, ordered by national ID and rental date.
 ///
 /// ## Errors
 ///
 /// This function returns an error if the user retrieval fails or
 /// if there is an issue with the database connection.
-pub async fn users(pool: &PgPool, user: &UserQuery) -> Result<Vec<UserRow>, sqlx::Error> {
-    let result = sqlx::query_as!(
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn users(
+    pool: &PgPool,
+    library_id: &str,
+    user: &UserQuery,
+) -> Result<Vec<UserRow>, LibraryError> {
+    let result = timed_sql!(sqlx::query_as!(
         UserRow,
         r#"
         SELECT users_history.book_name, users.nation_id, users.name as user_name
         FROM users_history
-        JOIN users ON users_history.nation_id = users.nation_id
+        JOIN users ON users_history.library_id = users.library_id
+            AND users_history.nation_id = users.nation_id
         WHERE
-            ($1::text IS NULL OR users.name = $1)
-            AND ($2::text IS NULL OR users_history.book_name = $2)
+            users.library_id = $1
+            AND users.deleted_at IS NULL
+            AND ($2::text IS NULL OR users.name = $2)
+            AND ($3::text IS NULL OR users_history.book_name = $3)
+        ORDER BY users.nation_id, users_history.created_at, users_history.id
+        LIMIT $4 OFFSET $5
         "#,
+        library_id,
         user.user_name,
-        user.book_name
+        user.book_name,
+        user.limit,
+        user.offset.unwrap_or_default(),
     )
-    .fetch_all(pool)
+    .fetch_all(pool))
     .await?;
     if result.is_empty() {
-        return Err(sqlx::Error::RowNotFound);
+        return Err(LibraryError::NotFound);
     }
     Ok(result)
 }
 
-/// Retrieve the rental history of a user based on the given national ID.
+/// Retrieves a page of the users with outstanding rentals.
+///
+/// A rental is outstanding when it is the latest rental of a book copy that
+/// is still rented. A user is listed once per outstanding rental, and the
+/// rentals are ordered by national ID and book name. Deleted users and books
+/// are left out.
 ///
 /// ## Arguments
 ///
 /// * `pool`: The PostgreSQL database connection pool.
-/// * `nation_id`: The national ID of the user.
+/// * `library_id`: The ID of the library whose users are retrieved.
+/// * `limit`: The maximum number of rentals to return.
+/// * `offset`: The number of rentals to skip.
 ///
 /// ## Returns
 ///
-/// A vector of `UserHistoryRow` representing the user's rental history.
+/// A tuple of the page of `UserRow` objects and the total number of
+/// outstanding rentals. The page is empty if it is past the last rental.
 ///
 /// ## Errors
 ///
-/// This function returns an error if the retrieval fails or
-/// if there is an issue with the database connection.
-pub async fn get_user(
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn active_users(
     pool: &PgPool,
-    nation_id: String,
-) -> Result<Vec<UserHistoryRow>, sqlx::Error> {
-    let result = sqlx::query_as!(
-        UserHistoryRow,
+    library_id: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<UserRow>, i64), LibraryError> {
+    let users = timed_sql!(sqlx::query_as!(
+        UserRow,
         r#"
-        SELECT users.name, users_history.nation_id, users_history.book_name, users_history.due_date
-        FROM users_history
-        JOIN users ON users.nation_id = users_history.nation_id 
-        WHERE users_history.nation_id = $1
+        WITH current_rentals AS (
+            SELECT DISTINCT ON (users_history.book_name, users_history.copy_number)
+                users_history.nation_id, users_history.book_name
+            FROM users_history
+            JOIN book ON book.library_id = users_history.library_id
+                AND book.name = users_history.book_name
+                AND book.copy_number = users_history.copy_number
+            WHERE users_history.library_id = $1
+                AND book.status = 'Rented' AND book.deleted_at IS NULL
+            ORDER BY users_history.book_name, users_history.copy_number,
+                users_history.created_at DESC
+        )
+        SELECT current_rentals.book_name as "book_name!", users.nation_id,
+            users.name as user_name
+        FROM current_rentals
+        JOIN users ON users.library_id = $1 AND users.nation_id = current_rentals.nation_id
+        WHERE users.deleted_at IS NULL
+        ORDER BY users.nation_id, current_rentals.book_name
+        LIMIT $2 OFFSET $3
         "#,
-        nation_id
+        library_id,
+        limit,
+        offset,
     )
-    .fetch_all(pool)
+    .fetch_all(pool))
     .await?;
-    if result.is_empty() {
-        return Err(sqlx::Error::RowNotFound);
-    }
-    Ok(result)
+    let total = timed_sql!(sqlx::query_scalar!(
+        r#"
+        WITH current_rentals AS (
+            SELECT DISTINCT ON (users_history.book_name, users_history.copy_number)
+                users_history.nation_id
+            FROM users_history
+            JOIN book ON book.library_id = users_history.library_id
+                AND book.name = users_history.book_name
+                AND book.copy_number = users_history.copy_number
+            WHERE users_history.library_id = $1
+                AND book.status = 'Rented' AND book.deleted_at IS NULL
+            ORDER BY users_history.book_name, users_history.copy_number,
+                users_history.created_at DESC
+        )
+        SELECT COUNT(*) as "total!"
+        FROM current_rentals
+        JOIN users ON users.library_id = $1 AND users.nation_id = current_rentals.nation_id
+        WHERE users.deleted_at IS NULL
+        "#,
+        library_id,
+    )
+    .fetch_one(pool))
+    .await?;
+    Ok((users, total))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::library::author::Author;
-    use crate::library::book::{self, Book};
-    use fake::faker::name::en::Name as FakeUser;
-    use fake::Fake;
-    use rand::Rng;
+/// Retrieves a page of the users without a rental in the last `days` days.
+///
+/// Users who never rented a book are inactive too. The users are ordered by
+/// their last rental, those who never rented first, then by national ID.
+/// Deleted users are left out.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose users are retrieved.
+/// * `days`: The number of days without a rental after which a user is
+///   inactive.
+/// * `limit`: The maximum number of users to return.
+/// * `offset`: The number of users to skip.
+///
+/// ## Returns
+///
+/// A tuple of the page of `InactiveUserRow` objects and the total number of
+/// inactive users. The page is empty if it is past the last user.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn inactive_users(
+    pool: &PgPool,
+    library_id: &str,
+    days: i64,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<InactiveUserRow>, i64), LibraryError> {
+    let users = timed_sql!(sqlx::query_as!(
+        InactiveUserRow,
+        r#"
+        SELECT users.nation_id, users.name, users.email,
+            MAX(users_history.created_at) as last_rental_at
+        FROM users
+        LEFT JOIN users_history ON users_history.library_id = users.library_id
+            AND users_history.nation_id = users.nation_id
+        WHERE users.library_id = $1 AND users.deleted_at IS NULL
+        GROUP BY users.nation_id, users.name, users.email
+        HAVING MAX(users_history.created_at) IS NULL
+            OR MAX(users_history.created_at) <= NOW() - $2::int8 * INTERVAL '1 day'
+        ORDER BY last_rental_at NULLS FIRST, users.nation_id
+        LIMIT $3 OFFSET $4
+        "#,
+        library_id,
+        days,
+        limit,
+        offset,
+    )
+    .fetch_all(pool))
+    .await?;
+    let total = timed_sql!(sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "total!"
+        FROM users
+        WHERE users.library_id = $1 AND users.deleted_at IS NULL
+            AND NOT EXISTS (
+                SELECT 1 FROM users_history
+                WHERE users_history.library_id = users.library_id
+                    AND users_history.nation_id = users.nation_id
+                    AND users_history.created_at > NOW() - $2::int8 * INTERVAL '1 day'
+            )
+        "#,
+        library_id,
+        days,
+    )
+    .fetch_one(pool))
+    .await?;
+    Ok((users, total))
+}
 
-    impl User {
-        /// Creates a fake user with randomized national ID and name.
-        ///
-        /// ## Returns
-        ///
-        /// A `User` struct representing the fake user.
-        ///
+/// Count the users matching the given query parameters.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose users are counted.
+/// * `user`: The query parameters for filtering users.
+///
+/// ## Returns
+///
+/// The number of users with the given name who rented the given book. Unlike
+/// `users`, which lists rentals, each user is counted once, and users who never
+/// rented a book are counted unless the query has a book name.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn count_users(
+    pool: &PgPool,
+    library_id: &str,
+    user: &UserQuery,
+) -> Result<i64, LibraryError> {
+    timed_sql!(sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM users
+        WHERE
+            library_id = $1
+            AND deleted_at IS NULL
+            AND ($2::text IS NULL OR name = $2)
+            AND ($3::text IS NULL OR EXISTS (
+                SELECT 1 FROM users_history
+                WHERE users_history.library_id = users.library_id
+                    AND users_history.nation_id = users.nation_id
+                    AND users_history.book_name = $3
+            ))
+        "#,
+        library_id,
+        user.user_name,
+        user.book_name
+    )
+    .fetch_one(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Count the rentals `users` would return without pagination.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library whose users are counted.
+/// * `user`: The query parameters for filtering users, `limit` and `offset`
+///   are ignored.
+///
+/// ## Returns
+///
+/// The number of rentals of the users with the given name for the given book.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn count_user_rentals(
+    pool: &PgPool,
+    library_id: &str,
+    user: &UserQuery,
+) -> Result<i64, LibraryError> {
+    timed_sql!(sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM users_history
+        JOIN users ON users_history.library_id = users.library_id
+            AND users_history.nation_id = users.nation_id
+        WHERE
+            users.library_id = $1
+            AND users.deleted_at IS NULL
+            AND ($2::text IS NULL OR users.name = $2)
+            AND ($3::text IS NULL OR users_history.book_name = $3)
+        "#,
+        library_id,
+        user.user_name,
+        user.book_name
+    )
+    .fetch_one(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Retrieve the profile of a user based on the given national ID.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the user belongs to.
+/// * `nation_id`: The national ID of the user.
+///
+/// ## Returns
+///
+/// The `User` with the given national ID, without their rental history.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if there is no user with
+/// the given national ID that is not deleted, or an error if the retrieval
+/// fails or there is an issue with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn get_user_profile(
+    pool: &PgPool,
+    library_id: &str,
+    nation_id: &str,
+) -> Result<User, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        User,
+        r#"
+        SELECT nation_id, name, email, phone,
+            created_at as "created_at?", updated_at as "updated_at?"
+        FROM users
+        WHERE library_id = $1 AND nation_id = $2 AND deleted_at IS NULL
+        "#,
+        library_id,
+        nation_id,
+    )
+    .fetch_one(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Retrieve the rental history of a user based on the given national ID.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the user belongs to.
+/// * `nation_id`: The national ID of the user.
+///
+/// ## Returns
+///
+/// A vector of `UserHistoryRow` representing the user's rental history.
+///
+/// ## Errors
+///
+/// This function returns an error if the retrieval fails or
+/// if there is an issue with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn get_user(
+    pool: &PgPool,
+    library_id: &str,
+    nation_id: String,
+) -> Result<Vec<UserHistoryRow>, LibraryError> {
+    let result = timed_sql!(sqlx::query_as!(
+        UserHistoryRow,
+        r#"
+        SELECT users_history.id as history_id, users.name, users_history.nation_id,
+            users.email, users.phone, users_history.book_name, users_history.due_date
+        FROM users_history
+        JOIN users ON users.library_id = users_history.library_id
+            AND users.nation_id = users_history.nation_id
+        WHERE users_history.library_id = $1 AND users_history.nation_id = $2
+            AND users.deleted_at IS NULL
+        "#,
+        library_id,
+        nation_id
+    )
+    .fetch_all(pool))
+    .await?;
+    if result.is_empty() {
+        return Err(LibraryError::NotFound);
+    }
+    Ok(result)
+}
+
+/// Retrieves the current rental of a copy of a book, i.e. the latest rental
+/// of the copy when it is rented and the rental is not returned yet.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the book belongs to.
+/// * `book_id`: The ID of the copy of the book.
+///
+/// ## Returns
+///
+/// The `UserHistoryRow` of the current rental, `None` if the copy is not
+/// rented or does not exist.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn current_renter(
+    pool: &PgPool,
+    library_id: &str,
+    book_id: Uuid,
+) -> Result<Option<UserHistoryRow>, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        UserHistoryRow,
+        r#"
+        SELECT users_history.id as history_id, users.name, users_history.nation_id,
+            users.email, users.phone, users_history.book_name, users_history.due_date
+        FROM book
+        JOIN users_history ON users_history.library_id = book.library_id
+            AND users_history.book_name = book.name
+            AND users_history.copy_number = book.copy_number
+        JOIN users ON users.library_id = users_history.library_id
+            AND users.nation_id = users_history.nation_id
+        WHERE book.library_id = $1 AND book.id = $2 AND book.status = 'Rented'
+            AND users_history.returned_at IS NULL
+        ORDER BY users_history.created_at DESC
+        LIMIT 1
+        "#,
+        library_id,
+        book_id,
+    )
+    .fetch_optional(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Retrieves the overdue rentals of a user, i.e. their current rentals whose
+/// due date has passed.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the user belongs to.
+/// * `nation_id`: The national ID of the user.
+///
+/// ## Returns
+///
+/// A vector of `UserHistoryRow`, the earliest due date first. The vector is
+/// empty if the user has no overdue rental or does not exist.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn user_overdue_rentals(
+    pool: &PgPool,
+    library_id: &str,
+    nation_id: &str,
+) -> Result<Vec<UserHistoryRow>, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        UserHistoryRow,
+        r#"
+        WITH current_rentals AS (
+            SELECT DISTINCT ON (users_history.book_name, users_history.copy_number)
+                users_history.id, users_history.nation_id, users_history.book_name,
+                users_history.due_date
+            FROM users_history
+            JOIN book ON book.library_id = users_history.library_id
+                AND book.name = users_history.book_name
+                AND book.copy_number = users_history.copy_number
+            WHERE users_history.library_id = $1 AND book.status = 'Rented'
+                AND book.deleted_at IS NULL
+            ORDER BY users_history.book_name, users_history.copy_number,
+                users_history.created_at DESC
+        )
+        SELECT current_rentals.id as history_id, users.name, users.nation_id,
+            users.email, users.phone, current_rentals.book_name, current_rentals.due_date
+        FROM current_rentals
+        JOIN users ON users.library_id = $1 AND users.nation_id = current_rentals.nation_id
+        WHERE current_rentals.nation_id = $2 AND users.deleted_at IS NULL
+            AND current_rentals.due_date::date < CURRENT_DATE
+        ORDER BY current_rentals.due_date::date, current_rentals.book_name
+        "#,
+        library_id,
+        nation_id,
+    )
+    .fetch_all(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Updates the profile of a user.
+///
+/// Only the fields set in `update` are changed, the national ID is never
+/// updated.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the user belongs to.
+/// * `nation_id`: The national ID of the user to update.
+/// * `update`: The fields to update.
+///
+/// ## Returns
+///
+/// The updated `User`.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if there is no user with
+/// the given national ID that is not deleted, or an error if the update fails
+/// or there is an issue with the database connection.
+#[tracing::instrument(level = "debug", skip(pool, update))]
+pub async fn update_user(
+    pool: &PgPool,
+    library_id: &str,
+    nation_id: &str,
+    update: &UserUpdate,
+) -> Result<User, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        User,
+        r#"
+        UPDATE users
+        SET
+            name = COALESCE($3, name),
+            email = COALESCE($4, email),
+            phone = COALESCE($5, phone)
+        WHERE library_id = $1 AND nation_id = $2 AND deleted_at IS NULL
+        RETURNING nation_id, name, email, phone,
+            created_at as "created_at?", updated_at as "updated_at?"
+        "#,
+        library_id,
+        nation_id,
+        update.name,
+        update.email,
+        update.phone,
+    )
+    .fetch_one(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Changes the national ID of a user.
+///
+/// The national ID identifies the user across tables, so the rental history
+/// and the reviews of the user are updated in the same transaction.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the user belongs to.
+/// * `old_id`: The current national ID of the user.
+/// * `new_id`: The corrected national ID.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if there is no user with
+/// `old_id` that is not deleted, a unique violation if `new_id` is already
+/// taken, or an error if there is an issue with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn change_nation_id(
+    pool: &PgPool,
+    library_id: &str,
+    old_id: &str,
+    new_id: &str,
+) -> Result<(), LibraryError> {
+    let mut transaction = pool.begin().await?;
+
+    // the references keep the old national ID until they are updated below
+    timed_sql!(sqlx::query!(
+        "SET CONSTRAINTS users_history_nation_id_fkey, book_reviews_nation_id_fkey, \
+            damaged_reports_nation_id_fkey DEFERRED"
+    )
+    .execute(&mut transaction))
+    .await?;
+
+    let result = timed_sql!(sqlx::query!(
+        r#"
+        UPDATE users
+        SET nation_id = $3
+        WHERE library_id = $1 AND nation_id = $2 AND deleted_at IS NULL
+        "#,
+        library_id,
+        old_id,
+        new_id,
+    )
+    .execute(&mut transaction))
+    .await?;
+    if result.rows_affected() == 0 {
+        transaction.rollback().await?;
+        return Err(LibraryError::NotFound);
+    }
+
+    timed_sql!(sqlx::query!(
+        "UPDATE users_history SET nation_id = $3 WHERE library_id = $1 AND nation_id = $2",
+        library_id,
+        old_id,
+        new_id,
+    )
+    .execute(&mut transaction))
+    .await?;
+    timed_sql!(sqlx::query!(
+        "UPDATE book_reviews SET nation_id = $3 WHERE library_id = $1 AND nation_id = $2",
+        library_id,
+        old_id,
+        new_id,
+    )
+    .execute(&mut transaction))
+    .await?;
+    timed_sql!(sqlx::query!(
+        "UPDATE damaged_reports SET nation_id = $3 WHERE library_id = $1 AND nation_id = $2",
+        library_id,
+        old_id,
+        new_id,
+    )
+    .execute(&mut transaction))
+    .await?;
+
+    transaction.commit().await.map_err(LibraryError::from)
+}
+
+/// Retrieves the rental statistics of a user.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the user belongs to.
+/// * `nation_id`: The national ID of the user.
+///
+/// ## Returns
+///
+/// The `UserStats` of the user.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if there is no user with
+/// the given national ID that is not deleted, or an error if the retrieval
+/// fails or there is an issue with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn user_stats(
+    pool: &PgPool,
+    library_id: &str,
+    nation_id: &str,
+) -> Result<UserStats, LibraryError> {
+    timed_sql!(sqlx::query_as!(
+        UserStats,
+        r#"
+        WITH rentals AS (
+            SELECT users_history.nation_id, users_history.due_date, book.category,
+                book.status = 'Rented' AND ROW_NUMBER() OVER (
+                    PARTITION BY users_history.book_name, users_history.copy_number
+                    ORDER BY users_history.created_at DESC
+                ) = 1 AS active
+            FROM users_history
+            JOIN book ON book.library_id = users_history.library_id
+                AND book.name = users_history.book_name
+                AND book.copy_number = users_history.copy_number
+            WHERE users_history.library_id = $1
+        ),
+        user_rentals AS (
+            SELECT * FROM rentals WHERE nation_id = $2
+        )
+        SELECT
+            (SELECT COUNT(*) FROM user_rentals) as "total_rentals!",
+            (SELECT COUNT(*) FROM user_rentals WHERE active) as "active_rentals!",
+            (SELECT COUNT(*) FROM user_rentals
+                WHERE active AND due_date::date < CURRENT_DATE) as "overdue_rentals!",
+            (SELECT category FROM user_rentals
+                GROUP BY category
+                ORDER BY COUNT(*) DESC, category
+                LIMIT 1) as favorite_category
+        FROM users
+        WHERE library_id = $1 AND nation_id = $2 AND deleted_at IS NULL
+        "#,
+        library_id,
+        nation_id,
+    )
+    .fetch_one(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+/// Soft-deletes a user by marking it as deleted.
+///
+/// The user and the rental history are kept in the database, and the national
+/// ID stays reserved, but the user is no longer returned by the other user
+/// queries until it is restored.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the user belongs to.
+/// * `nation_id`: The national ID of the user to delete.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if there is no user with
+/// the given national ID that is not already deleted, or an error if there is
+/// an issue with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn delete_user(
+    pool: &PgPool,
+    library_id: &str,
+    nation_id: String,
+) -> Result<(), LibraryError> {
+    let result = timed_sql!(sqlx::query!(
+        r#"
+        UPDATE users
+        SET deleted_at = NOW()
+        WHERE library_id = $1 AND nation_id = $2 AND deleted_at IS NULL
+        "#,
+        library_id,
+        nation_id,
+    )
+    .execute(pool))
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(LibraryError::NotFound);
+    }
+    Ok(())
+}
+
+/// Deletes a rental of a user, e.g. one recorded by mistake.
+///
+/// When the rental is the latest one of a copy that is still rented, the
+/// copy is made available again in the same transaction, so the deleted
+/// rental does not leave it rented by no one.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the user belongs to.
+/// * `nation_id`: The national ID of the user.
+/// * `history_id`: The ID of the rental, see `UserHistoryRow::history_id`.
+///
+/// ## Returns
+///
+/// The name of the book made available again, `None` if the rental was not
+/// the current one of its copy.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if the user has no rental
+/// with the given ID, or an error if there is an issue with the database
+/// connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn delete_history_row(
+    pool: &PgPool,
+    library_id: &str,
+    nation_id: &str,
+    history_id: Uuid,
+) -> Result<Option<String>, LibraryError> {
+    let mut transaction = pool.begin().await?;
+    let deleted = timed_sql!(sqlx::query!(
+        r#"
+        DELETE FROM users_history
+        WHERE library_id = $1 AND nation_id = $2 AND id = $3
+        RETURNING book_name, copy_number, created_at
+        "#,
+        library_id,
+        nation_id,
+        history_id,
+    )
+    .fetch_one(&mut transaction))
+    .await?;
+    // the copy stays rented when it was rented again after the deleted rental
+    let released = timed_sql!(sqlx::query_scalar!(
+        r#"
+        UPDATE book
+        SET status = 'Available'
+        WHERE library_id = $1 AND name = $2 AND copy_number = $3 AND status = 'Rented'
+            AND NOT EXISTS (
+                SELECT 1 FROM users_history
+                WHERE library_id = $1 AND book_name = $2 AND copy_number = $3
+                    AND created_at > $4
+            )
+        RETURNING name
+        "#,
+        library_id,
+        deleted.book_name,
+        deleted.copy_number,
+        deleted.created_at,
+    )
+    .fetch_optional(&mut transaction))
+    .await?;
+    transaction.commit().await?;
+    Ok(released)
+}
+
+/// Restores a soft-deleted user.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the user belongs to.
+/// * `nation_id`: The national ID of the user to restore.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if there is no deleted
+/// user with the given national ID, or an error if there is an issue with the
+/// database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn restore_user(
+    pool: &PgPool,
+    library_id: &str,
+    nation_id: String,
+) -> Result<(), LibraryError> {
+    let result = timed_sql!(sqlx::query!(
+        r#"
+        UPDATE users
+        SET deleted_at = NULL
+        WHERE library_id = $1 AND nation_id = $2 AND deleted_at IS NOT NULL
+        "#,
+        library_id,
+        nation_id,
+    )
+    .execute(pool))
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(LibraryError::NotFound);
+    }
+    Ok(())
+}
+
+/// Blacklists a user, preventing them from renting books.
+///
+/// Blacklisting an already blacklisted user keeps the original time of the
+/// blacklisting.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the user belongs to.
+/// * `nation_id`: The national ID of the user to blacklist.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if there is no user with
+/// the given national ID that is not deleted, or an error if there is an
+/// issue with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn blacklist_user(
+    pool: &PgPool,
+    library_id: &str,
+    nation_id: &str,
+) -> Result<(), LibraryError> {
+    let result = timed_sql!(sqlx::query!(
+        r#"
+        UPDATE users
+        SET blacklisted_at = COALESCE(blacklisted_at, now())
+        WHERE library_id = $1 AND nation_id = $2 AND deleted_at IS NULL
+        "#,
+        library_id,
+        nation_id,
+    )
+    .execute(pool))
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(LibraryError::NotFound);
+    }
+    Ok(())
+}
+
+/// Removes a user from the blacklist, allowing them to rent books again.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the user belongs to.
+/// * `nation_id`: The national ID of the user to remove from the blacklist.
+///
+/// ## Errors
+///
+/// This function returns `LibraryError::NotFound` if there is no user with
+/// the given national ID that is not deleted, or an error if there is an
+/// issue with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn unblacklist_user(
+    pool: &PgPool,
+    library_id: &str,
+    nation_id: &str,
+) -> Result<(), LibraryError> {
+    let result = timed_sql!(sqlx::query!(
+        r#"
+        UPDATE users
+        SET blacklisted_at = NULL
+        WHERE library_id = $1 AND nation_id = $2 AND deleted_at IS NULL
+        "#,
+        library_id,
+        nation_id,
+    )
+    .execute(pool))
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(LibraryError::NotFound);
+    }
+    Ok(())
+}
+
+/// Retrieves a page of blacklisted users, most recently blacklisted first.
+///
+/// ## Arguments
+///
+/// * `pool`: The PostgreSQL database connection pool.
+/// * `library_id`: The ID of the library the users belong to.
+/// * `query`: The pagination parameters.
+///
+/// ## Returns
+///
+/// A vector of `BlacklistedUserRow` representing the blacklisted users that
+/// are not deleted. The vector is empty if the page is past the last user.
+///
+/// ## Errors
+///
+/// This function returns an error if the query fails or if there is an issue
+/// with the database connection.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn blacklisted_users(
+    pool: &PgPool,
+    library_id: &str,
+    query: &BlacklistedUsersQuery,
+) -> Result<Vec<BlacklistedUserRow>, LibraryError> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_BLACKLISTED_USERS_LIMIT)
+        .clamp(1, MAX_BLACKLISTED_USERS_LIMIT);
+    let offset = query.offset.unwrap_or_default().max(0);
+    timed_sql!(sqlx::query_as!(
+        BlacklistedUserRow,
+        r#"
+        SELECT nation_id, name, email, phone, blacklisted_at as "blacklisted_at!"
+        FROM users
+        WHERE library_id = $1 AND blacklisted_at IS NOT NULL AND deleted_at IS NULL
+        ORDER BY blacklisted_at DESC, nation_id
+        LIMIT $2 OFFSET $3
+        "#,
+        library_id,
+        limit,
+        offset,
+    )
+    .fetch_all(pool))
+    .await
+    .map_err(LibraryError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::author::Author;
+    use crate::library::book::{self, Book, Status};
+    use crate::library::review::{self, ReviewBody};
+    use crate::middleware::library::DEFAULT_LIBRARY_ID;
+    use crate::tests::sequential::sequential_test;
+    use fake::faker::name::en::Name as FakeUser;
+    use fake::Fake;
+    use rand::Rng;
+
+    impl User {
+        /// Creates a fake user with randomized national ID and name.
+        ///
+        /// ## Returns
+        ///
+        /// A `User` struct representing the fake user.
+        ///
         /// ## Errors
         ///
         /// This function does not return any errors.
         pub async fn create_fake_user() -> Self {
             let mut rng = rand::thread_rng();
+            let nation_id = rng.gen_range(10000000000_i64..=99999999999_i64).to_string();
             Self {
-                nation_id: rng.gen_range(10000000000_i64..=99999999999_i64).to_string(),
+                email: Some(format!("{nation_id}@example.com")),
+                phone: Some(format!("+{nation_id}")),
+                nation_id,
                 name: FakeUser().fake::<String>(),
+                created_at: None,
+                updated_at: None,
+            }
+        }
+    }
+
+    #[test]
+    fn test_active_users_query() {
+        let query = ActiveUsersQuery::default();
+        assert_eq!(query.limit_or_default(), DEFAULT_ACTIVE_USERS_LIMIT);
+        assert_eq!(query.offset_or_default(), 0);
+        let query = ActiveUsersQuery {
+            limit: Some(MAX_ACTIVE_USERS_LIMIT + 1),
+            offset: Some(-1),
+        };
+        assert_eq!(query.limit_or_default(), MAX_ACTIVE_USERS_LIMIT);
+        assert_eq!(query.offset_or_default(), 0);
+    }
+
+    #[test]
+    fn test_inactive_users_query() {
+        let query = InactiveUsersQuery::default();
+        assert!(query.validate().is_ok());
+        assert_eq!(query.days_or_default(), DEFAULT_INACTIVE_DAYS);
+        assert_eq!(query.limit_or_default(), DEFAULT_INACTIVE_USERS_LIMIT);
+        assert_eq!(query.offset_or_default(), 0);
+        let query = InactiveUsersQuery {
+            days: Some(0),
+            limit: Some(MAX_INACTIVE_USERS_LIMIT + 1),
+            offset: Some(-1),
+        };
+        assert!(query.validate().is_err());
+        assert_eq!(query.limit_or_default(), MAX_INACTIVE_USERS_LIMIT);
+        assert_eq!(query.offset_or_default(), 0);
+    }
+
+    #[test]
+    fn test_validate_user() {
+        let mut user = User {
+            nation_id: "12345678901".to_owned(),
+            name: "name".to_owned(),
+            email: None,
+            phone: None,
+            created_at: None,
+            updated_at: None,
+        };
+        assert!(user.validate().is_ok());
+        user.email = Some("name@example.com".to_owned());
+        assert!(user.validate().is_ok());
+        user.email = Some("name.example.com".to_owned());
+        assert!(user.validate().is_err());
+        user.email = None;
+        user.phone = Some("+905551234567".to_owned());
+        assert!(user.validate().is_ok());
+        user.phone = Some("905551234567".to_owned());
+        assert!(user.validate().is_err());
+        user.phone = Some("+90 555 123".to_owned());
+        assert!(user.validate().is_err());
+        user.phone = Some("+".to_owned());
+        assert!(user.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_user_update() {
+        let mut update = UserUpdate::default();
+        assert!(update.validate().is_ok());
+        update.name = Some("name".to_owned());
+        assert!(update.validate().is_ok());
+        update.name = Some("  ".to_owned());
+        assert!(update.validate().is_err());
+        update.name = None;
+        update.email = Some("name.example.com".to_owned());
+        assert!(update.validate().is_err());
+        update.email = None;
+        update.phone = Some("905551234567".to_owned());
+        assert!(update.validate().is_err());
+    }
+
+    sequential_test!(
+        async fn test_user() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            // insert user
+            let user = User::create_fake_user().await;
+            let result_id = insert_user(&pool, DEFAULT_LIBRARY_ID, &user)
+                .await
+                .expect("failed to insert user");
+            let fetched_user = timed_sql!(sqlx::query_as!(
+                User,
+                r#"
+                SELECT nation_id, name, email, phone,
+                    created_at as "created_at?", updated_at as "updated_at?"
+                FROM users
+                WHERE id = $1
+                "#,
+                result_id,
+            )
+            .fetch_one(&pool))
+            .await
+            .expect("failed to fetch the user");
+            assert!(fetched_user.created_at.is_some());
+            assert_eq!(fetched_user.created_at, fetched_user.updated_at);
+            assert_eq!(
+                User {
+                    created_at: None,
+                    updated_at: None,
+                    ..fetched_user.clone()
+                },
+                user
+            );
+            // insert book
+            let book = Book::create_fake_book(&pool).await;
+            let _insert_book = book::insert_book(&pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+            let fake_delivery_date = Author::create_fake_date().await;
+            let user_rent_book = &UserRentBook {
+                nation_id: fetched_user.nation_id,
+                book_name: book.name.clone(),
+                due_date: fake_delivery_date,
+            };
+            // rent_book
+            let _rent_result = rent_book(&pool, DEFAULT_LIBRARY_ID, user_rent_book)
+                .await
+                .expect("failed to rent book");
+            // get_user
+            let get_user_result = get_user(&pool, DEFAULT_LIBRARY_ID, user.nation_id.clone())
+                .await
+                .expect("failed to get user");
+            assert_eq!(user.name, get_user_result[0].name);
+            assert_eq!(user.email, get_user_result[0].email);
+            assert_eq!(user.phone, get_user_result[0].phone);
+            // users
+            // 1: all criterias
+            let user_query = &UserQuery {
+                user_name: Some(user.name.clone()),
+                book_name: Some(book.name.clone()),
+                limit: None,
+                offset: None,
+            };
+            let users_result = users(&pool, DEFAULT_LIBRARY_ID, user_query)
+                .await
+                .expect("failed to get users");
+            assert!(users_result
+                .iter()
+                .any(|result| result.user_name == user.name.clone()
+                    && result.book_name == book.name.clone()));
+            // 2: only user_name
+            let user_query = &UserQuery {
+                user_name: Some(user.name.clone()),
+                book_name: None,
+                limit: None,
+                offset: None,
+            };
+            let users_result = users(&pool, DEFAULT_LIBRARY_ID, user_query)
+                .await
+                .expect("failed to get users");
+            assert!(users_result
+                .iter()
+                .any(|result| result.user_name == user.name.clone()));
+            // 3: only book_name
+            let user_query = &UserQuery {
+                user_name: None,
+                book_name: Some(book.name.clone()),
+                limit: None,
+                offset: None,
+            };
+            let users_result = users(&pool, DEFAULT_LIBRARY_ID, user_query)
+                .await
+                .expect("failed to get users");
+            assert!(users_result
+                .iter()
+                .any(|result| result.book_name == book.name.clone()));
+            // 3: nothing is given
+            let user_query = &UserQuery {
+                user_name: None,
+                book_name: None,
+                limit: None,
+                offset: None,
+            };
+            let users_result = users(&pool, DEFAULT_LIBRARY_ID, user_query)
+                .await
+                .expect("failed to get users");
+            assert!(users_result
+                .iter()
+                .any(|result| result.user_name == user.name.clone()
+                    && result.book_name == book.name.clone()));
+            // get_user
+            let user_history_result = get_user(&pool, DEFAULT_LIBRARY_ID, user.nation_id.clone())
+                .await
+                .expect("failed to get user");
+            assert!(user_history_result
+                .iter()
+                .any(|result| result.nation_id == user.nation_id && result.book_name == book.name));
+            // delete_user
+            delete_user(&pool, DEFAULT_LIBRARY_ID, user.nation_id.clone())
+                .await
+                .expect("failed to delete user");
+            assert!(
+                delete_user(&pool, DEFAULT_LIBRARY_ID, user.nation_id.clone())
+                    .await
+                    .is_err()
+            );
+            assert!(get_user(&pool, DEFAULT_LIBRARY_ID, user.nation_id.clone())
+                .await
+                .is_err());
+            let users_result = users(
+                &pool,
+                DEFAULT_LIBRARY_ID,
+                &UserQuery {
+                    user_name: Some(user.name.clone()),
+                    book_name: Some(book.name.clone()),
+                    limit: None,
+                    offset: None,
+                },
+            )
+            .await;
+            assert!(matches!(users_result, Err(LibraryError::NotFound)));
+            // deleted users cannot rent books
+            let another_book = Book::create_fake_book(&pool).await;
+            book::insert_book(&pool, DEFAULT_LIBRARY_ID, &another_book)
+                .await
+                .expect("failed to insert book");
+            let user_rent_another_book = &UserRentBook {
+                nation_id: user.nation_id.clone(),
+                book_name: another_book.name.clone(),
+                due_date: Author::create_fake_date().await,
+            };
+            assert!(rent_book(&pool, DEFAULT_LIBRARY_ID, user_rent_another_book)
+                .await
+                .is_err());
+            // the national ID stays reserved
+            assert!(insert_user(&pool, DEFAULT_LIBRARY_ID, &user).await.is_err());
+            // restore_user
+            restore_user(&pool, DEFAULT_LIBRARY_ID, user.nation_id.clone())
+                .await
+                .expect("failed to restore user");
+            assert!(
+                restore_user(&pool, DEFAULT_LIBRARY_ID, user.nation_id.clone())
+                    .await
+                    .is_err()
+            );
+            let user_history_result = get_user(&pool, DEFAULT_LIBRARY_ID, user.nation_id.clone())
+                .await
+                .expect("failed to get user");
+            assert!(user_history_result
+                .iter()
+                .any(|result| result.book_name == book.name));
+            // update_user
+            let update = UserUpdate {
+                name: Some(FakeUser().fake::<String>()),
+                email: None,
+                phone: Some("+905551234567".to_owned()),
+            };
+            let updated_user = update_user(&pool, DEFAULT_LIBRARY_ID, &user.nation_id, &update)
+                .await
+                .expect("failed to update user");
+            assert_eq!(
+                updated_user,
+                User {
+                    nation_id: user.nation_id.clone(),
+                    name: update.name.clone().unwrap(),
+                    email: user.email.clone(),
+                    phone: update.phone.clone(),
+                    ..updated_user.clone()
+                }
+            );
+            assert!(updated_user.updated_at > updated_user.created_at);
+            let result = update_user(&pool, DEFAULT_LIBRARY_ID, "00000000000", &update).await;
+            assert!(matches!(result, Err(LibraryError::NotFound)));
+            delete_user(&pool, DEFAULT_LIBRARY_ID, user.nation_id.clone())
+                .await
+                .expect("failed to delete user");
+            let result = update_user(&pool, DEFAULT_LIBRARY_ID, &user.nation_id, &update).await;
+            assert!(matches!(result, Err(LibraryError::NotFound)));
+        }
+    );
+
+    sequential_test!(
+        async fn test_user_stats() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let user = User::create_fake_user().await;
+            insert_user(&pool, DEFAULT_LIBRARY_ID, &user)
+                .await
+                .expect("failed to insert user");
+
+            // no rentals yet
+            let stats = user_stats(&pool, DEFAULT_LIBRARY_ID, &user.nation_id)
+                .await
+                .expect("failed to get user stats");
+            assert_eq!(
+                stats,
+                UserStats {
+                    total_rentals: 0,
+                    active_rentals: 0,
+                    overdue_rentals: 0,
+                    favorite_category: None,
+                }
+            );
+
+            // two books of the user's favorite category, one of them overdue,
+            // and a returned book of another category
+            let favorite_category = format!("favorite {}", user.nation_id);
+            let other_category = format!("other {}", user.nation_id);
+            let rentals = [
+                (&favorite_category, "2000-01-01"),
+                (&favorite_category, "2999-01-01"),
+                (&other_category, "2999-01-01"),
+            ];
+            let mut book_names = Vec::new();
+            for (category, due_date) in rentals {
+                let mut book = Book::create_fake_book(&pool).await;
+                book.category = category.clone();
+                book::insert_book(&pool, DEFAULT_LIBRARY_ID, &book)
+                    .await
+                    .expect("failed to insert book");
+                let user_rent_book = UserRentBook {
+                    nation_id: user.nation_id.clone(),
+                    book_name: book.name.clone(),
+                    due_date: due_date.to_owned(),
+                };
+                rent_book(&pool, DEFAULT_LIBRARY_ID, &user_rent_book)
+                    .await
+                    .expect("failed to rent book");
+                book_names.push(book.name);
             }
+            timed_sql!(sqlx::query!(
+                "UPDATE book SET status = 'Available' WHERE name = $1",
+                book_names[2],
+            )
+            .execute(&pool))
+            .await
+            .expect("failed to return book");
+
+            let stats = user_stats(&pool, DEFAULT_LIBRARY_ID, &user.nation_id)
+                .await
+                .expect("failed to get user stats");
+            assert_eq!(
+                stats,
+                UserStats {
+                    total_rentals: 3,
+                    active_rentals: 2,
+                    overdue_rentals: 1,
+                    favorite_category: Some(favorite_category),
+                }
+            );
+
+            // a returned book rented by someone else is not active for the user
+            let another_user = User::create_fake_user().await;
+            insert_user(&pool, DEFAULT_LIBRARY_ID, &another_user)
+                .await
+                .expect("failed to insert user");
+            let user_rent_book = UserRentBook {
+                nation_id: another_user.nation_id.clone(),
+                book_name: book_names[2].clone(),
+                due_date: "2999-01-01".to_owned(),
+            };
+            rent_book(&pool, DEFAULT_LIBRARY_ID, &user_rent_book)
+                .await
+                .expect("failed to rent book");
+            let stats = user_stats(&pool, DEFAULT_LIBRARY_ID, &user.nation_id)
+                .await
+                .expect("failed to get user stats");
+            assert_eq!(stats.active_rentals, 2);
+            let stats = user_stats(&pool, DEFAULT_LIBRARY_ID, &another_user.nation_id)
+                .await
+                .expect("failed to get user stats");
+            assert_eq!(stats.active_rentals, 1);
+
+            // unknown user
+            let result = user_stats(&pool, DEFAULT_LIBRARY_ID, "00000000000").await;
+            assert!(matches!(result, Err(LibraryError::NotFound)));
         }
+    );
+
+    #[test]
+    fn test_validate_nation_id() {
+        assert!(validate_nation_id("12345678901").is_ok());
+        assert!(validate_nation_id("1234567890").is_err());
+        assert!(validate_nation_id("123456789012").is_err());
+        assert!(validate_nation_id("1234567890a").is_err());
+        assert!(validate_nation_id("١٢٣٤٥٦٧٨٩٠١").is_err());
     }
 
-    #[tokio::test]
-    async fn test_user() {
-        let pool = crate::database::postgres::init::pg_pool()
+    sequential_test!(
+        async fn test_change_nation_id() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let user = User::create_fake_user().await;
+            insert_user(&pool, DEFAULT_LIBRARY_ID, &user)
+                .await
+                .expect("failed to insert user");
+            let book = Book::create_fake_book(&pool).await;
+            let book_id = book::insert_book(&pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+            let user_rent_book = UserRentBook {
+                nation_id: user.nation_id.clone(),
+                book_name: book.name.clone(),
+                due_date: Author::create_fake_date().await,
+            };
+            rent_book(&pool, DEFAULT_LIBRARY_ID, &user_rent_book)
+                .await
+                .expect("failed to rent book");
+            let review = ReviewBody {
+                nation_id: user.nation_id.clone(),
+                rating: 5,
+                comment: None,
+            };
+            review::insert_review(&pool, DEFAULT_LIBRARY_ID, book_id, &review)
+                .await
+                .expect("failed to insert review");
+
+            let new_id = User::create_fake_user().await.nation_id;
+            change_nation_id(&pool, DEFAULT_LIBRARY_ID, &user.nation_id, &new_id)
+                .await
+                .expect("failed to change nation_id");
+
+            // every reference follows the new national ID
+            let user_history_result = get_user(&pool, DEFAULT_LIBRARY_ID, new_id.clone())
+                .await
+                .expect("failed to get user");
+            assert_eq!(user_history_result[0].name, user.name);
+            assert_eq!(user_history_result[0].book_name, book.name);
+            assert!(get_user(&pool, DEFAULT_LIBRARY_ID, user.nation_id.clone())
+                .await
+                .is_err());
+            let reviews = timed_sql!(sqlx::query_scalar!(
+                "SELECT nation_id FROM book_reviews WHERE book_name = $1",
+                book.name,
+            )
+            .fetch_all(&pool))
             .await
-            .expect("failed to connect to postgres");
-        // insert user
-        let user = User::create_fake_user().await;
-        let result_id = insert_user(&pool, &user)
+            .expect("failed to fetch reviews");
+            assert_eq!(reviews, vec![new_id.clone()]);
+            let old_references = timed_sql!(sqlx::query_scalar!(
+                r#"
+                SELECT
+                    (SELECT COUNT(*) FROM users WHERE nation_id = $1)
+                    + (SELECT COUNT(*) FROM users_history WHERE nation_id = $1)
+                    + (SELECT COUNT(*) FROM book_reviews WHERE nation_id = $1)
+                "#,
+                user.nation_id,
+            )
+            .fetch_one(&pool))
             .await
-            .expect("failed to insert user");
-        let fetched_user = sqlx::query_as!(
-            User,
-            r#"
-            SELECT nation_id, name
-            FROM users
-            WHERE id = $1
-            "#,
-            result_id,
-        )
-        .fetch_one(&pool)
-        .await
-        .expect("failed to fetch the user");
-        assert_eq!(fetched_user, user);
-        // insert book
-        let book = Book::create_fake_book(&pool).await;
-        let _insert_book = book::insert_book(&pool, &book)
+            .expect("failed to count references");
+            assert_eq!(old_references, Some(0));
+
+            // the national ID of another user cannot be taken
+            let another_user = User::create_fake_user().await;
+            insert_user(&pool, DEFAULT_LIBRARY_ID, &another_user)
+                .await
+                .expect("failed to insert user");
+            let result =
+                change_nation_id(&pool, DEFAULT_LIBRARY_ID, &new_id, &another_user.nation_id).await;
+            assert!(matches!(result, Err(LibraryError::Conflict(_))));
+            assert!(get_user(&pool, DEFAULT_LIBRARY_ID, new_id.clone())
+                .await
+                .is_ok());
+
+            // unknown user
+            let result =
+                change_nation_id(&pool, DEFAULT_LIBRARY_ID, &user.nation_id, "00000000000").await;
+            assert!(matches!(result, Err(LibraryError::NotFound)));
+        }
+    );
+    sequential_test!(
+        async fn test_delete_history_row() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let user = User::create_fake_user().await;
+            insert_user(&pool, DEFAULT_LIBRARY_ID, &user)
+                .await
+                .expect("failed to insert user");
+            let another_user = User::create_fake_user().await;
+            insert_user(&pool, DEFAULT_LIBRARY_ID, &another_user)
+                .await
+                .expect("failed to insert user");
+            let book = Book::create_fake_book(&pool).await;
+            let book_id = book::insert_book(&pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+            let rent = |nation_id: &str| UserRentBook {
+                nation_id: nation_id.to_owned(),
+                book_name: book.name.clone(),
+                due_date: "2030-06-15".to_owned(),
+            };
+            let history_id = |nation_id: &str| {
+                let nation_id = nation_id.to_owned();
+                let pool = pool.clone();
+                async move {
+                    get_user(&pool, DEFAULT_LIBRARY_ID, nation_id)
+                        .await
+                        .expect("failed to get user")[0]
+                        .history_id
+                }
+            };
+
+            // the current rental makes the copy available again
+            rent_book(&pool, DEFAULT_LIBRARY_ID, &rent(&user.nation_id))
+                .await
+                .expect("failed to rent book");
+            let id = history_id(&user.nation_id).await;
+            let released = delete_history_row(&pool, DEFAULT_LIBRARY_ID, &user.nation_id, id)
+                .await
+                .expect("failed to delete history row");
+            assert_eq!(released, Some(book.name.clone()));
+            let status = book::get_book(&pool, DEFAULT_LIBRARY_ID, book_id)
+                .await
+                .expect("failed to get book")
+                .status;
+            assert_eq!(status, Status::Available);
+            assert!(matches!(
+                delete_history_row(&pool, DEFAULT_LIBRARY_ID, &user.nation_id, id).await,
+                Err(LibraryError::NotFound)
+            ));
+
+            // a past rental leaves the copy rented by the next renter
+            rent_book(&pool, DEFAULT_LIBRARY_ID, &rent(&user.nation_id))
+                .await
+                .expect("failed to rent book");
+            timed_sql!(sqlx::query!(
+                "UPDATE book SET status = 'Available' WHERE id = $1",
+                book_id
+            )
+            .execute(&pool))
             .await
-            .expect("failed to insert book");
-        let fake_delivery_date = Author::create_fake_date().await;
-        let user_rent_book = &UserRentBook {
-            nation_id: fetched_user.nation_id,
-            book_name: book.name.clone(),
-            due_date: fake_delivery_date,
-        };
-        // rent_book
-        let _rent_result = rent_book(&pool, user_rent_book)
+            .expect("failed to return book");
+            rent_book(&pool, DEFAULT_LIBRARY_ID, &rent(&another_user.nation_id))
+                .await
+                .expect("failed to rent book");
+            let id = history_id(&user.nation_id).await;
+            // the rental belongs to another user
+            assert!(matches!(
+                delete_history_row(&pool, DEFAULT_LIBRARY_ID, &another_user.nation_id, id).await,
+                Err(LibraryError::NotFound)
+            ));
+            let released = delete_history_row(&pool, DEFAULT_LIBRARY_ID, &user.nation_id, id)
+                .await
+                .expect("failed to delete history row");
+            assert_eq!(released, None);
+            let status = book::get_book(&pool, DEFAULT_LIBRARY_ID, book_id)
+                .await
+                .expect("failed to get book")
+                .status;
+            assert_eq!(status, Status::Rented);
+        }
+    );
+    sequential_test!(
+        async fn test_register_and_rent() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let book = Book::create_fake_book(&pool).await;
+            book::insert_book(&pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+            let rent = RentBook {
+                book_name: book.name.clone(),
+                due_date: "2030-06-15".to_owned(),
+            };
+
+            let user = User::create_fake_user().await;
+            let (_, rental) = register_and_rent(&pool, DEFAULT_LIBRARY_ID, &user, &rent)
+                .await
+                .expect("failed to register and rent");
+            assert_eq!(rental.nation_id, user.nation_id);
+            let history = get_user(&pool, DEFAULT_LIBRARY_ID, user.nation_id.clone())
+                .await
+                .expect("failed to get user");
+            assert_eq!(history[0].book_name, book.name);
+
+            // the user is not created when the book is not available
+            let another_user = User::create_fake_user().await;
+            assert!(matches!(
+                register_and_rent(&pool, DEFAULT_LIBRARY_ID, &another_user, &rent).await,
+                Err(LibraryError::NotFound)
+            ));
+            let users = timed_sql!(sqlx::query_scalar!(
+                "SELECT COUNT(*) FROM users WHERE nation_id = $1",
+                another_user.nation_id,
+            )
+            .fetch_one(&pool))
             .await
-            .expect("failed to rent book");
-        // get_user
-        let get_user_result = get_user(&pool, user.nation_id.clone())
+            .expect("failed to count users");
+            assert_eq!(users, Some(0));
+        }
+    );
+    sequential_test!(
+        async fn test_set_due_date() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let user = User::create_fake_user().await;
+            insert_user(&pool, DEFAULT_LIBRARY_ID, &user)
+                .await
+                .expect("failed to insert user");
+            let book = Book::create_fake_book(&pool).await;
+            book::insert_book(&pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+            let due_date = NaiveDate::from_ymd_opt(2020, 1, 31).expect("invalid date");
+            // not rented yet
+            assert!(matches!(
+                set_due_date(
+                    &pool,
+                    DEFAULT_LIBRARY_ID,
+                    &user.nation_id,
+                    &book.name,
+                    due_date
+                )
+                .await,
+                Err(LibraryError::NotFound)
+            ));
+
+            let user_rent_book = UserRentBook {
+                nation_id: user.nation_id.clone(),
+                book_name: book.name.clone(),
+                due_date: "2030-06-15".to_owned(),
+            };
+            rent_book(&pool, DEFAULT_LIBRARY_ID, &user_rent_book)
+                .await
+                .expect("failed to rent book");
+            // an earlier due date is allowed
+            set_due_date(
+                &pool,
+                DEFAULT_LIBRARY_ID,
+                &user.nation_id,
+                &book.name,
+                due_date,
+            )
             .await
-            .expect("failed to get user");
-        assert_eq!(user.name, get_user_result[0].name);
-        // users
-        // 1: all criterias
-        let user_query = &UserQuery {
-            user_name: Some(user.name.clone()),
-            book_name: Some(book.name.clone()),
-        };
-        let users_result = users(&pool, user_query).await.expect("failed to get users");
-        assert!(users_result
-            .iter()
-            .any(|result| result.user_name == user.name.clone()
-                && result.book_name == book.name.clone()));
-        // 2: only user_name
-        let user_query = &UserQuery {
-            user_name: Some(user.name.clone()),
-            book_name: None,
-        };
-        let users_result = users(&pool, user_query).await.expect("failed to get users");
-        assert!(users_result
-            .iter()
-            .any(|result| result.user_name == user.name.clone()));
-        // 3: only book_name
-        let user_query = &UserQuery {
-            user_name: None,
-            book_name: Some(book.name.clone()),
-        };
-        let users_result = users(&pool, user_query).await.expect("failed to get users");
-        assert!(users_result
-            .iter()
-            .any(|result| result.book_name == book.name.clone()));
-        // 3: nothing is given
-        let user_query = &UserQuery {
-            user_name: None,
-            book_name: None,
-        };
-        let users_result = users(&pool, user_query).await.expect("failed to get users");
-        assert!(users_result
-            .iter()
-            .any(|result| result.user_name == user.name.clone()
-                && result.book_name == book.name.clone()));
-        // get_user
-        let user_history_result = get_user(&pool, user.nation_id.clone())
+            .expect("failed to set due date");
+            let history = get_user(&pool, DEFAULT_LIBRARY_ID, user.nation_id.clone())
+                .await
+                .expect("failed to get user");
+            assert_eq!(history[0].due_date, "2020-01-31");
+            assert!(matches!(
+                set_due_date(
+                    &pool,
+                    DEFAULT_LIBRARY_ID,
+                    "00000000000",
+                    &book.name,
+                    due_date
+                )
+                .await,
+                Err(LibraryError::NotFound)
+            ));
+        }
+    );
+
+    sequential_test!(
+        async fn test_return_book() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let user = User::create_fake_user().await;
+            insert_user(&pool, DEFAULT_LIBRARY_ID, &user)
+                .await
+                .expect("failed to insert user");
+            let book = Book::create_fake_book(&pool).await;
+            let book_id = book::insert_book(&pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+            // not rented yet
+            assert!(matches!(
+                return_book(&pool, DEFAULT_LIBRARY_ID, &user.nation_id, &book.name).await,
+                Err(LibraryError::NotFound)
+            ));
+
+            let user_rent_book = UserRentBook {
+                nation_id: user.nation_id.clone(),
+                book_name: book.name.clone(),
+                due_date: "2030-06-15".to_owned(),
+            };
+            rent_book(&pool, DEFAULT_LIBRARY_ID, &user_rent_book)
+                .await
+                .expect("failed to rent book");
+            // rented by another user
+            assert!(matches!(
+                return_book(&pool, DEFAULT_LIBRARY_ID, "00000000000", &book.name).await,
+                Err(LibraryError::NotFound)
+            ));
+            return_book(&pool, DEFAULT_LIBRARY_ID, &user.nation_id, &book.name)
+                .await
+                .expect("failed to return book");
+            let returned = book::get_book(&pool, DEFAULT_LIBRARY_ID, book_id)
+                .await
+                .expect("failed to get book");
+            assert_eq!(returned.status, Status::Available);
+            let returned_at = timed_sql!(sqlx::query_scalar!(
+                "SELECT returned_at FROM users_history WHERE library_id = $1 AND nation_id = $2",
+                DEFAULT_LIBRARY_ID,
+                user.nation_id,
+            )
+            .fetch_one(&pool))
             .await
-            .expect("failed to get user");
-        assert!(user_history_result
-            .iter()
-            .any(|result| result.nation_id == user.nation_id && result.book_name == book.name));
-    }
+            .expect("failed to get rental");
+            assert!(returned_at.is_some());
+            let renter = current_renter(&pool, DEFAULT_LIBRARY_ID, book_id)
+                .await
+                .expect("failed to get current renter");
+            assert_eq!(renter, None);
+            // already returned
+            assert!(matches!(
+                return_book(&pool, DEFAULT_LIBRARY_ID, &user.nation_id, &book.name).await,
+                Err(LibraryError::NotFound)
+            ));
+        }
+    );
+
+    sequential_test!(
+        async fn test_user_overdue_rentals() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let user = User::create_fake_user().await;
+            insert_user(&pool, DEFAULT_LIBRARY_ID, &user)
+                .await
+                .expect("failed to insert user");
+            let overdue = user_overdue_rentals(&pool, DEFAULT_LIBRARY_ID, &user.nation_id)
+                .await
+                .expect("failed to get overdue rentals");
+            assert!(overdue.is_empty());
+
+            // an overdue rental, a rental not due yet and a returned overdue
+            // rental
+            let mut books = vec![];
+            for due_date in ["2020-01-31", "2999-01-01", "2020-01-31"] {
+                let book = Book::create_fake_book(&pool).await;
+                book::insert_book(&pool, DEFAULT_LIBRARY_ID, &book)
+                    .await
+                    .expect("failed to insert book");
+                let user_rent_book = UserRentBook {
+                    nation_id: user.nation_id.clone(),
+                    book_name: book.name.clone(),
+                    due_date: due_date.to_owned(),
+                };
+                rent_book(&pool, DEFAULT_LIBRARY_ID, &user_rent_book)
+                    .await
+                    .expect("failed to rent book");
+                books.push(book.name);
+            }
+            timed_sql!(sqlx::query!(
+                "UPDATE book SET status = 'Available' WHERE name = $1",
+                books[2],
+            )
+            .execute(&pool))
+            .await
+            .expect("failed to return book");
+
+            let overdue = user_overdue_rentals(&pool, DEFAULT_LIBRARY_ID, &user.nation_id)
+                .await
+                .expect("failed to get overdue rentals");
+            assert_eq!(overdue.len(), 1);
+            assert_eq!(overdue[0].book_name, books[0]);
+            assert_eq!(overdue[0].nation_id, user.nation_id);
+            assert_eq!(overdue[0].due_date, "2020-01-31");
+        }
+    );
 }
@@ -1,31 +1,77 @@
-use crate::docs::api::ApiDoc;
+use crate::config::AppConfig;
+use crate::docs::{self, api::ApiDoc};
+use crate::helper::web::{ApiVersion, ErrorBody, ServerErr};
+use crate::middleware::accept::RequireJsonAcceptLayer;
+use crate::middleware::audit::AuditLayer;
+use crate::middleware::content_type::RequireJsonContentTypeLayer;
+use crate::middleware::idempotency::IdempotencyLayer;
+use crate::middleware::library::{LibraryIdLayer, DEFAULT_LIBRARY_ID};
+use crate::middleware::role::RequireAdminLayer;
+use crate::middleware::timing::QueryTimingLayer;
+use crate::telemetry::config::DEFAULT_SERVICE_NAME;
 use axum::{
-    routing::{get, post},
-    Router,
+    error_handling::HandleErrorLayer,
+    extract::DefaultBodyLimit,
+    http::{HeaderValue, StatusCode},
+    response::Json,
+    routing::{any, delete, get, post, put},
+    BoxError, Extension, Router,
 };
+use opentelemetry::metrics::Meter;
 use sqlx::PgPool;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tower::{
+    buffer::BufferLayer, limit::RateLimitLayer, load_shed::error::Overloaded,
+    load_shed::LoadShedLayer, timeout::error::Elapsed, timeout::TimeoutLayer, ServiceBuilder,
+};
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{Any, CorsLayer};
+pub mod audit;
 pub mod author;
+pub mod batch;
 pub mod book;
+pub mod damage;
+pub mod events;
+pub mod review;
+pub mod stats;
+pub mod tag;
 pub mod user;
+pub mod version;
+pub mod ws;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
+/// The resources whose routes were served under `/api` before the API was
+/// versioned.
+const UNVERSIONED_RESOURCES: [&str; 5] = ["book", "author", "user", "stats", "audit"];
+
 /// Represents a web application for a library.
 ///
-/// This struct holds a reference to a PostgreSQL connection pool `PgPool` and
-/// is used to handle web requests related to the library. It is cloneable and
-/// exposes the `pool` field for accessing the connection pool.
+/// This struct holds a reference to a PostgreSQL connection pool `PgPool`,
+/// the `AppConfig` of the application, the ID of the library serving
+/// requests without an `X-Library-Id` header, the channel the changes of
+/// the status of the books are broadcast on and the `Meter` recording the
+/// metrics of the requests, and is used to handle web
+/// requests related to the library. It is cloneable and exposes the `pool`
+/// field for accessing the connection pool.
 #[derive(Clone)]
 #[allow(dead_code)]
 pub struct LibraryWeb {
     pool: PgPool,
+    config: AppConfig,
+    default_library_id: String,
+    book_events: broadcast::Sender<ws::BookStatusEvent>,
+    meter: Meter,
 }
 
 impl LibraryWeb {
     /// Creates a new instance of `LibraryWeb`.
     ///
     /// This function takes a PostgreSQL connection pool `pool` and returns
-    /// a new `LibraryWeb` instance.
+    /// a new `LibraryWeb` instance configured from the environment, see
+    /// `AppConfig::from_env`, that serves the default library to requests
+    /// without an `X-Library-Id` header.
     ///
     /// ## Arguments
     ///
@@ -34,59 +80,328 @@ impl LibraryWeb {
     /// ## Returns
     ///
     /// A new `LibraryWeb` instance.
+    #[allow(dead_code)]
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        Self::with_config(pool, AppConfig::from_env(), DEFAULT_LIBRARY_ID.to_owned())
+    }
+
+    /// Creates a new instance of `LibraryWeb` with the given configuration,
+    /// recording its metrics with the meter of the default service name.
+    ///
+    /// ## Arguments
+    ///
+    /// * `pool`: The PostgreSQL database connection pool.
+    /// * `config`: The configuration of the application.
+    /// * `default_library_id`: The ID of the library serving requests without
+    ///   an `X-Library-Id` header.
+    ///
+    /// ## Returns
+    ///
+    /// A new `LibraryWeb` instance.
+    pub fn with_config(pool: PgPool, config: AppConfig, default_library_id: String) -> Self {
+        Self {
+            pool,
+            config,
+            default_library_id,
+            book_events: ws::book_events_channel(),
+            meter: opentelemetry::global::meter(DEFAULT_SERVICE_NAME),
+        }
+    }
+
+    /// Sets the `Meter` recording the metrics of the requests, e.g. the one
+    /// of the configured service name, see `TelemetryConfig::meter`.
+    pub fn with_meter(self, meter: Meter) -> Self {
+        Self { meter, ..self }
     }
 
     /// Sets up the router for the library web service.
     ///
-    /// This function configures the router for handling various routes and
-    /// middleware for tracing. It returns the configured `Router`.
+    /// This function configures the router for handling various routes,
+    /// nested under the prefix of their API version, e.g. `/api/v1`, and
+    /// middleware for selecting the library, auditing, database query timing,
+    /// request body content type and `Accept` header checking, request body
+    /// size limiting, request timeout, tracing and response compression. CORS
+    /// and rate limiting are added when they are enabled in the `AppConfig`.
+    /// It returns the configured `Router`.
     ///
     /// ## Returns
     ///
     /// A configured `Router` for the library web service.
     pub fn setup_router(self) -> Router {
+        let body_limit = self.config.request_body_limit_bytes;
+        let cors_layer = cors_layer(&self.config.cors_origins);
+        let rate_limit_rpm = self.config.rate_limit_rpm;
+        let request_timeout = Duration::from_millis(self.config.request_timeout_ms);
+        let library_id_layer = LibraryIdLayer::new(self.default_library_id.clone());
+        // the exports, the reports, the QR codes, the events, the Swagger UI
+        // and the YAML spec don't respond with JSON
+        let v1_prefix = ApiVersion::V1.prefix();
+        let accept_layer = RequireJsonAcceptLayer::new([
+            format!("{v1_prefix}/events"),
+            format!("{v1_prefix}/book/export/csv"),
+            format!("{v1_prefix}/book/report/pdf"),
+            format!("{v1_prefix}/book/:book_id/qr-code"),
+            format!("{v1_prefix}/author/export/json"),
+            "/api/swagger".to_owned(),
+            "/api/openapi.yaml".to_owned(),
+        ]);
+
         let book_routes = Router::new()
             .route("/", get(book::books))
             .route("/create", post(book::create_book))
-            .route("/:book_id", get(book::get_book));
+            .route("/count", get(book::count_books))
+            .route("/multi-copy", post(book::create_book_copies))
+            .route("/bulk", delete(book::delete_books))
+            .route("/merge", post(book::merge_books))
+            .route("/available", get(book::available_books))
+            .route("/rented", get(book::rented_books))
+            .route("/deleted", get(book::deleted_books))
+            .route("/new-arrivals", get(book::new_arrivals))
+            .route("/popular-this-month", get(book::popular_books_this_month))
+            .route("/trending", get(book::trending_books))
+            .route("/import/csv", post(book::import_books_csv))
+            .route("/export/csv", get(book::export_books_csv))
+            .route("/report/pdf", get(book::catalogue_report_pdf))
+            .route("/random", get(book::random_book))
+            .route("/by-name/:name", get(book::get_book_by_name))
+            .route("/isbn/:isbn", get(book::get_book_by_isbn))
+            .route("/category/:category", get(book::books_by_category))
+            .route("/year/:year", get(book::books_by_year))
+            .route("/author/:author_name", get(book::books_by_author_name))
+            .route("/:book_id", get(book::get_book).delete(book::delete_book))
+            .route("/:book_id/restore", post(book::restore_book))
+            .route("/:book_id/author", put(book::reassign_book_author))
+            .route("/:book_id/availability", get(book::book_availability))
+            .route("/:book_id/copies", get(book::book_copies))
+            .route("/:book_id/current-renter", get(book::current_renter))
+            .route("/:book_id/rental-rate", get(book::rental_rate))
+            .route("/:book_id/qr-code", get(book::book_qr_code))
+            .route("/:book_id/similar", get(book::similar_books))
+            .route("/:book_id/rate", post(review::rate_book))
+            .route("/:book_id/review", post(review::review_book))
+            .route("/:book_id/reviews", get(review::reviews))
+            .route("/:book_id/flag", post(damage::flag_book))
+            .route("/:book_id/flags", get(damage::book_flags))
+            .route("/:book_id/tags", get(tag::book_tags).post(tag::tag_book))
+            .route("/:book_id/tags/:tag", delete(tag::untag_book));
 
         let author_routes = Router::new()
             .route("/", get(author::authors))
             .route("/create", post(author::create_author))
-            .route("/:author_id", get(author::get_author));
+            .route("/count", get(author::count_authors))
+            .route("/merge", post(author::merge_authors))
+            .route("/export/json", get(author::export_authors_json))
+            .route("/import/json", post(author::import_authors_json))
+            .route("/by-name/:name", get(author::get_author_by_name))
+            .route(
+                "/:author_id",
+                get(author::get_author).patch(author::patch_author),
+            )
+            .route("/:author_id/country-peers", get(author::country_peers))
+            .route("/:author_id/stats", get(author::author_stats));
 
+        #[allow(deprecated)]
         let user_routes = Router::new()
             .route("/", get(user::users))
             .route("/create", post(user::create_user))
-            .route("/rent/:nation_id", post(user::rent_book))
-            .route("/:nation_id", get(user::get_user));
-
-        Router::new()
-            .nest("/api/book", book_routes)
-            .nest("/api/author", author_routes)
-            .nest("/api/user", user_routes)
-            .merge(
-                SwaggerUi::new("/api/swagger").url("/api/docs/openapi.json", ApiDoc::openapi()),
+            .route("/register", post(user::register_and_rent))
+            .route("/count", get(user::count_users))
+            .route("/active", get(user::active_users))
+            .route("/inactive", get(user::inactive_users))
+            .route("/blacklisted", get(user::blacklisted_users))
+            .route(
+                "/rent/:nation_id",
+                post(user::rent_book).layer(IdempotencyLayer::new(request_timeout)),
             )
+            .route(
+                "/rent/:nation_id/batch",
+                post(user::rent_books).layer(IdempotencyLayer::new(request_timeout)),
+            )
+            .route(
+                "/rent/:nation_id/:book_name/transfer",
+                put(user::transfer_rental),
+            )
+            .route(
+                "/rent/:nation_id/:book_name/due-date",
+                put(user::set_due_date).route_layer(RequireAdminLayer),
+            )
+            .route(
+                "/rent/:nation_id/:book_name/return",
+                post(user::return_book),
+            )
+            .route("/by-nation-id/:nation_id", get(user::user_history))
+            .route("/profile/:nation_id", get(user::user_profile))
+            .route(
+                "/:nation_id",
+                get(user::get_user)
+                    .put(user::update_user)
+                    .delete(user::delete_user),
+            )
+            .route("/:nation_id/restore", post(user::restore_user))
+            .route(
+                "/:nation_id/history/:history_id",
+                delete(user::delete_history_row).route_layer(RequireAdminLayer),
+            )
+            .route(
+                "/:nation_id/blacklist",
+                post(user::blacklist_user).delete(user::unblacklist_user),
+            )
+            .route("/:nation_id/nation_id", put(user::change_nation_id))
+            .route("/:nation_id/stats", get(user::user_stats))
+            .route("/:nation_id/overdue", get(user::user_overdue_rentals))
+            .route(
+                "/:nation_id/recommendations",
+                get(user::book_recommendations),
+            );
+
+        let stats_routes = Router::new()
+            .route("/top-books", get(stats::top_books))
+            .route("/top-users", get(stats::top_users))
+            .route("/category-distribution", get(stats::category_distribution))
+            .route("/monthly-rentals", get(stats::monthly_rentals));
+
+        let v1_routes = Router::new()
+            .nest("/book", book_routes)
+            .nest("/author", author_routes)
+            .nest("/user", user_routes)
+            .nest("/stats", stats_routes)
+            .route("/audit", get(audit::audits))
+            .route("/ws/book-updates", get(ws::book_updates))
+            .route("/events/book-available", get(events::book_available))
+            .route("/tag", get(tag::tags));
+
+        let mut router = Router::new()
+            .nest(&v1_prefix, v1_routes)
+            .route("/api/version", get(version::version))
+            .route("/api/openapi.yaml", get(docs::api::openapi_yaml));
+        // the unversioned paths of the routes predating versioning redirect
+        // to the latest version
+        for resource in UNVERSIONED_RESOURCES {
+            router = router
+                .route(
+                    &format!("/api/{resource}"),
+                    any(version::redirect_to_latest),
+                )
+                .route(
+                    &format!("/api/{resource}/*path"),
+                    any(version::redirect_to_latest),
+                );
+        }
+        let router = router
+            .merge(SwaggerUi::new("/api/swagger").url("/api/docs/openapi.json", ApiDoc::openapi()));
+        let router = with_timeout(router, request_timeout)
+            .layer(AuditLayer::new(body_limit))
+            .layer(QueryTimingLayer::new(&self.meter))
+            .layer(RequireJsonContentTypeLayer)
+            .layer(accept_layer)
+            .layer(library_id_layer)
+            .layer(Extension(self.pool.clone()))
+            .layer(DefaultBodyLimit::max(body_limit))
             .layer(axum_tracing_opentelemetry::opentelemetry_tracing_layer())
+            .layer(CompressionLayer::new())
             .with_state(self)
-            .with_state(())
+            .with_state(());
+        // the requests of a batch go through the routes and layers above,
+        // which do not include the batch route itself, and the whole batch
+        // must complete within the request timeout
+        let api = router.clone();
+        let batch_route = Router::new().route(
+            batch::BATCH_PATH,
+            post(batch::batch)
+                .layer(Extension(api))
+                .layer(DefaultBodyLimit::max(body_limit)),
+        );
+        let router = router.merge(with_timeout(batch_route, request_timeout));
+
+        let router = match cors_layer {
+            Some(cors_layer) => router.layer(cors_layer),
+            None => router,
+        };
+        if rate_limit_rpm == 0 {
+            return router;
+        }
+        // requests over the limit are rejected instead of waiting for capacity
+        router.layer(
+            ServiceBuilder::new()
+                .layer(HandleErrorLayer::new(|err: BoxError| async move {
+                    if err.is::<Overloaded>() {
+                        (StatusCode::TOO_MANY_REQUESTS, "Too many requests")
+                    } else {
+                        (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
+                    }
+                }))
+                .layer(BufferLayer::new(1024))
+                .layer(LoadShedLayer::new())
+                .layer(RateLimitLayer::new(
+                    rate_limit_rpm.into(),
+                    Duration::from_secs(60),
+                )),
+        )
     }
 }
 
+/// Answers `504 Gateway Timeout` to the requests of `router` taking longer
+/// than `timeout`.
+fn with_timeout<S>(router: Router<S>, timeout: Duration) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router.layer(
+        ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(move |err: BoxError| async move {
+                if err.is::<Elapsed>() {
+                    let body = ErrorBody {
+                        error: ServerErr::Timeout,
+                        message: Some(format!("request took longer than {timeout:?}")),
+                    };
+                    (StatusCode::GATEWAY_TIMEOUT, Json(body))
+                } else {
+                    tracing::error!(error = %err, "unhandled middleware error");
+                    let body = ErrorBody {
+                        error: ServerErr::Internal,
+                        message: None,
+                    };
+                    (StatusCode::INTERNAL_SERVER_ERROR, Json(body))
+                }
+            }))
+            .layer(TimeoutLayer::new(timeout)),
+    )
+}
+
+/// Builds the CORS layer for the allowed origins, `None` if none is allowed.
+fn cors_layer(origins: &[String]) -> Option<CorsLayer> {
+    if origins.is_empty() {
+        return None;
+    }
+    let layer = CorsLayer::new().allow_methods(Any).allow_headers(Any);
+    if origins.iter().any(|origin| origin == "*") {
+        return Some(layer.allow_origin(Any));
+    }
+    let origins = origins
+        .iter()
+        .map(|origin| HeaderValue::from_str(origin).expect("invalid CORS origin"))
+        .collect::<Vec<_>>();
+    Some(layer.allow_origin(origins))
+}
+
 #[cfg(test)]
 pub mod tests {
     use axum::{
         body::Bytes,
-        http::{header::CONTENT_TYPE, Method, Request},
+        http::{
+            header::{ACCEPT, ACCESS_CONTROL_ALLOW_ORIGIN, CONTENT_TYPE, ORIGIN},
+            Method, Request,
+        },
+        routing,
     };
     use http_body::combinators::UnsyncBoxBody;
     use serde::{de::DeserializeOwned, Serialize};
     use tower::ServiceExt;
 
     use super::*;
+    use crate::database::postgres::init::TestSchema;
+    use crate::middleware::library::LIBRARY_ID_HEADER;
+    use crate::tests::sequential::sequential_test;
 
     impl LibraryWeb {
         /// Creates a new instance of `LibraryWeb` for testing purposes.
@@ -102,8 +417,35 @@ pub mod tests {
                 pool: crate::database::postgres::init::pg_pool()
                     .await
                     .expect("failed to create postgres pool"),
+                config: AppConfig::default(),
+                default_library_id: DEFAULT_LIBRARY_ID.to_owned(),
+                book_events: ws::book_events_channel(),
+                meter: opentelemetry::global::meter(DEFAULT_SERVICE_NAME),
             }
         }
+
+        /// Creates a new instance of `LibraryWeb` isolated from the other
+        /// tests.
+        ///
+        /// The instance uses a `TestSchema` of its own instead of the shared
+        /// tables, so the test sees only the data it inserts and leaves no
+        /// data behind. The schema is dropped when the returned `TestSchema`
+        /// is dropped, so it must be kept alive until the end of the test.
+        ///
+        /// ## Returns
+        ///
+        /// A new `LibraryWeb` instance and the `TestSchema` it uses.
+        pub async fn new_test_isolated() -> (Self, TestSchema) {
+            let (schema, pool) = TestSchema::create().await;
+            let library_web = Self {
+                pool,
+                config: AppConfig::default(),
+                default_library_id: DEFAULT_LIBRARY_ID.to_owned(),
+                book_events: ws::book_events_channel(),
+                meter: opentelemetry::global::meter(DEFAULT_SERVICE_NAME),
+            };
+            (library_web, schema)
+        }
     }
 
     /// Sends a request to the specified router and returns the response.
@@ -187,6 +529,95 @@ pub mod tests {
         send_request(router, request).await
     }
 
+    /// Sends a PUT request to the specified router and returns the response.
+    ///
+    /// This function sends a PUT request with the specified URI and body to the
+    /// provided router and returns the corresponding HTTP response.
+    ///
+    /// ## Arguments
+    ///
+    /// * `router`: The router to send the request to.
+    /// * `uri`: The URI for the PUT request.
+    /// * `body`: The body of the PUT request.
+    ///
+    /// ## Returns
+    ///
+    /// The HTTP response returned by the router.
+    pub async fn put<T: Serialize>(
+        router: &Router,
+        uri: impl AsRef<str>,
+        body: &T,
+    ) -> hyper::Response<UnsyncBoxBody<Bytes, axum::Error>> {
+        let request = Request::builder()
+            .method(Method::PUT)
+            .uri(uri.as_ref())
+            .header(CONTENT_TYPE, "application/json")
+            .body(
+                serde_json::to_vec(body)
+                    .expect("failed to serialize PUT body")
+                    .into(),
+            )
+            .expect("failed to build PUT request");
+        send_request(router, request).await
+    }
+
+    /// Sends a PATCH request to the specified router and returns the response.
+    ///
+    /// This function sends a PATCH request with the specified URI and body to
+    /// the provided router and returns the corresponding HTTP response.
+    ///
+    /// ## Arguments
+    ///
+    /// * `router`: The router to send the request to.
+    /// * `uri`: The URI for the PATCH request.
+    /// * `body`: The body of the PATCH request.
+    ///
+    /// ## Returns
+    ///
+    /// The HTTP response returned by the router.
+    pub async fn patch<T: Serialize>(
+        router: &Router,
+        uri: impl AsRef<str>,
+        body: &T,
+    ) -> hyper::Response<UnsyncBoxBody<Bytes, axum::Error>> {
+        let request = Request::builder()
+            .method(Method::PATCH)
+            .uri(uri.as_ref())
+            .header(CONTENT_TYPE, "application/json")
+            .body(
+                serde_json::to_vec(body)
+                    .expect("failed to serialize PATCH body")
+                    .into(),
+            )
+            .expect("failed to build PATCH request");
+        send_request(router, request).await
+    }
+
+    /// Sends a DELETE request to the specified router and returns the response.
+    ///
+    /// This function sends a DELETE request with the specified URI to the
+    /// provided router and returns the corresponding HTTP response.
+    ///
+    /// ## Arguments
+    ///
+    /// * `router`: The router to send the request to.
+    /// * `uri`: The URI for the DELETE request.
+    ///
+    /// ## Returns
+    ///
+    /// The HTTP response returned by the router.
+    pub async fn delete(
+        router: &Router,
+        uri: impl AsRef<str>,
+    ) -> hyper::Response<UnsyncBoxBody<Bytes, axum::Error>> {
+        let request = Request::builder()
+            .method(Method::DELETE)
+            .uri(uri.as_ref())
+            .body(hyper::Body::empty())
+            .expect("failed to build DELETE request");
+        send_request(router, request).await
+    }
+
     /// Deserializes the response body into the specified type.
     ///
     /// This function takes an HTTP response and deserializes its body into the
@@ -215,4 +646,243 @@ pub mod tests {
             .expect("failed to read response body into bytes");
         serde_json::from_slice::<T>(&bytes).expect("failed to deserialize response")
     }
+
+    sequential_test!(
+        async fn test_openapi_yaml() {
+            let lib = LibraryWeb::new_test().await;
+            let router = lib.setup_router();
+            let request = Request::builder()
+                .uri("/api/openapi.yaml")
+                .header(ACCEPT, "text/yaml")
+                .body(hyper::Body::empty())
+                .expect("failed to build GET request");
+            let response = send_request(&router, request).await;
+            assert_eq!(response.status(), 200);
+            assert_eq!(response.headers()[CONTENT_TYPE], "text/yaml");
+            let bytes = hyper::body::to_bytes(response.into_body())
+                .await
+                .expect("failed to read response body");
+            let spec: serde_yaml::Value =
+                serde_yaml::from_slice(&bytes).expect("failed to parse the spec");
+            assert_eq!(spec["info"]["title"], "Library API");
+        }
+    );
+
+    sequential_test!(
+        async fn test_cors() {
+            let lib = LibraryWeb::new_test().await;
+            let config = AppConfig {
+                cors_origins: vec!["https://example.com".to_owned()],
+                ..AppConfig::default()
+            };
+            let router =
+                LibraryWeb::with_config(lib.pool, config, lib.default_library_id).setup_router();
+            let request = Request::builder()
+                .uri("/api/docs/openapi.json")
+                .header(ORIGIN, "https://example.com")
+                .body(hyper::Body::empty())
+                .expect("failed to build GET request");
+            let response = send_request(&router, request).await;
+            assert_eq!(response.status(), 200);
+            assert_eq!(
+                response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN],
+                "https://example.com"
+            );
+            let request = Request::builder()
+                .uri("/api/docs/openapi.json")
+                .header(ORIGIN, "https://other.example.com")
+                .body(hyper::Body::empty())
+                .expect("failed to build GET request");
+            let response = send_request(&router, request).await;
+            assert!(!response.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+        }
+    );
+
+    sequential_test!(
+        async fn test_library_id() {
+            let lib = LibraryWeb::new_test().await;
+            let book = crate::library::book::Book::create_fake_book(&lib.pool).await;
+            let router = lib.setup_router();
+            let response = post(&router, "/api/v1/book/create", &book).await;
+            assert_eq!(response.status(), 201);
+            let book_id = deserialize_response_body::<book::CreatedBookBody>(response)
+                .await
+                .id;
+            let response = get(&router, format!("/api/v1/book/{book_id}/availability")).await;
+            assert_eq!(response.status(), 200);
+
+            let library_id = format!("branch-{}", rand::random::<u32>());
+            let request = Request::builder()
+                .uri(format!("/api/v1/book/{book_id}/availability"))
+                .header(LIBRARY_ID_HEADER, &library_id)
+                .body(hyper::Body::empty())
+                .expect("failed to build GET request");
+            let response = send_request(&router, request).await;
+            assert_eq!(response.status(), 404);
+            // names are only unique within a library
+            let author = crate::library::author::Author {
+                name: book.author.clone(),
+                country: "Turkey".to_owned(),
+                birth_date: "1950-01-01".to_owned(),
+                created_at: None,
+                updated_at: None,
+            };
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri("/api/v1/author/create")
+                .header(LIBRARY_ID_HEADER, &library_id)
+                .header(CONTENT_TYPE, "application/json")
+                .body(
+                    serde_json::to_vec(&author)
+                        .expect("failed to serialize POST body")
+                        .into(),
+                )
+                .expect("failed to build POST request");
+            let response = send_request(&router, request).await;
+            assert_eq!(response.status(), 201);
+
+            let request = Request::builder()
+                .uri(format!("/api/v1/book/{book_id}/availability"))
+                .header(LIBRARY_ID_HEADER, "branch/1")
+                .body(hyper::Body::empty())
+                .expect("failed to build GET request");
+            let response = send_request(&router, request).await;
+            assert_eq!(response.status(), 400);
+        }
+    );
+
+    sequential_test!(
+        async fn test_error_responses() {
+            let lib = LibraryWeb::new_test().await;
+            let book = crate::library::book::Book::create_fake_book(&lib.pool).await;
+            let router = lib.setup_router();
+
+            let response = get(&router, format!("/api/v1/book/{}", uuid::Uuid::nil())).await;
+            assert_eq!(response.status(), 404);
+            let body = deserialize_response_body::<serde_json::Value>(response).await;
+            assert_eq!(body, serde_json::json!({"error": "not_found"}));
+
+            let response = post(&router, "/api/v1/book/create", &book).await;
+            assert_eq!(response.status(), 201);
+            let response = post(&router, "/api/v1/book/create", &book).await;
+            assert_eq!(response.status(), 409);
+            let body = deserialize_response_body::<ErrorBody>(response).await;
+            assert_eq!(body.error, ServerErr::Conflict);
+            assert!(body.message.is_some());
+
+            let invalid_book = crate::library::book::Book {
+                language: Some("english".to_owned()),
+                ..book
+            };
+            let response = post(&router, "/api/v1/book/create", &invalid_book).await;
+            assert_eq!(response.status(), 400);
+            let body = deserialize_response_body::<ErrorBody>(response).await;
+            assert_eq!(body.error, ServerErr::BadRequest);
+            assert!(body.message.is_some());
+        }
+    );
+
+    sequential_test!(
+        async fn test_unsupported_media_type() {
+            let lib = LibraryWeb::new_test().await;
+            let book = crate::library::book::Book::create_fake_book(&lib.pool).await;
+            let router = lib.setup_router();
+
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri("/api/v1/book/create")
+                .header(CONTENT_TYPE, "text/plain")
+                .body(
+                    serde_json::to_vec(&book)
+                        .expect("failed to serialize POST body")
+                        .into(),
+                )
+                .expect("failed to build POST request");
+            let response = send_request(&router, request).await;
+            assert_eq!(response.status(), 415);
+            let body = deserialize_response_body::<ErrorBody>(response).await;
+            assert_eq!(body.error, ServerErr::UnsupportedMediaType);
+            assert!(body.message.is_some());
+
+            // requests without a body don't need a content type
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri(format!("/api/v1/book/{}/restore", uuid::Uuid::nil()))
+                .body(hyper::Body::empty())
+                .expect("failed to build POST request");
+            let response = send_request(&router, request).await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_not_acceptable() {
+            let lib = LibraryWeb::new_test().await;
+            let router = lib.setup_router();
+
+            let request = Request::builder()
+                .uri("/api/v1/book")
+                .header(ACCEPT, "text/html")
+                .body(hyper::Body::empty())
+                .expect("failed to build GET request");
+            let response = send_request(&router, request).await;
+            assert_eq!(response.status(), 406);
+            let body = deserialize_response_body::<ErrorBody>(response).await;
+            assert_eq!(body.error, ServerErr::NotAcceptable);
+            assert!(body.message.is_some());
+
+            let request = Request::builder()
+                .uri("/api/v1/book")
+                .header(ACCEPT, "application/json")
+                .body(hyper::Body::empty())
+                .expect("failed to build GET request");
+            let response = send_request(&router, request).await;
+            assert_eq!(response.status(), 200);
+
+            // the CSV export is not checked
+            let request = Request::builder()
+                .uri("/api/v1/book/export/csv")
+                .header(ACCEPT, "text/csv")
+                .body(hyper::Body::empty())
+                .expect("failed to build GET request");
+            let response = send_request(&router, request).await;
+            assert_eq!(response.status(), 200);
+        }
+    );
+
+    sequential_test!(
+        async fn test_rate_limit() {
+            let lib = LibraryWeb::new_test().await;
+            let config = AppConfig {
+                rate_limit_rpm: 1,
+                ..AppConfig::default()
+            };
+            let router =
+                LibraryWeb::with_config(lib.pool, config, lib.default_library_id).setup_router();
+            let response = get(&router, "/api/docs/openapi.json").await;
+            assert_eq!(response.status(), 200);
+            let response = get(&router, "/api/docs/openapi.json").await;
+            assert_eq!(response.status(), 429);
+        }
+    );
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        let router = Router::new()
+            .route("/fast", routing::get(|| async { "fast" }))
+            .route(
+                "/slow",
+                routing::get(|| async {
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    "slow"
+                }),
+            );
+        let router = with_timeout(router, Duration::from_millis(20));
+        let response = get(&router, "/fast").await;
+        assert_eq!(response.status(), 200);
+        let response = get(&router, "/slow").await;
+        assert_eq!(response.status(), 504);
+        let body = deserialize_response_body::<ErrorBody>(response).await;
+        assert_eq!(body.error, ServerErr::Timeout);
+    }
 }
@@ -0,0 +1,106 @@
+use super::LibraryWeb;
+use crate::helper::web::Response;
+use crate::library::audit::{self, AuditQuery, AuditRow};
+use crate::middleware::library::LibraryId;
+use crate::telemetry::events::log_success;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Represents the body of a response containing audit log entries.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct AuditsBody {
+    pub audits: Vec<AuditRow>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/audit",
+    tag = "audit",
+    params(
+        AuditQuery
+    ),
+    responses(
+        (status = 200, description = "list audit log entries", body = AuditsBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn audits(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Query(query): Query<AuditQuery>,
+) -> Response<AuditsBody> {
+    let audits = audit::audits(&library_web.pool, &library_id, &query).await?;
+    let response = AuditsBody { audits };
+    log_success!(
+        StatusCode::OK,
+        "audit",
+        "list",
+        count = response.audits.len()
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::book::Book;
+    use crate::library_web::book::CreatedBookBody;
+    use crate::library_web::tests::{deserialize_response_body, get, post};
+    use crate::tests::sequential::sequential_test;
+    use std::time::Duration;
+
+    sequential_test!(
+        async fn test_audit_create_book() {
+            let lib = LibraryWeb::new_test().await;
+            let request_body = Book::create_fake_book(&lib.pool).await;
+            let router = lib.setup_router();
+
+            let response = post(&router, "/api/v1/book/create", &request_body).await;
+            assert_eq!(response.status(), 201);
+            let book_id = deserialize_response_body::<CreatedBookBody>(response)
+                .await
+                .id;
+            let response = post(&router, format!("/api/v1/book/{book_id}/rate"), &()).await;
+            assert!(response.status().is_client_error());
+
+            // the audit log entry is written in the background
+            let mut audits = Vec::new();
+            for _ in 0..20 {
+                let response = get(&router, "/api/v1/audit?entity_type=book&limit=500").await;
+                assert_eq!(response.status(), 200);
+                audits = deserialize_response_body::<AuditsBody>(response)
+                    .await
+                    .audits;
+                if audits.iter().any(|row| {
+                    row.payload_json
+                        .as_ref()
+                        .is_some_and(|payload| payload["name"] == request_body.name.as_str())
+                }) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            let row = audits
+                .iter()
+                .find(|row| {
+                    row.payload_json
+                        .as_ref()
+                        .is_some_and(|payload| payload["name"] == request_body.name.as_str())
+                })
+                .expect("audit log entry not found");
+            assert_eq!(row.entity_type, "book");
+            assert_eq!(row.action, "POST /api/v1/book/create");
+            assert!(audits.iter().all(|row| row.entity_type == "book"));
+            // failed requests are not audited
+            assert!(!audits
+                .iter()
+                .any(|row| row.action == "POST /api/v1/book/:book_id/rate"
+                    && row.entity_id == Some(book_id.to_string())));
+        }
+    );
+}
@@ -1,12 +1,26 @@
 use super::LibraryWeb;
-use crate::helper::web::{internal_server_error, Response};
-use crate::library::author::{self, Author, AuthorQuery, AuthorRow};
+use crate::helper::pagination::{add_link_headers, base_url, PaginatedResponse};
+use crate::helper::web::Response;
+use crate::library::author::{
+    self, Author, AuthorPatch, AuthorQuery, AuthorRow, AuthorStats, CountryPeersQuery,
+    MergeAuthorsBody, DEFAULT_COUNTRY_PEERS_LIMIT, MAX_AUTHOR_IMPORT_LINES,
+    MAX_COUNTRY_PEERS_LIMIT,
+};
+use crate::library::error::LibraryError;
+use crate::library_web::book::{CountBody, ImportError, ImportResultBody};
+use crate::middleware::library::LibraryId;
+use crate::telemetry::events::log_success;
+use async_stream::try_stream;
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::Json,
+    body::{Bytes, StreamBody},
+    extract::{OriginalUri, Path, Query, State},
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+    response::{IntoResponse, Json},
+    BoxError,
 };
+use futures::{Stream, TryStreamExt};
 use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
@@ -31,147 +45,911 @@ pub struct GetAuthorBody {
 
 #[utoipa::path(
     post,
-    path = "/api/author/create",
+    path = "/api/v1/author/create",
     tag = "author",
     request_body = Author,
+    security(
+        ("BearerAuth" = [])
+    ),
     responses(
         (status = 201, description = "author created succesfully", body = CreatedAuthorBody),
-        (status = 500, description = "Internal server error", body = String)
+        (status = 409, description = "Author already exists", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
     )
 )]
 pub async fn create_author(
     State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
     Json(author): Json<Author>,
 ) -> Response<CreatedAuthorBody> {
-    let Ok(author_id) = author::insert_author(&library_web.pool, &author).await else {
-        return internal_server_error().await;
-    };
+    let author_id = author::insert_author(&library_web.pool, &library_id, &author).await?;
     let response = CreatedAuthorBody {
         info: author,
         id: author_id,
     };
-    (StatusCode::CREATED, Ok(Json(response)))
+    log_success!(StatusCode::CREATED, "author", "create", author_id = %author_id);
+    Ok((StatusCode::CREATED, Json(response)))
 }
 
 #[utoipa::path(
     get,
-    path = "/api/author",
+    path = "/api/v1/author",
     tag = "author",
     params(
         AuthorQuery
     ),
     responses(
-        (status = 200, description = "list matching authors", body = AuthorsBody),
-        (status = 500, description = "Internal server error", body = String)
+        (status = 200, description = "list matching authors", body = AuthorsBody,
+            headers(("Link" = String, description = "Links to the first, previous, next and last pages, set when paginating with `limit` and `offset`"))),
+        (status = 404, description = "No author found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
     )
 )]
 pub async fn authors(
     State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
     Query(author): Query<AuthorQuery>,
-) -> Response<AuthorsBody> {
-    let Ok(authors) = author::authors(&library_web.pool, &author).await else {
-        return internal_server_error().await;
-    };
+) -> PaginatedResponse<AuthorsBody> {
+    let authors = author::authors(&library_web.pool, &library_id, &author).await?;
+    let mut response_headers = HeaderMap::new();
+    if let Some(limit) = author.limit {
+        let total = author::count_authors(&library_web.pool, &library_id, &author).await?;
+        add_link_headers(
+            &mut response_headers,
+            &base_url(&headers, &uri),
+            limit,
+            author.offset.unwrap_or_default(),
+            total,
+        );
+    }
     let response = AuthorsBody { authors };
-    (StatusCode::OK, Ok(Json(response)))
+    log_success!(
+        StatusCode::OK,
+        "author",
+        "list",
+        count = response.authors.len()
+    );
+    Ok((StatusCode::OK, response_headers, Json(response)))
 }
 
 #[utoipa::path(
     get,
-    path = "/api/author/{author_id}",
+    path = "/api/v1/author/count",
+    tag = "author",
+    params(
+        AuthorQuery
+    ),
+    responses(
+        (status = 200, description = "number of matching authors", body = CountBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn count_authors(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Query(author): Query<AuthorQuery>,
+) -> Response<CountBody> {
+    let count = author::count_authors(&library_web.pool, &library_id, &author).await?;
+    let response = CountBody { count };
+    log_success!(StatusCode::OK, "author", "count", count);
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/author/{author_id}",
     tag = "author",
     params(
         ("author_id"= Uuid, Path,),
     ),
     responses(
         (status = 200, description = "list author", body = GetAuthorBody),
-        (status = 500, description = "Internal server error", body = String)
+        (status = 404, description = "Author not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
     )
 )]
 pub async fn get_author(
     State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
     Path(author_id): Path<Uuid>,
 ) -> Response<GetAuthorBody> {
-    let author = match author::get_author(&library_web.pool, author_id).await {
-        Ok(author) => author,
-        Err(_) => return internal_server_error().await,
-    };
+    let author = author::get_author(&library_web.pool, &library_id, author_id).await?;
     let response = GetAuthorBody { author };
-    (StatusCode::OK, Ok(Json(response)))
+    log_success!(StatusCode::OK, "author", "get", author_id = %author_id);
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/author/{author_id}/stats",
+    tag = "author",
+    params(
+        ("author_id"= Uuid, Path,),
+    ),
+    responses(
+        (status = 200, description = "statistics of the books of the author", body = AuthorStatsBody),
+        (status = 404, description = "Author not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn author_stats(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(author_id): Path<Uuid>,
+) -> Response<AuthorStatsBody> {
+    let stats = author::author_stats(&library_web.pool, &library_id, author_id).await?;
+    let response = AuthorStatsBody { stats };
+    log_success!(StatusCode::OK, "author", "stats", author_id = %author_id);
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/author/{author_id}/country-peers",
+    tag = "author",
+    params(
+        ("author_id"= Uuid, Path,),
+        CountryPeersQuery
+    ),
+    responses(
+        (status = 200, description = "list authors from the same country", body = AuthorsBody),
+        (status = 404, description = "Author not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn country_peers(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(author_id): Path<Uuid>,
+    Query(query): Query<CountryPeersQuery>,
+) -> Response<AuthorsBody> {
+    let author = author::get_author(&library_web.pool, &library_id, author_id).await?;
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_COUNTRY_PEERS_LIMIT)
+        .clamp(1, MAX_COUNTRY_PEERS_LIMIT);
+    let authors = author::authors_by_country(
+        &library_web.pool,
+        &library_id,
+        &author.country,
+        author_id,
+        limit,
+    )
+    .await?;
+    let response = AuthorsBody { authors };
+    log_success!(StatusCode::OK, "author", "country_peers", author_id = %author_id);
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/author/by-name/{name}",
+    tag = "author",
+    params(
+        ("name"= String, Path, description = "URL-encoded name of the author"),
+    ),
+    responses(
+        (status = 200, description = "list author", body = GetAuthorBody),
+        (status = 404, description = "Author not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn get_author_by_name(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(name): Path<String>,
+) -> Response<GetAuthorBody> {
+    let author = author::get_author_by_name(&library_web.pool, &library_id, &name).await?;
+    let response = GetAuthorBody { author };
+    log_success!(StatusCode::OK, "author", "get_by_name");
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Represents the body of a response containing the statistics of an author.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct AuthorStatsBody {
+    pub stats: AuthorStats,
+}
+
+/// Represents the body of a response when an author updated.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct UpdatedAuthorBody {
+    pub info: Author,
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/author/{author_id}",
+    tag = "author",
+    request_body = AuthorPatch,
+    params(
+        ("author_id"= Uuid, Path,),
+    ),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "author updated succesfully", body = UpdatedAuthorBody),
+        (status = 400, description = "Invalid author patch", body = ErrorBody),
+        (status = 404, description = "Author not found", body = ErrorBody),
+        (status = 409, description = "Author already exists", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn patch_author(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(author_id): Path<Uuid>,
+    Json(patch): Json<AuthorPatch>,
+) -> Response<UpdatedAuthorBody> {
+    patch.validate().map_err(LibraryError::Invalid)?;
+    let info = author::patch_author(&library_web.pool, &library_id, author_id, &patch).await?;
+    let response = UpdatedAuthorBody { info };
+    log_success!(StatusCode::OK, "author", "update", author_id = %author_id);
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Represents the body of a response when two authors merged.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct MergedAuthorsBody {
+    pub author: AuthorRow,
+    pub books_reassigned: u64,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/author/merge",
+    tag = "author",
+    request_body = MergeAuthorsBody,
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "authors merged succesfully, returns the kept author", body = MergedAuthorsBody),
+        (status = 400, description = "Both authors are the same", body = ErrorBody),
+        (status = 404, description = "Author not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn merge_authors(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Json(body): Json<MergeAuthorsBody>,
+) -> Response<MergedAuthorsBody> {
+    let books_reassigned = author::merge_authors(
+        &library_web.pool,
+        &library_id,
+        body.keep_id,
+        body.discard_id,
+    )
+    .await?;
+    let author = author::get_author(&library_web.pool, &library_id, body.keep_id).await?;
+    let response = MergedAuthorsBody {
+        author,
+        books_reassigned,
+    };
+    log_success!(
+        StatusCode::OK,
+        "author",
+        "merge",
+        keep_id = %body.keep_id,
+        discard_id = %body.discard_id,
+        books_reassigned
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Streams all authors of the library as JSON lines.
+fn authors_ndjson_stream(
+    pool: PgPool,
+    library_id: String,
+) -> impl Stream<Item = Result<Bytes, BoxError>> + Send + 'static {
+    try_stream! {
+        let records = author::author_records(pool, library_id);
+        futures::pin_mut!(records);
+        while let Some(record) = records.try_next().await? {
+            let mut line = serde_json::to_vec(&record)?;
+            line.push(b'\n');
+            yield Bytes::from(line);
+        }
+    }
+}
+
+/// Exports all authors as newline-delimited JSON.
+///
+/// The response uses the non-standard `application/x-ndjson` content type:
+/// every line of the body is a complete `Author` JSON object.
+#[utoipa::path(
+    get,
+    path = "/api/v1/author/export/json",
+    tag = "author",
+    responses(
+        (status = 200, description = "one author JSON object per line",
+            body = Author, content_type = "application/x-ndjson")
+    )
+)]
+pub async fn export_authors_json(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+) -> axum::response::Response {
+    let body = StreamBody::new(authors_ndjson_stream(library_web.pool, library_id));
+    ([(CONTENT_TYPE, "application/x-ndjson")], body).into_response()
+}
+
+/// Imports authors from newline-delimited JSON.
+///
+/// The request uses the non-standard `application/x-ndjson` content type:
+/// every non-empty line of the body is an `Author` JSON object.
+#[utoipa::path(
+    post,
+    path = "/api/v1/author/import/json",
+    tag = "author",
+    request_body(content = Author, content_type = "application/x-ndjson",
+        description = "one author JSON object per line, at most 1000 lines"),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "all authors imported succesfully", body = ImportResultBody),
+        (status = 207, description = "some authors could not be imported", body = ImportResultBody),
+        (status = 400, description = "Invalid file", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn import_authors_json(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    headers: HeaderMap,
+    body: String,
+) -> Response<ImportResultBody> {
+    let is_ndjson = headers
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("application/x-ndjson"));
+    if !is_ndjson {
+        return Err(
+            LibraryError::Invalid("content type must be application/x-ndjson".to_owned()).into(),
+        );
+    }
+    let lines: Vec<(usize, &str)> = body
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line))
+        .filter(|(_, line)| !line.trim().is_empty())
+        .collect();
+    if lines.len() > MAX_AUTHOR_IMPORT_LINES {
+        let reason = format!("file must have at most {MAX_AUTHOR_IMPORT_LINES} lines");
+        return Err(LibraryError::Invalid(reason).into());
+    }
+
+    let mut errors = Vec::new();
+    let mut rows = Vec::new();
+    let mut authors = Vec::new();
+    for (row, line) in lines {
+        let author = match serde_json::from_str::<Author>(line) {
+            Ok(author) => author,
+            Err(err) => {
+                let reason = err.to_string();
+                errors.push(ImportError { row, reason });
+                continue;
+            }
+        };
+        if let Err(reason) = author.validate() {
+            errors.push(ImportError { row, reason });
+            continue;
+        }
+        rows.push(row);
+        authors.push(author);
+    }
+
+    let mut inserted_names =
+        author::insert_authors(&library_web.pool, &library_id, &authors).await?;
+    let mut inserted = 0;
+    for (row, author) in rows.into_iter().zip(authors) {
+        // a name is only inserted once, for its first row
+        match inserted_names.iter().position(|name| *name == author.name) {
+            Some(index) => {
+                inserted_names.swap_remove(index);
+                inserted += 1;
+            }
+            None => errors.push(ImportError {
+                row,
+                reason: "an author with the same name already exists".to_owned(),
+            }),
+        }
+    }
+    errors.sort_by_key(|error| error.row);
+
+    let status = if errors.is_empty() {
+        StatusCode::OK
+    } else {
+        StatusCode::MULTI_STATUS
+    };
+    let response = ImportResultBody { inserted, errors };
+    log_success!(status, "author", "import", inserted = response.inserted);
+    Ok((status, Json(response)))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::library_web::tests::{deserialize_response_body, get, post};
+    use crate::library::book::{Book, BookSummary};
+    use crate::library::stats::tests::rent_book_times;
+    use crate::library::user::{RentBook, User};
+    use crate::library_web::book::{BooksBody, CreatedBookBody};
+    use crate::library_web::tests::{deserialize_response_body, get, patch, post, send_request};
+    use crate::middleware::library::DEFAULT_LIBRARY_ID;
+    use crate::tests::sequential::sequential_test;
+    use axum::http::{Method, Request};
     use urlencoding::encode;
 
     async fn concurrency_create_author(router: axum::Router, author: Author) -> StatusCode {
-        let response = post(&router, "/api/author/create", &author).await;
+        let response = post(&router, "/api/v1/author/create", &author).await;
         response.status()
     }
 
-    #[tokio::test]
-    async fn test_concurrency_create_author() {
-        let router = LibraryWeb::new_test().await.setup_router();
-        let request_body = Author::create_fake_author().await;
-        let fut_a = concurrency_create_author(router.clone(), request_body.clone());
-        let fut_b = concurrency_create_author(router.clone(), request_body.clone());
-        let (status_a, status_b) = tokio::join!(fut_a, fut_b);
-        assert_eq!(status_a.min(status_b), 201, "should succeed");
-        assert_eq!(status_a.max(status_b), 500, "should fail");
-    }
+    sequential_test!(
+        async fn test_concurrency_create_author() {
+            let router = LibraryWeb::new_test().await.setup_router();
+            let request_body = Author::create_fake_author().await;
+            let fut_a = concurrency_create_author(router.clone(), request_body.clone());
+            let fut_b = concurrency_create_author(router.clone(), request_body.clone());
+            let (status_a, status_b) = tokio::join!(fut_a, fut_b);
+            assert_eq!(status_a.min(status_b), 201, "should succeed");
+            assert_eq!(status_a.max(status_b), 409, "should fail");
+        }
+    );
 
-    #[tokio::test]
-    async fn test_create_author_and_get_author() {
-        let router = LibraryWeb::new_test().await.setup_router();
-        let request_body = Author::create_fake_author().await;
-        let response = post(&router, "/api/author/create", &request_body).await;
-        assert_eq!(response.status(), 201);
+    sequential_test!(
+        async fn test_create_author_and_get_author() {
+            let router = LibraryWeb::new_test().await.setup_router();
+            let request_body = Author::create_fake_author().await;
+            let response = post(&router, "/api/v1/author/create", &request_body).await;
+            assert_eq!(response.status(), 201);
 
-        let response_body = deserialize_response_body::<CreatedAuthorBody>(response).await;
-        assert_eq!(response_body.info, request_body);
+            let response_body = deserialize_response_body::<CreatedAuthorBody>(response).await;
+            assert_eq!(response_body.info, request_body);
 
-        let uri = format!("/api/author/{}", response_body.id);
-        let response = get(&router, uri).await;
-        assert_eq!(response.status(), 200);
+            let uri = format!("/api/v1/author/{}", response_body.id);
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
 
-        let response_body = deserialize_response_body::<GetAuthorBody>(response).await;
-        assert_eq!(response_body.author.name, request_body.name);
-    }
+            let response_body = deserialize_response_body::<GetAuthorBody>(response).await;
+            assert_eq!(response_body.author.name, request_body.name);
+        }
+    );
 
-    #[tokio::test]
-    async fn test_authors() {
-        let router = LibraryWeb::new_test().await.setup_router();
-        let request_body = Author::create_fake_author().await;
-        let response = post(&router, "/api/author/create", &request_body).await;
-        assert_eq!(response.status(), 201);
-
-        let response_body_created = deserialize_response_body::<CreatedAuthorBody>(response).await;
-        assert_eq!(response_body_created.info, request_body);
-
-        let response = get(&router, "/api/author").await;
-        assert_eq!(response.status(), 200);
-        let response_body = deserialize_response_body::<AuthorsBody>(response).await;
-        assert!(response_body.authors.contains(&request_body));
-        let uri = format!(
-            "/api/author?name={}",
-            encode(&response_body_created.info.name)
-        );
-        let response = get(&router, uri).await;
-        assert_eq!(response.status(), 200);
-        let response_body = deserialize_response_body::<AuthorsBody>(response).await;
-        assert_eq!(response_body.authors[0].name, request_body.name);
-
-        let uri = format!(
-            "/api/author?name={}&country={}",
-            encode(&response_body_created.info.name),
-            encode(&response_body_created.info.country),
-        );
-        let response = get(&router, uri).await;
-        assert_eq!(response.status(), 200);
-        let response_body = deserialize_response_body::<AuthorsBody>(response).await;
-        assert_eq!(response_body.authors[0].name, request_body.name);
-        assert_eq!(response_body.authors[0].country, request_body.country);
+    sequential_test!(
+        async fn test_country_peers() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let router = lib.setup_router();
+            let mut ids = Vec::new();
+            let mut names = Vec::new();
+            for country in ["Narnia", "Narnia", "Oz"] {
+                let request_body = Author {
+                    country: country.to_owned(),
+                    ..Author::create_fake_author().await
+                };
+                let response = post(&router, "/api/v1/author/create", &request_body).await;
+                assert_eq!(response.status(), 201);
+                let response_body = deserialize_response_body::<CreatedAuthorBody>(response).await;
+                ids.push(response_body.id);
+                names.push(request_body.name);
+            }
+
+            // each author from the same country appears in the other's peers
+            for (author, peer) in [(0, 1), (1, 0)] {
+                let uri = format!("/api/v1/author/{}/country-peers?limit=5", ids[author]);
+                let response = get(&router, uri).await;
+                assert_eq!(response.status(), 200);
+                let response_body = deserialize_response_body::<AuthorsBody>(response).await;
+                let peers: Vec<_> = response_body.authors.iter().map(|a| &a.name).collect();
+                assert_eq!(peers, [&names[peer]]);
+            }
+            let uri = format!("/api/v1/author/{}/country-peers", ids[2]);
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<AuthorsBody>(response).await;
+            assert!(response_body.authors.is_empty());
+
+            let uri = format!("/api/v1/author/{}/country-peers", Uuid::nil());
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_authors() {
+            let router = LibraryWeb::new_test().await.setup_router();
+            let request_body = Author::create_fake_author().await;
+            let response = post(&router, "/api/v1/author/create", &request_body).await;
+            assert_eq!(response.status(), 201);
+
+            let response_body_created =
+                deserialize_response_body::<CreatedAuthorBody>(response).await;
+            assert_eq!(response_body_created.info, request_body);
+
+            let response = get(&router, "/api/v1/author").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<AuthorsBody>(response).await;
+            let author = response_body
+                .authors
+                .iter()
+                .find(|author| author.name == request_body.name)
+                .expect("author not found");
+            assert!(author.created_at.is_some());
+            let uri = format!(
+                "/api/v1/author?name={}",
+                encode(&response_body_created.info.name)
+            );
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<AuthorsBody>(response).await;
+            assert_eq!(response_body.authors[0].name, request_body.name);
+
+            let uri = format!(
+                "/api/v1/author?name={}&country={}",
+                encode(&response_body_created.info.name),
+                encode(&response_body_created.info.country),
+            );
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<AuthorsBody>(response).await;
+            assert_eq!(response_body.authors[0].name, request_body.name);
+            assert_eq!(response_body.authors[0].country, request_body.country);
+        }
+    );
+
+    sequential_test!(
+        async fn test_patch_author() {
+            let lib = LibraryWeb::new_test().await;
+            let mut fake_book = Book::create_fake_book(&lib.pool).await;
+            let router = lib.setup_router();
+            let request_body = Author::create_fake_author().await;
+            let response = post(&router, "/api/v1/author/create", &request_body).await;
+            assert_eq!(response.status(), 201);
+            let response_body = deserialize_response_body::<CreatedAuthorBody>(response).await;
+            let uri = format!("/api/v1/author/{}", response_body.id);
+            fake_book.author = request_body.name.clone();
+            let response = post(&router, "/api/v1/book/create", &fake_book).await;
+            assert_eq!(response.status(), 201);
+            let book_id = deserialize_response_body::<CreatedBookBody>(response)
+                .await
+                .id;
+
+            let new_name = format!("{} {}", request_body.name, response_body.id);
+            let patch_body = serde_json::json!({ "name": new_name, "birth_date": "1960-05-01" });
+            let response = patch(&router, &uri, &patch_body).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<UpdatedAuthorBody>(response).await;
+            assert!(response_body.info.updated_at > response_body.info.created_at);
+            assert_eq!(
+                response_body.info,
+                Author {
+                    name: new_name.clone(),
+                    country: request_body.country,
+                    birth_date: "1960-05-01".to_owned(),
+                    ..response_body.info.clone()
+                }
+            );
+
+            // the books follow the new name
+            let response = get(
+                &router,
+                format!("/api/v1/book?author={}", encode(&new_name)),
+            )
+            .await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            fake_book.author = new_name;
+            assert_eq!(
+                response_body.books,
+                vec![BookSummary::from((book_id, fake_book))]
+            );
+        }
+    );
+
+    sequential_test!(
+        async fn test_merge_authors() {
+            let lib = LibraryWeb::new_test().await;
+            let router = lib.setup_router();
+            let mut ids = vec![];
+            let mut authors = vec![];
+            for _ in 0..2 {
+                let author = Author::create_fake_author().await;
+                let response = post(&router, "/api/v1/author/create", &author).await;
+                assert_eq!(response.status(), 201);
+                ids.push(
+                    deserialize_response_body::<CreatedAuthorBody>(response)
+                        .await
+                        .id,
+                );
+                authors.push(author);
+            }
+            let mut fake_book = Book::create_fake_book(&lib.pool).await;
+            fake_book.author = authors[1].name.clone();
+            let response = post(&router, "/api/v1/book/create", &fake_book).await;
+            assert_eq!(response.status(), 201);
+
+            // same author
+            let request_body = MergeAuthorsBody {
+                keep_id: ids[0],
+                discard_id: ids[0],
+            };
+            let response = post(&router, "/api/v1/author/merge", &request_body).await;
+            assert_eq!(response.status(), 400);
+            // unknown author
+            let request_body = MergeAuthorsBody {
+                keep_id: ids[0],
+                discard_id: Uuid::nil(),
+            };
+            let response = post(&router, "/api/v1/author/merge", &request_body).await;
+            assert_eq!(response.status(), 404);
+
+            let request_body = MergeAuthorsBody {
+                keep_id: ids[0],
+                discard_id: ids[1],
+            };
+            let response = post(&router, "/api/v1/author/merge", &request_body).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<MergedAuthorsBody>(response).await;
+            assert_eq!(response_body.books_reassigned, 1);
+            assert_eq!(response_body.author.name, authors[0].name);
+            assert_eq!(response_body.author.books, Some(vec![fake_book.name]));
+            let response = get(&router, format!("/api/v1/author/{}", ids[1])).await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_author_stats() {
+            let lib = LibraryWeb::new_test().await;
+            let router = lib.clone().setup_router();
+            let author = Author::create_fake_author().await;
+            let response = post(&router, "/api/v1/author/create", &author).await;
+            assert_eq!(response.status(), 201);
+            let author_id = deserialize_response_body::<CreatedAuthorBody>(response)
+                .await
+                .id;
+            let mut books = vec![];
+            for _ in 0..2 {
+                let mut fake_book = Book::create_fake_book(&lib.pool).await;
+                fake_book.author = author.name.clone();
+                let response = post(&router, "/api/v1/book/create", &fake_book).await;
+                assert_eq!(response.status(), 201);
+                books.push(fake_book.name);
+            }
+            let user = User::create_fake_user().await;
+            let response = post(&router, "/api/v1/user/create", &user).await;
+            assert_eq!(response.status(), 201);
+
+            // the first book is rented twice, the second one never
+            rent_book_times(&lib.pool, &user.nation_id, &books[0], 1).await;
+            let rent_book = RentBook {
+                book_name: books[0].clone(),
+                due_date: "2999-01-01".to_owned(),
+            };
+            let uri = format!("/api/v1/user/rent/{}", encode(&user.nation_id));
+            let response = post(&router, uri, &rent_book).await;
+            assert_eq!(response.status(), 201);
+
+            let response = get(&router, format!("/api/v1/author/{author_id}/stats")).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<AuthorStatsBody>(response).await;
+            assert_eq!(
+                response_body.stats,
+                AuthorStats {
+                    total_books: 2,
+                    available_books: 1,
+                    rented_books: 1,
+                    total_rentals_all_time: 2,
+                    most_rented_title: Some(books[0].clone()),
+                }
+            );
+
+            let response = get(&router, format!("/api/v1/author/{}/stats", Uuid::nil())).await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_patch_author_invalid() {
+            let router = LibraryWeb::new_test().await.setup_router();
+            let request_body = Author::create_fake_author().await;
+            let response = post(&router, "/api/v1/author/create", &request_body).await;
+            assert_eq!(response.status(), 201);
+            let response_body = deserialize_response_body::<CreatedAuthorBody>(response).await;
+            let uri = format!("/api/v1/author/{}", response_body.id);
+
+            // empty patch
+            let response = patch(&router, &uri, &AuthorPatch::default()).await;
+            assert_eq!(response.status(), 400);
+
+            // unknown author
+            let patch_body = AuthorPatch {
+                country: Some("Turkey".to_owned()),
+                ..Default::default()
+            };
+            let response = patch(
+                &router,
+                format!("/api/v1/author/{}", Uuid::nil()),
+                &patch_body,
+            )
+            .await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_export_authors_json() {
+            let lib = LibraryWeb::new_test().await;
+            let author = Author::create_fake_author().await;
+            author::insert_author(&lib.pool, DEFAULT_LIBRARY_ID, &author)
+                .await
+                .expect("failed to insert author");
+            let router = lib.setup_router();
+            let response = get(&router, "/api/v1/author/export/json").await;
+            assert_eq!(response.status(), 200);
+            assert_eq!(response.headers()[CONTENT_TYPE], "application/x-ndjson");
+            let bytes = hyper::body::to_bytes(response.into_body())
+                .await
+                .expect("failed to read response body into bytes");
+            let authors = bytes
+                .split(|b| *b == b'\n')
+                .filter(|line| !line.is_empty())
+                .map(|line| {
+                    serde_json::from_slice::<Author>(line).expect("failed to deserialize line")
+                })
+                .collect::<Vec<_>>();
+            let exported = authors
+                .iter()
+                .find(|exported| exported.name == author.name)
+                .expect("author not exported");
+            assert_eq!(exported.country, author.country);
+            assert!(exported.created_at.is_some());
+        }
+    );
+
+    /// Sends a POST request with a newline-delimited JSON body.
+    async fn post_ndjson(
+        router: &axum::Router,
+        uri: &str,
+        content_type: &str,
+        body: String,
+    ) -> hyper::Response<http_body::combinators::UnsyncBoxBody<Bytes, axum::Error>> {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .header(CONTENT_TYPE, content_type)
+            .body(body.into())
+            .expect("failed to build POST request");
+        send_request(router, request).await
     }
+
+    sequential_test!(
+        async fn test_import_authors_json() {
+            let lib = LibraryWeb::new_test().await;
+            let author = Author::create_fake_author().await;
+            let router = lib.setup_router();
+            let body = format!(
+                "{}\n{{\"name\": \"broken\"\n",
+                serde_json::to_string(&author).expect("failed to serialize author")
+            );
+            let response = post_ndjson(
+                &router,
+                "/api/v1/author/import/json",
+                "application/x-ndjson",
+                body,
+            )
+            .await;
+            assert_eq!(response.status(), 207);
+            let response_body = deserialize_response_body::<ImportResultBody>(response).await;
+            assert_eq!(response_body.inserted, 1);
+            assert_eq!(response_body.errors.len(), 1);
+            assert_eq!(response_body.errors[0].row, 2);
+
+            let uri = format!("/api/v1/author?name={}", encode(&author.name));
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<AuthorsBody>(response).await;
+            assert_eq!(response_body.authors[0].country, author.country);
+
+            // importing the same author again fails for that row
+            let body = serde_json::to_string(&author).expect("failed to serialize author");
+            let response = post_ndjson(
+                &router,
+                "/api/v1/author/import/json",
+                "application/x-ndjson",
+                body,
+            )
+            .await;
+            assert_eq!(response.status(), 207);
+            let response_body = deserialize_response_body::<ImportResultBody>(response).await;
+            assert_eq!(response_body.inserted, 0);
+            assert_eq!(response_body.errors[0].row, 1);
+        }
+    );
+
+    sequential_test!(
+        async fn test_import_authors_json_invalid_file() {
+            let lib = LibraryWeb::new_test().await;
+            let author = Author::create_fake_author().await;
+            let router = lib.setup_router();
+            let line = serde_json::to_string(&author).expect("failed to serialize author");
+            let response = post_ndjson(
+                &router,
+                "/api/v1/author/import/json",
+                "application/json",
+                line.clone(),
+            )
+            .await;
+            assert_eq!(response.status(), 400);
+            let body = format!("{line}\n").repeat(MAX_AUTHOR_IMPORT_LINES + 1);
+            let response = post_ndjson(
+                &router,
+                "/api/v1/author/import/json",
+                "application/x-ndjson",
+                body,
+            )
+            .await;
+            assert_eq!(response.status(), 400);
+        }
+    );
+
+    sequential_test!(
+        async fn test_get_author_by_name() {
+            let lib = LibraryWeb::new_test().await;
+            let mut author = Author::create_fake_author().await;
+            // names with reserved characters are URL-encoded
+            author.name = format!("{} & {}/", author.name, rand::random::<u32>());
+            author::insert_author(&lib.pool, DEFAULT_LIBRARY_ID, &author)
+                .await
+                .expect("failed to insert author");
+            let mut book = Book::create_fake_book(&lib.pool).await;
+            book.author = author.name.clone();
+            crate::library::book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+            let router = lib.setup_router();
+
+            let uri = format!("/api/v1/author/by-name/{}", encode(&author.name));
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<GetAuthorBody>(response).await;
+            assert_eq!(response_body.author.name, author.name);
+            assert_eq!(response_body.author.books, Some(vec![book.name]));
+
+            let uri = format!("/api/v1/author/by-name/{}", encode("no such author"));
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_count_authors() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let router = lib.setup_router();
+            for country in ["Narnia", "Narnia", "Oz"] {
+                let request_body = Author {
+                    country: country.to_owned(),
+                    ..Author::create_fake_author().await
+                };
+                let response = post(&router, "/api/v1/author/create", &request_body).await;
+                assert_eq!(response.status(), 201);
+            }
+
+            for (uri, count) in [
+                ("/api/v1/author/count", 3),
+                ("/api/v1/author/count?country=Narnia", 2),
+                ("/api/v1/author/count?country=Atlantis", 0),
+            ] {
+                let response = get(&router, uri).await;
+                assert_eq!(response.status(), 200);
+                let response_body = deserialize_response_body::<CountBody>(response).await;
+                assert_eq!(response_body.count, count, "{uri}");
+            }
+        }
+    );
 }
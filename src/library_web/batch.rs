@@ -0,0 +1,271 @@
+use crate::helper::web::Response;
+use crate::library::error::LibraryError;
+use crate::middleware::library::LIBRARY_ID_HEADER;
+use crate::middleware::role::ROLE_HEADER;
+use crate::telemetry::events::log_success;
+use axum::{
+    body::Body,
+    extract::Extension,
+    http::{
+        header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE},
+        HeaderMap, Method, Request, StatusCode,
+    },
+    response::Json,
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tower::ServiceExt;
+use utoipa::ToSchema;
+
+/// Maximum number of requests in a batch.
+pub const MAX_BATCH_REQUESTS: usize = 10;
+
+/// The path of the batch route, which cannot be called from a batch.
+pub const BATCH_PATH: &str = "/api/v1/batch";
+
+/// Represents a batch of requests sent at once.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BatchRequest {
+    /// The requests, at most 10. They are sent one after the other, in order.
+    pub requests: Vec<SubRequest>,
+}
+
+/// Represents a request of a batch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct SubRequest {
+    /// The HTTP method, e.g. `GET`.
+    pub method: String,
+    /// The path of the route with its query string, e.g. `/api/v1/book?limit=5`.
+    pub path: String,
+    /// The JSON body of the request, if any.
+    #[schema(value_type = Option<Object>)]
+    pub body: Option<Value>,
+}
+
+/// Represents the response to a request of a batch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct SubResponse {
+    pub status: u16,
+    /// The body of the response: its JSON value, `null` when it is empty or
+    /// its text when it is not JSON.
+    #[schema(value_type = Object)]
+    pub body: Value,
+}
+
+/// Represents the body of a response containing the responses of a batch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BatchResponse {
+    /// The responses, in the order of the requests.
+    pub responses: Vec<SubResponse>,
+}
+
+impl BatchRequest {
+    /// Validates the batch before any of its requests is sent.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns a message if there are no requests, more than
+    /// `MAX_BATCH_REQUESTS` requests, or a request whose method is not an
+    /// HTTP method or whose path is not an API path or is the batch route.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.requests.is_empty() {
+            return Err("requests must not be empty".to_owned());
+        }
+        if self.requests.len() > MAX_BATCH_REQUESTS {
+            return Err(format!(
+                "a batch must have at most {MAX_BATCH_REQUESTS} requests"
+            ));
+        }
+        for request in &self.requests {
+            if Method::from_bytes(request.method.to_uppercase().as_bytes()).is_err() {
+                return Err(format!("invalid method {}", request.method));
+            }
+            let path = request.path.split('?').next().unwrap_or_default();
+            if !path.starts_with("/api/") || path.trim_end_matches('/') == BATCH_PATH {
+                return Err(format!("invalid path {}", request.path));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/batch",
+    tag = "batch",
+    request_body = BatchRequest,
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "responses of the requests, whatever their status", body = BatchResponse),
+        (status = 400, description = "Invalid batch", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody),
+        (status = 504, description = "Batch took longer than the request timeout", body = ErrorBody)
+    )
+)]
+pub async fn batch(
+    Extension(api): Extension<Router>,
+    headers: HeaderMap,
+    Json(batch): Json<BatchRequest>,
+) -> Response<BatchResponse> {
+    batch.validate().map_err(LibraryError::Invalid)?;
+    let mut responses = Vec::with_capacity(batch.requests.len());
+    for request in batch.requests {
+        responses.push(send(&api, &headers, request).await?);
+    }
+    let response = BatchResponse { responses };
+    log_success!(
+        StatusCode::OK,
+        "batch",
+        "send",
+        count = response.responses.len()
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Sends a request of a batch through the routes of the API.
+///
+/// The request selects the same library and carries the same credentials and
+/// role as the batch, so it is handled like it was sent on its own.
+async fn send(
+    api: &Router,
+    headers: &HeaderMap,
+    request: SubRequest,
+) -> Result<SubResponse, LibraryError> {
+    let invalid = |err: &dyn std::fmt::Display| LibraryError::Invalid(err.to_string());
+    let mut builder = Request::builder()
+        .method(request.method.to_uppercase().as_str())
+        .uri(&request.path)
+        .header(ACCEPT, "application/json");
+    for name in [LIBRARY_ID_HEADER, ROLE_HEADER, AUTHORIZATION.as_str()] {
+        if let Some(value) = headers.get(name) {
+            builder = builder.header(name, value);
+        }
+    }
+    let body = match request.body {
+        Some(body) => {
+            builder = builder.header(CONTENT_TYPE, "application/json");
+            Body::from(serde_json::to_vec(&body).map_err(|err| invalid(&err))?)
+        }
+        None => Body::empty(),
+    };
+    let request = builder.body(body).map_err(|err| invalid(&err))?;
+
+    // the router never fails, errors are responses
+    let response = api
+        .clone()
+        .oneshot(request)
+        .await
+        .unwrap_or_else(|err| match err {});
+    let status = response.status().as_u16();
+    let bytes = hyper::body::to_bytes(response.into_body())
+        .await
+        .unwrap_or_default();
+    let body = if bytes.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(&bytes)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()))
+    };
+    Ok(SubResponse { status, body })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::helper::web::{ErrorBody, ServerErr};
+    use crate::library::book::Book;
+    use crate::library_web::book::CreatedBookBody;
+    use crate::library_web::tests::{deserialize_response_body, post};
+    use crate::library_web::LibraryWeb;
+    use crate::tests::sequential::sequential_test;
+    use serde_json::json;
+
+    fn sub_request(method: &str, path: &str) -> SubRequest {
+        SubRequest {
+            method: method.to_owned(),
+            path: path.to_owned(),
+            body: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_batch() {
+        let mut batch = BatchRequest {
+            requests: vec![sub_request("get", "/api/v1/book?limit=1")],
+        };
+        assert!(batch.validate().is_ok());
+        batch.requests = vec![];
+        assert!(batch.validate().is_err());
+        batch.requests = vec![sub_request("GET", "/api/v1/book"); MAX_BATCH_REQUESTS + 1];
+        assert!(batch.validate().is_err());
+        batch.requests = vec![sub_request("NOT A METHOD", "/api/v1/book")];
+        assert!(batch.validate().is_err());
+        batch.requests = vec![sub_request("GET", "http://example.com/api/v1/book")];
+        assert!(batch.validate().is_err());
+        batch.requests = vec![sub_request("POST", BATCH_PATH)];
+        assert!(batch.validate().is_err());
+    }
+
+    sequential_test!(
+        async fn test_batch() {
+            let lib = LibraryWeb::new_test().await;
+            let fake_book = Book::create_fake_book(&lib.pool).await;
+            let router = lib.setup_router();
+
+            let batch = BatchRequest {
+                requests: vec![
+                    SubRequest {
+                        method: "POST".to_owned(),
+                        path: "/api/v1/book/create".to_owned(),
+                        body: Some(json!(fake_book)),
+                    },
+                    sub_request("GET", "/api/v1/book/00000000-0000-0000-0000-000000000000"),
+                    sub_request("GET", "/api/v1/book/no-such-route/at-all"),
+                ],
+            };
+            let response = post(&router, BATCH_PATH, &batch).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BatchResponse>(response).await;
+            let statuses: Vec<u16> = response_body
+                .responses
+                .iter()
+                .map(|response| response.status)
+                .collect();
+            assert_eq!(statuses, vec![201, 404, 404]);
+            let created: CreatedBookBody =
+                serde_json::from_value(response_body.responses[0].body.clone())
+                    .expect("failed to deserialize sub-response");
+            assert_eq!(created.info.name, fake_book.name);
+
+            // batches are not nested
+            let batch = BatchRequest {
+                requests: vec![sub_request("POST", BATCH_PATH)],
+            };
+            let response = post(&router, BATCH_PATH, &batch).await;
+            assert_eq!(response.status(), 400);
+        }
+    );
+
+    sequential_test!(
+        async fn test_batch_timeout() {
+            let lib = LibraryWeb::new_test().await;
+            let config = AppConfig {
+                request_timeout_ms: 1,
+                ..AppConfig::default()
+            };
+            let router =
+                LibraryWeb::with_config(lib.pool, config, lib.default_library_id).setup_router();
+            let batch = BatchRequest {
+                requests: vec![sub_request("GET", "/api/v1/book"); MAX_BATCH_REQUESTS],
+            };
+            let response = post(&router, BATCH_PATH, &batch).await;
+            assert_eq!(response.status(), 504);
+            let response_body = deserialize_response_body::<ErrorBody>(response).await;
+            assert_eq!(response_body.error, ServerErr::Timeout);
+        }
+    );
+}
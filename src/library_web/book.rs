@@ -1,12 +1,38 @@
 use super::LibraryWeb;
-use crate::helper::web::{internal_server_error, Response};
-use crate::library::book::{self, Book, BookQuery};
+use crate::helper::pagination::{add_link_headers, base_url, PaginatedResponse};
+use crate::helper::web::{ErrorBody, ErrorResponse, Response, ServerErr};
+use crate::library::book::{
+    self, Book, BookAuthor, BookAvailability, BookCopyRow, BookCsvRecord, BookQuery, BookSummary,
+    BooksPageQuery, DeleteResult, MergeBooksBody, NewArrivalsQuery, SimilarBooksQuery, Status,
+    BOOK_CSV_HEADER, DEFAULT_NEW_ARRIVALS_DAYS, DEFAULT_NEW_ARRIVALS_LIMIT, MAX_BOOK_COPIES,
+    MAX_BOOK_YEAR, MAX_BULK_DELETE_BOOKS, MAX_NEW_ARRIVALS_LIMIT, MIN_BOOK_YEAR,
+};
+use crate::library::error::LibraryError;
+use crate::library::review;
+use crate::library::stats::{self, RentalRateStats, TopQuery, TrendingQuery};
+use crate::library::user::{self, UserHistoryRow};
+use crate::library_web::stats::TopBooksBody;
+use crate::middleware::library::LibraryId;
+use crate::telemetry::events::log_success;
+use async_stream::try_stream;
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::Json,
+    body::{Bytes, StreamBody},
+    extract::{Multipart, OriginalUri, Path, Query, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE},
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Json},
+    BoxError,
 };
+use chrono::{NaiveDate, Utc};
+use futures::{Stream, TryStreamExt};
+use image::{DynamicImage, ImageOutputFormat, Luma};
+use printpdf::{BuiltinFont, Mm, PdfDocument};
+use qrcode::QrCode;
 use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::io::Cursor;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
@@ -20,162 +46,3057 @@ pub struct CreatedBookBody {
 /// Represents the body of a response containing multiple books.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub struct BooksBody {
-    pub books: Vec<Book>,
+    pub books: Vec<BookSummary>,
+    /// The `after` cursor of the next page, set when paginating with a cursor
+    /// and more books may follow.
+    pub next_cursor: Option<Uuid>,
+    /// The total number of matching books, set when listing the books of a
+    /// category.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<i64>,
+}
+
+/// Represents the body of a response containing the number of matching
+/// books, authors or users.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct CountBody {
+    pub count: i64,
 }
 
-/// Represents the body of a response containing a single book.
+/// Represents the body of a response when a book deleted or restored.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BookMessageBody {
+    pub message: String,
+    pub id: Uuid,
+}
+
+/// Represents the body of a response containing a single book and its rating.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct GetBookBody {
     pub book: Book,
+    pub average_rating: Option<f64>,
+    pub review_count: i64,
 }
 
 #[utoipa::path(
     post,
-    path = "/api/book/create",
+    path = "/api/v1/book/create",
     tag = "book",
     request_body = Book,
+    security(
+        ("BearerAuth" = [])
+    ),
     responses(
         (status = 201, description = "book created succesfully", body = CreatedBookBody),
-        (status = 500, description = "Internal server error", body = String)
+        (status = 400, description = "Invalid book", body = ErrorBody),
+        (status = 409, description = "Book already exists", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
     )
 )]
 pub async fn create_book(
     State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
     Json(book): Json<Book>,
 ) -> Response<CreatedBookBody> {
-    let Ok(book_id) = book::insert_book(&library_web.pool, &book).await else {
-        return internal_server_error().await;
-    };
+    book.validate().map_err(LibraryError::Invalid)?;
+    let book_id = book::insert_book(&library_web.pool, &library_id, &book).await?;
     let response = CreatedBookBody {
         info: book,
         id: book_id,
     };
-    (StatusCode::CREATED, Ok(Json(response)))
+    log_success!(StatusCode::CREATED, "book", "create", book_id = %book_id);
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Represents the body of a request to register multiple copies of a book.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct CreateCopiesBody {
+    pub book: Book,
+    /// The number of copies to register, between 1 and `MAX_BOOK_COPIES`.
+    pub copies: i32,
+}
+
+/// Represents the body of a response when multiple copies of a book are created.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct CreatedCopiesBody {
+    pub info: Book,
+    /// The IDs of the copies, ordered by copy number.
+    pub ids: Vec<Uuid>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/book/multi-copy",
+    tag = "book",
+    request_body = CreateCopiesBody,
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 201, description = "book copies created succesfully", body = CreatedCopiesBody),
+        (status = 400, description = "Invalid book or number of copies", body = ErrorBody),
+        (status = 409, description = "Book already exists", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn create_book_copies(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Json(body): Json<CreateCopiesBody>,
+) -> Response<CreatedCopiesBody> {
+    body.book.validate().map_err(LibraryError::Invalid)?;
+    if !(1..=MAX_BOOK_COPIES).contains(&body.copies) {
+        return Err(LibraryError::Invalid(format!(
+            "copies must be between 1 and {MAX_BOOK_COPIES}"
+        ))
+        .into());
+    }
+    let ids =
+        book::insert_book_copies(&library_web.pool, &library_id, &body.book, body.copies).await?;
+    let response = CreatedCopiesBody {
+        info: body.book,
+        ids,
+    };
+    log_success!(
+        StatusCode::CREATED,
+        "book",
+        "create_copies",
+        count = response.ids.len()
+    );
+    Ok((StatusCode::CREATED, Json(response)))
 }
 
 #[utoipa::path(
     get,
-    path = "/api/book",
+    path = "/api/v1/book",
     tag = "book",
     params(
         BookQuery
     ),
     responses(
-        (status = 200, description = "list matching books", body = BooksBody),
-        (status = 500, description = "Internal server error", body = String)
+        (status = 200, description = "list matching books", body = BooksBody,
+            headers(("Link" = String, description = "Links to the first, previous, next and last pages, set when paginating with `limit` and `offset`"))),
+        (status = 404, description = "No book found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
     )
 )]
 pub async fn books(
     State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    Query(book): Query<BookQuery>,
+) -> PaginatedResponse<BooksBody> {
+    let result = book::books(&library_web.pool, &library_id, &book).await;
+    let (status, response) = books_response(result, &book)?;
+    let mut response_headers = HeaderMap::new();
+    // a cursor has no previous or last page
+    if let (Some(limit), None) = (book.limit_or_default(), book.after) {
+        let total = book::count_books(&library_web.pool, &library_id, &book).await?;
+        add_link_headers(
+            &mut response_headers,
+            &base_url(&headers, &uri),
+            limit,
+            book.offset_or_default(),
+            total,
+        );
+    }
+    log_success!(status, "book", "list", count = response.books.len());
+    Ok((status, response_headers, response))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/count",
+    tag = "book",
+    params(
+        BookQuery
+    ),
+    responses(
+        (status = 200, description = "number of matching books, pagination is ignored", body = CountBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn count_books(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
     Query(book): Query<BookQuery>,
+) -> Response<CountBody> {
+    let count = book::count_books(&library_web.pool, &library_id, &book).await?;
+    let response = CountBody { count };
+    log_success!(StatusCode::OK, "book", "count", count);
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/available",
+    tag = "book",
+    params(
+        BookQuery
+    ),
+    responses(
+        (status = 200, description = "list available books", body = BooksBody),
+        (status = 404, description = "No book found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn available_books(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Query(mut book): Query<BookQuery>,
+) -> Response<BooksBody> {
+    book.status = Some(Status::Available);
+    let result = book::books(&library_web.pool, &library_id, &book).await;
+    let (status, response) = books_response(result, &book)?;
+    log_success!(
+        status,
+        "book",
+        "list_available",
+        count = response.books.len()
+    );
+    Ok((status, response))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/rented",
+    tag = "book",
+    params(
+        BookQuery
+    ),
+    responses(
+        (status = 200, description = "list rented books", body = BooksBody),
+        (status = 404, description = "No book found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn rented_books(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Query(mut book): Query<BookQuery>,
+) -> Response<BooksBody> {
+    book.status = Some(Status::Rented);
+    let result = book::books(&library_web.pool, &library_id, &book).await;
+    let (status, response) = books_response(result, &book)?;
+    log_success!(status, "book", "list_rented", count = response.books.len());
+    Ok((status, response))
+}
+
+/// Builds the response listing the books returned for `query`.
+fn books_response(
+    result: Result<Vec<BookSummary>, LibraryError>,
+    query: &BookQuery,
+) -> Response<BooksBody> {
+    let books = match result {
+        Ok(books) => books,
+        // the page after the last book is empty
+        Err(LibraryError::NotFound) if query.after.is_some() => vec![],
+        Err(err) => return Err(err.into()),
+    };
+    let next_cursor = query.next_cursor(&books);
+    let response = BooksBody {
+        books,
+        next_cursor,
+        total: None,
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/category/{category}",
+    tag = "book",
+    params(
+        ("category"= String, Path,),
+        BooksPageQuery
+    ),
+    responses(
+        (status = 200, description = "list books of the category", body = BooksBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn books_by_category(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(category): Path<String>,
+    Query(query): Query<BooksPageQuery>,
+) -> Response<BooksBody> {
+    let (books, total) = book::books_by_category(
+        &library_web.pool,
+        &library_id,
+        &category,
+        query.limit_or_default(),
+        query.offset_or_default(),
+    )
+    .await?;
+    let response = BooksBody {
+        books,
+        next_cursor: None,
+        total: Some(total),
+    };
+    log_success!(
+        StatusCode::OK,
+        "book",
+        "list_by_category",
+        count = response.books.len()
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/author/{author_name}",
+    tag = "book",
+    params(
+        ("author_name"= String, Path, description = "URL-encoded name of the author"),
+        BooksPageQuery
+    ),
+    responses(
+        (status = 200, description = "list books of the author", body = BooksBody),
+        (status = 404, description = "Author not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn books_by_author_name(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(author_name): Path<String>,
+    Query(query): Query<BooksPageQuery>,
+) -> Response<BooksBody> {
+    let (books, total) = book::books_by_author_name(
+        &library_web.pool,
+        &library_id,
+        &author_name,
+        query.limit_or_default(),
+        query.offset_or_default(),
+    )
+    .await?;
+    let response = BooksBody {
+        books,
+        next_cursor: None,
+        total: Some(total),
+    };
+    log_success!(
+        StatusCode::OK,
+        "book",
+        "list_by_author",
+        count = response.books.len()
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/year/{year}",
+    tag = "book",
+    params(
+        ("year"= i32, Path, description = "Publication year, between 1000 and 2100"),
+        BooksPageQuery
+    ),
+    responses(
+        (status = 200, description = "list books published in the year", body = BooksBody),
+        (status = 400, description = "Invalid year", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn books_by_year(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(year): Path<i32>,
+    Query(query): Query<BooksPageQuery>,
 ) -> Response<BooksBody> {
-    let Ok(books) = book::books(&library_web.pool, &book).await else {
-        return internal_server_error().await;
+    if !(MIN_BOOK_YEAR..=MAX_BOOK_YEAR).contains(&year) {
+        return Err(LibraryError::Invalid(format!(
+            "year must be between {MIN_BOOK_YEAR} and {MAX_BOOK_YEAR}"
+        ))
+        .into());
+    }
+    let (books, total) = book::books_by_year(
+        &library_web.pool,
+        &library_id,
+        year,
+        query.limit_or_default(),
+        query.offset_or_default(),
+    )
+    .await?;
+    let response = BooksBody {
+        books,
+        next_cursor: None,
+        total: Some(total),
     };
-    let response = BooksBody { books };
-    (StatusCode::OK, Ok(Json(response)))
+    log_success!(
+        StatusCode::OK,
+        "book",
+        "list_by_year",
+        year,
+        count = response.books.len()
+    );
+    Ok((StatusCode::OK, Json(response)))
 }
 
 #[utoipa::path(
     get,
-    path = "/api/book/{book_id}",
+    path = "/api/v1/book/{book_id}",
     tag = "book",
     params(
         ("book_id"= Uuid, Path,),
     ),
     responses(
         (status = 200, description = "list book", body = GetBookBody),
-        (status = 500, description = "Internal server error", body = String)
+        (status = 404, description = "Book not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
     )
 )]
 pub async fn get_book(
     State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
     Path(book_id): Path<Uuid>,
 ) -> Response<GetBookBody> {
-    let book = match book::get_book(&library_web.pool, book_id).await {
-        Ok(book) => book,
-        Err(_) => return internal_server_error().await,
+    let book = book::get_book(&library_web.pool, &library_id, book_id).await?;
+    let rating = review::book_rating(&library_web.pool, &library_id, &book.name).await?;
+    let response = GetBookBody {
+        book,
+        average_rating: rating.average_rating,
+        review_count: rating.review_count,
     };
-    let response = GetBookBody { book };
-    (StatusCode::OK, Ok(Json(response)))
+    log_success!(StatusCode::OK, "book", "get", book_id = %book_id);
+    Ok((StatusCode::OK, Json(response)))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::library_web::tests::{deserialize_response_body, get, post};
-    use urlencoding::encode;
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/by-name/{name}",
+    tag = "book",
+    params(
+        ("name"= String, Path, description = "URL-encoded name of the book"),
+    ),
+    responses(
+        (status = 200, description = "list book", body = GetBookBody),
+        (status = 404, description = "Book not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn get_book_by_name(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(name): Path<String>,
+) -> Response<GetBookBody> {
+    let book = book::get_book_by_name(&library_web.pool, &library_id, &name).await?;
+    let rating = review::book_rating(&library_web.pool, &library_id, &book.name).await?;
+    let response = GetBookBody {
+        book,
+        average_rating: rating.average_rating,
+        review_count: rating.review_count,
+    };
+    log_success!(StatusCode::OK, "book", "get_by_name", name = %name);
+    Ok((StatusCode::OK, Json(response)))
+}
 
-    async fn concurrency_create_book(router: axum::Router, book: Book) -> StatusCode {
-        let response = post(&router, "/api/book/create", &book).await;
-        response.status()
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/isbn/{isbn}",
+    tag = "book",
+    params(
+        ("isbn"= String, Path, description = "ISBN-10 or ISBN-13 of the book, hyphens are allowed"),
+    ),
+    responses(
+        (status = 200, description = "list book", body = GetBookBody),
+        (status = 400, description = "Invalid ISBN check digit", body = ErrorBody),
+        (status = 404, description = "Book not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn get_book_by_isbn(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(isbn): Path<String>,
+) -> Response<GetBookBody> {
+    if !book::is_valid_isbn(&isbn) {
+        let reason = "isbn must be a valid ISBN-10 or ISBN-13".to_owned();
+        return Err(LibraryError::Invalid(reason).into());
     }
+    let book = book::get_book_by_isbn(&library_web.pool, &library_id, &isbn).await?;
+    let rating = review::book_rating(&library_web.pool, &library_id, &book.name).await?;
+    let response = GetBookBody {
+        book,
+        average_rating: rating.average_rating,
+        review_count: rating.review_count,
+    };
+    log_success!(StatusCode::OK, "book", "get_by_isbn", isbn = %isbn);
+    Ok((StatusCode::OK, Json(response)))
+}
 
-    #[tokio::test]
-    async fn test_concurrency_create_book() {
-        let lib = LibraryWeb::new_test().await;
-        let request_body = Book::create_fake_book(&lib.pool).await;
-        let router = lib.setup_router();
-        let fut_a = concurrency_create_book(router.clone(), request_body.clone());
-        let fut_b = concurrency_create_book(router.clone(), request_body.clone());
-        let (status_a, status_b) = tokio::join!(fut_a, fut_b);
-        assert_eq!(status_a.min(status_b), 201, "should succeed");
-        assert_eq!(status_a.max(status_b), 500, "should fail");
-    }
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/random",
+    tag = "book",
+    responses(
+        (status = 200, description = "random available book", body = GetBookBody),
+        (status = 404, description = "No book available", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn random_book(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+) -> Response<GetBookBody> {
+    let book = book::random_book(&library_web.pool, &library_id).await?;
+    let rating = review::book_rating(&library_web.pool, &library_id, &book.name).await?;
+    let response = GetBookBody {
+        book,
+        average_rating: rating.average_rating,
+        review_count: rating.review_count,
+    };
+    log_success!(StatusCode::OK, "book", "random", book_id = %response.book.id);
+    Ok((StatusCode::OK, Json(response)))
+}
 
-    #[tokio::test]
-    async fn test_create_book_and_get_book() {
-        let lib = LibraryWeb::new_test().await;
-        let request_body = Book::create_fake_book(&lib.pool).await;
-        let router = lib.setup_router();
-        let response = post(&router, "/api/book/create", &request_body).await;
-        assert_eq!(response.status(), 201);
+/// Represents the body of a response containing the availability of a book.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BookAvailabilityBody {
+    pub availability: BookAvailability,
+}
 
-        let response_body = deserialize_response_body::<CreatedBookBody>(response).await;
-        assert_eq!(response_body.info, request_body);
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/{book_id}/availability",
+    tag = "book",
+    params(
+        ("book_id"= Uuid, Path,),
+    ),
+    responses(
+        (status = 200, description = "availability of the book", body = BookAvailabilityBody),
+        (status = 404, description = "Book not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn book_availability(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(book_id): Path<Uuid>,
+) -> Response<BookAvailabilityBody> {
+    let availability = book::book_availability(&library_web.pool, &library_id, book_id).await?;
+    let response = BookAvailabilityBody { availability };
+    log_success!(StatusCode::OK, "book", "availability", book_id = %book_id);
+    Ok((StatusCode::OK, Json(response)))
+}
 
-        let uri = format!("/api/book/{}", response_body.id);
-        let response = get(&router, uri).await;
-        assert_eq!(response.status(), 200);
+/// Represents the body of a response containing the copies of a book.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BookCopiesBody {
+    pub copies: Vec<BookCopyRow>,
+}
 
-        let response_body = deserialize_response_body::<GetBookBody>(response).await;
-        assert_eq!(response_body.book.name, request_body.name);
-    }
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/{book_id}/copies",
+    tag = "book",
+    params(
+        ("book_id"= Uuid, Path,),
+    ),
+    responses(
+        (status = 200, description = "list the copies of the book", body = BookCopiesBody),
+        (status = 404, description = "Book not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn book_copies(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(book_id): Path<Uuid>,
+) -> Response<BookCopiesBody> {
+    let book = book::get_book(&library_web.pool, &library_id, book_id).await?;
+    let copies = book::book_copies(&library_web.pool, &library_id, &book.name).await?;
+    let response = BookCopiesBody { copies };
+    log_success!(StatusCode::OK, "book", "copies", book_id = %book_id);
+    Ok((StatusCode::OK, Json(response)))
+}
 
-    #[tokio::test]
-    async fn test_books() {
-        let lib = LibraryWeb::new_test().await;
-        let request_body = Book::create_fake_book(&lib.pool).await;
-        let router = lib.setup_router();
-
-        let response = post(&router, "/api/book/create", &request_body).await;
-        assert_eq!(response.status(), 201);
-        let response_body_created = deserialize_response_body::<CreatedBookBody>(response).await;
-        assert_eq!(response_body_created.info, request_body);
-
-        let response = get(&router, "/api/book").await;
-        assert_eq!(response.status(), 200);
-        let response_body = deserialize_response_body::<BooksBody>(response).await;
-        assert!(response_body.books.contains(&request_body));
-
-        let uri = format!(
-            "/api/book?name={}",
-            encode(&response_body_created.info.name)
-        );
-        let response = get(&router, uri).await;
-        assert_eq!(response.status(), 200);
-        let response_body = deserialize_response_body::<BooksBody>(response).await;
-        assert_eq!(response_body.books[0].name, request_body.name);
-
-        let uri = format!(
-            "/api/book?name={}&author={}",
-            encode(&response_body_created.info.name),
-            encode(&response_body_created.info.author),
-        );
-        let response = get(&router, uri).await;
-        assert_eq!(response.status(), 200);
-        let response_body = deserialize_response_body::<BooksBody>(response).await;
-        assert_eq!(response_body.books[0].name, request_body.name);
-        assert_eq!(response_body.books[0].author, request_body.author);
-    }
+/// Represents the body of a response containing the current rental of a
+/// copy of a book.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct CurrentRenterBody {
+    pub renter: Option<UserHistoryRow>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/{book_id}/current-renter",
+    tag = "book",
+    params(
+        ("book_id"= Uuid, Path,),
+    ),
+    responses(
+        (status = 200, description = "get the current rental of the copy, null if it is not rented", body = CurrentRenterBody),
+        (status = 404, description = "Book not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn current_renter(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(book_id): Path<Uuid>,
+) -> Response<CurrentRenterBody> {
+    book::get_book(&library_web.pool, &library_id, book_id).await?;
+    let renter = user::current_renter(&library_web.pool, &library_id, book_id).await?;
+    let response = CurrentRenterBody { renter };
+    log_success!(
+        StatusCode::OK,
+        "book",
+        "current_renter",
+        book_id = %book_id,
+        rented = response.renter.is_some()
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Represents the body of a response containing how often a book is rented.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RentalRateBody {
+    pub stats: RentalRateStats,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/{book_id}/rental-rate",
+    tag = "book",
+    params(
+        ("book_id"= Uuid, Path,),
+    ),
+    responses(
+        (status = 200, description = "get how often the book is rented, all its copies included", body = RentalRateBody),
+        (status = 404, description = "Book not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn rental_rate(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(book_id): Path<Uuid>,
+) -> Response<RentalRateBody> {
+    let book = book::get_book(&library_web.pool, &library_id, book_id).await?;
+    let stats = stats::rental_rate(&library_web.pool, &library_id, &book.name).await?;
+    let response = RentalRateBody { stats };
+    log_success!(
+        StatusCode::OK,
+        "book",
+        "rental_rate",
+        book_id = %book_id,
+        total_rentals = response.stats.total_rentals
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Encodes `data` in a QR code, returned as a PNG image.
+fn qr_code_png(data: &str) -> Result<Vec<u8>, String> {
+    let code = QrCode::new(data.as_bytes()).map_err(|err| err.to_string())?;
+    let image = code.render::<Luma<u8>>().build();
+    let mut png = Vec::new();
+    DynamicImage::ImageLuma8(image)
+        .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+        .map_err(|err| err.to_string())?;
+    Ok(png)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/{book_id}/qr-code",
+    tag = "book",
+    params(
+        ("book_id"= Uuid, Path,),
+    ),
+    responses(
+        (status = 200, description = "PNG image of a QR code encoding the path of the book, `/api/book/{book_id}`",
+            body = String, content_type = "image/png"),
+        (status = 404, description = "Book not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn book_qr_code(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(book_id): Path<Uuid>,
+) -> Result<axum::response::Response, ErrorResponse> {
+    book::get_book(&library_web.pool, &library_id, book_id).await?;
+    // the unversioned path keeps the printed labels valid across versions
+    let png = qr_code_png(&format!("/api/book/{book_id}")).map_err(|err| {
+        tracing::error!(error = %err, "failed to generate QR code");
+        let body = ErrorBody {
+            error: ServerErr::Internal,
+            message: None,
+        };
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(body))
+    })?;
+    log_success!(StatusCode::OK, "book", "qr_code", book_id = %book_id);
+    let headers = [
+        (CONTENT_TYPE, "image/png"),
+        (CACHE_CONTROL, "max-age=86400"),
+    ];
+    Ok((headers, png).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/{book_id}/similar",
+    tag = "book",
+    params(
+        ("book_id"= Uuid, Path,),
+        SimilarBooksQuery
+    ),
+    responses(
+        (status = 200, description = "list books sharing the category or the author of the book", body = BooksBody),
+        (status = 404, description = "Book not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn similar_books(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(book_id): Path<Uuid>,
+    Query(query): Query<SimilarBooksQuery>,
+) -> Response<BooksBody> {
+    book::get_book(&library_web.pool, &library_id, book_id).await?;
+    let books = book::similar_books(
+        &library_web.pool,
+        &library_id,
+        book_id,
+        query.limit_or_default(),
+    )
+    .await?;
+    let response = BooksBody {
+        books,
+        next_cursor: None,
+        total: None,
+    };
+    log_success!(
+        StatusCode::OK,
+        "book",
+        "similar",
+        book_id = %book_id,
+        count = response.books.len()
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/book/{book_id}",
+    tag = "book",
+    params(
+        ("book_id"= Uuid, Path,),
+    ),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "book deleted succesfully", body = BookMessageBody),
+        (status = 404, description = "Book not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn delete_book(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(book_id): Path<Uuid>,
+) -> Response<BookMessageBody> {
+    book::delete_book(&library_web.pool, &library_id, book_id).await?;
+    let response = BookMessageBody {
+        message: "successfully book deleted".to_owned(),
+        id: book_id,
+    };
+    log_success!(StatusCode::OK, "book", "delete", book_id = %book_id);
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Represents the body of a request to delete several books.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BulkDeleteRequest {
+    /// The IDs of the books to delete, between 1 and `MAX_BULK_DELETE_BOOKS`.
+    pub ids: Vec<Uuid>,
+}
+
+/// Represents the body of a response when several books are deleted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BulkDeleteBody {
+    pub results: Vec<DeleteResult>,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/book/bulk",
+    tag = "book",
+    request_body = BulkDeleteRequest,
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 207, description = "outcome of the deletion of each book", body = BulkDeleteBody),
+        (status = 400, description = "Invalid number of books", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn delete_books(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Json(body): Json<BulkDeleteRequest>,
+) -> Response<BulkDeleteBody> {
+    if !(1..=MAX_BULK_DELETE_BOOKS).contains(&body.ids.len()) {
+        return Err(LibraryError::Invalid(format!(
+            "ids must contain between 1 and {MAX_BULK_DELETE_BOOKS} books"
+        ))
+        .into());
+    }
+    let results = book::delete_books(&library_web.pool, &library_id, &body.ids).await?;
+    let response = BulkDeleteBody { results };
+    log_success!(
+        StatusCode::MULTI_STATUS,
+        "book",
+        "bulk_delete",
+        count = response.results.len()
+    );
+    Ok((StatusCode::MULTI_STATUS, Json(response)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/book/{book_id}/restore",
+    tag = "book",
+    params(
+        ("book_id"= Uuid, Path,),
+    ),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "book restored succesfully", body = BookMessageBody),
+        (status = 404, description = "Book not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn restore_book(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(book_id): Path<Uuid>,
+) -> Response<BookMessageBody> {
+    book::restore_book(&library_web.pool, &library_id, book_id).await?;
+    let response = BookMessageBody {
+        message: "successfully book restored".to_owned(),
+        id: book_id,
+    };
+    log_success!(StatusCode::OK, "book", "restore", book_id = %book_id);
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/book/{book_id}/author",
+    tag = "book",
+    request_body = BookAuthor,
+    params(
+        ("book_id"= Uuid, Path,),
+    ),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "book author reassigned succesfully", body = BookMessageBody),
+        (status = 400, description = "Invalid author", body = ErrorBody),
+        (status = 404, description = "Book or author not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn reassign_book_author(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(book_id): Path<Uuid>,
+    Json(body): Json<BookAuthor>,
+) -> Response<BookMessageBody> {
+    if body.author.trim().is_empty() {
+        return Err(LibraryError::Invalid("author must not be empty".to_owned()).into());
+    }
+    book::reassign_book_author(&library_web.pool, &library_id, book_id, &body.author).await?;
+    let response = BookMessageBody {
+        message: "successfully book author reassigned".to_owned(),
+        id: book_id,
+    };
+    log_success!(StatusCode::OK, "book", "reassign_author", book_id = %book_id);
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Represents the body of a response when two books merged.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct MergedBooksBody {
+    pub book: Book,
+    pub rentals_moved: u64,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/book/merge",
+    tag = "book",
+    request_body = MergeBooksBody,
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "books merged succesfully, returns the kept book", body = MergedBooksBody),
+        (status = 400, description = "Both books are the same", body = ErrorBody),
+        (status = 403, description = "Discarded book is rented", body = ErrorBody),
+        (status = 404, description = "Book not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn merge_books(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Json(body): Json<MergeBooksBody>,
+) -> Response<MergedBooksBody> {
+    let rentals_moved = book::merge_books(
+        &library_web.pool,
+        &library_id,
+        body.keep_id,
+        body.discard_id,
+    )
+    .await?;
+    let book = book::get_book(&library_web.pool, &library_id, body.keep_id).await?;
+    let response = MergedBooksBody {
+        book,
+        rentals_moved,
+    };
+    log_success!(
+        StatusCode::OK,
+        "book",
+        "merge",
+        keep_id = %body.keep_id,
+        discard_id = %body.discard_id,
+        rentals_moved
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Represents the body of a response containing recently added books.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct NewArrivalsBody {
+    pub books: Vec<Book>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/new-arrivals",
+    tag = "book",
+    params(
+        NewArrivalsQuery
+    ),
+    responses(
+        (status = 200, description = "list recently added books", body = NewArrivalsBody),
+        (status = 400, description = "Invalid query", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn new_arrivals(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Query(query): Query<NewArrivalsQuery>,
+) -> Response<NewArrivalsBody> {
+    query.validate().map_err(LibraryError::Invalid)?;
+    let days = query.days.unwrap_or(DEFAULT_NEW_ARRIVALS_DAYS);
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_NEW_ARRIVALS_LIMIT)
+        .clamp(1, MAX_NEW_ARRIVALS_LIMIT);
+    let books = book::new_arrivals(&library_web.pool, &library_id, days, limit).await?;
+    let response = NewArrivalsBody { books };
+    log_success!(
+        StatusCode::OK,
+        "book",
+        "new_arrivals",
+        count = response.books.len()
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/popular-this-month",
+    tag = "book",
+    params(
+        TopQuery
+    ),
+    responses(
+        (status = 200, description = "list most rented books of the current month", body = TopBooksBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn popular_books_this_month(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Query(query): Query<TopQuery>,
+) -> Response<TopBooksBody> {
+    let books =
+        stats::popular_books_this_month(&library_web.pool, &library_id, query.limit_or_default())
+            .await?;
+    let response = TopBooksBody { books };
+    log_success!(
+        StatusCode::OK,
+        "book",
+        "popular_this_month",
+        count = response.books.len()
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/trending",
+    tag = "book",
+    params(
+        TrendingQuery
+    ),
+    responses(
+        (status = 200, description = "list most rented books of the last hours", body = TopBooksBody),
+        (status = 400, description = "Invalid number of hours", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn trending_books(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Query(query): Query<TrendingQuery>,
+) -> Response<TopBooksBody> {
+    query.validate().map_err(LibraryError::Invalid)?;
+    let books = stats::trending_books(
+        &library_web.pool,
+        &library_id,
+        query.hours_or_default(),
+        query.limit_or_default(),
+    )
+    .await?;
+    let response = TopBooksBody { books };
+    log_success!(
+        StatusCode::OK,
+        "book",
+        "trending",
+        count = response.books.len()
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/deleted",
+    tag = "book",
+    responses(
+        (status = 200, description = "list deleted books", body = BooksBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn deleted_books(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+) -> Response<BooksBody> {
+    let books = book::deleted_books(&library_web.pool, &library_id).await?;
+    let response = BooksBody {
+        books,
+        next_cursor: None,
+        total: None,
+    };
+    log_success!(
+        StatusCode::OK,
+        "book",
+        "list_deleted",
+        count = response.books.len()
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Represents the form of a request to import books from a CSV file.
+#[derive(Debug, ToSchema)]
+#[allow(dead_code)]
+pub struct ImportBooksForm {
+    /// A CSV file with the columns `name,year,category,status,author,isbn`.
+    #[schema(value_type = String, format = Binary)]
+    pub file: Vec<u8>,
+}
+
+/// Represents a row of an imported CSV file that could not be inserted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ImportError {
+    /// The line of the file the row starts on, the header is line 1.
+    pub row: usize,
+    pub reason: String,
+}
+
+/// Represents the body of a response to a CSV import.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ImportResultBody {
+    pub inserted: usize,
+    pub errors: Vec<ImportError>,
+}
+
+/// Returns the line a CSV record or error starts on.
+fn csv_row(position: Option<&csv::Position>) -> usize {
+    position.map_or(0, |position| position.line() as usize)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/book/import/csv",
+    tag = "book",
+    request_body(content = ImportBooksForm, content_type = "multipart/form-data"),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "all books imported succesfully", body = ImportResultBody),
+        (status = 207, description = "some books could not be imported", body = ImportResultBody),
+        (status = 400, description = "Invalid file", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn import_books_csv(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    mut multipart: Multipart,
+) -> Response<ImportResultBody> {
+    let file = loop {
+        match multipart.next_field().await {
+            Ok(Some(field)) if field.name() == Some("file") => match field.bytes().await {
+                Ok(file) => break file,
+                Err(_) => {
+                    return Err(LibraryError::Invalid("invalid multipart body".to_owned()).into())
+                }
+            },
+            Ok(Some(_)) => continue,
+            Ok(None) => return Err(LibraryError::Invalid("missing file field".to_owned()).into()),
+            Err(_) => return Err(LibraryError::Invalid("invalid multipart body".to_owned()).into()),
+        }
+    };
+    let mut reader = csv::Reader::from_reader(file.as_ref());
+    let headers = match reader.headers() {
+        Ok(headers) if headers.iter().eq(BOOK_CSV_HEADER) => headers.clone(),
+        _ => {
+            let reason = format!("CSV header must be {}", BOOK_CSV_HEADER.join(","));
+            return Err(LibraryError::Invalid(reason).into());
+        }
+    };
+
+    let mut errors = Vec::new();
+    let mut rows = Vec::new();
+    let mut books = Vec::new();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                errors.push(ImportError {
+                    row: csv_row(err.position()),
+                    reason: err.to_string(),
+                });
+                continue;
+            }
+        };
+        let row = csv_row(record.position());
+        let book = match record.deserialize::<BookCsvRecord>(Some(&headers)) {
+            Ok(record) => Book::from(record),
+            Err(err) => {
+                let reason = match err.kind() {
+                    csv::ErrorKind::Deserialize { err, .. } => err.to_string(),
+                    _ => err.to_string(),
+                };
+                errors.push(ImportError { row, reason });
+                continue;
+            }
+        };
+        if let Err(reason) = book.validate() {
+            errors.push(ImportError { row, reason });
+            continue;
+        }
+        rows.push(row);
+        books.push(book);
+    }
+
+    let results = book::insert_books(&library_web.pool, &library_id, &books).await?;
+    let mut inserted = 0;
+    for (row, result) in rows.into_iter().zip(results) {
+        let reason = match result {
+            Ok(_) => {
+                inserted += 1;
+                continue;
+            }
+            Err(LibraryError::Conflict(_)) => {
+                "a book with the same name or isbn already exists".to_owned()
+            }
+            Err(LibraryError::DatabaseError(sqlx::Error::Database(err))) => {
+                match err.code().as_deref() {
+                    Some("23503") => "author does not exist".to_owned(),
+                    _ => err.message().to_owned(),
+                }
+            }
+            Err(err) => return Err(err.into()),
+        };
+        errors.push(ImportError { row, reason });
+    }
+    errors.sort_by_key(|error| error.row);
+
+    let status = if errors.is_empty() {
+        StatusCode::OK
+    } else {
+        StatusCode::MULTI_STATUS
+    };
+    let response = ImportResultBody { inserted, errors };
+    log_success!(status, "book", "import", inserted = response.inserted);
+    Ok((status, Json(response)))
+}
+
+/// Serializes a record as a single CSV line.
+fn csv_line(record: impl Serialize) -> Result<Bytes, csv::Error> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(vec![]);
+    writer.serialize(record)?;
+    let line = writer.into_inner().map_err(|err| err.into_error())?;
+    Ok(Bytes::from(line))
+}
+
+/// Streams the books matching the query as CSV lines, header first.
+fn books_csv_stream(
+    pool: PgPool,
+    library_id: String,
+    query: BookQuery,
+) -> impl Stream<Item = Result<Bytes, BoxError>> + Send + 'static {
+    try_stream! {
+        yield csv_line(BOOK_CSV_HEADER)?;
+        let records = book::book_csv_records(pool, library_id, query);
+        futures::pin_mut!(records);
+        while let Some(record) = records.try_next().await? {
+            yield csv_line(record)?;
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/export/csv",
+    tag = "book",
+    params(
+        BookQuery
+    ),
+    responses(
+        (status = 200, description = "CSV file of the matching books, with the same columns as the import",
+            body = String, content_type = "text/csv")
+    )
+)]
+pub async fn export_books_csv(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Query(query): Query<BookQuery>,
+) -> axum::response::Response {
+    let body = StreamBody::new(books_csv_stream(library_web.pool, library_id, query));
+    let headers = [
+        (CONTENT_TYPE, "text/csv"),
+        (CONTENT_DISPOSITION, "attachment; filename=\"books.csv\""),
+    ];
+    (headers, body).into_response()
+}
+
+/// The columns of the catalogue report, with their position on the page in
+/// millimeters and the maximum number of characters they show.
+const CATALOGUE_COLUMNS: [(&str, f64, usize); 5] = [
+    ("Name", 10.0, 34),
+    ("Author", 75.0, 26),
+    ("Year", 125.0, 6),
+    ("Status", 140.0, 12),
+    ("Category", 162.0, 22),
+];
+
+/// Truncates `text` to `max_chars` characters, ending with `...` when it is
+/// cut.
+fn truncate_cell(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_owned();
+    }
+    let mut cell: String = text.chars().take(max_chars.saturating_sub(3)).collect();
+    cell.push_str("...");
+    cell
+}
+
+/// Renders the catalogue of a library as an A4 PDF: a header with the library
+/// and the date, followed by a table of the books continued on as many pages
+/// as needed.
+fn catalogue_pdf(
+    library_id: &str,
+    date: NaiveDate,
+    records: &[BookCsvRecord],
+) -> Result<Vec<u8>, printpdf::Error> {
+    const PAGE_WIDTH: f64 = 210.0;
+    const PAGE_HEIGHT: f64 = 297.0;
+    const MARGIN: f64 = 15.0;
+    const ROW_HEIGHT: f64 = 6.0;
+
+    let title = format!("Catalogue of the library {library_id}");
+    let (doc, page, layer) = PdfDocument::new(&title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica)?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold)?;
+
+    let mut layer = doc.get_page(page).get_layer(layer);
+    layer.use_text(&title, 16.0, Mm(10.0), Mm(PAGE_HEIGHT - MARGIN), &bold);
+    let subtitle = format!("{date}, {} books", records.len());
+    layer.use_text(
+        subtitle,
+        10.0,
+        Mm(10.0),
+        Mm(PAGE_HEIGHT - MARGIN - 7.0),
+        &font,
+    );
+    let mut y = PAGE_HEIGHT - MARGIN - 20.0;
+    let mut header = true;
+    for record in records {
+        if y < MARGIN {
+            let (page, new_layer) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+            layer = doc.get_page(page).get_layer(new_layer);
+            y = PAGE_HEIGHT - MARGIN;
+            header = true;
+        }
+        // the column names are repeated at the top of every page
+        if header {
+            for (name, x, _) in CATALOGUE_COLUMNS {
+                layer.use_text(name, 10.0, Mm(x), Mm(y), &bold);
+            }
+            y -= ROW_HEIGHT;
+            header = false;
+        }
+        let status = match record.status {
+            Status::Available => "available",
+            Status::NOTAvailable => "not available",
+            Status::Rented => "rented",
+        };
+        let year = record.year.to_string();
+        let cells = [
+            record.name.as_str(),
+            record.author.as_str(),
+            year.as_str(),
+            status,
+            record.category.as_str(),
+        ];
+        for ((_, x, max_chars), cell) in CATALOGUE_COLUMNS.into_iter().zip(cells) {
+            layer.use_text(truncate_cell(cell, max_chars), 9.0, Mm(x), Mm(y), &font);
+        }
+        y -= ROW_HEIGHT;
+    }
+    doc.save_to_bytes()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/report/pdf",
+    tag = "book",
+    params(
+        BookQuery
+    ),
+    responses(
+        (status = 200, description = "PDF catalogue of the matching books, with their name, author, year, status and category",
+            body = String, content_type = "application/pdf"),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn catalogue_report_pdf(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Query(query): Query<BookQuery>,
+) -> Result<axum::response::Response, ErrorResponse> {
+    let records: Vec<BookCsvRecord> =
+        book::book_csv_records(library_web.pool, library_id.clone(), query)
+            .try_collect()
+            .await?;
+    let pdf = catalogue_pdf(&library_id, Utc::now().date_naive(), &records).map_err(|err| {
+        tracing::error!(error = %err, "failed to render catalogue report");
+        let body = ErrorBody {
+            error: ServerErr::Internal,
+            message: None,
+        };
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(body))
+    })?;
+    log_success!(StatusCode::OK, "book", "report_pdf", count = records.len());
+    let headers = [
+        (CONTENT_TYPE, "application/pdf"),
+        (
+            CONTENT_DISPOSITION,
+            "attachment; filename=\"catalogue.pdf\"",
+        ),
+    ];
+    Ok((headers, pdf).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::author::Author;
+    use crate::library::stats::tests::{insert_rental_at, rent_book_times};
+    use crate::library::stats::TopBookRow;
+    use crate::library::user::{self, RentBook, User};
+    use crate::library_web::tests::{
+        delete, deserialize_response_body, get, post, put, send_request,
+    };
+    use crate::middleware::library::DEFAULT_LIBRARY_ID;
+    use crate::tests::sequential::sequential_test;
+    use axum::http::{
+        header::{ACCEPT, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE, HOST, LINK},
+        Method, Request,
+    };
+    use chrono::Utc;
+    use flate2::read::GzDecoder;
+    use std::collections::HashSet;
+    use std::io::Read;
+    use urlencoding::encode;
+
+    async fn concurrency_create_book(router: axum::Router, book: Book) -> StatusCode {
+        let response = post(&router, "/api/v1/book/create", &book).await;
+        response.status()
+    }
+
+    sequential_test!(
+        async fn test_concurrency_create_book() {
+            let lib = LibraryWeb::new_test().await;
+            let request_body = Book::create_fake_book(&lib.pool).await;
+            let router = lib.setup_router();
+            let fut_a = concurrency_create_book(router.clone(), request_body.clone());
+            let fut_b = concurrency_create_book(router.clone(), request_body.clone());
+            let (status_a, status_b) = tokio::join!(fut_a, fut_b);
+            assert_eq!(status_a.min(status_b), 201, "should succeed");
+            assert_eq!(status_a.max(status_b), 409, "should fail");
+        }
+    );
+
+    sequential_test!(
+        async fn test_create_book_and_get_book() {
+            let lib = LibraryWeb::new_test().await;
+            let request_body = Book::create_fake_book(&lib.pool).await;
+            let router = lib.setup_router();
+            let response = post(&router, "/api/v1/book/create", &request_body).await;
+            assert_eq!(response.status(), 201);
+
+            let response_body = deserialize_response_body::<CreatedBookBody>(response).await;
+            assert_eq!(response_body.info, request_body);
+
+            let uri = format!("/api/v1/book/{}", response_body.id);
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+
+            let response_body = deserialize_response_body::<GetBookBody>(response).await;
+            assert_eq!(response_body.book.name, request_body.name);
+            assert_eq!(response_body.book.description, request_body.description);
+            assert_eq!(
+                response_body.book.cover_image_url,
+                request_body.cover_image_url
+            );
+        }
+    );
+
+    sequential_test!(
+        async fn test_create_book_copies() {
+            let lib = LibraryWeb::new_test().await;
+            let book = Book::create_fake_book(&lib.pool).await;
+            let router = lib.clone().setup_router();
+            let request_body = CreateCopiesBody {
+                book: book.clone(),
+                copies: 3,
+            };
+            let response = post(&router, "/api/v1/book/multi-copy", &request_body).await;
+            assert_eq!(response.status(), 201);
+            let response_body = deserialize_response_body::<CreatedCopiesBody>(response).await;
+            assert_eq!(response_body.info, book);
+            assert_eq!(response_body.ids.len(), 3);
+            for (copy_number, id) in (1..).zip(&response_body.ids) {
+                let response = get(&router, format!("/api/v1/book/{id}")).await;
+                assert_eq!(response.status(), 200);
+                let response_body = deserialize_response_body::<GetBookBody>(response).await;
+                assert_eq!(response_body.book.copy_number, copy_number);
+            }
+
+            // the copies already exist
+            let response = post(&router, "/api/v1/book/multi-copy", &request_body).await;
+            assert_eq!(response.status(), 409);
+
+            // invalid number of copies
+            for copies in [0, book::MAX_BOOK_COPIES + 1] {
+                let request_body = CreateCopiesBody {
+                    book: Book::create_fake_book(&lib.pool).await,
+                    copies,
+                };
+                let response = post(&router, "/api/v1/book/multi-copy", &request_body).await;
+                assert_eq!(response.status(), 400);
+            }
+        }
+    );
+
+    sequential_test!(
+        async fn test_create_book_cover_image_url() {
+            let lib = LibraryWeb::new_test().await;
+            let router = lib.clone().setup_router();
+
+            // absent
+            let mut request_body = Book::create_fake_book(&lib.pool).await;
+            request_body.cover_image_url = None;
+            let response = post(&router, "/api/v1/book/create", &request_body).await;
+            assert_eq!(response.status(), 201);
+            let response_body = deserialize_response_body::<CreatedBookBody>(response).await;
+            assert_eq!(response_body.info.cover_image_url, None);
+
+            // valid
+            let mut request_body = Book::create_fake_book(&lib.pool).await;
+            request_body.cover_image_url = Some("http://example.com/cover.png".to_owned());
+            let response = post(&router, "/api/v1/book/create", &request_body).await;
+            assert_eq!(response.status(), 201);
+            let response_body = deserialize_response_body::<CreatedBookBody>(response).await;
+            let uri = format!("/api/v1/book?name={}", encode(&request_body.name));
+            let response = get(&router, uri).await;
+            let books_body = deserialize_response_body::<BooksBody>(response).await;
+            assert_eq!(
+                books_body.books[0].cover_image_url,
+                response_body.info.cover_image_url
+            );
+
+            // invalid
+            let mut request_body = Book::create_fake_book(&lib.pool).await;
+            request_body.cover_image_url = Some("javascript:alert(1)".to_owned());
+            let response = post(&router, "/api/v1/book/create", &request_body).await;
+            assert_eq!(response.status(), 400);
+        }
+    );
+
+    sequential_test!(
+        async fn test_create_book_invalid_description() {
+            let lib = LibraryWeb::new_test().await;
+            let mut request_body = Book::create_fake_book(&lib.pool).await;
+            request_body.description = Some("a".repeat(book::MAX_DESCRIPTION_LEN + 1));
+            let router = lib.setup_router();
+            let response = post(&router, "/api/v1/book/create", &request_body).await;
+            assert_eq!(response.status(), 400);
+        }
+    );
+
+    sequential_test!(
+        async fn test_create_book_invalid_edition() {
+            let lib = LibraryWeb::new_test().await;
+            let mut request_body = Book::create_fake_book(&lib.pool).await;
+            request_body.edition = Some(0);
+            let router = lib.setup_router();
+            let response = post(&router, "/api/v1/book/create", &request_body).await;
+            assert_eq!(response.status(), 400);
+        }
+    );
+
+    sequential_test!(
+        async fn test_create_book_invalid_language() {
+            let lib = LibraryWeb::new_test().await;
+            let mut request_body = Book::create_fake_book(&lib.pool).await;
+            request_body.language = Some("english".to_owned());
+            let router = lib.setup_router();
+            let response = post(&router, "/api/v1/book/create", &request_body).await;
+            assert_eq!(response.status(), 400);
+        }
+    );
+
+    sequential_test!(
+        async fn test_books_by_language() {
+            let lib = LibraryWeb::new_test().await;
+            let mut english_book = Book::create_fake_book(&lib.pool).await;
+            english_book.language = Some("en".to_owned());
+            let mut turkish_book = Book::create_fake_book(&lib.pool).await;
+            turkish_book.language = Some("tr".to_owned());
+            turkish_book.category = english_book.category.clone();
+            let router = lib.setup_router();
+
+            for book in [&english_book, &turkish_book] {
+                let response = post(&router, "/api/v1/book/create", book).await;
+                assert_eq!(response.status(), 201);
+            }
+
+            for book in [&english_book, &turkish_book] {
+                let uri = format!(
+                    "/api/v1/book?category={}&language={}",
+                    encode(&book.category),
+                    book.language.as_deref().unwrap(),
+                );
+                let response = get(&router, uri).await;
+                assert_eq!(response.status(), 200);
+                let response_body = deserialize_response_body::<BooksBody>(response).await;
+                assert!(response_body.books.iter().any(|b| b.name == book.name));
+                assert!(response_body
+                    .books
+                    .iter()
+                    .all(|b| b.language == book.language));
+            }
+        }
+    );
+
+    sequential_test!(
+        async fn test_books_by_page_count() {
+            let lib = LibraryWeb::new_test().await;
+            let mut short_book = Book::create_fake_book(&lib.pool).await;
+            short_book.page_count = Some(32);
+            let mut long_book = Book::create_fake_book(&lib.pool).await;
+            long_book.page_count = Some(1200);
+            long_book.category = short_book.category.clone();
+            let router = lib.setup_router();
+
+            for book in [&short_book, &long_book] {
+                let response = post(&router, "/api/v1/book/create", book).await;
+                assert_eq!(response.status(), 201);
+            }
+
+            let uri = format!(
+                "/api/v1/book?category={}&page_count_max=100",
+                encode(&short_book.category),
+            );
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert!(response_body
+                .books
+                .iter()
+                .any(|b| b.name == short_book.name));
+            assert!(!response_body.books.iter().any(|b| b.name == long_book.name));
+
+            let uri = format!(
+                "/api/v1/book?category={}&page_count_min=1000&page_count_max=1500",
+                encode(&long_book.category),
+            );
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert!(response_body.books.iter().any(|b| b.name == long_book.name));
+            assert!(!response_body
+                .books
+                .iter()
+                .any(|b| b.name == short_book.name));
+        }
+    );
+
+    sequential_test!(
+        async fn test_available_books() {
+            let lib = LibraryWeb::new_test().await;
+            let available_book = Book::create_fake_book(&lib.pool).await;
+            let mut another_available_book = Book::create_fake_book(&lib.pool).await;
+            another_available_book.category = available_book.category.clone();
+            let mut rented_book = Book::create_fake_book(&lib.pool).await;
+            rented_book.status = Status::Rented;
+            rented_book.category = available_book.category.clone();
+            let router = lib.setup_router();
+
+            for book in [&available_book, &another_available_book, &rented_book] {
+                let response = post(&router, "/api/v1/book/create", book).await;
+                assert_eq!(response.status(), 201);
+            }
+
+            let uri = format!(
+                "/api/v1/book/available?category={}",
+                encode(&available_book.category)
+            );
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert!(response_body
+                .books
+                .iter()
+                .any(|b| b.name == available_book.name));
+            assert!(response_body
+                .books
+                .iter()
+                .all(|b| b.status == Status::Available));
+
+            let uri = format!(
+                "/api/v1/book/available?category={}&limit=1&offset=1",
+                encode(&available_book.category)
+            );
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert_eq!(response_body.books.len(), 1);
+            assert_eq!(response_body.books[0].status, Status::Available);
+        }
+    );
+
+    sequential_test!(
+        async fn test_books_by_category() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let category = "poetry".to_owned();
+            let mut books = Vec::new();
+            for i in 0..3 {
+                let mut book = Book::create_fake_book(&lib.pool).await;
+                book.name = format!("book {i}");
+                book.category = category.clone();
+                books.push(book);
+            }
+            let router = lib.setup_router();
+            for book in &books {
+                let response = post(&router, "/api/v1/book/create", book).await;
+                assert_eq!(response.status(), 201);
+            }
+
+            let uri = format!("/api/v1/book/category/{}", encode(&category));
+            let response = get(&router, &uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert_eq!(response_body.total, Some(3));
+            let names: Vec<_> = response_body.books.iter().map(|b| &b.name).collect();
+            assert_eq!(names, books.iter().map(|b| &b.name).collect::<Vec<_>>());
+
+            let response = get(&router, format!("{uri}?limit=1&offset=1")).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert_eq!(response_body.total, Some(3));
+            assert_eq!(response_body.books.len(), 1);
+            assert_eq!(response_body.books[0].name, books[1].name);
+
+            // an unknown category has no books
+            let response = get(&router, "/api/v1/book/category/unknown").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert_eq!(response_body.total, Some(0));
+            assert!(response_body.books.is_empty());
+        }
+    );
+
+    sequential_test!(
+        async fn test_books_by_year() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let router = lib.clone().setup_router();
+            for (i, year) in [1999, 1999, 2001].into_iter().enumerate() {
+                let mut book = Book::create_fake_book(&lib.pool).await;
+                book.name = format!("book {i}");
+                book.year = year;
+                // every status is listed
+                book.status = Status::Rented;
+                let response = post(&router, "/api/v1/book/create", &book).await;
+                assert_eq!(response.status(), 201);
+            }
+
+            let response = get(&router, "/api/v1/book/year/1999").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert_eq!(response_body.total, Some(2));
+            let names: Vec<_> = response_body
+                .books
+                .iter()
+                .map(|b| b.name.as_str())
+                .collect();
+            assert_eq!(names, ["book 0", "book 1"]);
+
+            let response = get(&router, "/api/v1/book/year/1999?limit=1&offset=1").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert_eq!(response_body.total, Some(2));
+            assert_eq!(response_body.books.len(), 1);
+            assert_eq!(response_body.books[0].name, "book 1");
+
+            let response = get(&router, "/api/v1/book/year/1500").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert_eq!(response_body.total, Some(0));
+
+            for year in ["999", "2101", "nineteen"] {
+                let response = get(&router, format!("/api/v1/book/year/{year}")).await;
+                assert_eq!(response.status(), 400);
+            }
+        }
+    );
+
+    sequential_test!(
+        async fn test_books_by_author_name() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let router = lib.clone().setup_router();
+            let mut book = Book::create_fake_book(&lib.pool).await;
+            book.name = "book 0".to_owned();
+            let response = post(&router, "/api/v1/book/create", &book).await;
+            assert_eq!(response.status(), 201);
+            book.name = "book 1".to_owned();
+            book.isbn = Some(Book::fake_isbn());
+            let response = post(&router, "/api/v1/book/create", &book).await;
+            assert_eq!(response.status(), 201);
+
+            // the name is URL-decoded
+            let uri = format!("/api/v1/book/author/{}", encode(&book.author));
+            let response = get(&router, &uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert_eq!(response_body.total, Some(2));
+            let names: Vec<_> = response_body
+                .books
+                .iter()
+                .map(|b| b.name.as_str())
+                .collect();
+            assert_eq!(names, ["book 0", "book 1"]);
+
+            let response = get(&router, format!("{uri}?limit=1&offset=1")).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert_eq!(response_body.total, Some(2));
+            assert_eq!(response_body.books[0].name, "book 1");
+
+            // an author without books
+            let author = Author::create_fake_author().await;
+            let response = post(&router, "/api/v1/author/create", &author).await;
+            assert_eq!(response.status(), 201);
+            let uri = format!("/api/v1/book/author/{}", encode(&author.name));
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert_eq!(response_body.total, Some(0));
+            assert!(response_body.books.is_empty());
+
+            // an unknown author
+            let response = get(&router, "/api/v1/book/author/unknown%20author").await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_rented_books() {
+            let lib = LibraryWeb::new_test().await;
+            let mut rented_book = Book::create_fake_book(&lib.pool).await;
+            rented_book.status = Status::Rented;
+            let mut another_rented_book = Book::create_fake_book(&lib.pool).await;
+            another_rented_book.status = Status::Rented;
+            another_rented_book.category = rented_book.category.clone();
+            let mut available_book = Book::create_fake_book(&lib.pool).await;
+            available_book.category = rented_book.category.clone();
+            let router = lib.setup_router();
+
+            for book in [&rented_book, &another_rented_book, &available_book] {
+                let response = post(&router, "/api/v1/book/create", book).await;
+                assert_eq!(response.status(), 201);
+            }
+
+            let uri = format!(
+                "/api/v1/book/rented?category={}",
+                encode(&rented_book.category)
+            );
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert!(response_body
+                .books
+                .iter()
+                .any(|b| b.name == rented_book.name));
+            assert!(response_body
+                .books
+                .iter()
+                .all(|b| b.status == Status::Rented));
+
+            let uri = format!(
+                "/api/v1/book/rented?category={}&limit=1&offset=1",
+                encode(&rented_book.category)
+            );
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert_eq!(response_body.books.len(), 1);
+            assert_eq!(response_body.books[0].status, Status::Rented);
+        }
+    );
+
+    sequential_test!(
+        async fn test_book_availability() {
+            let lib = LibraryWeb::new_test().await;
+            let mut request_body = Book::create_fake_book(&lib.pool).await;
+            request_body.status = Status::NOTAvailable;
+            let router = lib.setup_router();
+            let response = post(&router, "/api/v1/book/create", &request_body).await;
+            assert_eq!(response.status(), 201);
+            let book_id = deserialize_response_body::<CreatedBookBody>(response)
+                .await
+                .id;
+
+            let response = get(&router, format!("/api/v1/book/{book_id}/availability")).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BookAvailabilityBody>(response).await;
+            assert_eq!(
+                response_body.availability,
+                BookAvailability {
+                    status: Status::NOTAvailable,
+                    queue_length: 0,
+                    estimated_return_date: None,
+                }
+            );
+
+            let response = get(
+                &router,
+                format!("/api/v1/book/{}/availability", Uuid::nil()),
+            )
+            .await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_book_copies() {
+            let lib = LibraryWeb::new_test().await;
+            let book = Book::create_fake_book(&lib.pool).await;
+            let router = lib.clone().setup_router();
+            let request_body = CreateCopiesBody {
+                book: book.clone(),
+                copies: 3,
+            };
+            let response = post(&router, "/api/v1/book/multi-copy", &request_body).await;
+            assert_eq!(response.status(), 201);
+            let ids = deserialize_response_body::<CreatedCopiesBody>(response)
+                .await
+                .ids;
+
+            let user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &user)
+                .await
+                .expect("failed to insert user");
+            let rent_book = RentBook {
+                book_name: book.name,
+                due_date: "2030-06-15".to_owned(),
+            };
+            let uri = format!("/api/v1/user/rent/{}", encode(&user.nation_id));
+            let response = post(&router, uri, &rent_book).await;
+            assert_eq!(response.status(), 201);
+
+            // every copy lists all the copies of the book
+            for id in &ids {
+                let response = get(&router, format!("/api/v1/book/{id}/copies")).await;
+                assert_eq!(response.status(), 200);
+                let response_body = deserialize_response_body::<BookCopiesBody>(response).await;
+                let expected: Vec<_> = (1..)
+                    .zip(&ids)
+                    .map(|(copy_number, id)| BookCopyRow {
+                        id: *id,
+                        copy_number,
+                        status: if copy_number == 1 {
+                            Status::Rented
+                        } else {
+                            Status::Available
+                        },
+                    })
+                    .collect();
+                assert_eq!(response_body.copies, expected);
+            }
+            let response = get(&router, format!("/api/v1/book/{}/copies", Uuid::nil())).await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_new_arrivals() {
+            let lib = LibraryWeb::new_test().await;
+            let request_body = Book::create_fake_book(&lib.pool).await;
+            let router = lib.setup_router();
+            let response = post(&router, "/api/v1/book/create", &request_body).await;
+            assert_eq!(response.status(), 201);
+
+            let response = get(&router, "/api/v1/book/new-arrivals?days=1&limit=100").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<NewArrivalsBody>(response).await;
+            let book = response_body
+                .books
+                .iter()
+                .find(|book| book.name == request_body.name)
+                .expect("book not found");
+            assert!(book.created_at.is_some());
+            assert_eq!(
+                Book {
+                    created_at: None,
+                    updated_at: None,
+                    ..book.clone()
+                },
+                request_body
+            );
+
+            let response = get(&router, "/api/v1/book/new-arrivals?limit=1").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<NewArrivalsBody>(response).await;
+            assert_eq!(response_body.books.len(), 1);
+
+            let response = get(&router, "/api/v1/book/new-arrivals?days=0").await;
+            assert_eq!(response.status(), 400);
+        }
+    );
+
+    sequential_test!(
+        async fn test_current_renter() {
+            let lib = LibraryWeb::new_test().await;
+            let book = Book::create_fake_book(&lib.pool).await;
+            let book_id = book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+            let fake_user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert user");
+            let router = lib.setup_router();
+            let uri = format!("/api/v1/book/{book_id}/current-renter");
+
+            // available book
+            let response = get(&router, &uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<CurrentRenterBody>(response).await;
+            assert_eq!(response_body.renter, None);
+
+            // rented book
+            let rent_book = RentBook {
+                book_name: book.name.clone(),
+                due_date: "2030-06-15".to_owned(),
+            };
+            let response = post(
+                &router,
+                format!("/api/v1/user/rent/{}", encode(&fake_user.nation_id)),
+                &rent_book,
+            )
+            .await;
+            assert_eq!(response.status(), 201);
+            let response = get(&router, &uri).await;
+            assert_eq!(response.status(), 200);
+            let renter = deserialize_response_body::<CurrentRenterBody>(response)
+                .await
+                .renter
+                .expect("book without renter");
+            assert_eq!(renter.nation_id, fake_user.nation_id);
+            assert_eq!(renter.book_name, book.name);
+            assert_eq!(renter.due_date, rent_book.due_date);
+
+            let response = get(
+                &router,
+                format!("/api/v1/book/{}/current-renter", Uuid::nil()),
+            )
+            .await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_rental_rate() {
+            let lib = LibraryWeb::new_test().await;
+            let book = Book::create_fake_book(&lib.pool).await;
+            let book_id = book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+            let fake_user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert user");
+            insert_rental_at(
+                &lib.pool,
+                &fake_user.nation_id,
+                &book.name,
+                "2002-03-01T00:00:00Z",
+            )
+            .await;
+            rent_book_times(&lib.pool, &fake_user.nation_id, &book.name, 2).await;
+            let router = lib.setup_router();
+
+            let response = get(&router, format!("/api/v1/book/{book_id}/rental-rate")).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<RentalRateBody>(response).await;
+            assert_eq!(
+                response_body.stats,
+                RentalRateStats {
+                    total_rentals: 3,
+                    rentals_last_30_days: 2,
+                    avg_days_to_return: None,
+                }
+            );
+
+            let response = get(&router, format!("/api/v1/book/{}/rental-rate", Uuid::nil())).await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_book_qr_code() {
+            let lib = LibraryWeb::new_test().await;
+            let book = Book::create_fake_book(&lib.pool).await;
+            let book_id = book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+            let router = lib.setup_router();
+
+            let request = Request::builder()
+                .method(Method::GET)
+                .uri(format!("/api/v1/book/{book_id}/qr-code"))
+                .header(ACCEPT, "image/png")
+                .body(hyper::Body::empty())
+                .expect("failed to build GET request");
+            let response = send_request(&router, request).await;
+            assert_eq!(response.status(), 200);
+            assert_eq!(response.headers()[CONTENT_TYPE], "image/png");
+            assert_eq!(response.headers()[CACHE_CONTROL], "max-age=86400");
+            let bytes = hyper::body::to_bytes(response.into_body())
+                .await
+                .expect("failed to read response body");
+            assert!(bytes.starts_with(b"\x89PNG"));
+
+            let response = get(&router, format!("/api/v1/book/{}/qr-code", Uuid::nil())).await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    #[test]
+    fn test_truncate_cell() {
+        assert_eq!(truncate_cell("Dune", 10), "Dune");
+        assert_eq!(truncate_cell("The Left Hand of Darkness", 10), "The Lef...");
+    }
+
+    sequential_test!(
+        async fn test_catalogue_report_pdf() {
+            let lib = LibraryWeb::new_test().await;
+            let book = Book::create_fake_book(&lib.pool).await;
+            book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+            let router = lib.setup_router();
+
+            let request = Request::builder()
+                .method(Method::GET)
+                .uri(format!(
+                    "/api/v1/book/report/pdf?name={}",
+                    encode(&book.name)
+                ))
+                .header(ACCEPT, "application/pdf")
+                .body(hyper::Body::empty())
+                .expect("failed to build GET request");
+            let response = send_request(&router, request).await;
+            assert_eq!(response.status(), 200);
+            assert_eq!(response.headers()[CONTENT_TYPE], "application/pdf");
+            assert_eq!(
+                response.headers()[CONTENT_DISPOSITION],
+                "attachment; filename=\"catalogue.pdf\""
+            );
+            let bytes = hyper::body::to_bytes(response.into_body())
+                .await
+                .expect("failed to read response body");
+            assert!(bytes.starts_with(b"%PDF"));
+        }
+    );
+
+    sequential_test!(
+        async fn test_similar_books() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let book = Book::create_fake_book(&lib.pool).await;
+            let ids = book::insert_book_copies(&lib.pool, DEFAULT_LIBRARY_ID, &book, 2)
+                .await
+                .expect("failed to insert book copies");
+            let mut same_both = Book::create_fake_book(&lib.pool).await;
+            same_both.category = book.category.clone();
+            same_both.author = book.author.clone();
+            let mut same_category = Book::create_fake_book(&lib.pool).await;
+            same_category.category = book.category.clone();
+            let mut same_author = Book::create_fake_book(&lib.pool).await;
+            same_author.category = format!("other-{}", book.category);
+            same_author.author = book.author.clone();
+            let mut unrelated = Book::create_fake_book(&lib.pool).await;
+            unrelated.category = format!("unrelated-{}", book.category);
+            book::insert_book_copies(&lib.pool, DEFAULT_LIBRARY_ID, &same_both, 2)
+                .await
+                .expect("failed to insert book copies");
+            for book in [&same_category, &same_author, &unrelated] {
+                book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, book)
+                    .await
+                    .expect("failed to insert book");
+            }
+            let router = lib.setup_router();
+
+            // the books sharing both come first, each book is listed once
+            let mut expected = vec![same_category.name.clone(), same_author.name.clone()];
+            expected.sort();
+            expected.insert(0, same_both.name.clone());
+            for id in &ids {
+                let response = get(&router, format!("/api/v1/book/{id}/similar")).await;
+                assert_eq!(response.status(), 200);
+                let response_body = deserialize_response_body::<BooksBody>(response).await;
+                let names: Vec<_> = response_body
+                    .books
+                    .iter()
+                    .map(|book| book.name.clone())
+                    .collect();
+                assert_eq!(names, expected);
+                assert!(response_body.books.iter().all(|book| book.copy_number == 1));
+            }
+
+            let uri = format!("/api/v1/book/{}/similar?limit=1", ids[0]);
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert_eq!(response_body.books.len(), 1);
+            assert_eq!(response_body.books[0].name, same_both.name);
+
+            let response = get(&router, format!("/api/v1/book/{}/similar", Uuid::nil())).await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_delete_and_restore_book() {
+            let lib = LibraryWeb::new_test().await;
+            let request_body = Book::create_fake_book(&lib.pool).await;
+            let router = lib.setup_router();
+            let response = post(&router, "/api/v1/book/create", &request_body).await;
+            assert_eq!(response.status(), 201);
+            let book_id = deserialize_response_body::<CreatedBookBody>(response)
+                .await
+                .id;
+            let uri_by_name = format!("/api/v1/book?name={}", encode(&request_body.name));
+
+            // delete
+            let response = delete(&router, format!("/api/v1/book/{book_id}")).await;
+            assert_eq!(response.status(), 200);
+            let response = delete(&router, format!("/api/v1/book/{book_id}")).await;
+            assert_eq!(response.status(), 404);
+
+            // deleted book is hidden
+            let response = get(&router, &uri_by_name).await;
+            assert_eq!(response.status(), 404);
+            let response = get(&router, format!("/api/v1/book/{book_id}")).await;
+            assert_eq!(response.status(), 404);
+            let response = get(&router, "/api/v1/book").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert!(!response_body
+                .books
+                .iter()
+                .any(|b| b.name == request_body.name));
+
+            // deleted
+            let response = get(&router, "/api/v1/book/deleted").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert!(response_body
+                .books
+                .iter()
+                .any(|b| b.name == request_body.name));
+
+            // restore
+            let response = post(&router, format!("/api/v1/book/{book_id}/restore"), &()).await;
+            assert_eq!(response.status(), 200);
+            let response = get(&router, &uri_by_name).await;
+            assert_eq!(response.status(), 200);
+        }
+    );
+
+    sequential_test!(
+        async fn test_merge_books() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let router = lib.clone().setup_router();
+            let keep = Book::create_fake_book(&lib.pool).await;
+            let keep_id = book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &keep)
+                .await
+                .expect("failed to insert book");
+            let discard = Book::create_fake_book(&lib.pool).await;
+            let discard_id = book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &discard)
+                .await
+                .expect("failed to insert book");
+            let fake_user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert user");
+            rent_book_times(&lib.pool, &fake_user.nation_id, &discard.name, 1).await;
+
+            // same or unknown books
+            let body = MergeBooksBody {
+                keep_id,
+                discard_id: keep_id,
+            };
+            let response = post(&router, "/api/v1/book/merge", &body).await;
+            assert_eq!(response.status(), 400);
+            let body = MergeBooksBody {
+                keep_id,
+                discard_id: Uuid::nil(),
+            };
+            let response = post(&router, "/api/v1/book/merge", &body).await;
+            assert_eq!(response.status(), 404);
+
+            let body = MergeBooksBody {
+                keep_id,
+                discard_id,
+            };
+            let response = post(&router, "/api/v1/book/merge", &body).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<MergedBooksBody>(response).await;
+            assert_eq!(response_body.book.name, keep.name);
+            assert_eq!(response_body.rentals_moved, 1);
+            let response = get(&router, format!("/api/v1/book/{discard_id}")).await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_reassign_book_author() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let router = lib.clone().setup_router();
+            let author = Author::create_fake_author().await;
+            let response = post(&router, "/api/v1/author/create", &author).await;
+            assert_eq!(response.status(), 201);
+            let book = Book {
+                author: author.name.clone(),
+                ..Book::create_fake_book(&lib.pool).await
+            };
+            let ids = book::insert_book_copies(&lib.pool, DEFAULT_LIBRARY_ID, &book, 2)
+                .await
+                .expect("failed to insert book copies");
+            let new_author = Author::create_fake_author().await;
+            let response = post(&router, "/api/v1/author/create", &new_author).await;
+            assert_eq!(response.status(), 201);
+
+            let uri = format!("/api/v1/book/{}/author", ids[0]);
+            let body = BookAuthor {
+                author: new_author.name.clone(),
+            };
+            let response = put(&router, &uri, &body).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BookMessageBody>(response).await;
+            assert_eq!(response_body.id, ids[0]);
+            // every copy of the title is reassigned
+            for id in &ids {
+                let response = get(&router, format!("/api/v1/book/{id}")).await;
+                assert_eq!(response.status(), 200);
+                let response_body = deserialize_response_body::<GetBookBody>(response).await;
+                assert_eq!(response_body.book.author, new_author.name);
+            }
+
+            let body = BookAuthor {
+                author: "missing author".to_owned(),
+            };
+            let response = put(&router, &uri, &body).await;
+            assert_eq!(response.status(), 404);
+            let body = BookAuthor {
+                author: " ".to_owned(),
+            };
+            let response = put(&router, &uri, &body).await;
+            assert_eq!(response.status(), 400);
+            let body = BookAuthor {
+                author: author.name,
+            };
+            let uri = format!("/api/v1/book/{}/author", Uuid::nil());
+            let response = put(&router, uri, &body).await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_create_book_body_too_large() {
+            let lib = LibraryWeb::new_test().await;
+            let mut request_body = Book::create_fake_book(&lib.pool).await;
+            request_body.description = Some("a".repeat(lib.config.request_body_limit_bytes + 1));
+            let router = lib.setup_router();
+            let response = post(&router, "/api/v1/book/create", &request_body).await;
+            assert_eq!(response.status(), 413);
+        }
+    );
+
+    sequential_test!(
+        async fn test_books_gzip() {
+            let lib = LibraryWeb::new_test().await;
+            let request_body = Book::create_fake_book(&lib.pool).await;
+            let router = lib.setup_router();
+            let response = post(&router, "/api/v1/book/create", &request_body).await;
+            assert_eq!(response.status(), 201);
+
+            let request = Request::builder()
+                .method(Method::GET)
+                .uri("/api/v1/book")
+                .header(ACCEPT_ENCODING, "gzip")
+                .body(hyper::Body::empty())
+                .expect("failed to build GET request");
+            let response = send_request(&router, request).await;
+            assert_eq!(response.status(), 200);
+            assert_eq!(response.headers()[CONTENT_ENCODING], "gzip");
+
+            let bytes = hyper::body::to_bytes(response.into_body())
+                .await
+                .expect("failed to read response body into bytes");
+            let mut decompressed = Vec::new();
+            GzDecoder::new(&bytes[..])
+                .read_to_end(&mut decompressed)
+                .expect("failed to decompress response body");
+            let response_body = serde_json::from_slice::<BooksBody>(&decompressed)
+                .expect("failed to deserialize response");
+            assert!(response_body
+                .books
+                .iter()
+                .any(|b| b.name == request_body.name));
+        }
+    );
+
+    sequential_test!(
+        async fn test_books_cursor_pagination() {
+            let lib = LibraryWeb::new_test().await;
+            let category = format!("cursor-{}", rand::random::<u32>());
+            let mut names = Vec::new();
+            for _ in 0..5 {
+                let mut book = Book::create_fake_book(&lib.pool).await;
+                book.category = category.clone();
+                book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &book)
+                    .await
+                    .expect("failed to insert book");
+                names.push(book.name);
+            }
+            let inserted_mid_pagination = Book::create_fake_book(&lib.pool).await;
+            let pool = lib.pool.clone();
+            let router = lib.setup_router();
+
+            let mut cursor = Some(Uuid::nil());
+            let mut pages = Vec::new();
+            while let Some(after) = cursor {
+                let uri =
+                    format!("/api/v1/book?category={category}&limit=2&offset=1&after={after}");
+                let response = get(&router, uri).await;
+                assert_eq!(response.status(), 200);
+                let response_body = deserialize_response_body::<BooksBody>(response).await;
+                if pages.is_empty() {
+                    let mut book = inserted_mid_pagination.clone();
+                    book.category = category.clone();
+                    book::insert_book(&pool, DEFAULT_LIBRARY_ID, &book)
+                        .await
+                        .expect("failed to insert book");
+                }
+                cursor = response_body.next_cursor;
+                pages.push(response_body.books);
+            }
+            assert!(pages[..pages.len() - 1].iter().all(|page| page.len() == 2));
+            let books = pages.concat();
+            assert!(books.windows(2).all(|pair| pair[0].id < pair[1].id));
+            // the books existing before the first page are all returned once, the
+            // offset being ignored
+            for name in &names {
+                assert_eq!(books.iter().filter(|book| &book.name == name).count(), 1);
+            }
+            assert!(books.len() <= names.len() + 1);
+
+            // without a cursor the books are paginated by offset and no cursor is
+            // returned
+            let uri = format!("/api/v1/book?category={category}&limit=2&offset=1");
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            names.push(inserted_mid_pagination.name);
+            names.sort();
+            assert_eq!(
+                response_body
+                    .books
+                    .iter()
+                    .map(|book| book.name.clone())
+                    .collect::<Vec<_>>(),
+                names[1..3]
+            );
+            assert_eq!(response_body.next_cursor, None);
+        }
+    );
+
+    sequential_test!(
+        async fn test_books_link_header() {
+            let lib = LibraryWeb::new_test().await;
+            let category = format!("link-{}", rand::random::<u32>());
+            for _ in 0..5 {
+                let mut book = Book::create_fake_book(&lib.pool).await;
+                book.category = category.clone();
+                book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &book)
+                    .await
+                    .expect("failed to insert book");
+            }
+            let router = lib.setup_router();
+            let page = |offset: i64| {
+                format!("http://localhost/api/v1/book?category={category}&limit=2&offset={offset}")
+            };
+
+            for (offset, links) in [
+                (0, vec![(0, "first"), (2, "next"), (4, "last")]),
+                (2, vec![(0, "first"), (0, "prev"), (4, "next"), (4, "last")]),
+                (4, vec![(0, "first"), (2, "prev"), (4, "last")]),
+            ] {
+                let request = Request::builder()
+                    .method(Method::GET)
+                    .uri(format!(
+                        "/api/v1/book?offset={offset}&category={category}&limit=2"
+                    ))
+                    .header(HOST, "localhost")
+                    .body(hyper::Body::empty())
+                    .expect("failed to build request");
+                let response = send_request(&router, request).await;
+                assert_eq!(response.status(), 200);
+                let expected = links
+                    .into_iter()
+                    .map(|(offset, rel)| format!("<{}>; rel=\"{rel}\"", page(offset)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                assert_eq!(response.headers()[LINK], expected);
+            }
+
+            // no link without a limit or with a cursor
+            for uri in [
+                format!("/api/v1/book?category={category}"),
+                format!(
+                    "/api/v1/book?category={category}&limit=2&after={}",
+                    Uuid::nil()
+                ),
+            ] {
+                let response = get(&router, uri).await;
+                assert_eq!(response.status(), 200);
+                assert!(response.headers().get(LINK).is_none());
+            }
+        }
+    );
+
+    sequential_test!(
+        async fn test_books() {
+            let lib = LibraryWeb::new_test().await;
+            let request_body = Book::create_fake_book(&lib.pool).await;
+            let router = lib.setup_router();
+
+            let response = post(&router, "/api/v1/book/create", &request_body).await;
+            assert_eq!(response.status(), 201);
+            let response_body_created =
+                deserialize_response_body::<CreatedBookBody>(response).await;
+            assert_eq!(response_body_created.info, request_body);
+
+            let response = get(&router, "/api/v1/book").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert!(response_body.books.contains(&BookSummary::from((
+                response_body_created.id,
+                request_body.clone()
+            ))));
+
+            let uri = format!(
+                "/api/v1/book?name={}",
+                encode(&response_body_created.info.name)
+            );
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert_eq!(response_body.books[0].name, request_body.name);
+
+            let uri = format!(
+                "/api/v1/book?name={}&author={}",
+                encode(&response_body_created.info.name),
+                encode(&response_body_created.info.author),
+            );
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert_eq!(response_body.books[0].name, request_body.name);
+            assert_eq!(response_body.books[0].author, request_body.author);
+
+            let uri = format!(
+                "/api/v1/book?name={}&publisher={}",
+                encode(&response_body_created.info.name),
+                encode(&request_body.publisher.clone().unwrap().to_lowercase()),
+            );
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert_eq!(response_body.books[0].publisher, request_body.publisher);
+
+            let uri = format!(
+                "/api/v1/book?name={}&edition_min={}",
+                encode(&response_body_created.info.name),
+                request_body.edition.unwrap(),
+            );
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert_eq!(response_body.books[0].edition, request_body.edition);
+        }
+    );
+
+    /// Sends a `multipart/form-data` request with a single file field.
+    async fn post_file(
+        router: &axum::Router,
+        uri: &str,
+        field: &str,
+        file: &[u8],
+    ) -> hyper::Response<http_body::combinators::UnsyncBoxBody<axum::body::Bytes, axum::Error>>
+    {
+        let boundary = "book-import-boundary";
+        let mut body = format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"{field}\"; \
+             filename=\"books.csv\"\r\nContent-Type: text/csv\r\n\r\n"
+        )
+        .into_bytes();
+        body.extend_from_slice(file);
+        body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .header(
+                CONTENT_TYPE,
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(body.into())
+            .expect("failed to build multipart request");
+        send_request(router, request).await
+    }
+
+    /// Writes the given rows as a CSV file with the book CSV header.
+    fn books_csv(rows: &[[&str; 6]]) -> Vec<u8> {
+        let mut writer = csv::Writer::from_writer(vec![]);
+        writer
+            .write_record(BOOK_CSV_HEADER)
+            .expect("failed to write CSV header");
+        for row in rows {
+            writer.write_record(row).expect("failed to write CSV row");
+        }
+        writer.into_inner().expect("failed to write CSV file")
+    }
+
+    sequential_test!(
+        async fn test_import_books_csv() {
+            let lib = LibraryWeb::new_test().await;
+            let router = lib.clone().setup_router();
+            let first = Book::create_fake_book(&lib.pool).await;
+            let second = Book::create_fake_book(&lib.pool).await;
+            let file = books_csv(&[
+                [
+                    &first.name,
+                    "2001",
+                    "csv",
+                    "available",
+                    &first.author,
+                    "978-0-306-40615-7",
+                ],
+                [
+                    &first.name,
+                    "not a year",
+                    "csv",
+                    "available",
+                    &first.author,
+                    "",
+                ],
+                [&second.name, "2002", "csv", "rented", &second.author, ""],
+                [&first.name, "2001", "csv", "available", &first.author, ""],
+                [
+                    "unknown author",
+                    "2003",
+                    "csv",
+                    "available",
+                    "no such author",
+                    "",
+                ],
+                [
+                    "invalid isbn",
+                    "2003",
+                    "csv",
+                    "available",
+                    &first.author,
+                    "123",
+                ],
+            ]);
+            let response = post_file(&router, "/api/v1/book/import/csv", "file", &file).await;
+            assert_eq!(response.status(), 207);
+            let response_body = deserialize_response_body::<ImportResultBody>(response).await;
+            assert_eq!(response_body.inserted, 2);
+            let rows: Vec<usize> = response_body.errors.iter().map(|e| e.row).collect();
+            assert_eq!(rows, [3, 5, 6, 7]);
+
+            let uri = format!("/api/v1/book?name={}", encode(&first.name));
+            let response = get(&router, uri).await;
+            let books_body = deserialize_response_body::<BooksBody>(response).await;
+            assert_eq!(
+                books_body.books[0].isbn.as_deref(),
+                Some("978-0-306-40615-7")
+            );
+            let uri = format!("/api/v1/book/rented?name={}", encode(&second.name));
+            let response = get(&router, uri).await;
+            let books_body = deserialize_response_body::<BooksBody>(response).await;
+            assert_eq!(books_body.books[0].isbn, None);
+        }
+    );
+
+    sequential_test!(
+        async fn test_import_books_csv_all_valid() {
+            let lib = LibraryWeb::new_test().await;
+            let router = lib.clone().setup_router();
+            let book = Book::create_fake_book(&lib.pool).await;
+            let file = books_csv(&[[&book.name, "1999", "csv", "available", &book.author, ""]]);
+            let response = post_file(&router, "/api/v1/book/import/csv", "file", &file).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<ImportResultBody>(response).await;
+            assert_eq!(
+                response_body,
+                ImportResultBody {
+                    inserted: 1,
+                    errors: vec![],
+                }
+            );
+        }
+    );
+
+    sequential_test!(
+        async fn test_import_books_csv_invalid_file() {
+            let lib = LibraryWeb::new_test().await;
+            let router = lib.setup_router();
+            let file = books_csv(&[]);
+            let response = post_file(&router, "/api/v1/book/import/csv", "books", &file).await;
+            assert_eq!(response.status(), 400);
+            let file = b"name,year,category\nbook,2000,csv\n";
+            let response = post_file(&router, "/api/v1/book/import/csv", "file", file).await;
+            assert_eq!(response.status(), 400);
+            let response = post(&router, "/api/v1/book/import/csv", &"not multipart").await;
+            assert_eq!(response.status(), 400);
+        }
+    );
+
+    sequential_test!(
+        async fn test_export_books_csv() {
+            let lib = LibraryWeb::new_test().await;
+            let router = lib.clone().setup_router();
+            let category = format!("export {}", rand::random::<u64>());
+            for status in [Status::Available, Status::Rented, Status::NOTAvailable] {
+                let mut book = Book::create_fake_book(&lib.pool).await;
+                book.category = category.clone();
+                book.status = status;
+                book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &book)
+                    .await
+                    .expect("failed to insert book");
+            }
+            let count = sqlx::query_scalar!(
+                r#"SELECT COUNT(*) as "count!" FROM book WHERE category = $1 AND deleted_at IS NULL"#,
+                category,
+            )
+            .fetch_one(&lib.pool)
+            .await
+            .expect("failed to count books");
+
+            let uri = format!("/api/v1/book/export/csv?category={}", encode(&category));
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            assert_eq!(response.headers()[CONTENT_TYPE], "text/csv");
+            assert_eq!(
+                response.headers()[CONTENT_DISPOSITION],
+                "attachment; filename=\"books.csv\""
+            );
+            let bytes = hyper::body::to_bytes(response.into_body())
+                .await
+                .expect("failed to read response body into bytes");
+            let mut reader = csv::Reader::from_reader(&bytes[..]);
+            let headers = reader.headers().expect("failed to read CSV header");
+            assert!(headers.iter().eq(BOOK_CSV_HEADER));
+            let records = reader
+                .deserialize::<BookCsvRecord>()
+                .collect::<Result<Vec<_>, _>>()
+                .expect("failed to read CSV records");
+            assert_eq!(records.len() as i64, count);
+            assert!(records.iter().all(|record| record.category == category));
+
+            // the export uses the import format
+            let mut writer = csv::Writer::from_writer(vec![]);
+            for record in &records {
+                writer
+                    .serialize(BookCsvRecord {
+                        name: format!("{} (copy)", record.name),
+                        isbn: None,
+                        ..record.clone()
+                    })
+                    .expect("failed to write CSV row");
+            }
+            let file = writer.into_inner().expect("failed to write CSV file");
+            let response = post_file(&router, "/api/v1/book/import/csv", "file", &file).await;
+            assert_eq!(response.status(), 200);
+        }
+    );
+
+    sequential_test!(
+        async fn test_get_book_by_name() {
+            let lib = LibraryWeb::new_test().await;
+            let mut request_body = Book::create_fake_book(&lib.pool).await;
+            // names with reserved characters are URL-encoded
+            request_body.name = format!("{} / {}?", request_body.name, rand::random::<u32>());
+            book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &request_body)
+                .await
+                .expect("failed to insert book");
+            let router = lib.setup_router();
+
+            let uri = format!("/api/v1/book/by-name/{}", encode(&request_body.name));
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<GetBookBody>(response).await;
+            assert_eq!(response_body.book.name, request_body.name);
+            assert_eq!(response_body.book.isbn, request_body.isbn);
+            assert_eq!(response_body.review_count, 0);
+
+            let uri = format!("/api/v1/book/by-name/{}", encode("no such book"));
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_get_book_by_isbn() {
+            let lib = LibraryWeb::new_test().await;
+            let request_body = Book::create_fake_book(&lib.pool).await;
+            book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &request_body)
+                .await
+                .expect("failed to insert book");
+            let router = lib.setup_router();
+
+            // hyphens are ignored
+            let isbn = request_body.isbn.clone().expect("fake book has an isbn");
+            let hyphenated = format!("{}-{}", &isbn[..3], &isbn[3..]);
+            let response = get(&router, format!("/api/v1/book/isbn/{hyphenated}")).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<GetBookBody>(response).await;
+            assert_eq!(response_body.book.name, request_body.name);
+            assert_eq!(response_body.book.isbn, Some(isbn.clone()));
+
+            // invalid check digit
+            let last = isbn.as_bytes()[12] - b'0';
+            let invalid = format!("{}{}", &isbn[..12], (last + 1) % 10);
+            let response = get(&router, format!("/api/v1/book/isbn/{invalid}")).await;
+            assert_eq!(response.status(), 400);
+
+            let response = get(&router, "/api/v1/book/isbn/978-0-306-40615-7").await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_random_book() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let router = lib.clone().setup_router();
+            let response = get(&router, "/api/v1/book/random").await;
+            assert_eq!(response.status(), 404);
+
+            let mut names = Vec::new();
+            for status in [Status::Available, Status::Available, Status::Rented] {
+                let mut fake_book = Book::create_fake_book(&lib.pool).await;
+                fake_book.status = status;
+                book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                    .await
+                    .expect("failed to insert book");
+                names.push(fake_book.name);
+            }
+
+            let mut seen = HashSet::new();
+            for _ in 0..10 {
+                let response = get(&router, "/api/v1/book/random").await;
+                assert_eq!(response.status(), 200);
+                let response_body = deserialize_response_body::<GetBookBody>(response).await;
+                assert_eq!(response_body.book.status, Status::Available);
+                seen.insert(response_body.book.name);
+            }
+            // both available books are picked, this fails with a probability of 2^-9
+            assert_eq!(seen, names[..2].iter().cloned().collect());
+        }
+    );
+
+    sequential_test!(
+        async fn test_trending_books() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let fake_user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert user");
+            let mut books = Vec::new();
+            for _ in 0..3 {
+                let fake_book = Book::create_fake_book(&lib.pool).await;
+                book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                    .await
+                    .expect("failed to insert book");
+                books.push(fake_book);
+            }
+            let hours_ago = |hours| (Utc::now() - chrono::Duration::hours(hours)).to_rfc3339();
+            for (book, hours, times) in [(&books[0], 2, 3), (&books[1], 10, 2), (&books[2], 48, 5)]
+            {
+                for _ in 0..times {
+                    insert_rental_at(
+                        &lib.pool,
+                        &fake_user.nation_id,
+                        &book.name,
+                        &hours_ago(hours),
+                    )
+                    .await;
+                }
+            }
+            let router = lib.setup_router();
+            let rentals = |response_body: TopBooksBody| {
+                response_body
+                    .books
+                    .into_iter()
+                    .map(|row| (row.book_name, row.rental_count))
+                    .collect::<Vec<_>>()
+            };
+
+            // last 24 hours by default
+            let response = get(&router, "/api/v1/book/trending").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<TopBooksBody>(response).await;
+            assert_eq!(
+                rentals(response_body),
+                vec![(books[0].name.clone(), 3), (books[1].name.clone(), 2)]
+            );
+
+            let response = get(&router, "/api/v1/book/trending?hours=72&limit=1").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<TopBooksBody>(response).await;
+            assert_eq!(rentals(response_body), vec![(books[2].name.clone(), 5)]);
+
+            let response = get(&router, "/api/v1/book/trending?hours=6").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<TopBooksBody>(response).await;
+            assert_eq!(rentals(response_body), vec![(books[0].name.clone(), 3)]);
+
+            for hours in [0, 721] {
+                let response = get(&router, format!("/api/v1/book/trending?hours={hours}")).await;
+                assert_eq!(response.status(), 400);
+            }
+        }
+    );
+
+    sequential_test!(
+        async fn test_popular_books_this_month() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let fake_user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert user");
+            let mut books = Vec::new();
+            for _ in 0..3 {
+                let fake_book = Book::create_fake_book(&lib.pool).await;
+                book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                    .await
+                    .expect("failed to insert book");
+                books.push(fake_book);
+            }
+            rent_book_times(&lib.pool, &fake_user.nation_id, &books[0].name, 3).await;
+            rent_book_times(&lib.pool, &fake_user.nation_id, &books[1].name, 2).await;
+            // rentals of previous months are left out
+            let two_months_ago = (Utc::now() - chrono::Duration::days(62)).to_rfc3339();
+            for _ in 0..5 {
+                insert_rental_at(
+                    &lib.pool,
+                    &fake_user.nation_id,
+                    &books[2].name,
+                    &two_months_ago,
+                )
+                .await;
+            }
+            let router = lib.setup_router();
+
+            let response = get(&router, "/api/v1/book/popular-this-month?limit=5").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<TopBooksBody>(response).await;
+            assert_eq!(
+                response_body.books,
+                vec![
+                    TopBookRow {
+                        book_name: books[0].name.clone(),
+                        rental_count: 3,
+                    },
+                    TopBookRow {
+                        book_name: books[1].name.clone(),
+                        rental_count: 2,
+                    },
+                ]
+            );
+
+            let response = get(&router, "/api/v1/book/popular-this-month?limit=1").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<TopBooksBody>(response).await;
+            assert_eq!(response_body.books.len(), 1);
+            assert_eq!(response_body.books[0].book_name, books[0].name);
+        }
+    );
+
+    sequential_test!(
+        async fn test_delete_books() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let fake_user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert user");
+            let mut ids = Vec::new();
+            let mut names = Vec::new();
+            for _ in 0..2 {
+                let fake_book = Book::create_fake_book(&lib.pool).await;
+                let id = book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                    .await
+                    .expect("failed to insert book");
+                ids.push(id);
+                names.push(fake_book.name);
+            }
+            let router = lib.setup_router();
+            let uri = format!("/api/v1/user/rent/{}", encode(&fake_user.nation_id));
+            let rent_book = RentBook {
+                book_name: names[1].clone(),
+                due_date: "2999-01-01".to_owned(),
+            };
+            assert_eq!(post(&router, uri, &rent_book).await.status(), 201);
+            let bulk_delete = |ids: Vec<Uuid>| {
+                Request::builder()
+                    .method(Method::DELETE)
+                    .uri("/api/v1/book/bulk")
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(
+                        serde_json::to_vec(&BulkDeleteRequest { ids })
+                            .unwrap()
+                            .into(),
+                    )
+                    .expect("failed to build bulk delete request")
+            };
+
+            let missing_id = Uuid::from_u128(0);
+            let response =
+                send_request(&router, bulk_delete(vec![ids[0], ids[1], missing_id])).await;
+            assert_eq!(response.status(), 207);
+            let response_body = deserialize_response_body::<BulkDeleteBody>(response).await;
+            assert_eq!(
+                response_body.results,
+                vec![
+                    DeleteResult {
+                        id: ids[0],
+                        success: true,
+                        reason: None,
+                    },
+                    DeleteResult {
+                        id: ids[1],
+                        success: false,
+                        reason: Some("currently rented".to_owned()),
+                    },
+                    DeleteResult {
+                        id: missing_id,
+                        success: false,
+                        reason: Some("not found".to_owned()),
+                    },
+                ]
+            );
+            let response = get(&router, format!("/api/v1/book/{}", ids[0])).await;
+            assert_eq!(response.status(), 404);
+            let response = get(&router, format!("/api/v1/book/{}", ids[1])).await;
+            assert_eq!(response.status(), 200);
+
+            for ids in [Vec::new(), vec![missing_id; MAX_BULK_DELETE_BOOKS + 1]] {
+                let response = send_request(&router, bulk_delete(ids)).await;
+                assert_eq!(response.status(), 400);
+            }
+        }
+    );
+
+    sequential_test!(
+        async fn test_count_books() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            for status in [Status::Available, Status::Available, Status::Rented] {
+                let mut fake_book = Book::create_fake_book(&lib.pool).await;
+                fake_book.category = "count".to_owned();
+                fake_book.status = status;
+                book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                    .await
+                    .expect("failed to insert book");
+            }
+            let router = lib.setup_router();
+
+            for (uri, count) in [
+                ("/api/v1/book/count", 2),
+                ("/api/v1/book/count?category=count&status=rented", 1),
+                ("/api/v1/book/count?limit=1&offset=1", 2),
+                ("/api/v1/book/count?category=missing", 0),
+            ] {
+                let response = get(&router, uri).await;
+                assert_eq!(response.status(), 200);
+                let response_body = deserialize_response_body::<CountBody>(response).await;
+                assert_eq!(response_body.count, count, "{uri}");
+            }
+        }
+    );
 }
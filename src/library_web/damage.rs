@@ -0,0 +1,150 @@
+use super::LibraryWeb;
+use crate::helper::web::Response;
+use crate::library::damage::{self, DamageReportRow, FlagBookBody};
+use crate::library::error::LibraryError;
+use crate::middleware::library::LibraryId;
+use crate::telemetry::events::log_success;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Represents the body of a response when a book flagged as damaged.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct CreatedFlagBody {
+    pub info: FlagBookBody,
+    pub id: Uuid,
+}
+
+/// Represents the body of a response containing the damage reports of a book.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct FlagsBody {
+    pub flags: Vec<DamageReportRow>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/book/{book_id}/flag",
+    tag = "damage",
+    request_body = FlagBookBody,
+    params(
+        ("book_id"= Uuid, Path,),
+    ),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 201, description = "book flagged succesfully", body = CreatedFlagBody),
+        (status = 400, description = "Invalid report", body = ErrorBody),
+        (status = 404, description = "Book or user not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn flag_book(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(book_id): Path<Uuid>,
+    Json(report): Json<FlagBookBody>,
+) -> Response<CreatedFlagBody> {
+    report.validate().map_err(LibraryError::Invalid)?;
+    let report_id = damage::flag_book(&library_web.pool, &library_id, book_id, &report).await?;
+    let response = CreatedFlagBody {
+        info: report,
+        id: report_id,
+    };
+    log_success!(StatusCode::CREATED, "damage", "flag", book_id = %book_id, report_id = %report_id);
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/{book_id}/flags",
+    tag = "damage",
+    params(
+        ("book_id"= Uuid, Path,),
+    ),
+    responses(
+        (status = 200, description = "list damage reports of the book", body = FlagsBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn book_flags(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(book_id): Path<Uuid>,
+) -> Response<FlagsBody> {
+    let flags = damage::book_flags(&library_web.pool, &library_id, book_id).await?;
+    let response = FlagsBody { flags };
+    log_success!(
+        StatusCode::OK,
+        "damage",
+        "list",
+        book_id = %book_id,
+        count = response.flags.len()
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::book::{self, Book};
+    use crate::library::user::{self, User};
+    use crate::library_web::tests::{deserialize_response_body, get, post};
+    use crate::middleware::library::DEFAULT_LIBRARY_ID;
+    use crate::tests::sequential::sequential_test;
+
+    sequential_test!(
+        async fn test_flag_book_and_book_flags() {
+            let lib = LibraryWeb::new_test().await;
+
+            // insert user, book
+            let fake_user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert fake user");
+            let fake_book = Book::create_fake_book(&lib.pool).await;
+            let book_id = book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                .await
+                .expect("failed to insert fake book");
+
+            // flag book
+            let router = lib.setup_router();
+            let uri = format!("/api/v1/book/{book_id}/flag");
+            let request_body = FlagBookBody {
+                nation_id: fake_user.nation_id.clone(),
+                description: "water damage on the last pages".to_owned(),
+            };
+            let response = post(&router, &uri, &request_body).await;
+            assert_eq!(response.status(), 201);
+            let response_body = deserialize_response_body::<CreatedFlagBody>(response).await;
+            assert_eq!(response_body.info, request_body);
+            let report_id = response_body.id;
+
+            // the description is capped
+            let invalid_body = FlagBookBody {
+                description: "a".repeat(damage::MAX_DAMAGE_DESCRIPTION_LEN + 1),
+                ..request_body.clone()
+            };
+            let response = post(&router, &uri, &invalid_body).await;
+            assert_eq!(response.status(), 400);
+
+            // unknown book
+            let uri = format!("/api/v1/book/{}/flag", Uuid::nil());
+            let response = post(&router, uri, &request_body).await;
+            assert_eq!(response.status(), 404);
+
+            // book flags
+            let response = get(&router, format!("/api/v1/book/{book_id}/flags")).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<FlagsBody>(response).await;
+            assert_eq!(response_body.flags.len(), 1);
+            assert_eq!(response_body.flags[0].id, report_id);
+            assert_eq!(response_body.flags[0].description, request_body.description);
+        }
+    );
+}
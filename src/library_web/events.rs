@@ -0,0 +1,142 @@
+use super::LibraryWeb;
+use crate::library::book::Status;
+use crate::middleware::library::LibraryId;
+use axum::{
+    extract::{Query, State},
+    response::sse::{Event, KeepAlive, Sse},
+};
+use chrono::{DateTime, Utc};
+use futures::{future, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio_stream::wrappers::BroadcastStream;
+use utoipa::{IntoParams, ToSchema};
+
+/// Represents the query parameters for the book availability events.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, IntoParams)]
+pub struct BookAvailableQuery {
+    /// Only sends the events of the book with the given name, the events of
+    /// all the books if not given.
+    pub book_name: Option<String>,
+}
+
+/// Represents the data of an event sent when a book becomes available.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BookAvailableEvent {
+    pub book_name: String,
+    pub available_at: DateTime<Utc>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/events/book-available",
+    tag = "book",
+    params(
+        BookAvailableQuery
+    ),
+    responses(
+        (status = 200, description = "Server-Sent Events stream, an event is sent each time a book of the library becomes available",
+            body = BookAvailableEvent, content_type = "text/event-stream")
+    )
+)]
+pub async fn book_available(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Query(query): Query<BookAvailableQuery>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    // the events skipped by a client not keeping up are dropped
+    let events =
+        BroadcastStream::new(library_web.book_events.subscribe()).filter_map(move |event| {
+            let event = event.ok().filter(|event| {
+                event.library_id == library_id
+                    && event.status == Status::Available
+                    && query
+                        .book_name
+                        .as_ref()
+                        .map_or(true, |book_name| *book_name == event.book_name)
+            });
+            future::ready(event.map(|event| {
+                Event::default().json_data(BookAvailableEvent {
+                    book_name: event.book_name,
+                    available_at: event.updated_at,
+                })
+            }))
+        });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library_web::tests::send_request;
+    use crate::library_web::ws::BookStatusEvent;
+    use crate::middleware::library::DEFAULT_LIBRARY_ID;
+    use crate::tests::sequential::sequential_test;
+    use axum::http::{
+        header::{ACCEPT, CONTENT_TYPE},
+        Method, Request,
+    };
+    use hyper::body::HttpBody;
+    use std::time::Duration;
+    use urlencoding::encode;
+
+    sequential_test!(
+        async fn test_book_available() {
+            let lib = LibraryWeb::new_test().await;
+            let router = lib.clone().setup_router();
+            let book_name = format!("book-{}", uuid::Uuid::new_v4());
+
+            let request = Request::builder()
+                .method(Method::GET)
+                .uri(format!(
+                    "/api/v1/events/book-available?book_name={}",
+                    encode(&book_name)
+                ))
+                .header(ACCEPT, "text/event-stream")
+                .body(hyper::Body::empty())
+                .expect("failed to build GET request");
+            let response = send_request(&router, request).await;
+            assert_eq!(response.status(), 200);
+            assert_eq!(response.headers()[CONTENT_TYPE], "text/event-stream");
+
+            // only the book becoming available is sent
+            lib.send_book_event(BookStatusEvent::new(
+                DEFAULT_LIBRARY_ID,
+                &book_name,
+                Status::Rented,
+            ));
+            lib.send_book_event(BookStatusEvent::new(
+                DEFAULT_LIBRARY_ID,
+                "another book",
+                Status::Available,
+            ));
+            lib.send_book_event(BookStatusEvent::new(
+                "another-library",
+                &book_name,
+                Status::Available,
+            ));
+            let available = BookStatusEvent::new(DEFAULT_LIBRARY_ID, &book_name, Status::Available);
+            lib.send_book_event(available.clone());
+
+            let mut body = response.into_body();
+            let chunk = tokio::time::timeout(Duration::from_secs(5), body.data())
+                .await
+                .expect("no event received")
+                .expect("stream closed")
+                .expect("failed to read event");
+            let chunk = std::str::from_utf8(&chunk).expect("event is not UTF-8");
+            let data = chunk
+                .trim()
+                .strip_prefix("data:")
+                .expect("event without data");
+            let event: BookAvailableEvent =
+                serde_json::from_str(data.trim()).expect("failed to deserialize event");
+            assert_eq!(
+                event,
+                BookAvailableEvent {
+                    book_name,
+                    available_at: available.updated_at,
+                }
+            );
+        }
+    );
+}
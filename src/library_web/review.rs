@@ -0,0 +1,267 @@
+use super::LibraryWeb;
+use crate::helper::web::Response;
+use crate::library::error::LibraryError;
+use crate::library::review::{self, RatingBody, ReviewBody, ReviewQuery, ReviewRow};
+use crate::middleware::library::LibraryId;
+use crate::telemetry::events::log_success;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Represents the body of a response when a book rated.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct CreatedRatingBody {
+    pub info: RatingBody,
+    pub id: Uuid,
+}
+
+/// Represents the body of a response when a book reviewed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct CreatedReviewBody {
+    pub info: ReviewBody,
+    pub id: Uuid,
+}
+
+/// Represents the body of a response containing a page of reviews.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ReviewsBody {
+    pub reviews: Vec<ReviewRow>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/book/{book_id}/rate",
+    tag = "review",
+    request_body = RatingBody,
+    params(
+        ("book_id"= Uuid, Path,),
+    ),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 201, description = "book rated succesfully", body = CreatedRatingBody),
+        (status = 400, description = "Invalid rating", body = ErrorBody),
+        (status = 404, description = "Book not found", body = ErrorBody),
+        (status = 409, description = "Book already reviewed by the user", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn rate_book(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(book_id): Path<Uuid>,
+    Json(rating): Json<RatingBody>,
+) -> Response<CreatedRatingBody> {
+    let review = ReviewBody::from(rating.clone());
+    review.validate().map_err(LibraryError::Invalid)?;
+    let review_id = review::insert_review(&library_web.pool, &library_id, book_id, &review).await?;
+    let response = CreatedRatingBody {
+        info: rating,
+        id: review_id,
+    };
+    log_success!(StatusCode::CREATED, "review", "rate", book_id = %book_id, review_id = %review_id);
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/book/{book_id}/review",
+    tag = "review",
+    request_body = ReviewBody,
+    params(
+        ("book_id"= Uuid, Path,),
+    ),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 201, description = "book reviewed succesfully", body = CreatedReviewBody),
+        (status = 400, description = "Invalid review", body = ErrorBody),
+        (status = 404, description = "Book not found", body = ErrorBody),
+        (status = 409, description = "Book already reviewed by the user", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn review_book(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(book_id): Path<Uuid>,
+    Json(review): Json<ReviewBody>,
+) -> Response<CreatedReviewBody> {
+    review.validate().map_err(LibraryError::Invalid)?;
+    let review_id = review::insert_review(&library_web.pool, &library_id, book_id, &review).await?;
+    let response = CreatedReviewBody {
+        info: review,
+        id: review_id,
+    };
+    log_success!(
+        StatusCode::CREATED,
+        "review",
+        "create",
+        book_id = %book_id,
+        review_id = %review_id
+    );
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/{book_id}/reviews",
+    tag = "review",
+    params(
+        ("book_id"= Uuid, Path,),
+        ReviewQuery
+    ),
+    responses(
+        (status = 200, description = "list book reviews", body = ReviewsBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn reviews(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(book_id): Path<Uuid>,
+    Query(query): Query<ReviewQuery>,
+) -> Response<ReviewsBody> {
+    let reviews = review::reviews(&library_web.pool, &library_id, book_id, &query).await?;
+    let response = ReviewsBody { reviews };
+    log_success!(
+        StatusCode::OK,
+        "review",
+        "list",
+        book_id = %book_id,
+        count = response.reviews.len()
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::book::{self, Book};
+    use crate::library::user::{self, User};
+    use crate::library_web::book::GetBookBody;
+    use crate::library_web::tests::{deserialize_response_body, get, post};
+    use crate::middleware::library::DEFAULT_LIBRARY_ID;
+    use crate::tests::sequential::sequential_test;
+
+    sequential_test!(
+        async fn test_rate_book_and_get_book() {
+            let lib = LibraryWeb::new_test().await;
+
+            // insert user, book
+            let fake_user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert fake user");
+            let fake_book = Book::create_fake_book(&lib.pool).await;
+            let book_id = book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                .await
+                .expect("failed to insert fake book");
+
+            // rate book
+            let router = lib.setup_router();
+            let uri = format!("/api/v1/book/{book_id}/rate");
+            let request_body = RatingBody {
+                nation_id: fake_user.nation_id.clone(),
+                rating: 4,
+            };
+            let response = post(&router, &uri, &request_body).await;
+            assert_eq!(response.status(), 201);
+            let response_body = deserialize_response_body::<CreatedRatingBody>(response).await;
+            assert_eq!(response_body.info, request_body);
+
+            // the same user cannot rate twice
+            let response = post(&router, &uri, &request_body).await;
+            assert_eq!(response.status(), 409);
+
+            // get_book
+            let response = get(&router, format!("/api/v1/book/{book_id}")).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<GetBookBody>(response).await;
+            assert_eq!(response_body.average_rating, Some(4.0));
+            assert_eq!(response_body.review_count, 1);
+        }
+    );
+
+    sequential_test!(
+        async fn test_rate_book_invalid_rating() {
+            let lib = LibraryWeb::new_test().await;
+            let router = lib.setup_router();
+            let uri = format!("/api/v1/book/{}/rate", Uuid::nil());
+            let request_body = RatingBody {
+                nation_id: "12345678901".to_owned(),
+                rating: 6,
+            };
+            let response = post(&router, uri, &request_body).await;
+            assert_eq!(response.status(), 400);
+        }
+    );
+
+    sequential_test!(
+        async fn test_review_book_and_reviews() {
+            let lib = LibraryWeb::new_test().await;
+
+            // insert users, book
+            let fake_user_1 = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user_1)
+                .await
+                .expect("failed to insert fake user");
+            let fake_user_2 = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user_2)
+                .await
+                .expect("failed to insert fake user");
+            let fake_book = Book::create_fake_book(&lib.pool).await;
+            let book_id = book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                .await
+                .expect("failed to insert fake book");
+
+            // review book
+            let router = lib.setup_router();
+            let uri = format!("/api/v1/book/{book_id}/review");
+            for (fake_user, comment) in [(&fake_user_1, None), (&fake_user_2, Some("nice"))] {
+                let request_body = ReviewBody {
+                    nation_id: fake_user.nation_id.clone(),
+                    rating: 3,
+                    comment: comment.map(str::to_owned),
+                };
+                let response = post(&router, &uri, &request_body).await;
+                assert_eq!(response.status(), 201);
+                let response_body = deserialize_response_body::<CreatedReviewBody>(response).await;
+                assert_eq!(response_body.info, request_body);
+            }
+
+            // too long comment
+            let request_body = ReviewBody {
+                nation_id: fake_user_1.nation_id.clone(),
+                rating: 3,
+                comment: Some("a".repeat(review::MAX_COMMENT_LEN + 1)),
+            };
+            let response = post(&router, &uri, &request_body).await;
+            assert_eq!(response.status(), 400);
+
+            // reviews
+            let response = get(&router, format!("/api/v1/book/{book_id}/reviews")).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<ReviewsBody>(response).await;
+            assert_eq!(response_body.reviews.len(), 2);
+            assert!(response_body
+                .reviews
+                .iter()
+                .any(|review| review.nation_id == fake_user_2.nation_id
+                    && review.comment.as_deref() == Some("nice")));
+
+            let uri = format!("/api/v1/book/{book_id}/reviews?limit=1&offset=1");
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<ReviewsBody>(response).await;
+            assert_eq!(response_body.reviews.len(), 1);
+        }
+    );
+}
@@ -0,0 +1,325 @@
+use super::LibraryWeb;
+use crate::helper::web::Response;
+use crate::library::error::LibraryError;
+use crate::library::stats::{
+    self, CategoryStat, MonthlyRentalStat, MonthlyRentalsQuery, TopBookRow, TopQuery, TopUserRow,
+};
+use crate::middleware::library::LibraryId;
+use crate::telemetry::events::log_success;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Represents the body of a response containing the most rented books.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TopBooksBody {
+    pub books: Vec<TopBookRow>,
+}
+
+/// Represents the body of a response containing the most active users.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TopUsersBody {
+    pub users: Vec<TopUserRow>,
+}
+
+/// Represents the body of a response containing the books per category.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct CategoryDistributionBody {
+    pub categories: Vec<CategoryStat>,
+}
+
+/// Represents the body of a response containing the rental counts per month.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct MonthlyRentalsBody {
+    pub year: i32,
+    pub months: Vec<MonthlyRentalStat>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/stats/top-books",
+    tag = "stats",
+    params(
+        TopQuery
+    ),
+    responses(
+        (status = 200, description = "list most rented books", body = TopBooksBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn top_books(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Query(query): Query<TopQuery>,
+) -> Response<TopBooksBody> {
+    let books = stats::top_books(&library_web.pool, &library_id, query.limit_or_default()).await?;
+    let response = TopBooksBody { books };
+    log_success!(
+        StatusCode::OK,
+        "stats",
+        "top_books",
+        count = response.books.len()
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/stats/top-users",
+    tag = "stats",
+    params(
+        TopQuery
+    ),
+    responses(
+        (status = 200, description = "list most active users", body = TopUsersBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn top_users(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Query(query): Query<TopQuery>,
+) -> Response<TopUsersBody> {
+    let users = stats::top_users(&library_web.pool, &library_id, query.limit_or_default()).await?;
+    let response = TopUsersBody { users };
+    log_success!(
+        StatusCode::OK,
+        "stats",
+        "top_users",
+        count = response.users.len()
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/stats/category-distribution",
+    tag = "stats",
+    responses(
+        (status = 200, description = "list books per category", body = CategoryDistributionBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn category_distribution(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+) -> Response<CategoryDistributionBody> {
+    let categories = stats::category_distribution(&library_web.pool, &library_id).await?;
+    let response = CategoryDistributionBody { categories };
+    log_success!(StatusCode::OK, "stats", "category_distribution");
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/stats/monthly-rentals",
+    tag = "stats",
+    params(
+        MonthlyRentalsQuery
+    ),
+    responses(
+        (status = 200, description = "list rental counts per month", body = MonthlyRentalsBody),
+        (status = 400, description = "Invalid year", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn monthly_rentals(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Query(query): Query<MonthlyRentalsQuery>,
+) -> Response<MonthlyRentalsBody> {
+    query.validate().map_err(LibraryError::Invalid)?;
+    let months = stats::monthly_rentals(&library_web.pool, &library_id, query.year).await?;
+    let response = MonthlyRentalsBody {
+        year: query.year,
+        months,
+    };
+    log_success!(
+        StatusCode::OK,
+        "stats",
+        "monthly_rentals",
+        year = response.year
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::book::{self, Book, Status};
+    use crate::library::stats::tests::{insert_rental_at, rent_book_times};
+    use crate::library::user::{self, User};
+    use crate::library_web::tests::{deserialize_response_body, get, post};
+    use crate::middleware::library::DEFAULT_LIBRARY_ID;
+    use crate::tests::sequential::sequential_test;
+
+    sequential_test!(
+        async fn test_top_books() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &user)
+                .await
+                .expect("failed to insert user");
+            let popular_book = Book::create_fake_book(&lib.pool).await;
+            let other_book = Book::create_fake_book(&lib.pool).await;
+            for book in [&popular_book, &other_book] {
+                book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, book)
+                    .await
+                    .expect("failed to insert book");
+            }
+            rent_book_times(&lib.pool, &user.nation_id, &popular_book.name, 8).await;
+            rent_book_times(&lib.pool, &user.nation_id, &other_book.name, 7).await;
+            let router = lib.setup_router();
+
+            let response = get(&router, "/api/v1/stats/top-books?limit=50").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<TopBooksBody>(response).await;
+            assert_eq!(
+                response_body.books,
+                vec![
+                    TopBookRow {
+                        book_name: popular_book.name.clone(),
+                        rental_count: 8,
+                    },
+                    TopBookRow {
+                        book_name: other_book.name,
+                        rental_count: 7,
+                    },
+                ]
+            );
+
+            let response = get(&router, "/api/v1/stats/top-books?limit=1").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<TopBooksBody>(response).await;
+            assert_eq!(response_body.books.len(), 1);
+            assert_eq!(response_body.books[0].book_name, popular_book.name);
+        }
+    );
+
+    sequential_test!(
+        async fn test_top_users() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let active_user = User::create_fake_user().await;
+            let other_user = User::create_fake_user().await;
+            for user in [&active_user, &other_user] {
+                user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, user)
+                    .await
+                    .expect("failed to insert user");
+            }
+            let book = Book::create_fake_book(&lib.pool).await;
+            book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+            rent_book_times(&lib.pool, &active_user.nation_id, &book.name, 4).await;
+            rent_book_times(&lib.pool, &other_user.nation_id, &book.name, 2).await;
+            let router = lib.setup_router();
+
+            let response = get(&router, "/api/v1/stats/top-users?limit=50").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<TopUsersBody>(response).await;
+            assert_eq!(
+                response_body.users,
+                vec![
+                    TopUserRow {
+                        nation_id: active_user.nation_id,
+                        user_name: active_user.name,
+                        rental_count: 4,
+                    },
+                    TopUserRow {
+                        nation_id: other_user.nation_id,
+                        user_name: other_user.name,
+                        rental_count: 2,
+                    },
+                ]
+            );
+        }
+    );
+
+    sequential_test!(
+        async fn test_category_distribution() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let category = format!("category {}", User::create_fake_user().await.nation_id);
+            let mut books = Vec::new();
+            for status in [Status::Available, Status::Rented, Status::Rented] {
+                let mut book = Book::create_fake_book(&lib.pool).await;
+                book.category = category.clone();
+                book.status = status;
+                books.push(book);
+            }
+            let router = lib.setup_router();
+            for book in &books {
+                let response = post(&router, "/api/v1/book/create", book).await;
+                assert_eq!(response.status(), 201);
+            }
+
+            let response = get(&router, "/api/v1/stats/category-distribution").await;
+            assert_eq!(response.status(), 200);
+            let response_body =
+                deserialize_response_body::<CategoryDistributionBody>(response).await;
+            assert_eq!(
+                response_body.categories,
+                vec![CategoryStat {
+                    category,
+                    total: 3,
+                    available: 1,
+                    rented: 2,
+                }]
+            );
+        }
+    );
+
+    sequential_test!(
+        async fn test_monthly_rentals() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &user)
+                .await
+                .expect("failed to insert user");
+            let book = Book::create_fake_book(&lib.pool).await;
+            book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+            let router = lib.clone().setup_router();
+
+            let response = get(&router, "/api/v1/stats/monthly-rentals?year=2004").await;
+            assert_eq!(response.status(), 200);
+            let before = deserialize_response_body::<MonthlyRentalsBody>(response).await;
+            assert_eq!(before.year, 2004);
+            assert_eq!(before.months.len(), 12);
+            assert!(before.months.iter().all(|month| month.rental_count == 0));
+            insert_rental_at(
+                &lib.pool,
+                &user.nation_id,
+                &book.name,
+                "2004-06-10T08:00:00Z",
+            )
+            .await;
+            let response = get(&router, "/api/v1/stats/monthly-rentals?year=2004").await;
+            assert_eq!(response.status(), 200);
+            let after = deserialize_response_body::<MonthlyRentalsBody>(response).await;
+            assert_eq!(after.months[5].month, 6);
+            assert_eq!(after.months[5].rental_count, 1);
+            assert_eq!(
+                after
+                    .months
+                    .iter()
+                    .map(|month| month.rental_count)
+                    .sum::<i64>(),
+                1
+            );
+
+            for uri in [
+                "/api/v1/stats/monthly-rentals?year=1999",
+                "/api/v1/stats/monthly-rentals?year=9999",
+                "/api/v1/stats/monthly-rentals",
+            ] {
+                let response = get(&router, uri).await;
+                assert_eq!(response.status(), 400);
+            }
+        }
+    );
+}
@@ -0,0 +1,222 @@
+use super::LibraryWeb;
+use crate::helper::web::Response;
+use crate::library::book;
+use crate::library::error::LibraryError;
+use crate::library::tag::{self, TagBookBody, TagRow};
+use crate::middleware::library::LibraryId;
+use crate::telemetry::events::log_success;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Represents the body of a response containing the tags of a book.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BookTagsBody {
+    pub tags: Vec<String>,
+}
+
+/// Represents the body of a response containing the tags of the library.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TagsBody {
+    pub tags: Vec<TagRow>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/book/{book_id}/tags",
+    tag = "tag",
+    params(
+        ("book_id"= Uuid, Path,),
+    ),
+    responses(
+        (status = 200, description = "list book tags", body = BookTagsBody),
+        (status = 404, description = "Book not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn book_tags(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(book_id): Path<Uuid>,
+) -> Response<BookTagsBody> {
+    book::get_book(&library_web.pool, &library_id, book_id).await?;
+    let tags = tag::book_tags(&library_web.pool, &library_id, book_id).await?;
+    let response = BookTagsBody { tags };
+    log_success!(
+        StatusCode::OK,
+        "tag",
+        "list_book",
+        book_id = %book_id,
+        count = response.tags.len()
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/book/{book_id}/tags",
+    tag = "tag",
+    request_body = TagBookBody,
+    params(
+        ("book_id"= Uuid, Path,),
+    ),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "book tagged succesfully, returns all the tags of the book", body = BookTagsBody),
+        (status = 400, description = "Invalid tags", body = ErrorBody),
+        (status = 404, description = "Book not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn tag_book(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(book_id): Path<Uuid>,
+    Json(body): Json<TagBookBody>,
+) -> Response<BookTagsBody> {
+    body.validate().map_err(LibraryError::Invalid)?;
+    tag::tag_book(&library_web.pool, &library_id, book_id, &body.normalized()).await?;
+    let tags = tag::book_tags(&library_web.pool, &library_id, book_id).await?;
+    let response = BookTagsBody { tags };
+    log_success!(
+        StatusCode::OK,
+        "tag",
+        "tag_book",
+        book_id = %book_id,
+        count = response.tags.len()
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/book/{book_id}/tags/{tag}",
+    tag = "tag",
+    params(
+        ("book_id"= Uuid, Path,),
+        ("tag"= String, Path,),
+    ),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "tag removed succesfully, returns the remaining tags of the book", body = BookTagsBody),
+        (status = 404, description = "Book or tag not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn untag_book(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path((book_id, tag_name)): Path<(Uuid, String)>,
+) -> Response<BookTagsBody> {
+    tag::untag_book(&library_web.pool, &library_id, book_id, &tag_name).await?;
+    let tags = tag::book_tags(&library_web.pool, &library_id, book_id).await?;
+    let response = BookTagsBody { tags };
+    log_success!(
+        StatusCode::OK,
+        "tag",
+        "untag_book",
+        book_id = %book_id,
+        tag = %tag_name
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/tag",
+    tag = "tag",
+    responses(
+        (status = 200, description = "list tags with their number of books", body = TagsBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn tags(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+) -> Response<TagsBody> {
+    let tags = tag::tags(&library_web.pool, &library_id).await?;
+    let response = TagsBody { tags };
+    log_success!(StatusCode::OK, "tag", "list", count = response.tags.len());
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::book::Book;
+    use crate::library_web::tests::{delete, deserialize_response_body, get, post};
+    use crate::middleware::library::DEFAULT_LIBRARY_ID;
+    use crate::tests::sequential::sequential_test;
+
+    sequential_test!(
+        async fn test_book_tags() {
+            let lib = LibraryWeb::new_test().await;
+
+            // insert book
+            let fake_book = Book::create_fake_book(&lib.pool).await;
+            let book_id = book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                .await
+                .expect("failed to insert fake book");
+
+            // tag book
+            let router = lib.setup_router();
+            let uri = format!("/api/v1/book/{book_id}/tags");
+            let tag_1 = format!("tag-{}", Uuid::new_v4());
+            let tag_2 = format!("tag-{}", Uuid::new_v4());
+            let request_body = TagBookBody {
+                tags: vec![tag_1.to_uppercase(), format!(" {tag_2} ")],
+            };
+            let response = post(&router, &uri, &request_body).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BookTagsBody>(response).await;
+            let mut expected = vec![tag_1.clone(), tag_2.clone()];
+            expected.sort();
+            assert_eq!(response_body.tags, expected);
+
+            // invalid tags, unknown book
+            let response = post(&router, &uri, &TagBookBody { tags: vec![] }).await;
+            assert_eq!(response.status(), 400);
+            let response = post(
+                &router,
+                format!("/api/v1/book/{}/tags", Uuid::nil()),
+                &request_body,
+            )
+            .await;
+            assert_eq!(response.status(), 404);
+
+            // book tags
+            let response = get(&router, &uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BookTagsBody>(response).await;
+            assert_eq!(response_body.tags, expected);
+            let response = get(&router, format!("/api/v1/book/{}/tags", Uuid::nil())).await;
+            assert_eq!(response.status(), 404);
+
+            // tags
+            let response = get(&router, "/api/v1/tag").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<TagsBody>(response).await;
+            assert!(response_body
+                .tags
+                .iter()
+                .any(|tag| tag.name == tag_1 && tag.book_count == 1));
+
+            // untag book
+            let response = delete(&router, format!("{uri}/{tag_1}")).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BookTagsBody>(response).await;
+            assert_eq!(response_body.tags, vec![tag_2.clone()]);
+            let response = delete(&router, format!("{uri}/{tag_1}")).await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+}
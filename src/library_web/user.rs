@@ -1,14 +1,25 @@
 use super::LibraryWeb;
-use crate::helper::web::{internal_server_error, Response};
+use crate::helper::pagination::{add_link_headers, base_url, PaginatedResponse};
+use crate::helper::web::{ErrorBody, ErrorResponse, Response, ServerErr};
+use crate::library::book::{self, RecommendationsQuery, Status};
+use crate::library::error::LibraryError;
 use crate::library::user::{
-    self, RentBook, User, UserHistoryRow, UserQuery, UserRentBook, UserRow,
+    self, ActiveUsersQuery, BlacklistedUserRow, BlacklistedUsersQuery, InactiveUserRow,
+    InactiveUsersQuery, RentBook, RentBooks, RentBooksOutcome, User, UserHistoryRow, UserQuery,
+    UserRentBook, UserRow, UserStats, UserUpdate,
 };
+use crate::library_web::book::{BooksBody, CountBody};
+use crate::library_web::ws::BookStatusEvent;
+use crate::middleware::library::LibraryId;
+use crate::telemetry::events::log_success;
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
+    extract::{OriginalUri, Path, Query, State},
+    http::{HeaderMap, StatusCode},
     response::Json,
 };
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
+use tracing::{field::Empty, Span};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
@@ -23,6 +34,23 @@ pub struct CreatedUserBody {
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub struct UsersBody {
     pub users: Vec<UserRow>,
+    /// The total number of matching rows, set when listing the active users.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<i64>,
+}
+
+/// Represents the body of a response containing inactive users.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct InactiveUsersBody {
+    pub users: Vec<InactiveUserRow>,
+    /// The total number of inactive users.
+    pub total: i64,
+}
+
+/// Represents the body of a response containing blacklisted users.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BlacklistedUsersBody {
+    pub users: Vec<BlacklistedUserRow>,
 }
 
 /// Represents the body of a response containing a user's history.
@@ -31,6 +59,43 @@ pub struct GetUserBody {
     pub user: Vec<UserHistoryRow>,
 }
 
+/// Represents the body of a response containing a user's rental statistics.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct UserStatsBody {
+    pub stats: UserStats,
+}
+
+/// Represents the body of a response containing the overdue rentals of a user.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct OverdueRentalsBody {
+    pub rentals: Vec<UserHistoryRow>,
+}
+
+/// Represents the body of a response when a user updated.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct UpdatedUserBody {
+    pub info: User,
+}
+
+/// Represents the body of a request to correct a user's national ID.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ChangeNationIdBody {
+    pub new_nation_id: String,
+}
+
+/// Represents the body of a request to transfer a rental to another user.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TransferBody {
+    pub to_nation_id: String,
+}
+
+/// Represents the body of a response when a user deleted or restored.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct UserMessageBody {
+    pub message: String,
+    pub nation_id: String,
+}
+
 /// Represents the body of a response when a user rents a book.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub struct RentedBookBody {
@@ -38,236 +103,1802 @@ pub struct RentedBookBody {
     pub info: UserRentBook,
 }
 
+/// Represents the body of a response when a user rents several books.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct RentedBooksBody {
+    pub message: String,
+    pub books: Vec<UserRentBook>,
+}
+
 #[utoipa::path(
     post,
-    path = "/api/user/create",
+    path = "/api/v1/user/create",
     tag = "user",
     request_body = User,
+    security(
+        ("BearerAuth" = [])
+    ),
     responses(
         (status = 201, description = "user created succesfully", body = CreatedUserBody),
-        (status = 500, description = "Internal server error", body = String)
+        (status = 400, description = "Invalid user", body = ErrorBody),
+        (status = 409, description = "User already exists", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
     )
 )]
+#[tracing::instrument(skip_all, fields(user.nation_id = Empty))]
 pub async fn create_user(
     State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
     Json(user): Json<User>,
 ) -> Response<CreatedUserBody> {
-    let Ok(user_id) = user::insert_user(&library_web.pool, &user).await else {
-        return internal_server_error().await;
-    };
+    Span::current().record("user.nation_id", user.nation_id.as_str());
+    user.validate().map_err(LibraryError::Invalid)?;
+    let user_id = user::insert_user(&library_web.pool, &library_id, &user).await?;
     let response = CreatedUserBody {
         info: user,
         id: user_id,
     };
-    (StatusCode::CREATED, Ok(Json(response)))
+    log_success!(StatusCode::CREATED, "user", "create", user_id = %user_id);
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Represents the body of a request to create a user renting a book.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct RegisterAndRentBody {
+    pub user: User,
+    pub book: RentBook,
+}
+
+/// Represents the body of a response when a user is created renting a book.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct RegisteredBody {
+    pub user_id: Uuid,
+    pub rental: UserRentBook,
 }
 
 #[utoipa::path(
     post,
-    path = "/api/user/rent",
+    path = "/api/v1/user/register",
+    tag = "user",
+    request_body = RegisterAndRentBody,
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 201, description = "user created and book rented succesfully", body = RegisteredBody),
+        (status = 400, description = "Invalid user", body = ErrorBody),
+        (status = 404, description = "Book not available, the user is not created", body = ErrorBody),
+        (status = 409, description = "User already exists", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(skip_all, fields(user.nation_id = Empty, book.name = Empty))]
+pub async fn register_and_rent(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Json(body): Json<RegisterAndRentBody>,
+) -> Response<RegisteredBody> {
+    Span::current()
+        .record("user.nation_id", body.user.nation_id.as_str())
+        .record("book.name", body.book.book_name.as_str());
+    body.user.validate().map_err(LibraryError::Invalid)?;
+    let (user_id, rental) =
+        user::register_and_rent(&library_web.pool, &library_id, &body.user, &body.book).await?;
+    library_web.send_book_event(BookStatusEvent::new(
+        &library_id,
+        &rental.book_name,
+        Status::Rented,
+    ));
+    let response = RegisteredBody { user_id, rental };
+    log_success!(StatusCode::CREATED, "user", "register", user_id = %user_id);
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/rent",
     tag = "user",
     request_body = RentBook,
     params(
         ("nation_id" = String, Path,),
+        ("X-Idempotency-Key" = Option<Uuid>, Header, description = "Key to safely retry the request with"),
+    ),
+    security(
+        ("BearerAuth" = [])
     ),
     responses(
+        (status = 200, description = "request with the idempotency key already handled", body = RentedBookBody),
         (status = 201, description = "book rented succesfully", body = RentedBookBody),
-        (status = 500, description = "Internal server error", body = String)
+        (status = 400, description = "Invalid idempotency key", body = ErrorBody),
+        (status = 403, description = "User is blacklisted", body = ErrorBody),
+        (status = 404, description = "Book not available or user not found", body = ErrorBody),
+        (status = 409, description = "request with the idempotency key in progress", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
     )
 )]
+#[tracing::instrument(skip_all, fields(user.nation_id = Empty, book.name = Empty))]
 pub async fn rent_book(
     State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
     Path(nation_id): Path<String>,
     Json(book): Json<RentBook>,
 ) -> Response<RentedBookBody> {
+    Span::current()
+        .record("user.nation_id", nation_id.as_str())
+        .record("book.name", book.book_name.as_str());
     let info = UserRentBook {
         nation_id,
         book_name: book.book_name,
         due_date: book.due_date,
     };
-    if user::rent_book(&library_web.pool, &info).await.is_err() {
-        return internal_server_error().await;
-    };
+    user::rent_book(&library_web.pool, &library_id, &info).await?;
+    library_web.send_book_event(BookStatusEvent::new(
+        &library_id,
+        &info.book_name,
+        Status::Rented,
+    ));
     let response = RentedBookBody {
         message: "successfully book rented".to_owned(),
         info,
     };
-    (StatusCode::CREATED, Ok(Json(response)))
+    log_success!(StatusCode::CREATED, "user", "rent");
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/rent/{nation_id}/batch",
+    tag = "user",
+    request_body = RentBooks,
+    params(
+        ("nation_id" = String, Path,),
+        ("X-Idempotency-Key" = Option<Uuid>, Header, description = "Key to safely retry the request with"),
+    ),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "request with the idempotency key already handled", body = RentedBooksBody),
+        (status = 201, description = "books rented succesfully", body = RentedBooksBody),
+        (status = 400, description = "Invalid number of books or idempotency key", body = ErrorBody),
+        (status = 403, description = "User is blacklisted", body = ErrorBody),
+        (status = 404, description = "User not found", body = ErrorBody),
+        (status = 409, description = "Some books not available, none rented", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(skip_all, fields(user.nation_id = Empty, books.count = Empty))]
+pub async fn rent_books(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(nation_id): Path<String>,
+    Json(body): Json<RentBooks>,
+) -> Response<RentedBooksBody> {
+    Span::current()
+        .record("user.nation_id", nation_id.as_str())
+        .record("books.count", body.books.len());
+    body.validate().map_err(LibraryError::Invalid)?;
+    match user::rent_books(&library_web.pool, &library_id, &nation_id, &body.books).await? {
+        RentBooksOutcome::Rented(books) => {
+            for book in &books {
+                library_web.send_book_event(BookStatusEvent::new(
+                    &library_id,
+                    &book.book_name,
+                    Status::Rented,
+                ));
+            }
+            let response = RentedBooksBody {
+                message: "successfully books rented".to_owned(),
+                books,
+            };
+            log_success!(
+                StatusCode::CREATED,
+                "user",
+                "rent_batch",
+                count = response.books.len()
+            );
+            Ok((StatusCode::CREATED, Json(response)))
+        }
+        RentBooksOutcome::Unavailable(book_names) => Err((
+            StatusCode::CONFLICT,
+            Json(ErrorBody {
+                error: ServerErr::Conflict,
+                message: Some(format!("books not available: {}", book_names.join(", "))),
+            }),
+        )),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/user/rent/{nation_id}/{book_name}/transfer",
+    tag = "user",
+    request_body = TransferBody,
+    params(
+        ("nation_id" = String, Path,),
+        ("book_name" = String, Path,),
+    ),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "rental transferred succesfully", body = UserMessageBody),
+        (status = 400, description = "Rental transferred to the same user", body = ErrorBody),
+        (status = 403, description = "User the rental is transferred to is blacklisted", body = ErrorBody),
+        (status = 404, description = "User not found or book not rented by the user", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(
+    skip_all,
+    fields(user.nation_id = Empty, user.to_nation_id = Empty, book.name = Empty)
+)]
+pub async fn transfer_rental(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path((nation_id, book_name)): Path<(String, String)>,
+    Json(body): Json<TransferBody>,
+) -> Response<UserMessageBody> {
+    Span::current()
+        .record("user.nation_id", nation_id.as_str())
+        .record("user.to_nation_id", body.to_nation_id.as_str())
+        .record("book.name", book_name.as_str());
+    if body.to_nation_id == nation_id {
+        return Err(LibraryError::Invalid(
+            "to_nation_id must differ from the current renter".to_owned(),
+        )
+        .into());
+    }
+    user::transfer_rental(
+        &library_web.pool,
+        &library_id,
+        &nation_id,
+        &body.to_nation_id,
+        &book_name,
+    )
+    .await?;
+    let response = UserMessageBody {
+        message: "successfully rental transferred".to_owned(),
+        nation_id: body.to_nation_id,
+    };
+    log_success!(StatusCode::OK, "user", "transfer_rental");
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Represents the body of a request to set the due date of a rental.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct SetDueDateBody {
+    /// The new due date, formatted as `YYYY-MM-DD`.
+    pub due_date: String,
+}
+
+/// Changes the due date of a rental.
+///
+/// Only admins can change due dates, other requests are rejected with
+/// `403 Forbidden`. A request is sent by an admin when its `X-User-Role`
+/// header is `admin`.
+#[utoipa::path(
+    put,
+    path = "/api/v1/user/rent/{nation_id}/{book_name}/due-date",
+    tag = "user",
+    request_body = SetDueDateBody,
+    params(
+        ("nation_id" = String, Path,),
+        ("book_name" = String, Path,),
+    ),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "due date changed succesfully", body = UserMessageBody),
+        (status = 400, description = "Invalid due date", body = ErrorBody),
+        (status = 403, description = "Not sent by an admin", body = ErrorBody),
+        (status = 404, description = "Book not rented by the user", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(skip_all, fields(user.nation_id = Empty, book.name = Empty))]
+pub async fn set_due_date(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path((nation_id, book_name)): Path<(String, String)>,
+    Json(body): Json<SetDueDateBody>,
+) -> Response<UserMessageBody> {
+    Span::current()
+        .record("user.nation_id", nation_id.as_str())
+        .record("book.name", book_name.as_str());
+    let due_date = NaiveDate::parse_from_str(&body.due_date, "%Y-%m-%d").map_err(|_| {
+        LibraryError::Invalid("due_date must be a date formatted as YYYY-MM-DD".to_owned())
+    })?;
+    user::set_due_date(
+        &library_web.pool,
+        &library_id,
+        &nation_id,
+        &book_name,
+        due_date,
+    )
+    .await?;
+    let response = UserMessageBody {
+        message: "successfully due date changed".to_owned(),
+        nation_id,
+    };
+    log_success!(StatusCode::OK, "user", "set_due_date", due_date = %due_date);
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/rent/{nation_id}/{book_name}/return",
+    tag = "user",
+    params(
+        ("nation_id" = String, Path,),
+        ("book_name" = String, Path,),
+    ),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "book returned succesfully, the copy is available again", body = UserMessageBody),
+        (status = 404, description = "Book not rented by the user", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(skip_all, fields(user.nation_id = Empty, book.name = Empty))]
+pub async fn return_book(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path((nation_id, book_name)): Path<(String, String)>,
+) -> Response<UserMessageBody> {
+    Span::current()
+        .record("user.nation_id", nation_id.as_str())
+        .record("book.name", book_name.as_str());
+    user::return_book(&library_web.pool, &library_id, &nation_id, &book_name).await?;
+    library_web.send_book_event(BookStatusEvent::new(
+        &library_id,
+        &book_name,
+        Status::Available,
+    ));
+    let response = UserMessageBody {
+        message: "successfully book returned".to_owned(),
+        nation_id,
+    };
+    log_success!(StatusCode::OK, "user", "return_book");
+    Ok((StatusCode::OK, Json(response)))
 }
 
 #[utoipa::path(
     get,
-    path = "/api/user",
+    path = "/api/v1/user",
     tag = "user",
     params(
         UserQuery
     ),
     responses(
-        (status = 200, description = "list matching users", body = UsersBody),
-        (status = 500, description = "Internal server error", body = String)
+        (status = 200, description = "list matching users", body = UsersBody,
+            headers(("Link" = String, description = "Links to the first, previous, next and last pages, set when paginating with `limit` and `offset`"))),
+        (status = 404, description = "No user found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
     )
 )]
+#[tracing::instrument(skip_all, fields(query.user_name = Empty, query.book_name = Empty))]
 pub async fn users(
     State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
     Query(user): Query<UserQuery>,
+) -> PaginatedResponse<UsersBody> {
+    Span::current()
+        .record("query.user_name", user.user_name.as_deref())
+        .record("query.book_name", user.book_name.as_deref());
+    let users = user::users(&library_web.pool, &library_id, &user).await?;
+    let mut response_headers = HeaderMap::new();
+    if let Some(limit) = user.limit {
+        let total = user::count_user_rentals(&library_web.pool, &library_id, &user).await?;
+        add_link_headers(
+            &mut response_headers,
+            &base_url(&headers, &uri),
+            limit,
+            user.offset.unwrap_or_default(),
+            total,
+        );
+    }
+    let response = UsersBody { users, total: None };
+    log_success!(StatusCode::OK, "user", "list", count = response.users.len());
+    Ok((StatusCode::OK, response_headers, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/active",
+    tag = "user",
+    params(
+        ActiveUsersQuery
+    ),
+    responses(
+        (status = 200, description = "list users with outstanding rentals", body = UsersBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(skip_all, fields(query.limit = Empty, query.offset = Empty))]
+pub async fn active_users(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Query(query): Query<ActiveUsersQuery>,
 ) -> Response<UsersBody> {
-    let Ok(users) = user::users(&library_web.pool, &user).await else {
-        return internal_server_error().await;
+    Span::current()
+        .record("query.limit", query.limit)
+        .record("query.offset", query.offset);
+    let (users, total) = user::active_users(
+        &library_web.pool,
+        &library_id,
+        query.limit_or_default(),
+        query.offset_or_default(),
+    )
+    .await?;
+    let response = UsersBody {
+        users,
+        total: Some(total),
     };
-    let response = UsersBody { users };
-    (StatusCode::OK, Ok(Json(response)))
+    log_success!(
+        StatusCode::OK,
+        "user",
+        "list_active",
+        count = response.users.len()
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/inactive",
+    tag = "user",
+    params(
+        InactiveUsersQuery
+    ),
+    responses(
+        (status = 200, description = "list users without recent rentals", body = InactiveUsersBody),
+        (status = 400, description = "Invalid query", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(skip_all, fields(query.days = Empty, query.limit = Empty, query.offset = Empty))]
+pub async fn inactive_users(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Query(query): Query<InactiveUsersQuery>,
+) -> Response<InactiveUsersBody> {
+    Span::current()
+        .record("query.days", query.days)
+        .record("query.limit", query.limit)
+        .record("query.offset", query.offset);
+    query.validate().map_err(LibraryError::Invalid)?;
+    let (users, total) = user::inactive_users(
+        &library_web.pool,
+        &library_id,
+        query.days_or_default(),
+        query.limit_or_default(),
+        query.offset_or_default(),
+    )
+    .await?;
+    let response = InactiveUsersBody { users, total };
+    log_success!(
+        StatusCode::OK,
+        "user",
+        "list_inactive",
+        count = response.users.len()
+    );
+    Ok((StatusCode::OK, Json(response)))
 }
 
 #[utoipa::path(
     get,
-    path = "/api/user/{nation_id}",
+    path = "/api/v1/user/count",
+    tag = "user",
+    params(
+        UserQuery
+    ),
+    responses(
+        (status = 200, description = "number of matching users", body = CountBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(skip_all, fields(query.user_name = Empty, query.book_name = Empty))]
+pub async fn count_users(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Query(user): Query<UserQuery>,
+) -> Response<CountBody> {
+    Span::current()
+        .record("query.user_name", user.user_name.as_deref())
+        .record("query.book_name", user.book_name.as_deref());
+    let count = user::count_users(&library_web.pool, &library_id, &user).await?;
+    let response = CountBody { count };
+    log_success!(StatusCode::OK, "user", "count", count);
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/by-nation-id/{nation_id}",
     tag = "user",
     params(
         ("nation_id"= String, Path,),
     ),
     responses(
-        (status = 200, description = "list user", body = GetUserBody),
-        (status = 500, description = "Internal server error", body = String)
+        (status = 200, description = "rental history of the user", body = GetUserBody),
+        (status = 404, description = "User not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
     )
 )]
-pub async fn get_user(
+#[tracing::instrument(skip_all, fields(user.nation_id = Empty))]
+pub async fn user_history(
     State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
     Path(nation_id): Path<String>,
 ) -> Response<GetUserBody> {
-    let user = match user::get_user(&library_web.pool, nation_id).await {
-        Ok(author) => author,
-        Err(_) => return internal_server_error().await,
-    };
+    Span::current().record("user.nation_id", nation_id.as_str());
+    let user = user::get_user(&library_web.pool, &library_id, nation_id).await?;
     let response = GetUserBody { user };
-    (StatusCode::OK, Ok(Json(response)))
+    log_success!(StatusCode::OK, "user", "history");
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Deprecated alias of `GET /api/v1/user/by-nation-id/{nation_id}`.
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/{nation_id}",
+    tag = "user",
+    params(
+        ("nation_id"= String, Path,),
+    ),
+    responses(
+        (status = 200, description = "rental history of the user", body = GetUserBody),
+        (status = 404, description = "User not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[deprecated(note = "use `user_history`, served at `GET /api/v1/user/by-nation-id/{nation_id}`")]
+pub async fn get_user(
+    state: State<LibraryWeb>,
+    library_id: LibraryId,
+    nation_id: Path<String>,
+) -> Response<GetUserBody> {
+    user_history(state, library_id, nation_id).await
+}
+
+/// Represents the body of a response containing a user's profile.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct UserProfileBody {
+    pub user: User,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/profile/{nation_id}",
+    tag = "user",
+    params(
+        ("nation_id"= String, Path,),
+    ),
+    responses(
+        (status = 200, description = "profile of the user", body = UserProfileBody),
+        (status = 404, description = "User not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(skip_all, fields(user.nation_id = Empty))]
+pub async fn user_profile(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(nation_id): Path<String>,
+) -> Response<UserProfileBody> {
+    Span::current().record("user.nation_id", nation_id.as_str());
+    let user = user::get_user_profile(&library_web.pool, &library_id, &nation_id).await?;
+    let response = UserProfileBody { user };
+    log_success!(StatusCode::OK, "user", "profile");
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/{nation_id}/stats",
+    tag = "user",
+    params(
+        ("nation_id"= String, Path,),
+    ),
+    responses(
+        (status = 200, description = "rental statistics of the user", body = UserStatsBody),
+        (status = 404, description = "User not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(skip_all, fields(user.nation_id = Empty))]
+pub async fn user_stats(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(nation_id): Path<String>,
+) -> Response<UserStatsBody> {
+    Span::current().record("user.nation_id", nation_id.as_str());
+    let stats = user::user_stats(&library_web.pool, &library_id, &nation_id).await?;
+    let response = UserStatsBody { stats };
+    log_success!(StatusCode::OK, "user", "stats");
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/{nation_id}/overdue",
+    tag = "user",
+    params(
+        ("nation_id"= String, Path,),
+    ),
+    responses(
+        (status = 200, description = "current rentals of the user past their due date, the earliest due first", body = OverdueRentalsBody),
+        (status = 404, description = "User not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(skip_all, fields(user.nation_id = Empty))]
+pub async fn user_overdue_rentals(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(nation_id): Path<String>,
+) -> Response<OverdueRentalsBody> {
+    Span::current().record("user.nation_id", nation_id.as_str());
+    user::get_user_profile(&library_web.pool, &library_id, &nation_id).await?;
+    let rentals = user::user_overdue_rentals(&library_web.pool, &library_id, &nation_id).await?;
+    let response = OverdueRentalsBody { rentals };
+    log_success!(
+        StatusCode::OK,
+        "user",
+        "overdue",
+        count = response.rentals.len()
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/{nation_id}/recommendations",
+    tag = "user",
+    params(
+        ("nation_id"= String, Path,),
+        RecommendationsQuery
+    ),
+    responses(
+        (status = 200, description = "books recommended to the user", body = BooksBody),
+        (status = 404, description = "User not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(skip_all, fields(user.nation_id = Empty, query.limit = Empty))]
+pub async fn book_recommendations(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(nation_id): Path<String>,
+    Query(query): Query<RecommendationsQuery>,
+) -> Response<BooksBody> {
+    Span::current()
+        .record("user.nation_id", nation_id.as_str())
+        .record("query.limit", query.limit);
+    user::get_user_profile(&library_web.pool, &library_id, &nation_id).await?;
+    let books = book::book_recommendations(
+        &library_web.pool,
+        &library_id,
+        &nation_id,
+        query.limit_or_default(),
+    )
+    .await?;
+    let response = BooksBody {
+        books,
+        next_cursor: None,
+        total: None,
+    };
+    log_success!(
+        StatusCode::OK,
+        "user",
+        "recommendations",
+        count = response.books.len()
+    );
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/user/{nation_id}",
+    tag = "user",
+    request_body = UserUpdate,
+    params(
+        ("nation_id"= String, Path,),
+    ),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "user updated succesfully", body = UpdatedUserBody),
+        (status = 400, description = "Invalid user update", body = ErrorBody),
+        (status = 404, description = "User not found", body = ErrorBody),
+        (status = 409, description = "Email already exists", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(skip_all, fields(user.nation_id = Empty))]
+pub async fn update_user(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(nation_id): Path<String>,
+    Json(update): Json<UserUpdate>,
+) -> Response<UpdatedUserBody> {
+    Span::current().record("user.nation_id", nation_id.as_str());
+    update.validate().map_err(LibraryError::Invalid)?;
+    let info = user::update_user(&library_web.pool, &library_id, &nation_id, &update).await?;
+    let response = UpdatedUserBody { info };
+    log_success!(StatusCode::OK, "user", "update");
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/user/{nation_id}/nation_id",
+    tag = "user",
+    request_body = ChangeNationIdBody,
+    params(
+        ("nation_id"= String, Path,),
+    ),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "national ID changed succesfully", body = UserMessageBody),
+        (status = 400, description = "Invalid national ID", body = ErrorBody),
+        (status = 404, description = "User not found", body = ErrorBody),
+        (status = 409, description = "National ID already exists", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(skip_all, fields(user.nation_id = Empty, user.new_nation_id = Empty))]
+pub async fn change_nation_id(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(nation_id): Path<String>,
+    Json(body): Json<ChangeNationIdBody>,
+) -> Response<UserMessageBody> {
+    Span::current()
+        .record("user.nation_id", nation_id.as_str())
+        .record("user.new_nation_id", body.new_nation_id.as_str());
+    user::validate_nation_id(&body.new_nation_id).map_err(LibraryError::Invalid)?;
+    user::change_nation_id(
+        &library_web.pool,
+        &library_id,
+        &nation_id,
+        &body.new_nation_id,
+    )
+    .await?;
+    let response = UserMessageBody {
+        message: "successfully national ID changed".to_owned(),
+        nation_id: body.new_nation_id,
+    };
+    log_success!(StatusCode::OK, "user", "change_nation_id");
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/user/{nation_id}",
+    tag = "user",
+    params(
+        ("nation_id"= String, Path,),
+    ),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "user deleted succesfully", body = UserMessageBody),
+        (status = 404, description = "User not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(skip_all, fields(user.nation_id = Empty))]
+pub async fn delete_user(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(nation_id): Path<String>,
+) -> Response<UserMessageBody> {
+    Span::current().record("user.nation_id", nation_id.as_str());
+    user::delete_user(&library_web.pool, &library_id, nation_id.clone()).await?;
+    let response = UserMessageBody {
+        message: "successfully user deleted".to_owned(),
+        nation_id,
+    };
+    log_success!(StatusCode::OK, "user", "delete");
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Deletes a row of the rental history of a user.
+///
+/// Only admins can delete history rows, other requests are rejected with
+/// `403 Forbidden`. A request is sent by an admin when its `X-User-Role`
+/// header is `admin`.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/user/{nation_id}/history/{history_id}",
+    tag = "user",
+    params(
+        ("nation_id"= String, Path,),
+        ("history_id"= Uuid, Path,),
+    ),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 204, description = "rental deleted succesfully, the book is available again if it was the current rental"),
+        (status = 403, description = "Not sent by an admin", body = ErrorBody),
+        (status = 404, description = "Rental not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(skip_all, fields(user.nation_id = Empty))]
+pub async fn delete_history_row(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path((nation_id, history_id)): Path<(String, Uuid)>,
+) -> Result<StatusCode, ErrorResponse> {
+    Span::current().record("user.nation_id", nation_id.as_str());
+    let released =
+        user::delete_history_row(&library_web.pool, &library_id, &nation_id, history_id).await?;
+    if let Some(book_name) = released {
+        library_web.send_book_event(BookStatusEvent::new(
+            &library_id,
+            &book_name,
+            Status::Available,
+        ));
+    }
+    log_success!(
+        StatusCode::NO_CONTENT,
+        "user",
+        "delete_history_row",
+        history_id = %history_id
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/{nation_id}/restore",
+    tag = "user",
+    params(
+        ("nation_id"= String, Path,),
+    ),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "user restored succesfully", body = UserMessageBody),
+        (status = 404, description = "User not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(skip_all, fields(user.nation_id = Empty))]
+pub async fn restore_user(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(nation_id): Path<String>,
+) -> Response<UserMessageBody> {
+    Span::current().record("user.nation_id", nation_id.as_str());
+    user::restore_user(&library_web.pool, &library_id, nation_id.clone()).await?;
+    let response = UserMessageBody {
+        message: "successfully user restored".to_owned(),
+        nation_id,
+    };
+    log_success!(StatusCode::OK, "user", "restore");
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/{nation_id}/blacklist",
+    tag = "user",
+    params(
+        ("nation_id"= String, Path,),
+    ),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "user blacklisted succesfully", body = UserMessageBody),
+        (status = 404, description = "User not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(skip_all, fields(user.nation_id = Empty))]
+pub async fn blacklist_user(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(nation_id): Path<String>,
+) -> Response<UserMessageBody> {
+    Span::current().record("user.nation_id", nation_id.as_str());
+    user::blacklist_user(&library_web.pool, &library_id, &nation_id).await?;
+    let response = UserMessageBody {
+        message: "successfully user blacklisted".to_owned(),
+        nation_id,
+    };
+    log_success!(StatusCode::OK, "user", "blacklist");
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/user/{nation_id}/blacklist",
+    tag = "user",
+    params(
+        ("nation_id"= String, Path,),
+    ),
+    security(
+        ("BearerAuth" = [])
+    ),
+    responses(
+        (status = 200, description = "user unblacklisted succesfully", body = UserMessageBody),
+        (status = 404, description = "User not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(skip_all, fields(user.nation_id = Empty))]
+pub async fn unblacklist_user(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Path(nation_id): Path<String>,
+) -> Response<UserMessageBody> {
+    Span::current().record("user.nation_id", nation_id.as_str());
+    user::unblacklist_user(&library_web.pool, &library_id, &nation_id).await?;
+    let response = UserMessageBody {
+        message: "successfully user removed from the blacklist".to_owned(),
+        nation_id,
+    };
+    log_success!(StatusCode::OK, "user", "unblacklist");
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/blacklisted",
+    tag = "user",
+    params(
+        BlacklistedUsersQuery
+    ),
+    responses(
+        (status = 200, description = "list blacklisted users", body = BlacklistedUsersBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(skip_all, fields(query.limit = Empty, query.offset = Empty))]
+pub async fn blacklisted_users(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    Query(query): Query<BlacklistedUsersQuery>,
+) -> Response<BlacklistedUsersBody> {
+    Span::current()
+        .record("query.limit", query.limit)
+        .record("query.offset", query.offset);
+    let users = user::blacklisted_users(&library_web.pool, &library_id, &query).await?;
+    let response = BlacklistedUsersBody { users };
+    log_success!(
+        StatusCode::OK,
+        "user",
+        "list_blacklisted",
+        count = response.users.len()
+    );
+    Ok((StatusCode::OK, Json(response)))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::library::book::{self, Book};
-    use crate::library::user;
-    use crate::library_web::tests::{deserialize_response_body, get, post};
+    use crate::library::book::{Book, Status};
+    use crate::library::stats::tests::{insert_rental_at, rent_book_times};
+    use crate::library::user::{self, MAX_RENT_BOOKS};
+    use crate::library_web::book::GetBookBody;
+    use crate::library_web::tests::{
+        delete, deserialize_response_body, get, post, put, send_request,
+    };
+    use crate::middleware::idempotency::IDEMPOTENCY_KEY_HEADER;
+    use crate::middleware::library::DEFAULT_LIBRARY_ID;
+    use crate::middleware::role::{ADMIN_ROLE, ROLE_HEADER};
+    use crate::tests::sequential::sequential_test;
+    use axum::http::{header::CONTENT_TYPE, Method, Request};
+    use chrono::Utc;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::Subscriber;
+    use tracing_subscriber::layer::{Context as LayerContext, Layer, SubscriberExt};
+    use tracing_subscriber::Registry;
     use urlencoding::encode;
 
+    /// Sends a PUT request with the `X-User-Role: admin` header.
+    async fn put_as_admin<T: Serialize>(router: &axum::Router, uri: &str, body: &T) -> StatusCode {
+        let request = Request::builder()
+            .method(Method::PUT)
+            .uri(uri)
+            .header(CONTENT_TYPE, "application/json")
+            .header(ROLE_HEADER, ADMIN_ROLE)
+            .body(
+                serde_json::to_vec(body)
+                    .expect("failed to serialize PUT body")
+                    .into(),
+            )
+            .expect("failed to build PUT request");
+        send_request(router, request).await.status()
+    }
+
+    /// Sends a DELETE request with the `X-User-Role: admin` header.
+    async fn delete_as_admin(router: &axum::Router, uri: &str) -> StatusCode {
+        let request = Request::builder()
+            .method(Method::DELETE)
+            .uri(uri)
+            .header(ROLE_HEADER, ADMIN_ROLE)
+            .body(hyper::Body::empty())
+            .expect("failed to build DELETE request");
+        send_request(router, request).await.status()
+    }
+
     async fn concurrency_create_user(router: axum::Router, user: User) -> StatusCode {
-        let response = post(&router, "/api/user/create", &user).await;
+        let response = post(&router, "/api/v1/user/create", &user).await;
         response.status()
     }
 
-    #[tokio::test]
-    async fn test_concurrency_create_user() {
-        let lib = LibraryWeb::new_test().await;
-        let request_body = User::create_fake_user().await;
-        let router = lib.setup_router();
-        let fut_a = concurrency_create_user(router.clone(), request_body.clone());
-        let fut_b = concurrency_create_user(router.clone(), request_body.clone());
-        let (status_a, status_b) = tokio::join!(fut_a, fut_b);
-        assert_eq!(status_a.min(status_b), 201, "should succeed");
-        assert_eq!(status_a.max(status_b), 500, "should fail");
-    }
+    sequential_test!(
+        async fn test_concurrency_create_user() {
+            let lib = LibraryWeb::new_test().await;
+            let request_body = User::create_fake_user().await;
+            let router = lib.setup_router();
+            let fut_a = concurrency_create_user(router.clone(), request_body.clone());
+            let fut_b = concurrency_create_user(router.clone(), request_body.clone());
+            let (status_a, status_b) = tokio::join!(fut_a, fut_b);
+            assert_eq!(status_a.min(status_b), 201, "should succeed");
+            assert_eq!(status_a.max(status_b), 409, "should fail");
+        }
+    );
 
-    #[tokio::test]
-    async fn test_create_user() {
-        let lib = LibraryWeb::new_test().await;
-        let request_body = User::create_fake_user().await;
-        let router = lib.setup_router();
+    sequential_test!(
+        async fn test_create_user() {
+            let lib = LibraryWeb::new_test().await;
+            let request_body = User::create_fake_user().await;
+            let router = lib.setup_router();
 
-        let response = post(&router, "/api/user/create", &request_body).await;
-        assert_eq!(response.status(), 201);
-        let response_body = deserialize_response_body::<CreatedUserBody>(response).await;
-        assert_eq!(response_body.info, request_body);
-    }
+            let response = post(&router, "/api/v1/user/create", &request_body).await;
+            assert_eq!(response.status(), 201);
+            let response_body = deserialize_response_body::<CreatedUserBody>(response).await;
+            assert_eq!(response_body.info, request_body);
+        }
+    );
 
-    #[tokio::test]
-    async fn test_rent_book_and_get_user() {
-        let lib = LibraryWeb::new_test().await;
+    sequential_test!(
+        async fn test_create_user_invalid_email() {
+            let lib = LibraryWeb::new_test().await;
+            let mut request_body = User::create_fake_user().await;
+            request_body.email = Some("not-an-email".to_owned());
+            let router = lib.setup_router();
 
-        // insert user, book
-        let fake_user = User::create_fake_user().await;
-        let _insert_fake_user = user::insert_user(&lib.pool, &fake_user)
-            .await
-            .expect("failed to insert fake user");
-        let fake_book = Book::create_fake_book(&lib.pool).await;
-        let _insert_fake_book = book::insert_book(&lib.pool, &fake_book)
-            .await
-            .expect("failed to insert fake book");
+            let response = post(&router, "/api/v1/user/create", &request_body).await;
+            assert_eq!(response.status(), 400);
+        }
+    );
 
-        // rent book
-        let router = lib.setup_router();
-        let uri = format!("/api/user/rent/{}?", encode(&fake_user.nation_id));
-        let user_rent_book = RentBook {
-            book_name: fake_book.name,
-            due_date: "2023-05-09".to_owned(),
-        };
-        let response = post(&router, uri, &user_rent_book).await;
-        assert_eq!(response.status(), 201);
-
-        // get_user
-        let uri = format!("/api/user/{}", &fake_user.nation_id);
-        let response = get(&router, uri).await;
-        assert_eq!(response.status(), 200);
-        let response_body = deserialize_response_body::<GetUserBody>(response).await;
-        assert_eq!(response_body.user[0].nation_id, fake_user.nation_id);
-    }
+    sequential_test!(
+        async fn test_create_user_invalid_phone() {
+            let lib = LibraryWeb::new_test().await;
+            let mut request_body = User::create_fake_user().await;
+            request_body.phone = Some("555-1234".to_owned());
+            let router = lib.setup_router();
 
-    #[tokio::test]
-    async fn test_users() {
-        let lib = LibraryWeb::new_test().await;
+            let response = post(&router, "/api/v1/user/create", &request_body).await;
+            assert_eq!(response.status(), 400);
+        }
+    );
 
-        // insert user, book
-        let fake_user = User::create_fake_user().await;
-        let _insert_fake_user = user::insert_user(&lib.pool, &fake_user)
-            .await
-            .expect("failed to insert fake user");
-        let fake_book = Book::create_fake_book(&lib.pool).await;
-        let _insert_fake_book = book::insert_book(&lib.pool, &fake_book)
-            .await
-            .expect("failed to insert fake book");
+    sequential_test!(
+        async fn test_rent_book_and_get_user() {
+            let lib = LibraryWeb::new_test().await;
 
-        // rent book
-        let router = lib.setup_router();
-        let uri = format!("/api/user/rent/{}?", encode(&fake_user.nation_id));
-        let user_rent_book = RentBook {
-            book_name: fake_book.name.clone(),
-            due_date: "2023-05-09".to_owned(),
-        };
-        let response = post(&router, uri, &user_rent_book).await;
-        assert_eq!(response.status(), 201);
-
-        // users
-        let uri = format!("/api/user/{}", &fake_user.nation_id);
-        let response = get(&router, uri).await;
-        assert_eq!(response.status(), 200);
-        let response_body = deserialize_response_body::<GetUserBody>(response).await;
-        assert_eq!(response_body.user[0].nation_id, fake_user.nation_id);
-
-        let uri = format!("/api/user?user_name={}", encode(&fake_user.name));
-        let response = get(&router, uri).await;
-        assert_eq!(response.status(), 200);
-        let response_body = deserialize_response_body::<UsersBody>(response).await;
-        assert_eq!(response_body.users[0].user_name, fake_user.name);
-
-        let uri = format!("/api/user?book_name={}", encode(&fake_book.name),);
-        let response = get(&router, uri).await;
-        assert_eq!(response.status(), 200);
-        let response_body = deserialize_response_body::<UsersBody>(response).await;
-        assert_eq!(response_body.users[0].book_name, fake_book.name);
-
-        let uri = format!(
-            "/api/user?user_name={}&book_name={}",
-            encode(&fake_user.name),
-            encode(&fake_book.name),
-        );
-        let response = get(&router, uri).await;
-        assert_eq!(response.status(), 200);
-        let response_body = deserialize_response_body::<UsersBody>(response).await;
-        assert_eq!(response_body.users[0].user_name, fake_user.name);
-        assert_eq!(response_body.users[0].book_name, fake_book.name);
-    }
+            // insert user, book
+            let fake_user = User::create_fake_user().await;
+            let _insert_fake_user = user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert fake user");
+            let fake_book = Book::create_fake_book(&lib.pool).await;
+            let _insert_fake_book = book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                .await
+                .expect("failed to insert fake book");
+
+            // rent book
+            let router = lib.setup_router();
+            let uri = format!("/api/v1/user/rent/{}?", encode(&fake_user.nation_id));
+            let user_rent_book = RentBook {
+                book_name: fake_book.name,
+                due_date: "2023-05-09".to_owned(),
+            };
+            let response = post(&router, uri, &user_rent_book).await;
+            assert_eq!(response.status(), 201);
+
+            // get_user
+            let uri = format!("/api/v1/user/{}", &fake_user.nation_id);
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<GetUserBody>(response).await;
+            assert_eq!(response_body.user[0].nation_id, fake_user.nation_id);
+            assert_eq!(response_body.user[0].email, fake_user.email);
+            assert_eq!(response_body.user[0].phone, fake_user.phone);
+        }
+    );
+
+    sequential_test!(
+        async fn test_users() {
+            let lib = LibraryWeb::new_test().await;
+
+            // insert user, book
+            let fake_user = User::create_fake_user().await;
+            let _insert_fake_user = user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert fake user");
+            let fake_book = Book::create_fake_book(&lib.pool).await;
+            let _insert_fake_book = book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                .await
+                .expect("failed to insert fake book");
+
+            // rent book
+            let router = lib.setup_router();
+            let uri = format!("/api/v1/user/rent/{}?", encode(&fake_user.nation_id));
+            let user_rent_book = RentBook {
+                book_name: fake_book.name.clone(),
+                due_date: "2023-05-09".to_owned(),
+            };
+            let response = post(&router, uri, &user_rent_book).await;
+            assert_eq!(response.status(), 201);
+
+            // users
+            let uri = format!("/api/v1/user/{}", &fake_user.nation_id);
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<GetUserBody>(response).await;
+            assert_eq!(response_body.user[0].nation_id, fake_user.nation_id);
+
+            let uri = format!("/api/v1/user?user_name={}", encode(&fake_user.name));
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<UsersBody>(response).await;
+            assert_eq!(response_body.users[0].user_name, fake_user.name);
+
+            let uri = format!("/api/v1/user?book_name={}", encode(&fake_book.name),);
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<UsersBody>(response).await;
+            assert_eq!(response_body.users[0].book_name, fake_book.name);
+
+            let uri = format!(
+                "/api/v1/user?user_name={}&book_name={}",
+                encode(&fake_user.name),
+                encode(&fake_book.name),
+            );
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<UsersBody>(response).await;
+            assert_eq!(response_body.users[0].user_name, fake_user.name);
+            assert_eq!(response_body.users[0].book_name, fake_book.name);
+        }
+    );
+
+    sequential_test!(
+        async fn test_user_stats() {
+            let lib = LibraryWeb::new_test().await;
+            let fake_user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert fake user");
+            let fake_book = Book::create_fake_book(&lib.pool).await;
+            book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                .await
+                .expect("failed to insert fake book");
+            let router = lib.setup_router();
+
+            let uri = format!("/api/v1/user/rent/{}", encode(&fake_user.nation_id));
+            let user_rent_book = RentBook {
+                book_name: fake_book.name,
+                due_date: "2000-01-01".to_owned(),
+            };
+            let response = post(&router, uri, &user_rent_book).await;
+            assert_eq!(response.status(), 201);
+
+            let uri = format!("/api/v1/user/{}/stats", encode(&fake_user.nation_id));
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<UserStatsBody>(response).await;
+            assert_eq!(
+                response_body.stats,
+                UserStats {
+                    total_rentals: 1,
+                    active_rentals: 1,
+                    overdue_rentals: 1,
+                    favorite_category: Some(fake_book.category),
+                }
+            );
+
+            let response = get(&router, "/api/v1/user/00000000000/stats").await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_user_overdue_rentals() {
+            let lib = LibraryWeb::new_test().await;
+            let fake_user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert fake user");
+            let router = lib.clone().setup_router();
+            let uri = format!("/api/v1/user/{}/overdue", encode(&fake_user.nation_id));
+
+            // no overdue rental
+            let response = get(&router, &uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<OverdueRentalsBody>(response).await;
+            assert!(response_body.rentals.is_empty());
+
+            let mut books = vec![];
+            for due_date in ["2999-01-01", "2000-01-01"] {
+                let fake_book = Book::create_fake_book(&lib.pool).await;
+                book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                    .await
+                    .expect("failed to insert fake book");
+                let user_rent_book = RentBook {
+                    book_name: fake_book.name.clone(),
+                    due_date: due_date.to_owned(),
+                };
+                let response = post(
+                    &router,
+                    format!("/api/v1/user/rent/{}", encode(&fake_user.nation_id)),
+                    &user_rent_book,
+                )
+                .await;
+                assert_eq!(response.status(), 201);
+                books.push(fake_book.name);
+            }
+
+            let response = get(&router, &uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<OverdueRentalsBody>(response).await;
+            let overdue: Vec<_> = response_body
+                .rentals
+                .iter()
+                .map(|rental| &rental.book_name)
+                .collect();
+            assert_eq!(overdue, [&books[1]]);
+
+            let response = get(&router, "/api/v1/user/00000000000/overdue").await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_book_recommendations() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let fake_user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert fake user");
+            let new_user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &new_user)
+                .await
+                .expect("failed to insert fake user");
+            let mut books = vec![];
+            for (category, status) in [
+                ("fantasy", Status::Available),
+                ("fantasy", Status::Available),
+                ("fantasy", Status::Available),
+                ("fantasy", Status::Available),
+                ("fantasy", Status::Rented),
+                ("history", Status::Available),
+                ("history", Status::Available),
+            ] {
+                let book = Book {
+                    category: category.to_owned(),
+                    status,
+                    ..Book::create_fake_book(&lib.pool).await
+                };
+                book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &book)
+                    .await
+                    .expect("failed to insert fake book");
+                books.push(book);
+            }
+            // two fantasy rentals and one history rental
+            rent_book_times(&lib.pool, &fake_user.nation_id, &books[0].name, 1).await;
+            rent_book_times(&lib.pool, &fake_user.nation_id, &books[1].name, 1).await;
+            rent_book_times(&lib.pool, &fake_user.nation_id, &books[5].name, 1).await;
+            let router = lib.setup_router();
+
+            let uri = format!(
+                "/api/v1/user/{}/recommendations",
+                encode(&fake_user.nation_id)
+            );
+            let response = get(&router, &uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            let mut names: Vec<String> = response_body
+                .books
+                .iter()
+                .map(|book| book.name.clone())
+                .collect();
+            names.sort();
+            let mut expected = vec![books[2].name.clone(), books[3].name.clone()];
+            expected.sort();
+            assert_eq!(names, expected);
+            assert!(response_body
+                .books
+                .iter()
+                .all(|book| book.category == "fantasy" && book.status == Status::Available));
+
+            let response = get(&router, format!("{uri}?limit=1")).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert_eq!(response_body.books.len(), 1);
+
+            // no rental history
+            let uri = format!(
+                "/api/v1/user/{}/recommendations",
+                encode(&new_user.nation_id)
+            );
+            let response = get(&router, &uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BooksBody>(response).await;
+            assert!(response_body.books.is_empty());
+
+            let response = get(&router, "/api/v1/user/00000000000/recommendations").await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_update_user() {
+            let lib = LibraryWeb::new_test().await;
+            let fake_user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert fake user");
+            let router = lib.setup_router();
+            let uri = format!("/api/v1/user/{}", encode(&fake_user.nation_id));
+
+            let request_body = UserUpdate {
+                name: Some("updated name".to_owned()),
+                email: Some(format!("updated.{}@example.com", fake_user.nation_id)),
+                phone: None,
+            };
+            let response = put(&router, &uri, &request_body).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<UpdatedUserBody>(response).await;
+            assert!(response_body.info.created_at.is_some());
+            assert_eq!(
+                response_body.info,
+                User {
+                    nation_id: fake_user.nation_id.clone(),
+                    name: "updated name".to_owned(),
+                    email: request_body.email,
+                    phone: fake_user.phone,
+                    ..response_body.info.clone()
+                }
+            );
+
+            // the national ID in the body is ignored
+            let request_body = serde_json::json!({ "nation_id": "00000000000" });
+            let response = put(&router, &uri, &request_body).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<UpdatedUserBody>(response).await;
+            assert_eq!(response_body.info.nation_id, fake_user.nation_id);
+        }
+    );
+
+    sequential_test!(
+        async fn test_update_user_invalid() {
+            let lib = LibraryWeb::new_test().await;
+            let fake_user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert fake user");
+            let router = lib.setup_router();
+            let uri = format!("/api/v1/user/{}", encode(&fake_user.nation_id));
+
+            let request_body = UserUpdate {
+                email: Some("not-an-email".to_owned()),
+                ..Default::default()
+            };
+            let response = put(&router, &uri, &request_body).await;
+            assert_eq!(response.status(), 400);
+
+            let request_body = UserUpdate {
+                name: Some(String::new()),
+                ..Default::default()
+            };
+            let response = put(&router, &uri, &request_body).await;
+            assert_eq!(response.status(), 400);
+
+            // unknown user
+            let request_body = UserUpdate::default();
+            let response = put(&router, "/api/v1/user/00000000000", &request_body).await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_change_nation_id() {
+            let lib = LibraryWeb::new_test().await;
+            let fake_user = User::create_fake_user().await;
+            let another_user = User::create_fake_user().await;
+            for user in [&fake_user, &another_user] {
+                user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, user)
+                    .await
+                    .expect("failed to insert fake user");
+            }
+            let fake_book = Book::create_fake_book(&lib.pool).await;
+            book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                .await
+                .expect("failed to insert fake book");
+            let router = lib.setup_router();
+            let uri = format!("/api/v1/user/rent/{}", encode(&fake_user.nation_id));
+            let user_rent_book = RentBook {
+                book_name: fake_book.name.clone(),
+                due_date: "2023-05-09".to_owned(),
+            };
+            let response = post(&router, uri, &user_rent_book).await;
+            assert_eq!(response.status(), 201);
+
+            let uri = format!("/api/v1/user/{}/nation_id", encode(&fake_user.nation_id));
+            for (new_nation_id, status) in [
+                ("123".to_owned(), 400),
+                (another_user.nation_id.clone(), 409),
+            ] {
+                let request_body = ChangeNationIdBody { new_nation_id };
+                let response = put(&router, &uri, &request_body).await;
+                assert_eq!(response.status(), status);
+            }
+            let request_body = ChangeNationIdBody {
+                new_nation_id: User::create_fake_user().await.nation_id,
+            };
+            let response = put(&router, "/api/v1/user/00000000000/nation_id", &request_body).await;
+            assert_eq!(response.status(), 404);
+
+            let response = put(&router, &uri, &request_body).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<UserMessageBody>(response).await;
+            assert_eq!(response_body.nation_id, request_body.new_nation_id);
+
+            // the rental history follows the new national ID
+            let response = get(
+                &router,
+                format!("/api/v1/user/{}", request_body.new_nation_id),
+            )
+            .await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<GetUserBody>(response).await;
+            assert_eq!(response_body.user[0].book_name, fake_book.name);
+            let response = get(&router, format!("/api/v1/user/{}", fake_user.nation_id)).await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_register_and_rent() {
+            let lib = LibraryWeb::new_test().await;
+            let fake_book = Book::create_fake_book(&lib.pool).await;
+            book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                .await
+                .expect("failed to insert fake book");
+            let router = lib.setup_router();
+            let mut request_body = RegisterAndRentBody {
+                user: User::create_fake_user().await,
+                book: RentBook {
+                    book_name: fake_book.name.clone(),
+                    due_date: "2030-06-15".to_owned(),
+                },
+            };
+
+            let response = post(&router, "/api/v1/user/register", &request_body).await;
+            assert_eq!(response.status(), 201);
+            let response_body = deserialize_response_body::<RegisteredBody>(response).await;
+            assert_eq!(response_body.rental.nation_id, request_body.user.nation_id);
+            assert_eq!(response_body.rental.book_name, fake_book.name);
+            let uri = format!("/api/v1/user/{}", encode(&request_body.user.nation_id));
+            let response = get(&router, &uri).await;
+            assert_eq!(response.status(), 200);
+
+            // existing user
+            let response = post(&router, "/api/v1/user/register", &request_body).await;
+            assert_eq!(response.status(), 409);
+
+            // the book is rented, so the user is not created
+            request_body.user = User::create_fake_user().await;
+            let response = post(&router, "/api/v1/user/register", &request_body).await;
+            assert_eq!(response.status(), 404);
+            let uri = format!("/api/v1/user/{}", encode(&request_body.user.nation_id));
+            let response = get(&router, &uri).await;
+            assert_eq!(response.status(), 404);
+
+            // invalid user
+            request_body.user.email = Some("not-an-email".to_owned());
+            let response = post(&router, "/api/v1/user/register", &request_body).await;
+            assert_eq!(response.status(), 400);
+        }
+    );
+
+    sequential_test!(
+        async fn test_set_due_date() {
+            let lib = LibraryWeb::new_test().await;
+            let fake_user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert fake user");
+            let fake_book = Book::create_fake_book(&lib.pool).await;
+            book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                .await
+                .expect("failed to insert fake book");
+            let router = lib.setup_router();
+            let uri = format!(
+                "/api/v1/user/rent/{}/{}/due-date",
+                encode(&fake_user.nation_id),
+                encode(&fake_book.name)
+            );
+            let request_body = SetDueDateBody {
+                due_date: "2031-01-15".to_owned(),
+            };
+
+            // not rented
+            assert_eq!(put_as_admin(&router, &uri, &request_body).await, 404);
+
+            let user_rent_book = RentBook {
+                book_name: fake_book.name.clone(),
+                due_date: "2030-06-15".to_owned(),
+            };
+            let rent_uri = format!("/api/v1/user/rent/{}", encode(&fake_user.nation_id));
+            let response = post(&router, rent_uri, &user_rent_book).await;
+            assert_eq!(response.status(), 201);
+
+            // only admins can change the due date
+            let response = put(&router, &uri, &request_body).await;
+            assert_eq!(response.status(), 403);
+            let response_body = deserialize_response_body::<ErrorBody>(response).await;
+            assert_eq!(response_body.error, ServerErr::Forbidden);
+
+            let invalid = SetDueDateBody {
+                due_date: "15/01/2031".to_owned(),
+            };
+            assert_eq!(put_as_admin(&router, &uri, &invalid).await, 400);
+            assert_eq!(put_as_admin(&router, &uri, &request_body).await, 200);
+            let response = get(
+                &router,
+                format!("/api/v1/user/{}", encode(&fake_user.nation_id)),
+            )
+            .await;
+            let response_body = deserialize_response_body::<GetUserBody>(response).await;
+            assert_eq!(response_body.user[0].due_date, request_body.due_date);
+        }
+    );
+
+    sequential_test!(
+        async fn test_return_book() {
+            let lib = LibraryWeb::new_test().await;
+            let fake_user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert fake user");
+            let fake_book = Book::create_fake_book(&lib.pool).await;
+            let book_id = book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                .await
+                .expect("failed to insert fake book");
+            let router = lib.setup_router();
+            let uri = format!(
+                "/api/v1/user/rent/{}/{}/return",
+                encode(&fake_user.nation_id),
+                encode(&fake_book.name)
+            );
+
+            // not rented
+            let response = post(&router, &uri, &()).await;
+            assert_eq!(response.status(), 404);
+
+            let user_rent_book = RentBook {
+                book_name: fake_book.name.clone(),
+                due_date: "2030-06-15".to_owned(),
+            };
+            let rent_uri = format!("/api/v1/user/rent/{}", encode(&fake_user.nation_id));
+            let response = post(&router, rent_uri, &user_rent_book).await;
+            assert_eq!(response.status(), 201);
+
+            let response = post(&router, &uri, &()).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<UserMessageBody>(response).await;
+            assert_eq!(response_body.nation_id, fake_user.nation_id);
+            let response = get(&router, format!("/api/v1/book/{book_id}")).await;
+            let response_body = deserialize_response_body::<GetBookBody>(response).await;
+            assert_eq!(response_body.book.status, book::Status::Available);
+            // already returned
+            let response = post(&router, &uri, &()).await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_delete_history_row() {
+            let lib = LibraryWeb::new_test().await;
+            let fake_user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert fake user");
+            let fake_book = Book::create_fake_book(&lib.pool).await;
+            let book_id = book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                .await
+                .expect("failed to insert fake book");
+
+            // rent book
+            let router = lib.setup_router();
+            let uri = format!("/api/v1/user/rent/{}", encode(&fake_user.nation_id));
+            let user_rent_book = RentBook {
+                book_name: fake_book.name,
+                due_date: "2030-06-15".to_owned(),
+            };
+            let response = post(&router, uri, &user_rent_book).await;
+            assert_eq!(response.status(), 201);
+            let uri = format!("/api/v1/user/{}", encode(&fake_user.nation_id));
+            let response = get(&router, &uri).await;
+            let history_id = deserialize_response_body::<GetUserBody>(response)
+                .await
+                .user[0]
+                .history_id;
+
+            // only admins can delete the rental
+            let uri = format!("{uri}/history/{history_id}");
+            let response = delete(&router, &uri).await;
+            assert_eq!(response.status(), 403);
+            let response_body = deserialize_response_body::<ErrorBody>(response).await;
+            assert_eq!(response_body.error, ServerErr::Forbidden);
+
+            // delete the rental, the book is available again
+            assert_eq!(delete_as_admin(&router, &uri).await, 204);
+            let response = get(&router, format!("/api/v1/book/{book_id}")).await;
+            let response_body = deserialize_response_body::<GetBookBody>(response).await;
+            assert_eq!(response_body.book.status, book::Status::Available);
+            assert_eq!(delete_as_admin(&router, &uri).await, 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_delete_and_restore_user() {
+            let lib = LibraryWeb::new_test().await;
+
+            // insert user, book
+            let fake_user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert fake user");
+            let fake_book = Book::create_fake_book(&lib.pool).await;
+            book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                .await
+                .expect("failed to insert fake book");
+
+            // rent book
+            let router = lib.setup_router();
+            let uri = format!("/api/v1/user/rent/{}", encode(&fake_user.nation_id));
+            let user_rent_book = RentBook {
+                book_name: fake_book.name,
+                due_date: "2023-05-09".to_owned(),
+            };
+            let response = post(&router, uri, &user_rent_book).await;
+            assert_eq!(response.status(), 201);
+
+            // delete
+            let uri = format!("/api/v1/user/{}", encode(&fake_user.nation_id));
+            let response = delete(&router, &uri).await;
+            assert_eq!(response.status(), 200);
+            let response = get(&router, &uri).await;
+            assert_eq!(response.status(), 404);
+
+            // the national ID cannot be reused
+            let response = post(&router, "/api/v1/user/create", &fake_user).await;
+            assert_eq!(response.status(), 409);
+
+            // restore keeps the rental history
+            let response = post(&router, format!("{uri}/restore"), &()).await;
+            assert_eq!(response.status(), 200);
+            let response = get(&router, &uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<GetUserBody>(response).await;
+            assert_eq!(response_body.user[0].nation_id, fake_user.nation_id);
+        }
+    );
+
+    sequential_test!(
+        async fn test_blacklist_user() {
+            let lib = LibraryWeb::new_test().await;
+            let fake_user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert fake user");
+            let fake_book = Book::create_fake_book(&lib.pool).await;
+            book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                .await
+                .expect("failed to insert fake book");
+            let router = lib.setup_router();
+            let blacklist_uri = format!("/api/v1/user/{}/blacklist", encode(&fake_user.nation_id));
+            let rent_uri = format!("/api/v1/user/rent/{}", encode(&fake_user.nation_id));
+            let user_rent_book = RentBook {
+                book_name: fake_book.name,
+                due_date: "2030-05-09".to_owned(),
+            };
+
+            // a blacklisted user cannot rent books
+            let response = post(&router, &blacklist_uri, &()).await;
+            assert_eq!(response.status(), 200);
+            let response = post(&router, &rent_uri, &user_rent_book).await;
+            assert_eq!(response.status(), 403);
+            let response_body = deserialize_response_body::<ErrorBody>(response).await;
+            assert_eq!(response_body.error, ServerErr::Forbidden);
+
+            // the book was not rented
+            let response = delete(&router, &blacklist_uri).await;
+            assert_eq!(response.status(), 200);
+            let response = post(&router, &rent_uri, &user_rent_book).await;
+            assert_eq!(response.status(), 201);
+
+            let uri = format!("/api/v1/user/{}/blacklist", encode("unknown"));
+            let response = post(&router, &uri, &()).await;
+            assert_eq!(response.status(), 404);
+            let response = delete(&router, &uri).await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_blacklisted_users() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let mut users = Vec::new();
+            for _ in 0..3 {
+                let fake_user = User::create_fake_user().await;
+                user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                    .await
+                    .expect("failed to insert fake user");
+                users.push(fake_user);
+            }
+            let router = lib.setup_router();
+
+            let response = get(&router, "/api/v1/user/blacklisted").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BlacklistedUsersBody>(response).await;
+            assert!(response_body.users.is_empty());
+
+            for fake_user in &users[..2] {
+                let uri = format!("/api/v1/user/{}/blacklist", encode(&fake_user.nation_id));
+                let response = post(&router, uri, &()).await;
+                assert_eq!(response.status(), 200);
+            }
+            let response = get(&router, "/api/v1/user/blacklisted").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BlacklistedUsersBody>(response).await;
+            // most recently blacklisted first
+            let nation_ids: Vec<_> = response_body
+                .users
+                .iter()
+                .map(|user| user.nation_id.as_str())
+                .collect();
+            assert_eq!(nation_ids, [&users[1].nation_id, &users[0].nation_id]);
+            assert_eq!(response_body.users[1].name, users[0].name);
+
+            let response = get(&router, "/api/v1/user/blacklisted?limit=1&offset=1").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<BlacklistedUsersBody>(response).await;
+            assert_eq!(response_body.users.len(), 1);
+            assert_eq!(response_body.users[0].nation_id, users[0].nation_id);
+        }
+    );
 
     async fn concurrency_rent_book(router: axum::Router, user: User, book: Book) -> StatusCode {
-        let uri = format!("/api/user/rent/{}?", encode(&user.nation_id));
+        let uri = format!("/api/v1/user/rent/{}?", encode(&user.nation_id));
         let user_rent_book = RentBook {
             book_name: book.name,
             due_date: "2023-05-09".to_owned(),
@@ -276,26 +1907,520 @@ mod tests {
         response.status()
     }
 
-    #[tokio::test]
-    async fn test_concurrency_rent_book() {
-        let lib = LibraryWeb::new_test().await;
-        let fake_user_1 = User::create_fake_user().await;
-        let _insert_fake_user_1 = user::insert_user(&lib.pool, &fake_user_1)
-            .await
-            .expect("failed to insert fake user");
-        let fake_user_2 = User::create_fake_user().await;
-        let _insert_fake_user_2 = user::insert_user(&lib.pool, &fake_user_2)
-            .await
-            .expect("failed to insert fake user");
-        let fake_book = Book::create_fake_book(&lib.pool).await;
-        let _insert_fake_book = book::insert_book(&lib.pool, &fake_book)
-            .await
-            .expect("failed to insert fake book");
-        let router = lib.setup_router();
-        let fut_a = concurrency_rent_book(router.clone(), fake_user_1, fake_book.clone());
-        let fut_b = concurrency_rent_book(router.clone(), fake_user_2, fake_book.clone());
-        let (status_a, status_b) = tokio::join!(fut_a, fut_b);
-        assert_eq!(status_a.min(status_b), 201, "should succeed");
-        assert_eq!(status_a.max(status_b), 500, "should fail");
+    sequential_test!(
+        async fn test_concurrency_rent_book() {
+            let lib = LibraryWeb::new_test().await;
+            let fake_user_1 = User::create_fake_user().await;
+            let _insert_fake_user_1 =
+                user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user_1)
+                    .await
+                    .expect("failed to insert fake user");
+            let fake_user_2 = User::create_fake_user().await;
+            let _insert_fake_user_2 =
+                user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user_2)
+                    .await
+                    .expect("failed to insert fake user");
+            let fake_book = Book::create_fake_book(&lib.pool).await;
+            let _insert_fake_book = book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                .await
+                .expect("failed to insert fake book");
+            let router = lib.setup_router();
+            let fut_a = concurrency_rent_book(router.clone(), fake_user_1, fake_book.clone());
+            let fut_b = concurrency_rent_book(router.clone(), fake_user_2, fake_book.clone());
+            let (status_a, status_b) = tokio::join!(fut_a, fut_b);
+            assert_eq!(status_a.min(status_b), 201, "should succeed");
+            assert_eq!(status_a.max(status_b), 404, "should fail");
+        }
+    );
+
+    sequential_test!(
+        async fn test_user_profile_and_history() {
+            let lib = LibraryWeb::new_test().await;
+            let fake_user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert fake user");
+            let fake_book = Book::create_fake_book(&lib.pool).await;
+            book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                .await
+                .expect("failed to insert fake book");
+            let router = lib.setup_router();
+
+            // the profile does not need any rental
+            let uri = format!("/api/v1/user/profile/{}", encode(&fake_user.nation_id));
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<UserProfileBody>(response).await;
+            assert_eq!(
+                User {
+                    created_at: None,
+                    updated_at: None,
+                    ..response_body.user
+                },
+                fake_user
+            );
+            let response = get(&router, "/api/v1/user/profile/00000000000").await;
+            assert_eq!(response.status(), 404);
+
+            let uri = format!("/api/v1/user/rent/{}", encode(&fake_user.nation_id));
+            let user_rent_book = RentBook {
+                book_name: fake_book.name.clone(),
+                due_date: "2023-05-09".to_owned(),
+            };
+            let response = post(&router, uri, &user_rent_book).await;
+            assert_eq!(response.status(), 201);
+
+            // the deprecated route is an alias of the history
+            let uri = format!("/api/v1/user/by-nation-id/{}", encode(&fake_user.nation_id));
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let history = deserialize_response_body::<GetUserBody>(response).await;
+            assert_eq!(history.user[0].book_name, fake_book.name);
+            let uri = format!("/api/v1/user/{}", encode(&fake_user.nation_id));
+            let response = get(&router, uri).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<GetUserBody>(response).await;
+            assert_eq!(response_body, history);
+        }
+    );
+
+    /// A `tracing_subscriber::Layer` collecting the fields recorded on spans.
+    #[derive(Clone, Default)]
+    struct SpanFields(Arc<Mutex<HashMap<String, String>>>);
+
+    impl SpanFields {
+        fn get(&self, field: &str) -> Option<String> {
+            self.0.lock().unwrap().get(field).cloned()
+        }
+    }
+
+    impl Visit for SpanFields {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0
+                .lock()
+                .unwrap()
+                .insert(field.name().to_owned(), value.to_owned());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .lock()
+                .unwrap()
+                .insert(field.name().to_owned(), format!("{value:?}"));
+        }
     }
+
+    impl<S: Subscriber> Layer<S> for SpanFields {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: LayerContext<'_, S>) {
+            attrs.record(&mut self.clone());
+        }
+
+        fn on_record(&self, _id: &Id, values: &Record<'_>, _ctx: LayerContext<'_, S>) {
+            values.record(&mut self.clone());
+        }
+    }
+
+    sequential_test!(
+        async fn test_user_span_fields() {
+            let lib = LibraryWeb::new_test().await;
+            let router = lib.setup_router();
+            let fields = SpanFields::default();
+            let _subscriber =
+                tracing::subscriber::set_default(Registry::default().with(fields.clone()));
+
+            let response = get(&router, "/api/v1/user/profile/00000000000").await;
+            assert_eq!(response.status(), 404);
+            assert_eq!(fields.get("user.nation_id").as_deref(), Some("00000000000"));
+
+            get(&router, "/api/v1/user?user_name=span%20user").await;
+            assert_eq!(fields.get("query.user_name").as_deref(), Some("span user"));
+            assert_eq!(fields.get("query.book_name"), None);
+
+            get(&router, "/api/v1/user/blacklisted?limit=7").await;
+            assert_eq!(fields.get("query.limit").as_deref(), Some("7"));
+        }
+    );
+
+    sequential_test!(
+        async fn test_count_users() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let mut users = Vec::new();
+            for _ in 0..2 {
+                let fake_user = User::create_fake_user().await;
+                user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                    .await
+                    .expect("failed to insert fake user");
+                users.push(fake_user);
+            }
+            let fake_book = Book::create_fake_book(&lib.pool).await;
+            let router = lib.setup_router();
+            let response = post(&router, "/api/v1/book/create", &fake_book).await;
+            assert_eq!(response.status(), 201);
+            let uri = format!("/api/v1/user/rent/{}", encode(&users[0].nation_id));
+            let rent_book = RentBook {
+                book_name: fake_book.name.clone(),
+                due_date: "2023-05-09".to_owned(),
+            };
+            let response = post(&router, uri, &rent_book).await;
+            assert_eq!(response.status(), 201);
+
+            for (uri, count) in [
+                "/api/v1/user/count".to_owned(),
+                format!("/api/v1/user/count?user_name={}", encode(&users[1].name)),
+                format!("/api/v1/user/count?book_name={}", encode(&fake_book.name)),
+                "/api/v1/user/count?book_name=missing".to_owned(),
+            ]
+            .into_iter()
+            .zip([2, 1, 1, 0])
+            {
+                let response = get(&router, &uri).await;
+                assert_eq!(response.status(), 200);
+                let response_body = deserialize_response_body::<CountBody>(response).await;
+                assert_eq!(response_body.count, count, "{uri}");
+            }
+        }
+    );
+
+    sequential_test!(
+        async fn test_rent_book_idempotency_key() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let pool = lib.pool.clone();
+            let fake_user = User::create_fake_user().await;
+            user::insert_user(&pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert fake user");
+            // two copies, so a repeated rent would succeed
+            let fake_book = Book::create_fake_book(&pool).await;
+            book::insert_book_copies(&pool, DEFAULT_LIBRARY_ID, &fake_book, 2)
+                .await
+                .expect("failed to insert fake book copies");
+            let router = lib.setup_router();
+            let uri = format!("/api/v1/user/rent/{}", encode(&fake_user.nation_id));
+            let rent_book = RentBook {
+                book_name: fake_book.name.clone(),
+                due_date: "2023-05-09".to_owned(),
+            };
+            let rent_request = |key: &str| {
+                Request::builder()
+                    .method(Method::POST)
+                    .uri(&uri)
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(IDEMPOTENCY_KEY_HEADER, key)
+                    .body(serde_json::to_vec(&rent_book).unwrap().into())
+                    .expect("failed to build rent request")
+            };
+            let key = "6f1c2a3e-8d4b-4f0a-9c6e-2b7d5e1f3a90";
+
+            let response = send_request(&router, rent_request(key)).await;
+            assert_eq!(response.status(), 201);
+            let first = deserialize_response_body::<RentedBookBody>(response).await;
+            let response = send_request(&router, rent_request(key)).await;
+            assert_eq!(response.status(), 200);
+            let replayed = deserialize_response_body::<RentedBookBody>(response).await;
+            assert_eq!(replayed, first);
+
+            let rentals = sqlx::query_scalar::<_, i64>(
+                "SELECT COUNT(*) FROM users_history WHERE library_id = $1 AND nation_id = $2",
+            )
+            .bind(DEFAULT_LIBRARY_ID)
+            .bind(&fake_user.nation_id)
+            .fetch_one(&pool)
+            .await
+            .expect("failed to count rentals");
+            assert_eq!(rentals, 1);
+
+            let response = send_request(&router, rent_request("not-a-uuid")).await;
+            assert_eq!(response.status(), 400);
+            let response_body = deserialize_response_body::<ErrorBody>(response).await;
+            assert_eq!(response_body.error, ServerErr::BadRequest);
+        }
+    );
+
+    sequential_test!(
+        async fn test_active_users() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let mut users = Vec::new();
+            for _ in 0..3 {
+                let fake_user = User::create_fake_user().await;
+                user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                    .await
+                    .expect("failed to insert fake user");
+                users.push(fake_user);
+            }
+            users.sort_by(|a, b| a.nation_id.cmp(&b.nation_id));
+            let mut books = Vec::new();
+            for _ in 0..3 {
+                let fake_book = Book::create_fake_book(&lib.pool).await;
+                book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                    .await
+                    .expect("failed to insert fake book");
+                books.push(fake_book);
+            }
+            books.sort_by(|a, b| a.name.cmp(&b.name));
+            // the last user returned their rental, only the latest rental of a
+            // copy is outstanding
+            rent_book_times(&lib.pool, &users[2].nation_id, &books[0].name, 1).await;
+            for (user, book) in [
+                (&users[0], &books[0]),
+                (&users[1], &books[1]),
+                (&users[1], &books[2]),
+            ] {
+                let info = UserRentBook {
+                    nation_id: user.nation_id.clone(),
+                    book_name: book.name.clone(),
+                    due_date: "2999-01-01".to_owned(),
+                };
+                user::rent_book(&lib.pool, DEFAULT_LIBRARY_ID, &info)
+                    .await
+                    .expect("failed to rent book");
+            }
+            let router = lib.setup_router();
+
+            let response = get(&router, "/api/v1/user/active").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<UsersBody>(response).await;
+            let rentals: Vec<_> = response_body
+                .users
+                .iter()
+                .map(|row| (row.nation_id.as_str(), row.book_name.as_str()))
+                .collect();
+            assert_eq!(
+                rentals,
+                vec![
+                    (users[0].nation_id.as_str(), books[0].name.as_str()),
+                    (users[1].nation_id.as_str(), books[1].name.as_str()),
+                    (users[1].nation_id.as_str(), books[2].name.as_str()),
+                ]
+            );
+            assert_eq!(response_body.users[0].user_name, users[0].name);
+            assert_eq!(response_body.total, Some(3));
+
+            let response = get(&router, "/api/v1/user/active?limit=1&offset=1").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<UsersBody>(response).await;
+            assert_eq!(response_body.users.len(), 1);
+            assert_eq!(response_body.users[0].book_name, books[1].name);
+            assert_eq!(response_body.total, Some(3));
+        }
+    );
+
+    sequential_test!(
+        async fn test_inactive_users() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let mut users = Vec::new();
+            for _ in 0..3 {
+                let fake_user = User::create_fake_user().await;
+                user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                    .await
+                    .expect("failed to insert fake user");
+                users.push(fake_user);
+            }
+            let fake_book = Book::create_fake_book(&lib.pool).await;
+            book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                .await
+                .expect("failed to insert fake book");
+            // the first user never rented, the second rented 100 days ago and
+            // the third recently
+            let long_ago = (Utc::now() - chrono::Duration::days(100)).to_rfc3339();
+            insert_rental_at(&lib.pool, &users[1].nation_id, &fake_book.name, &long_ago).await;
+            rent_book_times(&lib.pool, &users[2].nation_id, &fake_book.name, 1).await;
+            let router = lib.setup_router();
+
+            let response = get(&router, "/api/v1/user/inactive").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<InactiveUsersBody>(response).await;
+            let nation_ids: Vec<_> = response_body
+                .users
+                .iter()
+                .map(|row| row.nation_id.as_str())
+                .collect();
+            assert_eq!(
+                nation_ids,
+                vec![users[0].nation_id.as_str(), users[1].nation_id.as_str()]
+            );
+            assert_eq!(response_body.users[0].last_rental_at, None);
+            assert!(response_body.users[1].last_rental_at.is_some());
+            assert_eq!(response_body.total, 2);
+
+            let response = get(&router, "/api/v1/user/inactive?days=120").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<InactiveUsersBody>(response).await;
+            assert_eq!(response_body.users.len(), 1);
+            assert_eq!(response_body.users[0].nation_id, users[0].nation_id);
+            assert_eq!(response_body.total, 1);
+
+            let response = get(&router, "/api/v1/user/inactive?limit=1&offset=1").await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<InactiveUsersBody>(response).await;
+            assert_eq!(response_body.users.len(), 1);
+            assert_eq!(response_body.users[0].nation_id, users[1].nation_id);
+            assert_eq!(response_body.total, 2);
+
+            let response = get(&router, "/api/v1/user/inactive?days=0").await;
+            assert_eq!(response.status(), 400);
+        }
+    );
+
+    sequential_test!(
+        async fn test_rent_books() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let pool = lib.pool.clone();
+            let fake_user = User::create_fake_user().await;
+            user::insert_user(&pool, DEFAULT_LIBRARY_ID, &fake_user)
+                .await
+                .expect("failed to insert fake user");
+            let mut books = Vec::new();
+            for _ in 0..3 {
+                let fake_book = Book::create_fake_book(&pool).await;
+                book::insert_book(&pool, DEFAULT_LIBRARY_ID, &fake_book)
+                    .await
+                    .expect("failed to insert fake book");
+                books.push(fake_book);
+            }
+            let router = lib.setup_router();
+            let uri = format!("/api/v1/user/rent/{}/batch", encode(&fake_user.nation_id));
+            let rent_books = |names: &[&str]| RentBooks {
+                books: names
+                    .iter()
+                    .map(|&name| RentBook {
+                        book_name: name.to_owned(),
+                        due_date: "2999-01-01".to_owned(),
+                    })
+                    .collect(),
+            };
+            let rentals = || async {
+                sqlx::query_scalar::<_, i64>(
+                    "SELECT COUNT(*) FROM users_history WHERE library_id = $1 AND nation_id = $2",
+                )
+                .bind(DEFAULT_LIBRARY_ID)
+                .bind(&fake_user.nation_id)
+                .fetch_one(&pool)
+                .await
+                .expect("failed to count rentals")
+            };
+
+            let response = post(
+                &router,
+                &uri,
+                &rent_books(&[&books[0].name, &books[1].name]),
+            )
+            .await;
+            assert_eq!(response.status(), 201);
+            let response_body = deserialize_response_body::<RentedBooksBody>(response).await;
+            let rented: Vec<_> = response_body
+                .books
+                .iter()
+                .map(|rental| rental.book_name.as_str())
+                .collect();
+            assert_eq!(rented, vec![books[0].name.as_str(), books[1].name.as_str()]);
+            assert_eq!(rentals().await, 2);
+
+            // the second book is rented now, so nothing is rented, not even
+            // the available third book
+            let response = post(
+                &router,
+                &uri,
+                &rent_books(&[&books[2].name, &books[1].name, "missing"]),
+            )
+            .await;
+            assert_eq!(response.status(), 409);
+            let response_body = deserialize_response_body::<ErrorBody>(response).await;
+            assert_eq!(response_body.error, ServerErr::Conflict);
+            assert_eq!(
+                response_body.message,
+                Some(format!("books not available: {}, missing", books[1].name))
+            );
+            assert_eq!(rentals().await, 2);
+            let response = get(
+                &router,
+                format!("/api/v1/book/by-name/{}", encode(&books[2].name)),
+            )
+            .await;
+            let response_body = deserialize_response_body::<GetBookBody>(response).await;
+            assert_eq!(response_body.book.status, Status::Available);
+
+            let too_many = [books[2].name.as_str(); MAX_RENT_BOOKS + 1];
+            for names in [&[][..], &too_many[..]] {
+                let response = post(&router, &uri, &rent_books(names)).await;
+                assert_eq!(response.status(), 400);
+            }
+            let uri = format!("/api/v1/user/rent/{}/batch", encode("00000000000"));
+            let response = post(&router, uri, &rent_books(&[&books[2].name])).await;
+            assert_eq!(response.status(), 404);
+        }
+    );
+
+    sequential_test!(
+        async fn test_transfer_rental() {
+            let (lib, _schema) = LibraryWeb::new_test_isolated().await;
+            let mut users = Vec::new();
+            for _ in 0..3 {
+                let fake_user = User::create_fake_user().await;
+                user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &fake_user)
+                    .await
+                    .expect("failed to insert fake user");
+                users.push(fake_user);
+            }
+            let mut books = Vec::new();
+            for _ in 0..2 {
+                let fake_book = Book::create_fake_book(&lib.pool).await;
+                book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &fake_book)
+                    .await
+                    .expect("failed to insert fake book");
+                books.push(fake_book);
+            }
+            // the second book was rented by the first user and returned
+            rent_book_times(&lib.pool, &users[0].nation_id, &books[1].name, 1).await;
+            let info = UserRentBook {
+                nation_id: users[0].nation_id.clone(),
+                book_name: books[0].name.clone(),
+                due_date: "2999-01-01".to_owned(),
+            };
+            user::rent_book(&lib.pool, DEFAULT_LIBRARY_ID, &info)
+                .await
+                .expect("failed to rent book");
+            let pool = lib.pool.clone();
+            let router = lib.setup_router();
+            let transfer = |from: &User, book: &Book| {
+                format!(
+                    "/api/v1/user/rent/{}/{}/transfer",
+                    encode(&from.nation_id),
+                    encode(&book.name)
+                )
+            };
+            let to = |user: &User| TransferBody {
+                to_nation_id: user.nation_id.clone(),
+            };
+
+            let response = put(&router, transfer(&users[0], &books[0]), &to(&users[1])).await;
+            assert_eq!(response.status(), 200);
+            let response_body = deserialize_response_body::<UserMessageBody>(response).await;
+            assert_eq!(response_body.nation_id, users[1].nation_id);
+            let (rentals, total) = user::active_users(&pool, DEFAULT_LIBRARY_ID, 10, 0)
+                .await
+                .expect("failed to get active users");
+            assert_eq!(total, 1);
+            assert_eq!(rentals[0].nation_id, users[1].nation_id);
+            assert_eq!(rentals[0].book_name, books[0].name);
+
+            // the book is not rented by the first user anymore, and the second
+            // book was returned
+            for book in &books {
+                let response = put(&router, transfer(&users[0], book), &to(&users[2])).await;
+                assert_eq!(response.status(), 404);
+            }
+            let response = put(&router, transfer(&users[1], &books[0]), &to(&users[1])).await;
+            assert_eq!(response.status(), 400);
+            let unknown = TransferBody {
+                to_nation_id: "00000000000".to_owned(),
+            };
+            let response = put(&router, transfer(&users[1], &books[0]), &unknown).await;
+            assert_eq!(response.status(), 404);
+            user::blacklist_user(&pool, DEFAULT_LIBRARY_ID, &users[2].nation_id)
+                .await
+                .expect("failed to blacklist user");
+            let response = put(&router, transfer(&users[1], &books[0]), &to(&users[2])).await;
+            assert_eq!(response.status(), 403);
+        }
+    );
 }
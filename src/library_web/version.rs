@@ -0,0 +1,109 @@
+use crate::helper::web::{ApiVersion, Response};
+use axum::{
+    http::{header, StatusCode, Uri},
+    response::{IntoResponse, Json},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Represents the body of a response containing the version of the API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct VersionBody {
+    pub version: ApiVersion,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/version",
+    tag = "version",
+    responses(
+        (status = 200, description = "get the latest version of the API", body = VersionBody)
+    )
+)]
+pub async fn version() -> Response<VersionBody> {
+    let response = VersionBody {
+        version: ApiVersion::LATEST,
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Redirects a request to an unversioned path, e.g. `/api/book`, to the same
+/// path of the latest version of the API, e.g. `/api/v1/book`, keeping the
+/// query string.
+pub async fn redirect_to_latest(uri: Uri) -> impl IntoResponse {
+    let path_and_query = uri
+        .path_and_query()
+        .map_or_else(|| uri.path(), |path_and_query| path_and_query.as_str());
+    let location = format!(
+        "{}{}",
+        ApiVersion::LATEST.prefix(),
+        path_and_query
+            .strip_prefix("/api")
+            .unwrap_or(path_and_query)
+    );
+    (
+        StatusCode::MOVED_PERMANENTLY,
+        [(header::LOCATION, location)],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library_web::tests::{deserialize_response_body, get, post};
+    use crate::library_web::LibraryWeb;
+    use crate::tests::sequential::sequential_test;
+    use axum::http::HeaderValue;
+
+    sequential_test!(
+        async fn test_version() {
+            let lib = LibraryWeb::new_test().await;
+            let router = lib.setup_router();
+
+            let response = get(&router, "/api/version").await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let body: VersionBody = deserialize_response_body(response).await;
+            assert_eq!(body.version, ApiVersion::V1);
+        }
+    );
+
+    sequential_test!(
+        async fn test_redirect_to_latest() {
+            let lib = LibraryWeb::new_test().await;
+            let router = lib.setup_router();
+
+            for (path, location) in [
+                ("/api/book", "/api/v1/book"),
+                ("/api/author", "/api/v1/author"),
+                ("/api/user", "/api/v1/user"),
+                (
+                    "/api/book/available?limit=1",
+                    "/api/v1/book/available?limit=1",
+                ),
+                (
+                    "/api/user/by-nation-id/12345678901",
+                    "/api/v1/user/by-nation-id/12345678901",
+                ),
+            ] {
+                let response = get(&router, path).await;
+                assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY, "{path}");
+                assert_eq!(
+                    response.headers().get(header::LOCATION),
+                    Some(&HeaderValue::from_static(location))
+                );
+            }
+
+            let response = post(&router, "/api/book/create", &()).await;
+            assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+            assert_eq!(
+                response.headers().get(header::LOCATION),
+                Some(&HeaderValue::from_static("/api/v1/book/create"))
+            );
+
+            for path in ["/api/v1/book", "/api/v1/author", "/api/v1/user"] {
+                let response = get(&router, path).await;
+                assert_eq!(response.status(), StatusCode::OK, "{path}");
+            }
+        }
+    );
+}
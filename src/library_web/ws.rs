@@ -0,0 +1,167 @@
+use super::LibraryWeb;
+use crate::library::book::Status;
+use crate::middleware::library::LibraryId;
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+use utoipa::ToSchema;
+
+/// Maximum number of book events kept for the clients not keeping up, the
+/// older events are skipped.
+pub const BOOK_EVENTS_CAPACITY: usize = 256;
+
+/// Represents a change of the status of a book, sent to the clients of
+/// `/api/v1/ws/book-updates` as a JSON text frame.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BookStatusEvent {
+    /// The library of the book, only its clients receive the event.
+    #[serde(skip)]
+    pub library_id: String,
+    pub book_name: String,
+    pub status: Status,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl BookStatusEvent {
+    /// Creates an event for a book of a library whose status changed now.
+    pub fn new(library_id: &str, book_name: &str, status: Status) -> Self {
+        Self {
+            library_id: library_id.to_owned(),
+            book_name: book_name.to_owned(),
+            status,
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+/// Creates the channel the book events are broadcast on.
+pub fn book_events_channel() -> broadcast::Sender<BookStatusEvent> {
+    broadcast::channel(BOOK_EVENTS_CAPACITY).0
+}
+
+impl LibraryWeb {
+    /// Broadcasts a book event to the connected clients. Having no client
+    /// connected is not an error, the event is dropped.
+    pub(crate) fn send_book_event(&self, event: BookStatusEvent) {
+        let _ = self.book_events.send(event);
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/ws/book-updates",
+    tag = "book",
+    responses(
+        (status = 101, description = "WebSocket opened, each change of the status of a book of the library is sent as a JSON text frame", body = BookStatusEvent),
+        (status = 400, description = "Not a WebSocket upgrade request")
+    )
+)]
+pub async fn book_updates(
+    State(library_web): State<LibraryWeb>,
+    LibraryId(library_id): LibraryId,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    // subscribe before upgrading so no event sent after the handshake is lost
+    let events = library_web.book_events.subscribe();
+    ws.on_upgrade(move |socket| forward_book_events(socket, events, library_id))
+}
+
+/// Forwards the events of the books of a library to a WebSocket until the
+/// client closes it.
+async fn forward_book_events(
+    mut socket: WebSocket,
+    mut events: broadcast::Receiver<BookStatusEvent>,
+    library_id: String,
+) {
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) if event.library_id == library_id => {
+                    let Ok(text) = serde_json::to_string(&event) else {
+                        continue;
+                    };
+                    if socket.send(Message::Text(text)).await.is_err() {
+                        return;
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "book updates client lagging, events skipped");
+                }
+                Err(RecvError::Closed) => return,
+            },
+            // the messages of the client are ignored, only its closing matters
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::book::{self, Book};
+    use crate::library::user::{self, RentBook, User};
+    use crate::library_web::tests::post;
+    use crate::middleware::library::DEFAULT_LIBRARY_ID;
+    use crate::tests::sequential::sequential_test;
+    use futures::StreamExt;
+    use std::time::Duration;
+    use urlencoding::encode;
+
+    sequential_test!(
+        async fn test_book_updates() {
+            let lib = LibraryWeb::new_test().await;
+            let book = Book::create_fake_book(&lib.pool).await;
+            book::insert_book(&lib.pool, DEFAULT_LIBRARY_ID, &book)
+                .await
+                .expect("failed to insert book");
+            let user = User::create_fake_user().await;
+            user::insert_user(&lib.pool, DEFAULT_LIBRARY_ID, &user)
+                .await
+                .expect("failed to insert user");
+            let router = lib.setup_router();
+
+            // WebSockets need a real connection
+            let listener =
+                std::net::TcpListener::bind("127.0.0.1:0").expect("failed to bind listener");
+            let addr = listener.local_addr().expect("failed to get local address");
+            let server = axum::Server::from_tcp(listener)
+                .expect("failed to create server")
+                .serve(router.clone().into_make_service());
+            tokio::spawn(server);
+            let (mut socket, _) =
+                tokio_tungstenite::connect_async(format!("ws://{addr}/api/v1/ws/book-updates"))
+                    .await
+                    .expect("failed to connect");
+
+            let rent_book = RentBook {
+                book_name: book.name.clone(),
+                due_date: "2030-06-15".to_owned(),
+            };
+            let uri = format!("/api/v1/user/rent/{}", encode(&user.nation_id));
+            let response = post(&router, uri, &rent_book).await;
+            assert_eq!(response.status(), 201);
+
+            let message = tokio::time::timeout(Duration::from_secs(5), socket.next())
+                .await
+                .expect("no book event received")
+                .expect("WebSocket closed")
+                .expect("failed to read WebSocket message");
+            let event: BookStatusEvent =
+                serde_json::from_str(message.to_text().expect("not a text frame"))
+                    .expect("failed to deserialize book event");
+            assert_eq!(event.book_name, book.name);
+            assert_eq!(event.status, Status::Rented);
+        }
+    );
+}
@@ -1,21 +1,38 @@
+mod config;
 mod database;
 mod docs;
 mod helper;
 mod library;
 mod library_web;
+mod middleware;
 mod telemetry;
+#[cfg(test)]
+mod tests;
 
+use crate::config::AppConfig;
 use crate::library_web::LibraryWeb;
+use crate::middleware::library::DEFAULT_LIBRARY_ID;
+use crate::telemetry::config::TelemetryConfig;
+use crate::telemetry::db_metrics::PoolMetrics;
 use std::net::SocketAddr;
 
 #[tokio::main]
 async fn main() {
-    telemetry::init::tracing();
+    let telemetry_config = TelemetryConfig::from_env();
+    telemetry::init::tracing(&telemetry_config);
+    let _metrics = telemetry::init::metrics(&telemetry_config);
 
     let pool = database::postgres::init::pg_pool()
         .await
         .expect("failed to connect to postgres");
-    let router = LibraryWeb::new(pool).setup_router();
+    let meter = telemetry_config.meter();
+    let pool_metrics =
+        PoolMetrics::new(pool.clone(), &meter).expect("failed to register pool metrics");
+    pool_metrics.spawn();
+    let config = AppConfig::from_env();
+    let router = LibraryWeb::with_config(pool, config, DEFAULT_LIBRARY_ID.to_owned())
+        .with_meter(meter)
+        .setup_router();
     // if you run with local:
     // let addr = SocketAddr::from(([127, 0, 0, 1], 8000));
     let addr = SocketAddr::from(([0, 0, 0, 0], 8000));
@@ -0,0 +1,168 @@
+use crate::helper::web::{ErrorBody, ServerErr};
+use axum::{
+    http::{header::ACCEPT, HeaderMap, Request, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+
+/// A `tower::Layer` that rejects the requests whose `Accept` header doesn't
+/// accept JSON with `406 Not Acceptable`.
+///
+/// JSON is accepted by the `application/json`, `application/*` and `*/*`
+/// media ranges, and by requests without an `Accept` header. The endpoints not
+/// responding with JSON, like the CSV export, are excluded by path: a request
+/// is not checked when its path is one of the excluded paths or nested under
+/// one of them. The excluded paths may have parameters, like the routes, e.g.
+/// `/api/v1/book/:book_id/qr-code`.
+#[derive(Debug, Clone)]
+pub struct RequireJsonAcceptLayer {
+    excluded_paths: Arc<[String]>,
+}
+
+impl RequireJsonAcceptLayer {
+    /// Creates a new `RequireJsonAcceptLayer` not checking `excluded_paths`.
+    pub fn new(excluded_paths: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            excluded_paths: excluded_paths.into_iter().collect(),
+        }
+    }
+}
+
+impl<S> Layer<S> for RequireJsonAcceptLayer {
+    type Service = RequireJsonAcceptService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequireJsonAcceptService {
+            inner,
+            excluded_paths: self.excluded_paths.clone(),
+        }
+    }
+}
+
+/// The `tower::Service` created by `RequireJsonAcceptLayer`.
+#[derive(Debug, Clone)]
+pub struct RequireJsonAcceptService<S> {
+    inner: S,
+    excluded_paths: Arc<[String]>,
+}
+
+impl<S, B> Service<Request<B>> for RequireJsonAcceptService<S>
+where
+    S: Service<Request<B>, Response = Response, Error = Infallible>,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let path = request.uri().path();
+        let excluded = self
+            .excluded_paths
+            .iter()
+            .any(|excluded_path| is_excluded(path, excluded_path));
+        if !excluded && !accepts_json(request.headers()) {
+            let response = (
+                StatusCode::NOT_ACCEPTABLE,
+                Json(ErrorBody {
+                    error: ServerErr::NotAcceptable,
+                    message: Some("responses are only available as application/json".to_owned()),
+                }),
+            )
+                .into_response();
+            return Box::pin(async move { Ok(response) });
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
+
+/// Returns whether `path` is `excluded_path` or nested under it. A segment of
+/// `excluded_path` starting with `:`, like `:book_id`, matches any segment.
+fn is_excluded(path: &str, excluded_path: &str) -> bool {
+    let mut segments = path.split('/');
+    excluded_path.split('/').all(|excluded_segment| {
+        segments.next().is_some_and(|segment| {
+            segment == excluded_segment
+                || (excluded_segment.starts_with(':') && !segment.is_empty())
+        })
+    })
+}
+
+/// Returns whether the `Accept` header of a request accepts JSON.
+fn accepts_json(headers: &HeaderMap) -> bool {
+    let mut accept = headers.get_all(ACCEPT).iter().peekable();
+    if accept.peek().is_none() {
+        return true;
+    }
+    accept
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|media_range| {
+            media_range
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_ascii_lowercase()
+        })
+        .any(|media_range| {
+            matches!(
+                media_range.as_str(),
+                "application/json" | "application/*" | "*/*"
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn headers(accept: Option<&'static str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(accept) = accept {
+            headers.insert(ACCEPT, HeaderValue::from_static(accept));
+        }
+        headers
+    }
+
+    #[test]
+    fn test_accepts_json() {
+        assert!(accepts_json(&headers(None)));
+        assert!(accepts_json(&headers(Some("application/json"))));
+        assert!(accepts_json(&headers(Some(
+            "Application/JSON; charset=utf-8"
+        ))));
+        assert!(accepts_json(&headers(Some(
+            "text/html, application/*;q=0.9"
+        ))));
+        assert!(accepts_json(&headers(Some(
+            "text/html,application/xhtml+xml,*/*;q=0.8"
+        ))));
+        assert!(!accepts_json(&headers(Some("text/html"))));
+        assert!(!accepts_json(&headers(Some("text/csv, text/plain"))));
+    }
+
+    #[test]
+    fn test_is_excluded() {
+        assert!(is_excluded("/api/swagger", "/api/swagger"));
+        assert!(is_excluded("/api/swagger/index.html", "/api/swagger"));
+        assert!(!is_excluded("/api/swaggers", "/api/swagger"));
+        assert!(!is_excluded("/api", "/api/swagger"));
+        let qr_code = "/api/v1/book/:book_id/qr-code";
+        assert!(is_excluded("/api/v1/book/some-id/qr-code", qr_code));
+        assert!(!is_excluded("/api/v1/book//qr-code", qr_code));
+        assert!(!is_excluded("/api/v1/book/some-id", qr_code));
+    }
+}
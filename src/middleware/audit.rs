@@ -0,0 +1,249 @@
+use crate::helper::web::ApiVersion;
+use crate::library::audit::{self, AuditEntry};
+use crate::middleware::library::LibraryId;
+use axum::{
+    body::Body,
+    extract::MatchedPath,
+    http::{Method, Request, StatusCode},
+    response::{IntoResponse, Response},
+};
+use http_body::{LengthLimitError, Limited};
+use serde_json::Value;
+use sqlx::PgPool;
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+
+/// The fields of a request body holding personal data, whose values are
+/// replaced by `REDACTED` in the audit log.
+const REDACTED_FIELDS: [&str; 2] = ["email", "phone"];
+
+/// The value stored instead of a redacted field.
+const REDACTED: &str = "[redacted]";
+
+/// A `tower::Layer` that writes an audit log entry for every successful
+/// mutating request.
+///
+/// A request is audited when its method is `POST`, `PUT`, `PATCH` or
+/// `DELETE` and the response has a 2xx status code. The entry is inserted in
+/// the background, so a failing insert never affects the response. The
+/// database pool and the `LibraryId` are taken from the request extensions,
+/// which means an `Extension<PgPool>` layer and a `LibraryIdLayer` must be
+/// added outside of this one.
+///
+/// The request body is buffered to be stored in the entry, so bodies larger
+/// than `body_limit` bytes are rejected with `413 Payload Too Large`. The
+/// personal data of the body, i.e. the `REDACTED_FIELDS`, is never stored.
+#[derive(Debug, Clone, Copy)]
+pub struct AuditLayer {
+    body_limit: usize,
+}
+
+impl AuditLayer {
+    /// Creates a new `AuditLayer` buffering at most `body_limit` bytes of a
+    /// request body.
+    pub fn new(body_limit: usize) -> Self {
+        Self { body_limit }
+    }
+}
+
+impl<S> Layer<S> for AuditLayer {
+    type Service = AuditService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuditService {
+            inner,
+            body_limit: self.body_limit,
+        }
+    }
+}
+
+/// The `tower::Service` created by `AuditLayer`.
+#[derive(Debug, Clone)]
+pub struct AuditService<S> {
+    inner: S,
+    body_limit: usize,
+}
+
+impl<S> Service<Request<Body>> for AuditService<S>
+where
+    S: Service<Request<Body>, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // take the service that was driven to readiness and leave a clone behind
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        if !is_mutating(request.method()) {
+            return Box::pin(inner.call(request));
+        }
+
+        let body_limit = self.body_limit;
+        Box::pin(async move {
+            let (parts, body) = request.into_parts();
+            let bytes = match hyper::body::to_bytes(Limited::new(body, body_limit)).await {
+                Ok(bytes) => bytes,
+                Err(err) if err.is::<LengthLimitError>() => {
+                    return Ok((StatusCode::PAYLOAD_TOO_LARGE, err.to_string()).into_response())
+                }
+                Err(err) => return Ok((StatusCode::BAD_REQUEST, err.to_string()).into_response()),
+            };
+            let pool = parts.extensions.get::<PgPool>().cloned();
+            let library_id = parts.extensions.get::<LibraryId>().cloned();
+            let entry = audit_entry(
+                &parts.method,
+                parts.uri.path(),
+                parts
+                    .extensions
+                    .get::<MatchedPath>()
+                    .map(MatchedPath::as_str),
+                &bytes,
+            );
+
+            let response = inner
+                .call(Request::from_parts(parts, Body::from(bytes)))
+                .await?;
+
+            if response.status().is_success() {
+                match (pool, library_id) {
+                    (Some(pool), Some(LibraryId(library_id))) => {
+                        tokio::spawn(async move {
+                            if let Err(err) = audit::insert_audit(&pool, &library_id, &entry).await
+                            {
+                                tracing::error!("failed to insert audit log entry: {}", err);
+                            }
+                        });
+                    }
+                    _ => tracing::warn!(
+                        "audit log skipped, no database pool or library ID in extensions"
+                    ),
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// Returns whether requests with the given method change data.
+fn is_mutating(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    )
+}
+
+/// Builds the audit log entry of a request.
+///
+/// The entity type is the path segment following `/api` and the API version,
+/// e.g. `book` for `/api/v1/book`, and the entity ID is the value of the first
+/// path parameter of the matched route, if any. The body is stored as JSON when
+/// it can be parsed as such, with the values of the `REDACTED_FIELDS` redacted.
+fn audit_entry(method: &Method, path: &str, matched_path: Option<&str>, body: &[u8]) -> AuditEntry {
+    let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+    let entity_type = segments
+        .iter()
+        .find(|segment| {
+            **segment != "api"
+                && !ApiVersion::ALL
+                    .iter()
+                    .any(|version| version.to_string() == **segment)
+        })
+        .map_or_else(String::new, |segment| segment.to_string());
+    let entity_id = matched_path.and_then(|matched_path| {
+        matched_path
+            .trim_matches('/')
+            .split('/')
+            .zip(segments.iter())
+            .find(|(pattern, _)| pattern.starts_with(':'))
+            .map(|(_, segment)| segment.to_string())
+    });
+    let route = matched_path.unwrap_or(path);
+    let mut payload_json = serde_json::from_slice(body).ok();
+    if let Some(payload) = payload_json.as_mut() {
+        redact(payload);
+    }
+    AuditEntry {
+        entity_type,
+        entity_id,
+        action: format!("{method} {route}"),
+        payload_json,
+    }
+}
+
+/// Replaces the values of the `REDACTED_FIELDS` of `value`, at any depth.
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if REDACTED_FIELDS.contains(&key.as_str()) {
+                    *value = Value::from(REDACTED);
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_audit_entry() {
+        let body = serde_json::to_vec(&json!({ "book_name": "name" })).unwrap();
+        let entry = audit_entry(
+            &Method::POST,
+            "/api/v1/user/rent/12345678901",
+            Some("/api/v1/user/rent/:nation_id"),
+            &body,
+        );
+        assert_eq!(entry.entity_type, "user");
+        assert_eq!(entry.entity_id.as_deref(), Some("12345678901"));
+        assert_eq!(entry.action, "POST /api/v1/user/rent/:nation_id");
+        assert_eq!(entry.payload_json, Some(json!({ "book_name": "name" })));
+
+        let entry = audit_entry(&Method::DELETE, "/api/v1/book/bulk", None, &[]);
+        assert_eq!(entry.entity_type, "book");
+        assert_eq!(entry.entity_id, None);
+        assert_eq!(entry.action, "DELETE /api/v1/book/bulk");
+        assert_eq!(entry.payload_json, None);
+    }
+
+    #[test]
+    fn test_audit_entry_redacts_personal_data() {
+        let body = serde_json::to_vec(&json!({
+            "nation_id": "12345678901",
+            "email": "user@example.com",
+            "phone": "+905551234567",
+            "users": [{ "email": "other@example.com", "name": "name" }],
+        }))
+        .unwrap();
+        let entry = audit_entry(&Method::POST, "/api/v1/user", None, &body);
+        assert_eq!(
+            entry.payload_json,
+            Some(json!({
+                "nation_id": "12345678901",
+                "email": REDACTED,
+                "phone": REDACTED,
+                "users": [{ "email": REDACTED, "name": "name" }],
+            }))
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use crate::helper::web::{ErrorBody, ServerErr};
+use axum::{
+    http::{header::CONTENT_TYPE, HeaderMap, Method, Request, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use http_body::Body as HttpBody;
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+
+/// A `tower::Layer` that rejects the `POST`, `PUT` and `PATCH` requests whose
+/// body is not JSON with `415 Unsupported Media Type`.
+///
+/// A body is accepted when its `Content-Type` is `application/json`, a
+/// `+json` type like `application/merge-patch+json`, or, for the file uploads,
+/// `application/x-ndjson` and `multipart/form-data`. Requests without a body,
+/// like `POST /api/v1/book/:book_id/restore`, don't need a `Content-Type`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequireJsonContentTypeLayer;
+
+impl<S> Layer<S> for RequireJsonContentTypeLayer {
+    type Service = RequireJsonContentTypeService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequireJsonContentTypeService { inner }
+    }
+}
+
+/// The `tower::Service` created by `RequireJsonContentTypeLayer`.
+#[derive(Debug, Clone)]
+pub struct RequireJsonContentTypeService<S> {
+    inner: S,
+}
+
+impl<S, B> Service<Request<B>> for RequireJsonContentTypeService<S>
+where
+    S: Service<Request<B>, Response = Response, Error = Infallible>,
+    S::Future: Send + 'static,
+    B: HttpBody,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let has_body = request.body().size_hint().exact() != Some(0);
+        if has_body_method(request.method()) && !is_supported(request.headers(), has_body) {
+            let response = (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                Json(ErrorBody {
+                    error: ServerErr::UnsupportedMediaType,
+                    message: Some(
+                        "request body must have Content-Type: application/json".to_owned(),
+                    ),
+                }),
+            )
+                .into_response();
+            return Box::pin(async move { Ok(response) });
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
+
+/// Returns whether requests with the given method are expected to have a body.
+fn has_body_method(method: &Method) -> bool {
+    matches!(*method, Method::POST | Method::PUT | Method::PATCH)
+}
+
+/// Returns whether the `Content-Type` of a request is supported.
+fn is_supported(headers: &HeaderMap, has_body: bool) -> bool {
+    let Some(content_type) = headers.get(CONTENT_TYPE) else {
+        return !has_body;
+    };
+    let Ok(content_type) = content_type.to_str() else {
+        return false;
+    };
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    mime == "application/json"
+        || (mime.starts_with("application/") && mime.ends_with("+json"))
+        || mime == "application/x-ndjson"
+        || mime == "multipart/form-data"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn headers(content_type: Option<&'static str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(content_type) = content_type {
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        }
+        headers
+    }
+
+    #[test]
+    fn test_is_supported() {
+        assert!(is_supported(&headers(Some("application/json")), true));
+        assert!(is_supported(
+            &headers(Some("Application/JSON; charset=utf-8")),
+            true
+        ));
+        assert!(is_supported(
+            &headers(Some("application/merge-patch+json")),
+            true
+        ));
+        assert!(is_supported(&headers(Some("application/x-ndjson")), true));
+        assert!(is_supported(
+            &headers(Some("multipart/form-data; boundary=x")),
+            true
+        ));
+        assert!(is_supported(&headers(None), false));
+        assert!(!is_supported(&headers(None), true));
+        assert!(!is_supported(&headers(Some("text/plain")), true));
+        assert!(!is_supported(&headers(Some("text/plain")), false));
+        assert!(!is_supported(&headers(Some("application/jsonp")), true));
+    }
+}
@@ -0,0 +1,286 @@
+use crate::helper::web::{ErrorBody, ErrorResponse, ServerErr};
+use crate::library::error::LibraryError;
+use crate::library::idempotency::{self, IdempotencyKeyState};
+use crate::middleware::library::LibraryId;
+use axum::{
+    body::{self, Body},
+    http::{Request, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use sqlx::PgPool;
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tower::{Layer, Service};
+use uuid::Uuid;
+
+/// Name of the header carrying the idempotency key of a request.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "x-idempotency-key";
+
+/// A `tower::Layer` that lets clients safely retry requests sent with an
+/// `X-Idempotency-Key` header.
+///
+/// The first request with a key is handled and, when successful, its JSON
+/// response body is stored for `IDEMPOTENCY_KEY_TTL`. Later requests with the
+/// same key are not handled again and get the stored body with `200 OK`. A
+/// key whose request failed can be used again.
+///
+/// Keys that are not UUIDs or were used for a request to another path are
+/// rejected with `400 Bad Request`, and requests whose key is used by a
+/// request still being handled with `409 Conflict`. Requests without the
+/// header are handled as usual.
+///
+/// A key is only held for `lease` while its request is handled, and is
+/// released as soon as the request is dropped before completing, e.g. when
+/// it times out or the client disconnects, so a retry is not rejected with
+/// `409 Conflict` until the key expires.
+///
+/// The database pool and the `LibraryId` are taken from the request
+/// extensions, which means an `Extension<PgPool>` layer and a
+/// `LibraryIdLayer` must be added outside of this one.
+#[derive(Debug, Clone, Copy)]
+pub struct IdempotencyLayer {
+    lease: Duration,
+}
+
+impl IdempotencyLayer {
+    /// Creates a new `IdempotencyLayer` holding the key of a request for at
+    /// most `lease` while the request is handled, e.g. the request timeout.
+    pub fn new(lease: Duration) -> Self {
+        Self { lease }
+    }
+}
+
+impl<S> Layer<S> for IdempotencyLayer {
+    type Service = IdempotencyService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        IdempotencyService {
+            inner,
+            lease: self.lease,
+        }
+    }
+}
+
+/// The `tower::Service` created by `IdempotencyLayer`.
+#[derive(Debug, Clone)]
+pub struct IdempotencyService<S> {
+    inner: S,
+    lease: Duration,
+}
+
+/// An idempotency key reserved by a request, released when dropped before
+/// the request is handled.
+struct Reservation {
+    pool: PgPool,
+    library_id: String,
+    key: Uuid,
+    handled: bool,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if self.handled {
+            return;
+        }
+        // the request was dropped, e.g. on timeout, so the key is released in
+        // the background
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let pool = self.pool.clone();
+        let library_id = std::mem::take(&mut self.library_id);
+        let key = self.key;
+        runtime.spawn(async move {
+            if let Err(err) = idempotency::release_idempotency_key(&pool, &library_id, key).await {
+                tracing::error!("failed to release idempotency key: {}", err);
+            }
+        });
+    }
+}
+
+impl<S> Service<Request<Body>> for IdempotencyService<S>
+where
+    S: Service<Request<Body>, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // take the service that was driven to readiness and leave a clone behind
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        let Some(key) = request.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+            return Box::pin(inner.call(request));
+        };
+        let Some(key) = key.to_str().ok().and_then(|key| Uuid::parse_str(key).ok()) else {
+            let err = LibraryError::Invalid("X-Idempotency-Key must be a UUID".to_owned());
+            return Box::pin(async move { Ok(err.into_response()) });
+        };
+        let pool = request.extensions().get::<PgPool>().cloned();
+        let library_id = request.extensions().get::<LibraryId>().cloned();
+        let (Some(pool), Some(LibraryId(library_id))) = (pool, library_id) else {
+            tracing::warn!("idempotency key ignored, no database pool or library ID in extensions");
+            return Box::pin(inner.call(request));
+        };
+
+        let lease = self.lease;
+        Box::pin(async move {
+            let path = request.uri().path().to_owned();
+            let state =
+                match idempotency::reserve_idempotency_key(&pool, &library_id, key, &path, lease)
+                    .await
+                {
+                    Ok(state) => state,
+                    Err(err) => return Ok(err.into_response()),
+                };
+            match state {
+                IdempotencyKeyState::Reserved => {}
+                IdempotencyKeyState::Completed(body) => {
+                    return Ok((StatusCode::OK, Json(body)).into_response())
+                }
+                IdempotencyKeyState::InProgress => {
+                    let response: ErrorResponse = (
+                        StatusCode::CONFLICT,
+                        Json(ErrorBody {
+                            error: ServerErr::Conflict,
+                            message: Some(
+                                "a request with this idempotency key is in progress".to_owned(),
+                            ),
+                        }),
+                    );
+                    return Ok(response.into_response());
+                }
+                IdempotencyKeyState::Mismatch => {
+                    let err = LibraryError::Invalid(
+                        "idempotency key already used for another request".to_owned(),
+                    );
+                    return Ok(err.into_response());
+                }
+            }
+
+            let mut reservation = Reservation {
+                pool,
+                library_id,
+                key,
+                handled: false,
+            };
+            let response = inner.call(request).await?;
+            let (pool, library_id) = (&reservation.pool, &reservation.library_id);
+            if !response.status().is_success() {
+                if let Err(err) = idempotency::release_idempotency_key(pool, library_id, key).await
+                {
+                    tracing::error!("failed to release idempotency key: {}", err);
+                }
+                reservation.handled = true;
+                return Ok(response);
+            }
+            let (parts, response_body) = response.into_parts();
+            let bytes = match hyper::body::to_bytes(response_body).await {
+                Ok(bytes) => bytes,
+                Err(err) => {
+                    tracing::error!("failed to read response body: {}", err);
+                    return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+                }
+            };
+            let stored = match serde_json::from_slice(&bytes) {
+                Ok(json) => {
+                    idempotency::complete_idempotency_key(pool, library_id, key, &json).await
+                }
+                // only JSON bodies are replayed
+                Err(_) => idempotency::release_idempotency_key(pool, library_id, key).await,
+            };
+            if let Err(err) = stored {
+                tracing::error!("failed to store idempotent response: {}", err);
+            }
+            reservation.handled = true;
+            Ok(Response::from_parts(parts, body::boxed(Body::from(bytes))))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library_web::tests::send_request;
+    use crate::middleware::library::{LibraryIdLayer, DEFAULT_LIBRARY_ID};
+    use crate::tests::sequential::sequential_test;
+    use axum::{http::Method, routing, Extension, Router};
+    use serde_json::{json, Value};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use tower::ServiceExt;
+
+    /// Hangs on its first call, as a request that never completes, and
+    /// answers `201 Created` afterwards.
+    async fn hang_once(Extension(calls): Extension<Arc<AtomicUsize>>) -> (StatusCode, Json<Value>) {
+        if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+            std::future::pending::<()>().await;
+        }
+        (StatusCode::CREATED, Json(json!({ "rented": true })))
+    }
+
+    sequential_test!(
+        async fn test_dropped_request_releases_key() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let key = Uuid::from_u128(2);
+            sqlx::query("DELETE FROM idempotency_cache WHERE idempotency_key = $1")
+                .bind(key)
+                .execute(&pool)
+                .await
+                .expect("failed to delete idempotency keys");
+            let calls = Arc::new(AtomicUsize::new(0));
+            let router = Router::new()
+                .route(
+                    "/rent",
+                    routing::post(hang_once).layer(IdempotencyLayer::new(Duration::from_secs(60))),
+                )
+                .layer(Extension(calls.clone()))
+                .layer(LibraryIdLayer::new(DEFAULT_LIBRARY_ID.to_owned()))
+                .layer(Extension(pool));
+            let request = || {
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/rent")
+                    .header(IDEMPOTENCY_KEY_HEADER, key.to_string())
+                    .body(Body::empty())
+                    .expect("failed to build request")
+            };
+
+            // the first request is dropped before completing, as on timeout
+            let dropped = tokio::time::timeout(
+                Duration::from_millis(200),
+                router.clone().oneshot(request()),
+            )
+            .await;
+            assert!(dropped.is_err());
+
+            // the key is released in the background, well before its lease ends
+            let mut response = send_request(&router, request()).await;
+            for _ in 0..50 {
+                if response.status() != StatusCode::CONFLICT {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                response = send_request(&router, request()).await;
+            }
+            assert_eq!(response.status(), StatusCode::CREATED);
+            assert_eq!(calls.load(Ordering::SeqCst), 2);
+        }
+    );
+}
@@ -0,0 +1,150 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, Request, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+
+/// Name of the header selecting the library a request operates on.
+pub const LIBRARY_ID_HEADER: &str = "x-library-id";
+
+/// ID of the library used when a request has no `X-Library-Id` header.
+pub const DEFAULT_LIBRARY_ID: &str = "default";
+
+/// Maximum length of a library ID, matching the `library_id` columns.
+pub const MAX_LIBRARY_ID_LENGTH: usize = 100;
+
+/// The ID of the library a request operates on.
+///
+/// `LibraryIdLayer` stores it in the request extensions, from where it is
+/// extracted by the handlers. Every query of the `library` module is scoped to
+/// a single library.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LibraryId(pub String);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for LibraryId
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts.extensions.get::<LibraryId>().cloned().ok_or((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Internal server error".to_owned(),
+        ))
+    }
+}
+
+/// A `tower::Layer` that reads the `X-Library-Id` header of every request and
+/// stores the `LibraryId` in the request extensions.
+///
+/// Requests without the header operate on the default library, so clients
+/// unaware of multitenancy keep working. Library IDs that are empty, longer
+/// than `MAX_LIBRARY_ID_LENGTH` or contain characters other than ASCII
+/// letters, digits, `-` and `_` are rejected with `400 Bad Request`.
+#[derive(Debug, Clone)]
+pub struct LibraryIdLayer {
+    default_library_id: String,
+}
+
+impl LibraryIdLayer {
+    /// Creates a new `LibraryIdLayer` falling back to `default_library_id`.
+    pub fn new(default_library_id: String) -> Self {
+        Self { default_library_id }
+    }
+}
+
+impl<S> Layer<S> for LibraryIdLayer {
+    type Service = LibraryIdService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        LibraryIdService {
+            inner,
+            default_library_id: self.default_library_id.clone(),
+        }
+    }
+}
+
+/// The `tower::Service` created by `LibraryIdLayer`.
+#[derive(Debug, Clone)]
+pub struct LibraryIdService<S> {
+    inner: S,
+    default_library_id: String,
+}
+
+impl<S, B> Service<Request<B>> for LibraryIdService<S>
+where
+    S: Service<Request<B>, Response = Response, Error = Infallible>,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        let library_id = match request.headers().get(LIBRARY_ID_HEADER) {
+            None => self.default_library_id.clone(),
+            Some(value) => match value.to_str().map_err(|_| ()).and_then(parse_library_id) {
+                Ok(library_id) => library_id,
+                Err(()) => {
+                    let response = (
+                        StatusCode::BAD_REQUEST,
+                        "X-Library-Id must be 1 to 100 letters, digits, '-' or '_'",
+                    )
+                        .into_response();
+                    return Box::pin(async move { Ok(response) });
+                }
+            },
+        };
+        request.extensions_mut().insert(LibraryId(library_id));
+        Box::pin(self.inner.call(request))
+    }
+}
+
+/// Parses the value of an `X-Library-Id` header.
+fn parse_library_id(value: &str) -> Result<String, ()> {
+    let valid = !value.is_empty()
+        && value.len() <= MAX_LIBRARY_ID_LENGTH
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(value.to_owned())
+    } else {
+        Err(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_library_id() {
+        assert_eq!(parse_library_id("branch-1_a"), Ok("branch-1_a".to_owned()));
+        assert_eq!(parse_library_id(""), Err(()));
+        assert_eq!(parse_library_id("branch 1"), Err(()));
+        assert_eq!(parse_library_id("branch/1"), Err(()));
+        assert_eq!(
+            parse_library_id(&"a".repeat(MAX_LIBRARY_ID_LENGTH)).map(|id| id.len()),
+            Ok(100)
+        );
+        assert_eq!(
+            parse_library_id(&"a".repeat(MAX_LIBRARY_ID_LENGTH + 1)),
+            Err(())
+        );
+    }
+}
@@ -0,0 +1,7 @@
+pub mod accept;
+pub mod audit;
+pub mod content_type;
+pub mod idempotency;
+pub mod library;
+pub mod role;
+pub mod timing;
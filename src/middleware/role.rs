@@ -0,0 +1,105 @@
+use crate::helper::web::{ErrorBody, ServerErr};
+use axum::{
+    http::{HeaderMap, Request, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+
+/// Name of the header carrying the role of the user sending a request.
+///
+/// The API doesn't verify the `Authorization` token itself, the gateway in
+/// front of it does and sets this header from the claims of the token.
+pub const ROLE_HEADER: &str = "x-user-role";
+
+/// The role allowed to use the admin routes.
+pub const ADMIN_ROLE: &str = "admin";
+
+/// A `tower::Layer` that rejects the requests not sent by an admin with
+/// `403 Forbidden`.
+///
+/// A request is sent by an admin when its `X-User-Role` header is `admin`.
+/// The layer is meant to be applied with `Router::route_layer`, so unknown
+/// paths are still answered with `404 Not Found`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequireAdminLayer;
+
+impl<S> Layer<S> for RequireAdminLayer {
+    type Service = RequireAdminService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequireAdminService { inner }
+    }
+}
+
+/// The `tower::Service` created by `RequireAdminLayer`.
+#[derive(Debug, Clone)]
+pub struct RequireAdminService<S> {
+    inner: S,
+}
+
+impl<S, B> Service<Request<B>> for RequireAdminService<S>
+where
+    S: Service<Request<B>, Response = Response, Error = Infallible>,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        if !is_admin(request.headers()) {
+            let response = (
+                StatusCode::FORBIDDEN,
+                Json(ErrorBody {
+                    error: ServerErr::Forbidden,
+                    message: Some("this operation requires the admin role".to_owned()),
+                }),
+            )
+                .into_response();
+            return Box::pin(async move { Ok(response) });
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
+
+/// Returns whether the `X-User-Role` header of a request is the admin role.
+fn is_admin(headers: &HeaderMap) -> bool {
+    headers
+        .get(ROLE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |role| role.trim().eq_ignore_ascii_case(ADMIN_ROLE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn headers(role: Option<&'static str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(role) = role {
+            headers.insert(ROLE_HEADER, HeaderValue::from_static(role));
+        }
+        headers
+    }
+
+    #[test]
+    fn test_is_admin() {
+        assert!(is_admin(&headers(Some("admin"))));
+        assert!(is_admin(&headers(Some("Admin"))));
+        assert!(!is_admin(&headers(None)));
+        assert!(!is_admin(&headers(Some(""))));
+        assert!(!is_admin(&headers(Some("user"))));
+        assert!(!is_admin(&headers(Some("administrator"))));
+    }
+}
@@ -0,0 +1,198 @@
+use crate::telemetry::db_metrics::QueryTime;
+use axum::{extract::MatchedPath, http::Request, response::Response};
+use opentelemetry::{
+    metrics::{Histogram, Meter, Unit},
+    KeyValue,
+};
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+use tracing::{field::Empty, Instrument};
+
+/// Name of the histogram of the database time of the requests.
+pub const SQL_QUERY_TIME_METRIC: &str = "sql_query_time_ms";
+
+/// A `tower::Layer` that measures the time every request spends on database
+/// queries.
+///
+/// The time of the queries awaited with `TimedQuery::timed` while handling a
+/// request is summed up and, when the request ran at least one query,
+/// recorded:
+///
+/// * in the `sql_query_time_ms` histogram, with the matched route as the
+///   `http.route` attribute.
+/// * as the `db.query_time_ms` field of a `sql_query_time` span wrapping the
+///   request.
+#[derive(Debug, Clone)]
+pub struct QueryTimingLayer {
+    histogram: Histogram<f64>,
+}
+
+impl QueryTimingLayer {
+    /// Creates a new `QueryTimingLayer` recording the histogram on `meter`.
+    pub fn new(meter: &Meter) -> Self {
+        let histogram = meter
+            .f64_histogram(SQL_QUERY_TIME_METRIC)
+            .with_description("Time spent on database queries per request")
+            .with_unit(Unit::new("ms"))
+            .init();
+        Self { histogram }
+    }
+}
+
+impl<S> Layer<S> for QueryTimingLayer {
+    type Service = QueryTimingService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        QueryTimingService {
+            inner,
+            histogram: self.histogram.clone(),
+        }
+    }
+}
+
+/// The `tower::Service` created by `QueryTimingLayer`.
+#[derive(Debug, Clone)]
+pub struct QueryTimingService<S> {
+    inner: S,
+    histogram: Histogram<f64>,
+}
+
+impl<S, B> Service<Request<B>> for QueryTimingService<S>
+where
+    S: Service<Request<B>, Response = Response, Error = Infallible>,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let route = request.extensions().get::<MatchedPath>().map_or_else(
+            || request.uri().path().to_owned(),
+            |path| path.as_str().to_owned(),
+        );
+        let span = tracing::info_span!(
+            "sql_query_time",
+            http.route = %route,
+            db.query_time_ms = Empty
+        );
+        let histogram = self.histogram.clone();
+        let query_time = Arc::new(QueryTime::default());
+        let response = Arc::clone(&query_time).scope(self.inner.call(request));
+        Box::pin(
+            async move {
+                let response = response.await;
+                if query_time.queries() > 0 {
+                    let millis = query_time.millis();
+                    tracing::Span::current().record("db.query_time_ms", millis);
+                    histogram.record(
+                        &opentelemetry::Context::current(),
+                        millis,
+                        &[KeyValue::new("http.route", route)],
+                    );
+                }
+                response
+            }
+            .instrument(span),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library_web::tests::get;
+    use crate::telemetry::db_metrics::TimedQuery;
+    use crate::tests::sequential::sequential_test;
+    use axum::{routing, Extension, Router};
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry::sdk::export::metrics::{
+        aggregation::{self, Count, Sum},
+        InstrumentationLibraryReader,
+    };
+    use opentelemetry::sdk::metrics::{
+        aggregators::HistogramAggregator, controllers, processors, selectors,
+    };
+    use sqlx::PgPool;
+
+    async fn sleep_query(Extension(pool): Extension<PgPool>) -> &'static str {
+        sqlx::query("SELECT pg_sleep(0.01)")
+            .execute(&pool)
+            .timed()
+            .await
+            .expect("failed to run query");
+        "ok"
+    }
+
+    sequential_test!(
+        async fn test_query_timing_layer() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let controller = controllers::basic(processors::factory(
+                selectors::simple::histogram([10.0, 100.0]),
+                aggregation::cumulative_temporality_selector(),
+            ))
+            .build();
+            let router = Router::new()
+                .route("/sleep/:id", routing::get(sleep_query))
+                .route("/no-query", routing::get(|| async { "ok" }))
+                .layer(QueryTimingLayer::new(&controller.meter("test")))
+                .layer(Extension(pool));
+
+            assert_eq!(get(&router, "/sleep/1").await.status(), 200);
+            assert_eq!(get(&router, "/sleep/2").await.status(), 200);
+            assert_eq!(get(&router, "/no-query").await.status(), 200);
+
+            controller
+                .collect(&opentelemetry::Context::current())
+                .expect("failed to collect metrics");
+            let mut recorded = Vec::new();
+            controller
+                .try_for_each(&mut |_library, reader| {
+                    reader.try_for_each(
+                        &aggregation::cumulative_temporality_selector(),
+                        &mut |record| {
+                            let histogram = record
+                                .aggregator()
+                                .and_then(|aggregator| {
+                                    aggregator.as_any().downcast_ref::<HistogramAggregator>()
+                                })
+                                .expect("not a histogram");
+                            let route = record
+                                .attributes()
+                                .iter()
+                                .find(|(key, _)| key.as_str() == "http.route")
+                                .map(|(_, value)| value.to_string());
+                            recorded.push((
+                                record.descriptor().name().to_owned(),
+                                route,
+                                histogram.count()?,
+                                histogram.sum()?.to_f64(record.descriptor().number_kind()),
+                            ));
+                            Ok(())
+                        },
+                    )
+                })
+                .expect("failed to read metrics");
+
+            // only the route running a query is recorded, once per request
+            assert_eq!(recorded.len(), 1);
+            let (name, route, count, sum) = &recorded[0];
+            assert_eq!(name, SQL_QUERY_TIME_METRIC);
+            assert_eq!(route.as_deref(), Some("/sleep/:id"));
+            assert_eq!(*count, 2);
+            assert!(*sum >= 20.0);
+        }
+    );
+}
@@ -0,0 +1,110 @@
+use opentelemetry::metrics::Meter;
+use opentelemetry::sdk::{trace::Sampler, Resource};
+use opentelemetry::KeyValue;
+use std::env::var;
+
+/// Default name of the service reported with the traces and metrics.
+pub const DEFAULT_SERVICE_NAME: &str = "library-api";
+
+/// Default version of the service reported with the traces and metrics.
+pub const DEFAULT_SERVICE_VERSION: &str = "unknown";
+
+/// Represents the configuration of the telemetry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TelemetryConfig {
+    /// Name of the service, reported as `service.name`.
+    pub service_name: String,
+    /// Version of the service, reported as `service.version`.
+    pub service_version: String,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            service_name: DEFAULT_SERVICE_NAME.to_owned(),
+            service_version: DEFAULT_SERVICE_VERSION.to_owned(),
+        }
+    }
+}
+
+impl TelemetryConfig {
+    /// Reads the configuration from the environment variables.
+    ///
+    /// * `SERVICE_NAME`: defaults to `DEFAULT_SERVICE_NAME`.
+    /// * `SERVICE_VERSION`: defaults to `DEFAULT_SERVICE_VERSION`.
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| var(name).ok())
+    }
+
+    /// Reads the configuration from the variables returned by `get`.
+    fn from_vars(get: impl Fn(&str) -> Option<String>) -> Self {
+        let default = Self::default();
+        Self {
+            service_name: get("SERVICE_NAME").unwrap_or(default.service_name),
+            service_version: get("SERVICE_VERSION").unwrap_or(default.service_version),
+        }
+    }
+
+    /// Returns the resource describing the service, attached to the exported
+    /// traces and metrics.
+    pub fn resource(&self) -> Resource {
+        Resource::new([
+            KeyValue::new("service.name", self.service_name.clone()),
+            KeyValue::new("service.version", self.service_version.clone()),
+        ])
+    }
+
+    /// Returns the meter of the service, named after `service_name`.
+    ///
+    /// The name is leaked since meters require a static name, so this is
+    /// meant to be called once at startup.
+    pub fn meter(&self) -> Meter {
+        let name: &'static str = Box::leak(self.service_name.clone().into_boxed_str());
+        opentelemetry::global::meter(name)
+    }
+
+    /// Returns the sampler deciding which traces are exported.
+    pub fn sampler(&self) -> Sampler {
+        Sampler::AlwaysOn
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::{Key, Value};
+    use std::collections::HashMap;
+
+    fn from_map(vars: &[(&str, &str)]) -> TelemetryConfig {
+        let vars: HashMap<_, _> = vars.iter().copied().collect();
+        TelemetryConfig::from_vars(|name| vars.get(name).map(|value| value.to_string()))
+    }
+
+    #[test]
+    fn test_telemetry_config_defaults() {
+        let resource = from_map(&[]).resource();
+        assert_eq!(resource.len(), 2);
+        assert_eq!(
+            resource.get(Key::new("service.name")),
+            Some(Value::from(DEFAULT_SERVICE_NAME))
+        );
+        assert_eq!(
+            resource.get(Key::new("service.version")),
+            Some(Value::from(DEFAULT_SERVICE_VERSION))
+        );
+    }
+
+    #[test]
+    fn test_telemetry_config() {
+        let resource =
+            from_map(&[("SERVICE_NAME", "library"), ("SERVICE_VERSION", "1.2.3")]).resource();
+        assert_eq!(
+            resource.get(Key::new("service.name")),
+            Some(Value::from("library"))
+        );
+        assert_eq!(
+            resource.get(Key::new("service.version")),
+            Some(Value::from("1.2.3"))
+        );
+    }
+}
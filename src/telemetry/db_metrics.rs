@@ -0,0 +1,356 @@
+use crate::database::postgres::init::ACQUIRE_TIMEOUT;
+use crate::helper::db::{slow_query_threshold, warn_if_slow};
+use futures::Stream;
+use opentelemetry::metrics::{Meter, MetricsError, Unit};
+use sqlx::PgPool;
+use std::borrow::Cow;
+use std::future::Future;
+use std::panic::Location;
+use std::pin::Pin;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// How often the pool is read.
+pub const POOL_METRICS_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Holds the latest readings of the pool, reported by the gauges.
+#[derive(Debug, Default)]
+struct PoolReadings {
+    size: AtomicU64,
+    idle: AtomicU64,
+}
+
+/// Reports the health of a PostgreSQL connection pool as OpenTelemetry gauges.
+///
+/// The gauges are:
+///
+/// * `db.pool.size`: the number of open connections.
+/// * `db.pool.idle`: the number of idle connections.
+/// * `db.pool.active`: the number of connections in use.
+/// * `db.pool.acquire_timeout`: how long acquiring a connection may take, in
+///   seconds.
+pub struct PoolMetrics {
+    pool: PgPool,
+    readings: Arc<PoolReadings>,
+}
+
+impl PoolMetrics {
+    /// Creates the gauges of the pool on the given meter.
+    ///
+    /// ## Arguments
+    ///
+    /// * `pool`: The PostgreSQL database connection pool.
+    /// * `meter`: The meter to create the gauges on.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the gauges cannot be registered.
+    pub fn new(pool: PgPool, meter: &Meter) -> Result<Self, MetricsError> {
+        let size = meter
+            .u64_observable_gauge("db.pool.size")
+            .with_description("Number of open connections in the pool")
+            .init();
+        let idle = meter
+            .u64_observable_gauge("db.pool.idle")
+            .with_description("Number of idle connections in the pool")
+            .init();
+        let active = meter
+            .u64_observable_gauge("db.pool.active")
+            .with_description("Number of connections of the pool in use")
+            .init();
+        let acquire_timeout = meter
+            .f64_observable_gauge("db.pool.acquire_timeout")
+            .with_description("How long acquiring a connection from the pool may take")
+            .with_unit(Unit::new("s"))
+            .init();
+
+        let readings = Arc::new(PoolReadings::default());
+        let observed = Arc::clone(&readings);
+        meter.register_callback(move |cx| {
+            let size_reading = observed.size.load(Ordering::Relaxed);
+            let idle_reading = observed.idle.load(Ordering::Relaxed);
+            size.observe(cx, size_reading, &[]);
+            idle.observe(cx, idle_reading, &[]);
+            active.observe(cx, size_reading.saturating_sub(idle_reading), &[]);
+            acquire_timeout.observe(cx, ACQUIRE_TIMEOUT.as_secs_f64(), &[]);
+        })?;
+        Ok(Self { pool, readings })
+    }
+
+    /// Reads the current state of the pool.
+    pub fn record(&self) {
+        let size = u64::from(self.pool.size());
+        let idle = self.pool.num_idle() as u64;
+        self.readings.size.store(size, Ordering::Relaxed);
+        self.readings.idle.store(idle, Ordering::Relaxed);
+    }
+
+    /// Spawns a task that reads the pool every `POOL_METRICS_INTERVAL`.
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(POOL_METRICS_INTERVAL);
+            loop {
+                interval.tick().await;
+                self.record();
+            }
+        })
+    }
+}
+
+tokio::task_local! {
+    /// The database time of the request being handled.
+    static QUERY_TIME: Arc<QueryTime>;
+}
+
+/// Accumulates the time spent on the database queries awaited with
+/// `TimedQuery::timed` while a future runs in its `scope`.
+#[derive(Debug, Default)]
+pub struct QueryTime {
+    micros: AtomicU64,
+    queries: AtomicU64,
+}
+
+impl QueryTime {
+    /// Runs `future`, adding the time of the queries it awaits to `self`.
+    pub async fn scope<F: Future>(self: Arc<Self>, future: F) -> F::Output {
+        QUERY_TIME.scope(self, future).await
+    }
+
+    /// Returns the number of queries awaited so far.
+    pub fn queries(&self) -> u64 {
+        self.queries.load(Ordering::Relaxed)
+    }
+
+    /// Returns the total time of the queries awaited so far, in milliseconds.
+    pub fn millis(&self) -> f64 {
+        self.micros.load(Ordering::Relaxed) as f64 / 1000.0
+    }
+
+    fn add(&self, elapsed: Duration) {
+        let micros = u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX);
+        self.micros.fetch_add(micros, Ordering::Relaxed);
+        self.queries.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Times the database queries of the `library` module.
+///
+/// The time between the first poll of a query and its completion is added to
+/// the `QueryTime` of the current scope, if any, e.g. the one of the request
+/// set up by `QueryTimingLayer`. A query slower than `slow_query_threshold`
+/// is also logged as a warning, with the place where `timed` was called and,
+/// when timed with `timed_sql!`, the SQL of the query.
+pub trait TimedQuery: Future + Sized {
+    /// Wraps the query to time it.
+    #[track_caller]
+    fn timed(self) -> Timed<Self> {
+        Timed {
+            query: Box::pin(self),
+            timer: QueryTimer::new(Location::caller(), None),
+        }
+    }
+
+    /// Wraps the query to time it, `sql` being logged when it is slow.
+    #[track_caller]
+    fn timed_sql(self, sql: impl Into<Cow<'static, str>>) -> Timed<Self> {
+        Timed {
+            query: Box::pin(self),
+            timer: QueryTimer::new(Location::caller(), Some(sql.into())),
+        }
+    }
+}
+
+impl<F: Future> TimedQuery for F {}
+
+/// Times the database queries of the `library` module whose rows are
+/// streamed, like `TimedQuery`, from the first poll of the stream to its
+/// exhaustion.
+pub trait TimedStream: Stream + Sized {
+    /// Wraps the stream of rows of the query to time it, `sql` being logged
+    /// when it is slow.
+    #[track_caller]
+    fn timed_sql(self, sql: impl Into<Cow<'static, str>>) -> TimedRows<Self> {
+        TimedRows {
+            rows: Box::pin(self),
+            timer: QueryTimer::new(Location::caller(), Some(sql.into())),
+        }
+    }
+}
+
+impl<S: Stream> TimedStream for S {}
+
+/// Times a query built with one of the `sqlx` query macros, see
+/// `TimedQuery::timed`, with the SQL of the query logged when it is slow.
+///
+/// The query is followed by the calls running it, e.g.
+/// `timed_sql!(sqlx::query!("SELECT 1").fetch_one(pool))`, and its rows may
+/// be streamed with `fetch`, see `TimedStream`.
+macro_rules! timed_sql {
+    (sqlx::$query:ident!($($args:tt)*) $(.$call:ident($($call_args:tt)*))+) => {{
+        #[allow(unused_imports)]
+        use $crate::telemetry::db_metrics::{TimedQuery as _, TimedStream as _};
+        let query = sqlx::$query!($($args)*);
+        let sql = sqlx::Execute::sql(&query);
+        query$(.$call($($call_args)*))+.timed_sql(sql)
+    }};
+}
+
+pub(crate) use timed_sql;
+
+/// Measures a query from its first poll to its completion.
+#[derive(Debug)]
+struct QueryTimer {
+    started: Option<Instant>,
+    location: &'static Location<'static>,
+    sql: Option<Cow<'static, str>>,
+}
+
+impl QueryTimer {
+    fn new(location: &'static Location<'static>, sql: Option<Cow<'static, str>>) -> Self {
+        Self {
+            started: None,
+            location,
+            sql,
+        }
+    }
+
+    fn start(&mut self) {
+        self.started.get_or_insert_with(Instant::now);
+    }
+
+    fn finish(&self) {
+        let Some(started) = self.started else {
+            return;
+        };
+        let elapsed = started.elapsed();
+        warn_if_slow(
+            elapsed,
+            slow_query_threshold(),
+            self.location,
+            self.sql.as_deref(),
+        );
+        // queries awaited outside of a scope, e.g. by tests, are not timed
+        let _ = QUERY_TIME.try_with(|query_time| query_time.add(elapsed));
+    }
+}
+
+/// The future returned by `TimedQuery::timed`.
+#[derive(Debug)]
+pub struct Timed<F> {
+    query: Pin<Box<F>>,
+    timer: QueryTimer,
+}
+
+impl<F: Future> Future for Timed<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.timer.start();
+        let poll = self.query.as_mut().poll(cx);
+        if poll.is_ready() {
+            self.timer.finish();
+        }
+        poll
+    }
+}
+
+/// The stream returned by `TimedStream::timed_sql`.
+#[derive(Debug)]
+pub struct TimedRows<S> {
+    rows: Pin<Box<S>>,
+    timer: QueryTimer,
+}
+
+impl<S: Stream> Stream for TimedRows<S> {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.timer.start();
+        let poll = self.rows.as_mut().poll_next(cx);
+        if let Poll::Ready(None) = poll {
+            self.timer.finish();
+        }
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sequential::sequential_test;
+    use futures::TryStreamExt;
+
+    sequential_test!(
+        async fn test_pool_metrics() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let _conn = pool.acquire().await.expect("failed to acquire connection");
+            let meter = opentelemetry::global::meter("test");
+            let metrics = PoolMetrics::new(pool.clone(), &meter).expect("failed to create metrics");
+            metrics.record();
+            let size = metrics.readings.size.load(Ordering::Relaxed);
+            let idle = metrics.readings.idle.load(Ordering::Relaxed);
+            assert!(size >= 1);
+            assert!(idle < size);
+        }
+    );
+
+    sequential_test!(
+        async fn test_query_time() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let query_time = Arc::new(QueryTime::default());
+            Arc::clone(&query_time)
+                .scope(async {
+                    for _ in 0..2 {
+                        sqlx::query("SELECT pg_sleep(0.01)")
+                            .execute(&pool)
+                            .timed()
+                            .await
+                            .expect("failed to run query");
+                    }
+                })
+                .await;
+            assert_eq!(query_time.queries(), 2);
+            assert!(query_time.millis() >= 20.0);
+
+            // outside of a scope the query still runs
+            sqlx::query("SELECT 1")
+                .execute(&pool)
+                .timed()
+                .await
+                .expect("failed to run query");
+            assert_eq!(query_time.queries(), 2);
+        }
+    );
+
+    sequential_test!(
+        async fn test_timed_rows() {
+            let pool = crate::database::postgres::init::pg_pool()
+                .await
+                .expect("failed to connect to postgres");
+            let query_time = Arc::new(QueryTime::default());
+            let rows = Arc::clone(&query_time)
+                .scope(async {
+                    timed_sql!(
+                        sqlx::query_scalar!(r#"SELECT generate_series(1, 3) as "n!""#).fetch(&pool)
+                    )
+                    .try_collect::<Vec<_>>()
+                    .await
+                    .expect("failed to stream rows")
+                })
+                .await;
+            assert_eq!(rows, vec![1, 2, 3]);
+            // the stream is timed once, when exhausted
+            assert_eq!(query_time.queries(), 1);
+        }
+    );
+}
@@ -0,0 +1,31 @@
+/// Emits an `info` event for a successful response of a handler.
+///
+/// Every event has the same fields, so the logs of all the routes can be
+/// filtered alike:
+///
+/// - `status`: The HTTP status code of the response.
+/// - `entity`: The kind of resource handled, e.g. `"book"`.
+/// - `action`: What the handler did with it, e.g. `"create"`.
+///
+/// Fields identifying the resource, e.g. `book_id`, may follow with the
+/// usual `tracing` syntax. The event is emitted synchronously, right before
+/// the handler returns its response.
+///
+/// ## Example
+///
+/// ```ignore
+/// log_success!(StatusCode::CREATED, "book", "create", book_id = %book_id);
+/// ```
+macro_rules! log_success {
+    ($status:expr, $entity:literal, $action:literal $(, $($field:tt)+)?) => {
+        ::tracing::info!(
+            status = $status.as_u16(),
+            entity = $entity,
+            action = $action,
+            $($($field)+,)?
+            "request succeeded"
+        )
+    };
+}
+
+pub(crate) use log_success;
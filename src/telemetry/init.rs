@@ -1,10 +1,16 @@
-use std::env::var;
+use super::config::TelemetryConfig;
+use std::{env::var, time::Duration};
+
+/// How often the metrics are exported.
+pub const METRICS_EXPORT_PERIOD: Duration = Duration::from_secs(15);
+
 /// Configures tracing for the application.
 ///
 /// This function sets up the tracing subsystem for the application, including
 /// the filtering, formatting, and exporting of trace events. It initializes
-/// an OpenTelemetry exporter to send trace data to a specified endpoint.
-pub fn tracing() {
+/// an OpenTelemetry exporter to send trace data to a specified endpoint,
+/// described by the resource and sampled by the sampler of `config`.
+pub fn tracing(config: &TelemetryConfig) {
     use opentelemetry_otlp::WithExportConfig;
     use tracing_subscriber::prelude::*;
 
@@ -18,18 +24,13 @@ pub fn tracing() {
         .tonic()
         .with_endpoint(var("JAEGER_URL").expect("JAEGER_URL must be in environment"));
 
-    let otel_resource = opentelemetry::sdk::Resource::new([opentelemetry::KeyValue::new(
-        "service.name",
-        "backend",
-    )]);
-
     let otel_tracer = opentelemetry_otlp::new_pipeline()
         .tracing()
         .with_exporter(otel_exporter)
         .with_trace_config(
             opentelemetry::sdk::trace::config()
-                .with_resource(otel_resource)
-                .with_sampler(opentelemetry::sdk::trace::Sampler::AlwaysOn),
+                .with_resource(config.resource())
+                .with_sampler(config.sampler()),
         )
         .install_simple()
         .unwrap();
@@ -42,3 +43,42 @@ pub fn tracing() {
         .with(otel_trace_layer)
         .init();
 }
+
+/// Configures the export of metrics for the application.
+///
+/// This function sets up an OpenTelemetry metrics pipeline that exports the
+/// metrics to the same endpoint as the traces every `METRICS_EXPORT_PERIOD`,
+/// described by the resource of `config`, and installs it as the global meter
+/// provider.
+///
+/// ## Returns
+///
+/// The controller of the pipeline, which must be kept alive while metrics
+/// are exported.
+///
+/// ## Panics
+///
+/// This function will panic if the `JAEGER_URL` environment variable is not
+/// set or if the pipeline cannot be started.
+pub fn metrics(
+    config: &TelemetryConfig,
+) -> opentelemetry::sdk::metrics::controllers::BasicController {
+    use opentelemetry::sdk::{export::metrics::aggregation, metrics::selectors};
+    use opentelemetry_otlp::WithExportConfig;
+
+    let otel_exporter = opentelemetry_otlp::new_exporter()
+        .tonic()
+        .with_endpoint(var("JAEGER_URL").expect("JAEGER_URL must be in environment"));
+
+    opentelemetry_otlp::new_pipeline()
+        .metrics(
+            selectors::simple::inexpensive(),
+            aggregation::cumulative_temporality_selector(),
+            opentelemetry::runtime::Tokio,
+        )
+        .with_exporter(otel_exporter)
+        .with_resource(config.resource())
+        .with_period(METRICS_EXPORT_PERIOD)
+        .build()
+        .expect("failed to start metrics pipeline")
+}
@@ -1 +1,4 @@
+pub mod config;
+pub mod db_metrics;
+pub mod events;
 pub mod init;
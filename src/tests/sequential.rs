@@ -0,0 +1,45 @@
+use tokio::sync::{Mutex, MutexGuard};
+
+/// The lock held by every test touching the database.
+///
+/// A `tokio::sync::Mutex` is used because it is not poisoned when a test
+/// holding it panics, and because it can be shared by the runtimes of
+/// different `#[tokio::test]` tests.
+static DATABASE: Mutex<()> = Mutex::const_new(());
+
+/// Waits until no other test touches the database and returns a guard
+/// releasing the database when dropped.
+pub async fn lock() -> MutexGuard<'static, ()> {
+    DATABASE.lock().await
+}
+
+/// Declares an async test that runs only while no other test declared with
+/// this macro is running.
+///
+/// The tests share a single database, so running them in parallel lets them
+/// see the rows inserted by each other. `RUST_TEST_THREADS=1` in
+/// `.cargo/config.toml` is also needed, so that tests not declared with this
+/// macro do not run alongside them either.
+///
+/// ## Example
+///
+/// ```ignore
+/// sequential_test!(
+///     async fn test_books() {
+///         let lib = LibraryWeb::new_test().await;
+///         // ...
+///     }
+/// );
+/// ```
+macro_rules! sequential_test {
+    ($(#[$meta:meta])* async fn $name:ident() $body:block) => {
+        $(#[$meta])*
+        #[tokio::test]
+        async fn $name() {
+            let _guard = $crate::tests::sequential::lock().await;
+            $body
+        }
+    };
+}
+
+pub(crate) use sequential_test;